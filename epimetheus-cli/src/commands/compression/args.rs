@@ -2,7 +2,14 @@ use std::{fs::File, io::{BufRead, BufReader}, path::PathBuf};
 
 use anyhow::bail;
 use clap::{Args, Parser, Subcommand};
-use epimetheus_io::io::readers::bed::{InputReader, LineReader};
+use epimetheus_io::io::{
+    readers::{
+        bed::{InputReader, LineReader},
+        regions::PileupQuery,
+    },
+    writers::bgzip::DEFAULT_COMPRESSION_LEVEL,
+};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 
 #[derive(Args, Debug)]
 pub struct BgZipArgs {
@@ -14,6 +21,8 @@ pub struct BgZipArgs {
 pub enum BgZipCommands {
     Compress(BgzipWriterArgs),
     Decompress(BgzipExtractArgs),
+    Reblock(BgzipReblockArgs),
+    Index(BgzipIndexArgs),
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -48,6 +57,20 @@ pub struct BgzipWriterArgs {
         help = "Setting flag will override the file if exists."
     )]
     pub force: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Do not show the progress bar. The bar is written to stderr, so it never mixes with '--stdout' output."
+    )]
+    pub no_progress: bool,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_COMPRESSION_LEVEL,
+        help = "Compression level from 0 (no compression, fastest) to 9 (best compression, slowest)."
+    )]
+    pub level: u32,
 }
 
 impl BgzipWriterArgs {
@@ -56,6 +79,10 @@ impl BgzipWriterArgs {
             bail!("Cannot set '--keep' with '--stdin'. No file will be removed.")
         }
 
+        if self.level > 9 {
+            bail!("'--level' should be between 0 and 9. Got: {}", self.level)
+        }
+
         let reader = match (self.input.is_some(), self.stdin) {
             (true, false) => {
                 let file = File::open(&self.input.as_ref().unwrap())?;
@@ -97,6 +124,41 @@ impl BgzipWriterArgs {
     pub fn should_remove_input_file(&self) -> bool {
         !self.keep & !self.stdin
     }
+
+    /// Progress bar for the compression, drawn on stderr. A file input gets a bar over
+    /// its size in bytes; stdin has no known size and gets a spinner counting lines.
+    pub fn progress_bar(&self) -> anyhow::Result<ProgressBar> {
+        if self.no_progress {
+            return Ok(ProgressBar::hidden());
+        }
+
+        let pb = match &self.input {
+            Some(input) if !self.stdin => {
+                let size = std::fs::metadata(input)?.len();
+                let pb = ProgressBar::with_draw_target(Some(size), ProgressDrawTarget::stderr());
+                pb.set_style(
+                    ProgressStyle::default_bar()
+                        .template(
+                            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})",
+                        )?
+                        .progress_chars("#>-"),
+                );
+                pb
+            }
+            _ => {
+                let pb = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+                pb.set_style(
+                    ProgressStyle::default_spinner()
+                        .template(
+                            "{spinner:.green} [{elapsed_precise}] {human_pos} lines compressed",
+                        )?,
+                );
+                pb
+            }
+        };
+
+        Ok(pb)
+    }
 }
 
 
@@ -120,24 +182,54 @@ pub struct BgzipExtractArgs {
     )]
     pub ls: bool,
 
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "list contig names in pileup with their max indexed end coordinate ('.' if unknown)."
+    )]
+    pub ls_lengths: bool,
+
     #[arg(
         long,
         num_args(1..), 
         required = false,
-        help = "Optional vector of contig ids to query. Left empty the whole pileup will be read."
+        help = "Optional vector of contig ids to query. Left empty the whole pileup will be read. Use 'contig:start-end' (1-based, inclusive) to query a region of a contig."
     )]
     pub contigs: Option<Vec<String>>,
 
     #[arg(
         long,
         required = false,
-        help = "File with contig names in it."
+        help = "File with contig names in it. Lines can also be 'contig:start-end' regions."
+    )]
+    pub contigs_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        required = false,
+        conflicts_with_all = ["contigs", "contigs_file"],
+        help = "BED file with regions to extract (contig, start, end). Overlapping regions are merged, so each record is written once."
     )]
-    pub contigs_file: Option<PathBuf>
+    pub regions: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Read a pileup without the BGZF end-of-file block with a warning. By default such a truncated file is an error, since records of the lost blocks would be silently missing."
+    )]
+    pub allow_truncated: bool,
 }
 
 impl BgzipExtractArgs {
-    pub fn resolve_contigs(&self) -> anyhow::Result<Vec<String>> {
+    /// The contigs or `contig:start-end` regions to query. See [`PileupQuery`].
+    pub fn resolve_contigs(&self) -> anyhow::Result<Vec<PileupQuery>> {
+        self.resolve_contig_names()?
+            .iter()
+            .map(|contig| contig.parse())
+            .collect()
+    }
+
+    fn resolve_contig_names(&self) -> anyhow::Result<Vec<String>> {
         match (&self.contigs, &self.contigs_file) {
             (Some(contigs), None) => Ok(contigs.clone()),
             (None, Some(contig_file)) => {
@@ -160,12 +252,85 @@ impl BgzipExtractArgs {
             }
             (Some(_), Some(_)) => bail!("Cannot specify both --contigs and --contigs-file"),
             (None, None) => {
-                if self.ls {
+                if self.ls || self.ls_lengths || self.regions.is_some() {
                     Ok(Vec::new())
                 } else {
-                    bail!("Must specify either --contigs or --contigs-file")
+                    bail!("Must specify either --contigs, --contigs-file or --regions")
                 }
             }
         }
     }
 }
+
+#[derive(Parser, Debug, Clone)]
+pub struct BgzipReblockArgs {
+    #[arg(required = true, help = "Path to input pileup file [.bed.gz]. Does not need an index.")]
+    pub input: PathBuf,
+
+    #[arg(required = true, help = "Path to output pileup file [.bed.gz]. A new tabix index is written next to it.")]
+    pub output: PathBuf,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Setting flag will override the file if exists."
+    )]
+    pub force: bool,
+}
+
+impl BgzipReblockArgs {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for path in [&self.input, &self.output] {
+            if path.extension().and_then(|s| s.to_str()) != Some("gz") {
+                bail!("Pileup file should have bed.gz extension. Got: {}", path.display());
+            }
+        }
+
+        if self.input == self.output {
+            bail!("Input and output cannot be the same file.");
+        }
+
+        if !self.force && self.output.exists() {
+            bail!(
+                "Output file '{}' already exist. Set '--force' to override.",
+                self.output.display()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct BgzipIndexArgs {
+    #[arg(required = true, help = "Path to pileup file [.bed.gz]. The tabix index is written next to it as <INPUT>.tbi.")]
+    pub input: PathBuf,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Setting flag will override the index if exists."
+    )]
+    pub force: bool,
+}
+
+impl BgzipIndexArgs {
+    pub fn index_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.tbi", self.input.display()))
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.input.extension().and_then(|s| s.to_str()) != Some("gz") {
+            bail!("Pileup file should have bed.gz extension. Got: {}", self.input.display());
+        }
+
+        if !self.force && self.index_path().exists() {
+            bail!(
+                "Index file '{}' already exist. Set '--force' to override.",
+                self.index_path().display()
+            );
+        }
+
+        Ok(())
+    }
+}
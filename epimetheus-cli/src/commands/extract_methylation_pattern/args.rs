@@ -1,8 +1,22 @@
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    num::NonZeroUsize,
+    path::PathBuf,
+    str::FromStr,
+};
 
-use anyhow::anyhow;
+use anyhow::{Context, anyhow, bail};
 use clap::Parser;
-use epimetheus_core::models::methylation::MethylationOutput;
+use epimetheus_core::models::{
+    coverage_histogram::DEFAULT_COVERAGE_BIN_WIDTH,
+    methylation::{MethylationOutput, OutputFormat, RawFormat, StrandFormat, ValueScale},
+    pileup::{CoverageField, ParseErrorMode},
+};
+use epimetheus_core::services::domain::motif_processor::ModBaseMap;
+use regex::Regex;
+
+use crate::commands::extract_methylation_pattern::compat::CompatFormat;
 
 #[derive(Parser, Debug, Clone)]
 pub struct ContigMethylationPatternArgs {
@@ -10,16 +24,43 @@ pub struct ContigMethylationPatternArgs {
         short,
         long,
         required = true,
-        help = "Path to pileup. Can be .bed.gz (recommended see bgzip command) or .bed"
+        num_args(1..),
+        help = "Path to pileup. Can be .bed.gz (recommended see bgzip command), .bed or .parquet with the 18 pileup columns. Several .bed.gz pileups of the same assembly (e.g. one per flowcell) are merged per contig, summing the counts of identical positions."
     )]
-    pub pileup: PathBuf,
+    pub pileup: Vec<PathBuf>,
 
     #[arg(short, long, required = true, help = "Path to assembly.")]
     pub assembly: PathBuf,
 
-    #[arg(long, num_args(1..), help = "Specific contigs to process. Requires that a pileup is a .bed.gz file")]
+    #[arg(long, num_args(1..), help = "Specific contigs to process. A .bed pileup will be compressed to .bed.gz internally to allow the lookup.")]
     pub contigs: Option<Vec<String>>,
 
+    #[arg(
+        long,
+        help = "Only process assembly contigs whose id matches this regex. Example: '^bin3_'. Cannot be combined with '--contigs'."
+    )]
+    pub contigs_pattern: Option<String>,
+
+    #[arg(
+        long,
+        help = "Keep the .bed.gz (and .tbi) created when a .bed pileup is compressed internally. Only used together with '--contigs', '--contigs-pattern' or '--combine-c-mods'. By default the intermediate is discarded."
+    )]
+    pub keep_intermediate_gz: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Fail if the .tbi index of a .bed.gz pileup is older than the pileup. By default only a warning is given."
+    )]
+    pub strict_index: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Read a .bed.gz pileup without the BGZF end-of-file block with a warning. By default such a truncated file is an error, since records of the lost blocks would be silently missing."
+    )]
+    pub allow_truncated: bool,
+
     #[arg(
         short,
         long,
@@ -28,12 +69,57 @@ pub struct ContigMethylationPatternArgs {
     )]
     pub output: PathBuf,
 
-    #[arg(short, long, default_value_t = 1, help = "Number of parallel tasks.")]
-    pub threads: usize,
+    #[arg(
+        short,
+        long,
+        default_value = "1",
+        help = "Number of parallel tasks. 'auto' uses the available cores, but never more threads than contigs."
+    )]
+    pub threads: ThreadCount,
 
-    #[arg(short, long, required = true, num_args(1..), help = "Supply chain of motifs as <motif>_<mod_type>_<mod_position>. Example: '-m GATC_a_1 RGATCY_a_2'")]
+    #[arg(short, long, required_unless_present_any = ["config", "motifs_file"], num_args(1..), value_delimiter = ',', help = "Supply chain of motifs as <motif>_<mod_type>_<mod_position>, separated by spaces or commas. Example: '-m GATC_a_1 RGATCY_a_2' or '-m GATC_a_1,RGATCY_a_2'")]
     pub motifs: Vec<String>,
 
+    #[arg(
+        long,
+        conflicts_with = "motifs",
+        help = "File with one motif per line as <motif>_<mod_type>_<mod_position>. Blank lines and lines starting with '#' are ignored."
+    )]
+    pub motifs_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "JSON (.json) or YAML (.yaml, .yml) file with run parameters. Keys: motifs, min_valid_read_coverage, min_valid_cov_to_diff_fraction, methylated_threshold, bias_threshold and output_type. Flags given on the command line take precedence over the config, which takes precedence over the defaults."
+    )]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Check all motifs and exit without processing the pileup. Reports motifs whose mod position is out of bounds or targets a base incompatible with the mod type (by default A for 'a', C for 'm'/'h'/'21839', see '--mod-base-map'), and motifs with invalid IUPAC codes."
+    )]
+    pub check_motifs: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Count the occurrences of each motif in each contig and exit without processing the pileup. Writes a TSV of contig, motif, mod_type and occurrences to '--output'."
+    )]
+    pub count_only: bool,
+
+    #[arg(
+        long,
+        help = "Comma separated '<mod_code>:<base>' pairs setting the base each mod type is expected on in motifs. Example: 'a:A,m:C,h:C'. Mod codes not given keep the default (A for 'a', C for 'm'/'h'/'21839'/'C'). Bases must be A, C, G or T."
+    )]
+    pub mod_base_map: Option<ModBaseMap>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Drop motifs that are the reverse complement of an earlier motif with the same mod type and the mod position on the complementary base, so both measure the same sites. Without it such pairs only give a warning."
+    )]
+    pub dedup_revcomp_motifs: bool,
+
     #[arg(
         long,
         default_value_t = 3,
@@ -48,48 +134,692 @@ pub struct ContigMethylationPatternArgs {
     )]
     pub batch_size: usize,
 
+    #[arg(
+        long,
+        value_name = "GB",
+        help = "Soft memory limit in GB. Batches are made smaller when the estimated memory of their contigs (based on contig lengths) would exceed it. A batch always has at least one contig, even if it exceeds the limit on its own. Applies to .bed and .bed.gz pileups."
+    )]
+    pub max_memory: Option<f64>,
+
     #[arg(
         long,
         default_value_t = 0.8,
         help = "Required fraction of valid coverage relative to different read mapping. N_valid_cov / (N_valid_cov + N_diff)"
     )]
     pub min_valid_cov_to_diff_fraction: f32,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "Minimum pileup score (column 5). Records with a lower score are removed. 0 keeps all records."
+    )]
+    pub min_score: u32,
+
+    #[arg(
+        long,
+        default_value = "n_valid_cov",
+        help = "Pileup columns read as the valid coverage of a record: a count column or a sum of count columns joined by '+', e.g. 'n_valid_cov+n_other_mod'. The sum replaces the n_valid_cov column, so the coverage filters and the subtraction of n_other_mod apply to it. Count columns: n_valid_cov, n_modified, n_canonical, n_other_mod, n_delete, n_fail, n_diff, n_no_call."
+    )]
+    pub coverage_field: CoverageField,
     // #[arg(long, default_value_t = 0.9, help = "Maximum failed fraction relative to valid coverage. N_valid_cov / (N_valid_cov + N_diff)")]
     // pub : f32,
     #[arg(
         long,
         default_value_t = false,
-        help = "Allow epimetheus to continue if a contig in the pileup is not present in the assembly"
+        help = "Allow epimetheus to continue if a contig in the pileup is not present in the assembly. Records past the end of a contig are skipped instead of raising an error."
     )]
     pub allow_mismatch: bool,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ParseErrorMode::Fail,
+        help = "How to handle malformed pileup records. 'fail' aborts with the offending line. 'skip' logs and counts them and continues."
+    )]
+    pub on_parse_error: ParseErrorMode,
+
     #[arg(
         long,
         default_value_t = MethylationOutput::Median,
-        help = "Specify the type of methylation output type. Raw will give all motif methylations for each contig."
+        help = "Specify the type of methylation output type. Raw will give all motif methylations for each contig. Weighted-median is the median of the position methylations weighted by their valid coverage. Trimmed-mean is the mean of the position methylations between the 20th and 80th percentile. Strand-asymmetry gives the mean plus and minus strand methylation and the mean |plus - minus| over sites of palindromic motifs per contig; other motifs are skipped."
     )]
     pub output_type: MethylationOutput,
+
+    #[arg(
+        long,
+        help = "With '--output-type called-frequency', call a position methylated when its fraction of modified reads is at or above this threshold. The output is the fraction of positions called methylated per contig-motif, not a mean or median of the fractions. Default: 0.5."
+    )]
+    pub call_threshold: Option<f64>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["emit_checksums", "raw_strand_wide"],
+        help = "TSV with the columns 'contig' and 'group' (e.g. bins). Positions of all contigs in a group are pooled and the methylation is written per group and motif with a 'group' column instead of 'contig'. Contigs without a group are left out."
+    )]
+    pub contig_groups: Option<PathBuf>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["contig_groups", "emit_checksums", "raw_strand_wide"],
+        help = "Write the methylation per motif in sliding windows of this size (bp) along each contig instead of per contig. Columns: contig, window_start, window_end, motif, mod_type, mod_position, methylation, n_obs."
+    )]
+    pub window_size: Option<usize>,
+
+    #[arg(
+        long,
+        requires = "window_size",
+        help = "Distance (bp) between the starts of consecutive windows. Defaults to '--window-size' (non-overlapping windows)."
+    )]
+    pub window_step: Option<usize>,
+
+    #[arg(
+        long,
+        requires = "window_size",
+        help = "Write windows without observations with this methylation value (e.g. NA). By default they are skipped."
+    )]
+    pub na_value: Option<String>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "With '--output-type raw', write one row per motif occurrence with plus and minus strand counts side by side (n_modified_plus, n_valid_cov_plus, n_modified_minus, n_valid_cov_minus). A strand without methylation is zero-filled."
+    )]
+    pub raw_strand_wide: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = RawFormat::Tsv,
+        conflicts_with_all = [
+            "raw_strand_wide",
+            "emit_checksums",
+            "emit_metadata_header",
+            "per_motif_output_dir"
+        ],
+        help = "Layout of the '--output-type raw' output. 'bedmethyl' writes an 18 column bedMethyl pileup in the column order of modkit without header, one record per position and mod type, which can be compressed with 'epimetheus bgzip compress' and read as a pileup again. Counts not kept by epimetheus are written as 0 and n_canonical as n_valid_cov - n_modified."
+    )]
+    pub raw_format: RawFormat,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Tsv,
+        conflicts_with_all = [
+            "raw_format",
+            "raw_strand_wide",
+            "compat_format",
+            "contig_groups",
+            "window_size",
+            "include_stderr",
+            "emit_metadata_header",
+            "emit_checksums",
+            "scale_255",
+            "percentage",
+            "background_motif",
+            "background_value",
+            "per_motif_output_dir"
+        ],
+        help = "File format of the output. 'json' writes an array of objects with the fields of the TSV columns, with methylation values as fractions and NaN as null. The raw output gets one object per contig and motif with arrays of the per position fields."
+    )]
+    pub format: OutputFormat,
+
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with_all = [
+            "output_type",
+            "contig_groups",
+            "window_size",
+            "raw_strand_wide",
+            "emit_checksums",
+            "emit_metadata_header"
+        ],
+        help = "Write the output in the column layout of another tool instead. 'nanomotif' writes its motifs-scored layout: contig, motif, mod_position, mod_type, n_mod and n_nomod (motif sites with methylation at or above and below '--methylated-threshold') and motif_type (palindrome, bipartite or non-palindrome)."
+    )]
+    pub compat_format: Option<CompatFormat>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["contig_groups", "window_size"],
+        help = "With '--output-type weighted-mean', add a 'stderr' column with the standard error sqrt(p * (1 - p) / n), where p is the weighted mean and n the pooled valid coverage of the motif positions."
+    )]
+    pub include_stderr: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["contig_groups", "window_size", "raw_strand_wide"],
+        help = "Begin the output with '#'-prefixed lines recording the command, version, motifs and parameters. Tools reading the TSV should skip lines starting with '#' (e.g. pandas 'comment=\"#\"', polars 'comment_prefix=\"#\"')."
+    )]
+    pub emit_metadata_header: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Write methylation values as integers scaled to 0-255 (fraction * 255, rounded half away from zero) instead of fractions. Does not affect raw output."
+    )]
+    pub scale_255: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "scale_255",
+        help = "Write methylation values as percentages (fraction * 100) instead of fractions. The methylation value column is named 'methylation_percent'. Does not affect raw output."
+    )]
+    pub percentage: bool,
+
+    #[arg(
+        long,
+        value_name = "MOTIF",
+        conflicts_with_all = [
+            "contig_groups",
+            "window_size",
+            "compat_format",
+            "raw_strand_wide",
+            "emit_checksums",
+            "per_motif_output_dir"
+        ],
+        help = "Control motif (e.g. 'CCWGG_m_1') whose methylation value in a contig is that contig's background. Adds the columns 'background' and 'methylation_adjusted' (methylation_value - background, clamped to 0-1). The motif must also be given with '--motifs'. Contigs without the control motif get NA in both columns."
+    )]
+    pub background_motif: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "background_motif",
+            "contig_groups",
+            "window_size",
+            "compat_format",
+            "raw_strand_wide",
+            "emit_checksums",
+            "per_motif_output_dir"
+        ],
+        help = "Fixed background methylation (0-1), e.g. a basecaller baseline, subtracted from every methylation value. Adds the columns 'background' and 'methylation_adjusted' (methylation_value - background, clamped to 0-1)."
+    )]
+    pub background_value: Option<f64>,
+
+    #[arg(
+        long,
+        default_value = "symbol",
+        help = "How the strand column of the raw output is written: 'symbol' (+/-), 'word' (fwd/rev) or 'numeric' (1/-1)."
+    )]
+    pub strand_format: StrandFormat,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Pool 5mC (m) and 5hmC (h) records at the same cytosine into one signal reported as mod type 'C'. Modified counts are summed over the shared valid coverage. A .bed pileup will be compressed to .bed.gz internally."
+    )]
+    pub combine_c_mods: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "raw_strand_wide",
+        help = "Merge the plus and minus strand observations of each site of a palindromic motif (e.g. GATC) into one observation by summing n_modified and n_valid_cov, since both strands describe the same site. Merged raw rows are reported on the plus strand. Motifs that are not palindromic are not affected. Cannot be used with '--output-type strand-asymmetry'."
+    )]
+    pub collapse_strands: bool,
+
+    #[arg(
+        long,
+        default_value_t = 1,
+        help = "Leave out contig-motifs observed at fewer than this many motif positions (n_motif_obs), since their methylation estimates are unreliable. Applies to all output types; raw output drops the positions of such contig-motifs. The number of dropped contig-motifs is logged. Contigs reported by '--report-missing' are kept."
+    )]
+    pub min_motif_observations: u32,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Warn about symmetric sites of palindromic motifs where the plus and minus strand records have no mod type in common (e.g. 'a' on plus and 'm' on minus), which points to a merged or mislabeled pileup. Sites are reported in '--warnings-json'. A .bed pileup will be compressed to .bed.gz internally."
+    )]
+    pub check_mod_type_consistency: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Report contigs in the index of a .bed.gz pileup without any records with zero observations (n_motif_obs 0, methylation_value NaN) instead of leaving them out. Applies to the median, weighted-mean, weighted-median and trimmed-mean outputs. The number of such contigs is logged either way."
+    )]
+    pub report_missing: bool,
+
+    #[arg(
+        long,
+        help = "Load the motif occurrences from this cache file instead of scanning the assembly. The cache is only used if its checksum matches the assembly and motifs. Otherwise the assembly is scanned and the cache is rewritten with a warning. A missing cache is written, so later runs on the same assembly and motifs skip the scan."
+    )]
+    pub motif_occurrence_cache: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Skip the final sort and write rows in processing order. Faster for large outputs, but the row order is not deterministic across runs."
+    )]
+    pub no_sort: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "no_sort",
+        help = "Hold all results and sort the whole output by contig before writing it. By default rows are streamed to the output as contigs finish, so memory does not grow with the number of contigs. Streamed rows are sorted per contig and written in contig order for a .bed.gz pileup, but in the order of the pileup for a .bed pileup. Outputs needing all results at once (reports, groups, windows, compat formats, wide raw output, bedMethyl or JSON output, background adjustment, summaries, per motif files or checksums) are never streamed."
+    )]
+    pub sorted: bool,
+
+    #[arg(
+        long,
+        help = "Write a per-contig checksum (XXH3-64 over the contig's sorted output rows) to this file. Useful for comparing runs at the contig level."
+    )]
+    pub emit_checksums: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["contig_groups", "window_size", "compat_format", "raw_strand_wide"],
+        help = "Also write one file per motif with its rows for all contigs to this directory, named <motif>_<mod_type>_<mod_position>.tsv, and a manifest.tsv listing the files and their row counts. Cannot be combined with '--contig-groups', '--window-size', '--compat-format' or '--raw-strand-wide'."
+    )]
+    pub per_motif_output_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Write a per contig-motif report of the plus and minus strand median methylation to this file. Bias is the absolute difference between the strands."
+    )]
+    pub strand_bias_report: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = 0.5,
+        requires = "strand_bias_report",
+        help = "Flag contig-motifs in the strand bias report with a bias above this threshold."
+    )]
+    pub bias_threshold: f64,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["contig_groups", "window_size", "compat_format"],
+        help = "Also write a per contig summary to this file: the number of motifs with methylation above '--summary-threshold', the mean methylation of the motifs and the total motif observations. Requires a degree output type and cannot be combined with '--contig-groups', '--window-size' or '--compat-format'."
+    )]
+    pub summary_output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = 0.9,
+        requires = "summary_output",
+        help = "Count a motif in the contig summary when its methylation is above this threshold."
+    )]
+    pub summary_threshold: f64,
+
+    #[arg(
+        long,
+        help = "Write a genome-wide report per motif to this file: total motif occurrences, observed positions, pooled methylation fraction, and the number of contigs with observations and with methylation above '--methylated-threshold'. Motifs without observations are reported with zeros."
+    )]
+    pub motif_report: Option<PathBuf>,
+
+    #[arg(
+        long,
+        requires = "motif_report",
+        help = "Add the motif length, GC content and degeneracy (number of DNA sequences the motif matches) to the motif report. Degenerate bases count the fraction of their nucleotides that are G or C."
+    )]
+    pub motif_report_metrics: bool,
+
+    #[arg(
+        long,
+        help = "Write every motif match in the assembly to this file with whether it had a methylation record passing the filters, its coverage and methylation fraction, and why it was excluded otherwise."
+    )]
+    pub dump_motif_matches: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = 0.5,
+        help = "Count a contig as methylated for a motif in the motif report when its methylation (median with '--output-type median', weighted median with 'weighted-median', trimmed mean with 'trimmed-mean', weighted mean otherwise) is above this threshold. With '--compat-format', count a motif site as methylated when its methylation is at or above this threshold."
+    )]
+    pub methylated_threshold: f64,
+
+    #[arg(
+        long,
+        help = "Write warnings (contig mismatches, filtered contigs, malformed records) as JSON lines to this file. The regular log is unchanged."
+    )]
+    pub warnings_json: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Write a histogram of the valid coverage of the pileup records per mod type to this file, to help choosing '--min-valid-read-coverage'. Records are counted before the filters. The n_records_at_least_min column is the number of records kept with '--min-valid-read-coverage' set to the bin's min_coverage."
+    )]
+    pub coverage_histogram: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_COVERAGE_BIN_WIDTH,
+        requires = "coverage_histogram",
+        help = "Width of the coverage histogram bins."
+    )]
+    pub coverage_histogram_bin_width: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThreadCount {
+    Auto,
+    Fixed(usize),
+}
+
+impl ThreadCount {
+    /// Resolves the number of threads. With `auto` the available cores are used,
+    /// capped by the number of contigs to avoid idle threads on small inputs.
+    pub fn resolve(&self, n_contigs: usize) -> usize {
+        match self {
+            ThreadCount::Fixed(n) => *n,
+            ThreadCount::Auto => {
+                let cores = std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1);
+                cores.min(n_contigs).max(1)
+            }
+        }
+    }
+}
+
+impl FromStr for ThreadCount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(ThreadCount::Auto);
+        }
+
+        match s.parse::<usize>() {
+            Ok(0) => Err("Number of threads should be at least 1".to_string()),
+            Ok(n) => Ok(ThreadCount::Fixed(n)),
+            Err(_) => Err(format!(
+                "Invalid thread count '{}'. Use a positive number or 'auto'",
+                s
+            )),
+        }
+    }
 }
 
 impl ContigMethylationPatternArgs {
     pub fn validate_filter(&self) -> anyhow::Result<()> {
-        if let Some(_contigs) = &self.contigs {
-            if self.pileup.extension().and_then(|s| s.to_str()) != Some("gz") {
+        if self.raw_strand_wide && !matches!(self.output_type, MethylationOutput::Raw) {
+            return Err(anyhow!("'--raw-strand-wide' requires '--output-type raw'."));
+        }
+
+        if self.collapse_strands && matches!(self.output_type, MethylationOutput::StrandAsymmetry) {
+            return Err(anyhow!(
+                "'--collapse-strands' merges the strands compared by '--output-type strand-asymmetry'."
+            ));
+        }
+
+        if self.raw_format == RawFormat::Bedmethyl
+            && !matches!(self.output_type, MethylationOutput::Raw)
+        {
+            return Err(anyhow!("'--raw-format bedmethyl' requires '--output-type raw'."));
+        }
+
+        if self.contig_groups.is_some()
+            && matches!(
+                self.output_type,
+                MethylationOutput::Raw
+                    | MethylationOutput::CalledFrequency
+                    | MethylationOutput::StrandAsymmetry
+            )
+        {
+            return Err(anyhow!(
+                "'--contig-groups' requires '--output-type median', 'weighted-mean', 'weighted-median' or 'trimmed-mean'."
+            ));
+        }
+
+        if let Some(window_size) = self.window_size {
+            if matches!(
+                self.output_type,
+                MethylationOutput::Raw
+                    | MethylationOutput::CalledFrequency
+                    | MethylationOutput::StrandAsymmetry
+            ) {
+                return Err(anyhow!(
+                    "'--window-size' requires '--output-type median', 'weighted-mean', 'weighted-median' or 'trimmed-mean'."
+                ));
+            }
+            if window_size == 0 || self.window_step == Some(0) {
+                return Err(anyhow!(
+                    "'--window-size' and '--window-step' should be at least 1."
+                ));
+            }
+        }
+
+        if let Some(call_threshold) = self.call_threshold {
+            if !matches!(self.output_type, MethylationOutput::CalledFrequency) {
+                return Err(anyhow!(
+                    "'--call-threshold' requires '--output-type called-frequency'."
+                ));
+            }
+            if !(0.0..=1.0).contains(&call_threshold) {
+                return Err(anyhow!(
+                    "'--call-threshold' should be between 0 and 1. Got: {}",
+                    call_threshold
+                ));
+            }
+        }
+
+        if (self.background_motif.is_some() || self.background_value.is_some())
+            && matches!(
+                self.output_type,
+                MethylationOutput::Raw | MethylationOutput::StrandAsymmetry
+            )
+        {
+            return Err(anyhow!(
+                "'--background-motif' and '--background-value' require '--output-type median', 'weighted-mean', 'weighted-median', 'trimmed-mean' or 'called-frequency'."
+            ));
+        }
+        if let Some(background_value) = self.background_value {
+            if !(0.0..=1.0).contains(&background_value) {
+                return Err(anyhow!(
+                    "'--background-value' should be between 0 and 1. Got: {}",
+                    background_value
+                ));
+            }
+        }
+
+        if self.include_stderr && !matches!(self.output_type, MethylationOutput::WeightedMean) {
+            return Err(anyhow!(
+                "'--include-stderr' requires '--output-type weighted-mean'."
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.bias_threshold) {
+            return Err(anyhow!(
+                "'--bias-threshold' should be between 0 and 1. Got: {}",
+                self.bias_threshold
+            ));
+        }
+
+        if self.summary_output.is_some()
+            && matches!(
+                self.output_type,
+                MethylationOutput::Raw | MethylationOutput::StrandAsymmetry
+            )
+        {
+            return Err(anyhow!(
+                "'--summary-output' requires '--output-type median', 'weighted-mean', 'weighted-median', 'trimmed-mean' or 'called-frequency'."
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.summary_threshold) {
+            return Err(anyhow!(
+                "'--summary-threshold' should be between 0 and 1. Got: {}",
+                self.summary_threshold
+            ));
+        }
+
+        if let Some(max_memory) = self.max_memory {
+            if max_memory.is_nan() || max_memory <= 0.0 {
+                return Err(anyhow!("'--max-memory' should be above 0. Got: {}", max_memory));
+            }
+        }
+
+        if self.coverage_histogram_bin_width == 0 {
+            return Err(anyhow!(
+                "'--coverage-histogram-bin-width' should be at least 1."
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.methylated_threshold) {
+            return Err(anyhow!(
+                "'--methylated-threshold' should be between 0 and 1. Got: {}",
+                self.methylated_threshold
+            ));
+        }
+
+        if self.contigs.is_some() && self.contigs_pattern.is_some() {
+            return Err(anyhow!(
+                "'--contigs' and '--contigs-pattern' cannot be used together. Use one of them."
+            ));
+        }
+        self.contigs_regex()?;
+
+        if self.pileup.len() > 1 {
+            if let Some(pileup) = self
+                .pileup
+                .iter()
+                .find(|pileup| pileup.extension().and_then(|s| s.to_str()) != Some("gz"))
+            {
                 return Err(anyhow!(
-                    "Pileup must be tabix compressed to use the contig filter."
+                    "Multiple pileups are merged per contig and should all be .bed.gz. Got: {}",
+                    pileup.display()
+                ));
+            }
+        }
+
+        if let Some(intermediate) = &self.keep_intermediate_gz {
+            if !self.requires_internal_compression() {
+                return Err(anyhow!(
+                    "'--keep-intermediate-gz' is only used when a .bed pileup is compressed internally ('--contigs', '--contigs-pattern' or '--combine-c-mods')."
+                ));
+            }
+            if intermediate.extension().and_then(|s| s.to_str()) != Some("gz") {
+                return Err(anyhow!(
+                    "Intermediate file should have bed.gz extension. Got: {}",
+                    intermediate.display()
                 ));
             }
         }
 
         Ok(())
     }
+
+    /// The memory limit in bytes.
+    pub fn max_memory_bytes(&self) -> Option<u64> {
+        self.max_memory.map(|gb| (gb * 1e9) as u64)
+    }
+
+    /// A plain .bed pileup cannot be queried by contig. When a contig filter is
+    /// supplied, or records of a contig must be combined or checked together, the
+    /// pileup is therefore compressed to .bed.gz before processing.
+    pub fn requires_internal_compression(&self) -> bool {
+        (self.contigs.is_some()
+            || self.contigs_pattern.is_some()
+            || self.combine_c_mods
+            || self.check_mod_type_consistency)
+            && self.pileup.len() == 1
+            && self.pileup[0].extension().and_then(|s| s.to_str()) == Some("bed")
+    }
+
+    /// Lines for the output metadata header. Written without the `#` prefix, which
+    /// is added by the writer.
+    pub fn metadata_header(&self) -> Vec<String> {
+        let command = std::env::args().collect::<Vec<_>>().join(" ");
+        vec![
+            format!("command: {}", command),
+            format!("version: {}", env!("CARGO_PKG_VERSION")),
+            format!(
+                "pileup: {}",
+                self.pileup
+                    .iter()
+                    .map(|pileup| pileup.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            format!("assembly: {}", self.assembly.display()),
+            match &self.motifs_file {
+                Some(motifs_file) => format!("motifs_file: {}", motifs_file.display()),
+                None => format!("motifs: {}", self.motifs.join(" ")),
+            },
+            format!("output_type: {}", self.output_type.to_string()),
+            format!("min_valid_read_coverage: {}", self.min_valid_read_coverage),
+            format!(
+                "min_valid_cov_to_diff_fraction: {}",
+                self.min_valid_cov_to_diff_fraction
+            ),
+            format!("min_score: {}", self.min_score),
+            format!("coverage_field: {}", self.coverage_field),
+            format!("allow_mismatch: {}", self.allow_mismatch),
+            format!("combine_c_mods: {}", self.combine_c_mods),
+            format!("collapse_strands: {}", self.collapse_strands),
+            format!("min_motif_observations: {}", self.min_motif_observations),
+            format!("dedup_revcomp_motifs: {}", self.dedup_revcomp_motifs),
+            format!("scale_255: {}", self.scale_255),
+            format!("percentage: {}", self.percentage),
+        ]
+    }
+
+    /// How methylation values are written, from '--scale-255' and '--percentage'.
+    pub fn value_scale(&self) -> ValueScale {
+        if self.scale_255 {
+            ValueScale::Scale255
+        } else if self.percentage {
+            ValueScale::Percent
+        } else {
+            ValueScale::Fraction
+        }
+    }
+
+    /// The motifs of `--motifs`, or of `--motifs-file` if given.
+    pub fn resolve_motifs(&self) -> anyhow::Result<Vec<String>> {
+        let Some(motifs_file) = &self.motifs_file else {
+            return Ok(self.motifs.clone());
+        };
+
+        let file = File::open(motifs_file)
+            .with_context(|| format!("Failed to open motifs file: {}", motifs_file.display()))?;
+        let reader = BufReader::new(file);
+
+        let mut motifs = Vec::new();
+        for line_result in reader.lines() {
+            let line = line_result?;
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                motifs.push(trimmed.to_string());
+            }
+        }
+
+        if motifs.is_empty() {
+            bail!("No motifs found in file: {}", motifs_file.display());
+        }
+        Ok(motifs)
+    }
+
+    pub fn contigs_regex(&self) -> anyhow::Result<Option<Regex>> {
+        self.contigs_pattern
+            .as_deref()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .map_err(|e| anyhow!("Invalid '--contigs-pattern' regex '{}': {}", pattern, e))
+            })
+            .transpose()
+    }
 }
 
 #[derive(Parser, Debug, Clone)]
 pub struct BamReadMethylationPatternArgs {
-    #[arg(short, long, required = true, help = "Path to bam file.")]
+    #[arg(
+        short,
+        long,
+        required = true,
+        help = "Path to bam file. Files ending in .cram are read as CRAM and need --reference."
+    )]
     pub bam: PathBuf,
 
+    #[arg(
+        long,
+        help = "Indexed reference FASTA used to decode CRAM input. Not needed for BAM."
+    )]
+    pub reference: Option<PathBuf>,
+
     #[arg(short, long, required = true, help = "Path to assembly file.")]
     pub assembly: PathBuf,
 
@@ -104,11 +834,36 @@ pub struct BamReadMethylationPatternArgs {
     )]
     pub output: PathBuf,
 
-    #[arg(short, long, default_value_t = 1, help = "Number of parallel tasks.")]
+    #[arg(
+        short,
+        long,
+        default_value_t = 1,
+        help = "Number of threads. One thread writes the output and the others process contigs, so at least 2 are needed for parallel processing."
+    )]
     pub threads: usize,
 
-    #[arg(short, long, required = true, num_args(1..), help = "Supply chain of motifs as <motif>_<mod_type>_<mod_position>. Example: '-m GATC_a_1 RGATCY_a_2'")]
+    #[arg(short, long, required = true, num_args(1..), value_delimiter = ',', help = "Supply chain of motifs as <motif>_<mod_type>_<mod_position>, separated by spaces or commas. Example: '-m GATC_a_1 RGATCY_a_2' or '-m GATC_a_1,RGATCY_a_2'")]
     pub motifs: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Process at most this many reads per contig, for quick checks of deep BAMs. Secondary alignments and reads below --min-mapping-quality are skipped before counting."
+    )]
+    pub max_reads_per_contig: Option<NonZeroUsize>,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "Skip reads with a mapping quality below this. Skipped reads do not count towards --max-reads-per-contig."
+    )]
+    pub min_mapping_quality: u8,
+
+    #[arg(
+        long,
+        default_value = "symbol",
+        help = "How the strand column is written: 'symbol' (+/-), 'word' (fwd/rev) or 'numeric' (1/-1)."
+    )]
+    pub strand_format: StrandFormat,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -130,6 +885,133 @@ pub struct FastqReadMethylationPatternArgs {
     #[arg(short, long, default_value_t = 1, help = "Number of parallel tasks.")]
     pub threads: usize,
 
-    #[arg(short, long, required = true, num_args(1..), help = "Supply chain of motifs as <motif>_<mod_type>_<mod_position>. Example: '-m GATC_a_1 RGATCY_a_2'")]
+    #[arg(short, long, required = true, num_args(1..), value_delimiter = ',', help = "Supply chain of motifs as <motif>_<mod_type>_<mod_position>, separated by spaces or commas. Example: '-m GATC_a_1 RGATCY_a_2' or '-m GATC_a_1,RGATCY_a_2'")]
     pub motifs: Vec<String>,
+
+    #[arg(
+        long,
+        default_value = "symbol",
+        help = "How the strand column is written: 'symbol' (+/-), 'word' (fwd/rev) or 'numeric' (1/-1)."
+    )]
+    pub strand_format: StrandFormat,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_count_from_str() {
+        assert_eq!(ThreadCount::from_str("auto"), Ok(ThreadCount::Auto));
+        assert_eq!(ThreadCount::from_str("4"), Ok(ThreadCount::Fixed(4)));
+        assert!(ThreadCount::from_str("0").is_err());
+        assert!(ThreadCount::from_str("many").is_err());
+    }
+
+    #[test]
+    fn test_thread_count_auto_is_capped_by_contigs() {
+        assert!(ThreadCount::Auto.resolve(2) <= 2);
+        assert_eq!(ThreadCount::Auto.resolve(1), 1);
+        assert_eq!(ThreadCount::Auto.resolve(0), 1);
+        assert_eq!(ThreadCount::Fixed(8).resolve(2), 8);
+    }
+
+    #[test]
+    fn test_motifs_accept_comma_separated_and_mixed() {
+        let args = ContigMethylationPatternArgs::try_parse_from([
+            "contig",
+            "--pileup",
+            "pileup.bed.gz",
+            "--assembly",
+            "assembly.fa",
+            "--output",
+            "out.tsv",
+            "-m",
+            "GATC_a_1,RGATCY_a_2",
+            "CCWGG_m_1",
+        ])
+        .unwrap();
+
+        assert_eq!(args.motifs, vec!["GATC_a_1", "RGATCY_a_2", "CCWGG_m_1"]);
+    }
+
+    #[test]
+    fn test_motifs_file() -> anyhow::Result<()> {
+        use std::io::Write;
+
+        let mut motifs_file = tempfile::NamedTempFile::new()?;
+        writeln!(
+            motifs_file,
+            "# Motifs of bin 1\nGATC_a_1\n\n  RGATCY_a_2  \nCCWGG_m_1"
+        )?;
+        let base_args = [
+            "contig",
+            "--pileup",
+            "pileup.bed.gz",
+            "--assembly",
+            "assembly.fa",
+            "--output",
+            "out.tsv",
+        ];
+
+        let args = ContigMethylationPatternArgs::try_parse_from(
+            base_args
+                .iter()
+                .copied()
+                .chain(["--motifs-file", motifs_file.path().to_str().unwrap()]),
+        )?;
+        assert_eq!(
+            args.resolve_motifs()?,
+            vec!["GATC_a_1", "RGATCY_a_2", "CCWGG_m_1"]
+        );
+
+        let both = ContigMethylationPatternArgs::try_parse_from(base_args.iter().copied().chain([
+            "-m",
+            "GATC_a_1",
+            "--motifs-file",
+            motifs_file.path().to_str().unwrap(),
+        ]));
+        assert!(both.is_err());
+
+        let empty_file = tempfile::NamedTempFile::new()?;
+        let args = ContigMethylationPatternArgs::try_parse_from(
+            base_args
+                .iter()
+                .copied()
+                .chain(["--motifs-file", empty_file.path().to_str().unwrap()]),
+        )?;
+        assert!(args.resolve_motifs().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_pileups_must_be_bed_gz() {
+        let parse = |pileups: &[&str]| {
+            let base_args = [
+                "contig",
+                "-a",
+                "assembly.fa",
+                "-o",
+                "out.tsv",
+                "-m",
+                "GATC_a_1",
+            ];
+            ContigMethylationPatternArgs::try_parse_from(
+                base_args
+                    .into_iter()
+                    .chain(["-p"])
+                    .chain(pileups.iter().copied()),
+            )
+            .unwrap()
+        };
+
+        let args = parse(&["flowcell_1.bed.gz", "flowcell_2.bed.gz"]);
+        assert_eq!(args.pileup.len(), 2);
+        assert!(args.validate_filter().is_ok());
+
+        let mixed = parse(&["flowcell_1.bed.gz", "flowcell_2.bed"]);
+        assert!(mixed.validate_filter().is_err());
+        assert!(parse(&["pileup.bed"]).validate_filter().is_ok());
+    }
 }
@@ -0,0 +1,128 @@
+use std::{fs::File, path::Path};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use epimetheus_core::models::methylation::{MotifSiteCounts, MotifType};
+use polars::prelude::*;
+
+/// Output layouts of other tools the contig methylation can be written in.
+///
+/// Each format maps epimetheus columns to the column names of the tool. The epimetheus
+/// columns are built once in [`site_counts_dataframe`], so a new format only needs a
+/// variant and its column mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompatFormat {
+    /// The `motifs-scored.tsv` layout of nanomotif.
+    Nanomotif,
+}
+
+impl CompatFormat {
+    /// Pairs of (epimetheus column, column in the tool) in the column order of the tool.
+    ///
+    /// nanomotif:
+    /// - contig -> contig
+    /// - motif -> motif
+    /// - mod_position -> mod_position
+    /// - mod_type -> mod_type
+    /// - n_methylated -> n_mod
+    /// - n_unmethylated -> n_nomod
+    /// - motif_type -> motif_type
+    fn columns(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Nanomotif => &[
+                ("contig", "contig"),
+                ("motif", "motif"),
+                ("mod_position", "mod_position"),
+                ("mod_type", "mod_type"),
+                ("n_methylated", "n_mod"),
+                ("n_unmethylated", "n_nomod"),
+                ("motif_type", "motif_type"),
+            ],
+        }
+    }
+
+    pub fn to_dataframe(&self, counts: &[MotifSiteCounts]) -> Result<DataFrame> {
+        let columns = self.columns();
+        let mut df = site_counts_dataframe(counts)?
+            .select(columns.iter().map(|(source, _)| *source))?;
+
+        for (source, target) in columns {
+            if source != target {
+                df.rename(source, (*target).into())?;
+            }
+        }
+
+        Ok(df)
+    }
+}
+
+/// Site counts with epimetheus column names, one row per contig-motif.
+fn site_counts_dataframe(counts: &[MotifSiteCounts]) -> Result<DataFrame> {
+    let df = df![
+        "contig" => counts.iter().map(|c| c.contig.clone()).collect::<Vec<String>>(),
+        "motif" => counts.iter().map(|c| c.motif.sequence_to_string()).collect::<Vec<String>>(),
+        "mod_type" => counts.iter().map(|c| c.motif.mod_type.to_pileup_code()).collect::<Vec<&str>>(),
+        "mod_position" => counts.iter().map(|c| c.motif.mod_position as u32).collect::<Vec<u32>>(),
+        "n_methylated" => counts.iter().map(|c| c.n_methylated).collect::<Vec<u32>>(),
+        "n_unmethylated" => counts.iter().map(|c| c.n_unmethylated).collect::<Vec<u32>>(),
+        "motif_type" => counts.iter().map(motif_type).collect::<Vec<&str>>(),
+    ]?;
+
+    Ok(df)
+}
+
+/// Motif class as named by nanomotif: `palindrome`, `bipartite` or `non-palindrome`.
+fn motif_type(counts: &MotifSiteCounts) -> &'static str {
+    match counts.motif.motif_type() {
+        MotifType::ShortPalindrome => "palindrome",
+        MotifType::ShortNonPalindrome => "non-palindrome",
+        MotifType::Bipartite => "bipartite",
+    }
+}
+
+/// Writes the site counts as TSV in the layout of `format`.
+pub fn write_compat_output<P: AsRef<Path>>(
+    format: CompatFormat,
+    counts: &[MotifSiteCounts],
+    path: P,
+) -> Result<()> {
+    let mut df = format.to_dataframe(counts)?;
+    let mut file = File::create(path)?;
+    CsvWriter::new(&mut file)
+        .with_separator(b'\t')
+        .finish(&mut df)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epimetheus_core::services::domain::motif_processor::create_motifs;
+
+    #[test]
+    fn test_nanomotif_columns() -> Result<()> {
+        let motifs = create_motifs(&vec!["GATC_a_1".to_string(), "TCCCG_m_1".to_string()])?;
+        let counts: Vec<MotifSiteCounts> = motifs
+            .into_iter()
+            .map(|motif| MotifSiteCounts {
+                contig: "contig_1".to_string(),
+                motif,
+                n_methylated: 3,
+                n_unmethylated: 1,
+            })
+            .collect();
+
+        let df = CompatFormat::Nanomotif.to_dataframe(&counts)?;
+        let names: Vec<&str> = df.get_column_names().iter().map(|name| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["contig", "motif", "mod_position", "mod_type", "n_mod", "n_nomod", "motif_type"]
+        );
+
+        let motif_types: Vec<Option<&str>> = df.column("motif_type")?.str()?.into_iter().collect();
+        assert_eq!(motif_types, vec![Some("palindrome"), Some("non-palindrome")]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{ArgMatches, ValueEnum, parser::ValueSource};
+use epimetheus_core::models::methylation::MethylationOutput;
+use serde::Deserialize;
+
+use crate::argparser::{Args, Commands};
+use crate::commands::extract_methylation_pattern::{
+    SequenceCommand, args::ContigMethylationPatternArgs,
+};
+
+/// Run parameters of `methylation-pattern contig` read from a JSON or YAML file.
+///
+/// Keys are the long flag names with underscores. Precedence is CLI > config >
+/// defaults: a value is only taken from the config when its flag is not given on the
+/// command line.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MethylationPatternConfig {
+    pub motifs: Option<Vec<String>>,
+    pub min_valid_read_coverage: Option<u32>,
+    pub min_valid_cov_to_diff_fraction: Option<f32>,
+    pub methylated_threshold: Option<f64>,
+    pub bias_threshold: Option<f64>,
+    pub output_type: Option<String>,
+}
+
+impl MethylationPatternConfig {
+    /// Reads a .json, .yaml or .yml config.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read config: {}", path.display()))?;
+
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("json") => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid JSON config: {}", path.display())),
+            Some("yaml") | Some("yml") => serde_yaml_ng::from_str(&content)
+                .with_context(|| format!("Invalid YAML config: {}", path.display())),
+            _ => bail!(
+                "Config should have a .json, .yaml or .yml extension. Got: {}",
+                path.display()
+            ),
+        }
+    }
+
+    /// Sets the arguments given in the config whose flags were not given on the
+    /// command line.
+    pub fn apply(
+        &self,
+        args: &mut ContigMethylationPatternArgs,
+        matches: &ArgMatches,
+    ) -> Result<()> {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if let Some(motifs) = &self.motifs {
+            if !from_cli("motifs") && args.motifs_file.is_none() {
+                args.motifs = motifs.clone();
+            }
+        }
+        if let Some(value) = self.min_valid_read_coverage {
+            if !from_cli("min_valid_read_coverage") {
+                args.min_valid_read_coverage = value;
+            }
+        }
+        if let Some(value) = self.min_valid_cov_to_diff_fraction {
+            if !from_cli("min_valid_cov_to_diff_fraction") {
+                args.min_valid_cov_to_diff_fraction = value;
+            }
+        }
+        if let Some(value) = self.methylated_threshold {
+            if !from_cli("methylated_threshold") {
+                args.methylated_threshold = value;
+            }
+        }
+        if let Some(value) = self.bias_threshold {
+            if !from_cli("bias_threshold") {
+                args.bias_threshold = value;
+            }
+        }
+        if let Some(value) = &self.output_type {
+            if !from_cli("output_type") {
+                args.output_type =
+                    <MethylationOutput as ValueEnum>::from_str(&value.replace('_', "-"), true)
+                        .map_err(|e| anyhow!("Invalid output_type in config: {}", e))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Applies the `--config` of `methylation-pattern contig`, if given.
+pub fn apply_config(args: &mut Args, matches: &ArgMatches) -> Result<()> {
+    let Commands::MethylationPattern(methylation_args) = &mut args.command else {
+        return Ok(());
+    };
+    let SequenceCommand::Contig(contig_args) = &mut methylation_args.commands else {
+        return Ok(());
+    };
+    let Some(config_path) = contig_args.config.clone() else {
+        return Ok(());
+    };
+
+    let contig_matches = matches
+        .subcommand_matches("methylation-pattern")
+        .and_then(|m| m.subcommand_matches("contig"))
+        .context("Could not find the arguments of 'methylation-pattern contig'")?;
+
+    MethylationPatternConfig::from_path(&config_path)?.apply(contig_args, contig_matches)?;
+    if contig_args.motifs.is_empty() && contig_args.motifs_file.is_none() {
+        bail!(
+            "No motifs given. Supply '--motifs', '--motifs-file' or 'motifs' in the config: {}",
+            config_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+    use std::io::Write;
+
+    fn parse_contig_args(args: &[&str]) -> Result<(ContigMethylationPatternArgs, ArgMatches)> {
+        let matches = ContigMethylationPatternArgs::command().try_get_matches_from(
+            ["contig", "-p", "pileup.bed.gz", "-a", "assembly.fa", "-o", "out.tsv"]
+                .iter()
+                .chain(args),
+        )?;
+        let args = ContigMethylationPatternArgs::from_arg_matches(&matches)?;
+        Ok((args, matches))
+    }
+
+    #[test]
+    fn test_config_precedence() -> Result<()> {
+        let mut config_file = tempfile::Builder::new().suffix(".json").tempfile()?;
+        write!(
+            config_file,
+            r#"{{
+                "motifs": ["GATC_a_1", "CCWGG_m_1"],
+                "min_valid_read_coverage": 10,
+                "methylated_threshold": 0.7,
+                "output_type": "weighted_mean"
+            }}"#
+        )?;
+        config_file.flush()?;
+        let config_path = config_file.path().to_str().unwrap();
+
+        // The CLI overrides the config, the config overrides the defaults.
+        let (mut args, matches) = parse_contig_args(&[
+            "--config",
+            config_path,
+            "--min-valid-read-coverage",
+            "5",
+        ])?;
+        MethylationPatternConfig::from_path(config_file.path())?.apply(&mut args, &matches)?;
+
+        assert_eq!(args.motifs, vec!["GATC_a_1", "CCWGG_m_1"]);
+        assert_eq!(args.min_valid_read_coverage, 5);
+        assert_eq!(args.methylated_threshold, 0.7);
+        assert!(matches!(args.output_type, MethylationOutput::WeightedMean));
+        assert_eq!(args.min_valid_cov_to_diff_fraction, 0.8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_config() -> Result<()> {
+        let mut config_file = tempfile::Builder::new().suffix(".yaml").tempfile()?;
+        writeln!(config_file, "motifs:\n  - GATC_a_1\noutput_type: raw")?;
+        config_file.flush()?;
+
+        let config = MethylationPatternConfig::from_path(config_file.path())?;
+        assert_eq!(
+            config,
+            MethylationPatternConfig {
+                motifs: Some(vec!["GATC_a_1".to_string()]),
+                output_type: Some("raw".to_string()),
+                ..Default::default()
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_keys() -> Result<()> {
+        let mut config_file = tempfile::Builder::new().suffix(".json").tempfile()?;
+        write!(config_file, r#"{{"motif": ["GATC_a_1"]}}"#)?;
+        config_file.flush()?;
+
+        assert!(MethylationPatternConfig::from_path(config_file.path()).is_err());
+
+        Ok(())
+    }
+}
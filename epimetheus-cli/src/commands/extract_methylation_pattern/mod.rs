@@ -1,4 +1,6 @@
 pub mod args;
+pub mod compat;
+pub mod config;
 
 use clap::{Args, Subcommand};
 
@@ -12,6 +12,6 @@ pub struct MotifClusteringArgs {
     )]
     pub output: PathBuf,
 
-    #[arg(short, long, required = true, num_args(1..), help = "Supply chain of motifs as <motif>_<mod_type>_<mod_position>. Example: '-m GATC_a_1 RGATCY_a_2'")]
+    #[arg(short, long, required = true, num_args(1..), value_delimiter = ',', help = "Supply chain of motifs as <motif>_<mod_type>_<mod_position>, separated by spaces or commas. Example: '-m GATC_a_1 RGATCY_a_2' or '-m GATC_a_1,RGATCY_a_2'")]
     pub motifs: Vec<String>,
 }
@@ -1,15 +1,43 @@
-use anyhow::{Result, bail};
-use clap::Parser;
+use ahash::AHashMap;
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{CommandFactory, FromArgMatches};
+use epimetheus_core::algorithms::methylation_pattern::{
+    count_contig_motif_occurrences, find_motif_sites,
+};
+use epimetheus_core::io::writers::{
+    methylation_pattern::{write_output_streaming, write_per_motif_output},
+    reports::{
+        write_group_output, write_motif_matches, write_motif_occurrence_counts,
+        write_motif_report, write_raw_strand_wide, write_strand_bias_report, write_window_output,
+    },
+};
+use epimetheus_core::models::methylation::{
+    DEFAULT_CALL_THRESHOLD, MethylationBackground, MethylationOutput, MethylationPatternVariant,
+    OutputFormat, RawFormat,
+};
 use epimetheus_core::services::{
-    application::motif_clustering_service::motif_clustering, domain::motif_processor::create_motifs,
+    application::motif_clustering_service::motif_clustering,
+    domain::{
+        motif_processor::{
+            check_motifs, combine_bipartite_motifs, create_motifs,
+            create_motifs_with_mod_base_map, dedup_reverse_complement_motifs,
+        },
+        pileup_service::combine_cytosine_motifs,
+        warning_collector::enable_warning_collection,
+    },
 };
 
+use epimetheus_io::io::readers::bgzf_bed::{check_index_age, count_bgzf_blocks};
+use epimetheus_io::io::readers::contig_groups::read_contig_groups;
 use epimetheus_io::io::traits::*;
 use epimetheus_io::services::compression_service::CompressorService;
 use epimetheus_io::services::decompression_service::extract_from_pileup;
 
+use epimetheus_orchestration::contig_summary_service::{
+    summarize_methylation_pattern, write_contig_summary,
+};
 use epimetheus_orchestration::extract_methylation_pattern_service::{
-    MethylationInput, extract_methylation_pattern,
+    MethylationInput, MethylationPatternOptions, extract_methylation_pattern, read_parquet_pileup,
 };
 use epimetheus_orchestration::extract_read_methylation_service::{
     extract_read_methylation_pattern, extract_read_methylation_pattern_fastq,
@@ -21,6 +49,9 @@ use polars::io::csv::write::CsvWriter;
 use polars::prelude::*;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::num::NonZeroUsize;
+use std::sync::mpsc;
+use std::thread;
 use std::time::Instant;
 
 mod argparser;
@@ -30,27 +61,79 @@ use argparser::Args;
 
 pub use crate::commands::compression::args::BgZipCommands;
 use crate::commands::extract_methylation_pattern::SequenceCommand;
-use crate::utils::create_output_file;
+use crate::commands::extract_methylation_pattern::compat::write_compat_output;
+use crate::commands::extract_methylation_pattern::config::apply_config;
+use crate::utils::{compress_pileup_for_lookup, create_output_file};
 
 fn main() -> Result<()> {
     // let guard = pprof::ProfilerGuard::new(1000).unwrap();
     let total_duration = Instant::now();
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    apply_config(&mut args, &matches)?;
 
     match args.command {
         argparser::Commands::MethylationPattern(generic_methyl_args) => {
             match &generic_methyl_args.commands {
                 SequenceCommand::Contig(methyl_args) => {
+                    let mod_base_map = methyl_args.mod_base_map.clone().unwrap_or_default();
+                    for code in mod_base_map.unsupported_codes() {
+                        warn!(
+                            "Mod code '{}' in '--mod-base-map' is not a supported mod type and is ignored.",
+                            code
+                        );
+                    }
+
+                    let motif_args = methyl_args.resolve_motifs()?;
+
+                    if methyl_args.check_motifs {
+                        let problems = check_motifs(&motif_args, &mod_base_map);
+                        for (motif, problem) in &problems {
+                            warn!("Motif '{}': {}", motif, problem);
+                        }
+                        if !problems.is_empty() {
+                            bail!(
+                                "{} of {} motifs have problems",
+                                problems.len(),
+                                motif_args.len()
+                            );
+                        }
+                        info!("All {} motifs are valid", motif_args.len());
+                        return Ok(());
+                    }
+
                     create_output_file(&methyl_args.output)?;
 
-                    let motifs = create_motifs(&methyl_args.motifs)?;
+                    let warnings = methyl_args
+                        .warnings_json
+                        .as_deref()
+                        .map(|path| (enable_warning_collection(), path));
+
+                    let mut motifs = create_motifs_with_mod_base_map(&motif_args, &mod_base_map)?;
+                    if methyl_args.dedup_revcomp_motifs {
+                        let n_motifs = motifs.len();
+                        motifs = dedup_reverse_complement_motifs(motifs);
+                        info!(
+                            "Removed {} reverse complement motifs",
+                            n_motifs - motifs.len()
+                        );
+                    }
 
-                    if methyl_args.contigs.is_some() {
-                        methyl_args.validate_filter()?;
+                    if matches!(methyl_args.output_type, MethylationOutput::StrandAsymmetry) {
+                        for motif in motifs.iter().filter(|motif| !motif.is_palindromic()) {
+                            warn!(
+                                "Motif '{}_{}_{}' is not palindromic and is skipped in the strand asymmetry output.",
+                                motif.sequence_to_string(),
+                                motif.mod_type.to_pileup_code(),
+                                motif.mod_position
+                            );
+                        }
                     }
-                    let contigs = if let Some(contigs_filter) = &methyl_args.contigs {
+
+                    methyl_args.validate_filter()?;
+                    let mut contigs = if let Some(contigs_filter) = &methyl_args.contigs {
                         info!("Loading assembly - specified contigs provided");
                         epimetheus_io::io::readers::fasta::Reader::read_fasta(
                             &methyl_args.assembly,
@@ -64,36 +147,512 @@ fn main() -> Result<()> {
                         )?
                     };
 
+                    if let Some(pattern) = methyl_args.contigs_regex()? {
+                        contigs.retain(|contig_id, _| pattern.is_match(contig_id));
+                        info!(
+                            "Found {} contigs matching pattern '{}'",
+                            contigs.len(),
+                            pattern.as_str()
+                        );
+                    }
+
                     if contigs.len() == 0 {
                         bail!("No contigs found in assembly");
                     }
 
-                    let ext = methyl_args.pileup.extension().and_then(|s| s.to_str());
-                    let input = if ext == Some("gz") {
-                        MethylationInput::GzFile(methyl_args.pileup.clone())
+                    if methyl_args.count_only {
+                        let counts = count_contig_motif_occurrences(&contigs, &motifs);
+                        write_motif_occurrence_counts(&counts, &methyl_args.output)?;
+                        info!(
+                            "Written occurrences of {} motifs in {} contigs to: {}",
+                            motifs.len(),
+                            contigs.len(),
+                            methyl_args.output.display()
+                        );
+                        return Ok(());
+                    }
+
+                    let threads = methyl_args.threads.resolve(contigs.len());
+                    info!("Using {} threads", threads);
+
+                    // The temporary intermediate must outlive the methylation extraction.
+                    let pileup = &methyl_args.pileup[0];
+                    let ext = pileup.extension().and_then(|s| s.to_str());
+                    let (input, _intermediate_dir) = if methyl_args.pileup.len() > 1 {
+                        for pileup in &methyl_args.pileup {
+                            check_index_age(pileup, methyl_args.strict_index)?;
+                        }
+                        info!("Merging {} pileups per contig", methyl_args.pileup.len());
+                        (MethylationInput::GzFiles(methyl_args.pileup.clone()), None)
+                    } else if methyl_args.requires_internal_compression() {
+                        let (gz_path, tmp_dir) = compress_pileup_for_lookup(
+                            pileup,
+                            methyl_args.keep_intermediate_gz.as_deref(),
+                        )?;
+                        (MethylationInput::GzFile(gz_path), tmp_dir)
+                    } else if ext == Some("gz") {
+                        check_index_age(pileup, methyl_args.strict_index)?;
+                        (MethylationInput::GzFile(pileup.clone()), None)
+                    } else if ext == Some("parquet") {
+                        info!("Reading parquet pileup");
+                        (
+                            MethylationInput::DataFrame(read_parquet_pileup(pileup)?),
+                            None,
+                        )
                     } else if ext == Some("bed") {
-                        MethylationInput::BedFile(
-                            methyl_args.pileup.clone(),
-                            methyl_args.batch_size,
+                        (
+                            MethylationInput::BedFile(pileup.clone(), methyl_args.batch_size),
+                            None,
                         )
                     } else {
                         bail!("Unsupported file type")
                     };
 
+                    let contig_groups = match &methyl_args.contig_groups {
+                        Some(path) => {
+                            let contig_groups = read_contig_groups(path)?;
+                            let n_ungrouped = contigs
+                                .keys()
+                                .filter(|contig_id| !contig_groups.contains_key(*contig_id))
+                                .count();
+                            if n_ungrouped > 0 {
+                                warn!(
+                                    "{} contigs have no group in '{}' and are left out",
+                                    n_ungrouped,
+                                    path.display()
+                                );
+                            }
+                            Some(contig_groups)
+                        }
+                        None => None,
+                    };
+
+                    let contig_lengths: Option<AHashMap<String, usize>> =
+                        methyl_args.window_size.map(|_| {
+                            contigs
+                                .iter()
+                                .map(|(contig_id, contig)| {
+                                    (contig_id.clone(), contig.sequence.len())
+                                })
+                                .collect()
+                        });
+
+                    // The strand bias report, motif report, motif matches, contig groups,
+                    // windows and compat formats need the per position methylation, so the raw
+                    // pattern is extracted and aggregated afterwards.
+                    let needs_positions = methyl_args.strand_bias_report.is_some()
+                        || methyl_args.motif_report.is_some()
+                        || methyl_args.dump_motif_matches.is_some()
+                        || contig_groups.is_some()
+                        || contig_lengths.is_some()
+                        || methyl_args.compat_format.is_some();
+                    let extraction_output_type = if needs_positions {
+                        MethylationOutput::Raw
+                    } else {
+                        methyl_args.output_type.clone()
+                    };
+
+                    // The output is written while contigs finish, so memory does not grow
+                    // with the number of contigs, unless it needs all results at once or
+                    // a full sort is asked for.
+                    let held_by: Vec<&str> = [
+                        (methyl_args.sorted, "--sorted"),
+                        (methyl_args.strand_bias_report.is_some(), "--strand-bias-report"),
+                        (methyl_args.motif_report.is_some(), "--motif-report"),
+                        (methyl_args.dump_motif_matches.is_some(), "--dump-motif-matches"),
+                        (contig_groups.is_some(), "--contig-groups"),
+                        (contig_lengths.is_some(), "--window-size"),
+                        (methyl_args.compat_format.is_some(), "--compat-format"),
+                        (methyl_args.raw_strand_wide, "--raw-strand-wide"),
+                        (methyl_args.emit_checksums.is_some(), "--emit-checksums"),
+                        (methyl_args.summary_output.is_some(), "--summary-output"),
+                        (methyl_args.per_motif_output_dir.is_some(), "--per-motif-output-dir"),
+                        (methyl_args.background_motif.is_some(), "--background-motif"),
+                        (methyl_args.background_value.is_some(), "--background-value"),
+                        (methyl_args.raw_format != RawFormat::Tsv, "--raw-format"),
+                        (methyl_args.format != OutputFormat::Tsv, "--format"),
+                    ]
+                    .into_iter()
+                    .filter_map(|(holds, flag)| holds.then_some(flag))
+                    .collect();
+                    if !held_by.is_empty() {
+                        info!(
+                            "Holding all results before writing the output, as required by {}",
+                            held_by.join(", ")
+                        );
+                    }
+                    let (output_stream, output_writer) = if held_by.is_empty() {
+                        let (sender, receiver) = mpsc::channel();
+                        let output = methyl_args.output.clone();
+                        let output_type = methyl_args.output_type.clone();
+                        let value_scale = methyl_args.value_scale();
+                        let sort = !methyl_args.no_sort;
+                        let include_stderr = methyl_args.include_stderr;
+                        let strand_format = methyl_args.strand_format;
+                        let metadata = if methyl_args.emit_metadata_header {
+                            methyl_args.metadata_header()
+                        } else {
+                            Vec::new()
+                        };
+                        info!("Streaming output to: {}", output.display());
+                        let writer = thread::spawn(move || {
+                            write_output_streaming(
+                                receiver,
+                                output,
+                                &output_type,
+                                value_scale,
+                                sort,
+                                include_stderr,
+                                strand_format,
+                                &metadata,
+                            )
+                        });
+                        (Some(sender), Some(writer))
+                    } else {
+                        (None, None)
+                    };
+
+                    // With combined C mods, 5mC and 5hmC motifs are reported as 'C'.
+                    let report_motifs = if methyl_args.combine_c_mods {
+                        combine_cytosine_motifs(motifs.clone())
+                    } else {
+                        motifs.clone()
+                    };
+
+                    let background = if let Some(background_motif) = &methyl_args.background_motif
+                    {
+                        let mut control = create_motifs_with_mod_base_map(
+                            &vec![background_motif.clone()],
+                            &mod_base_map,
+                        )?;
+                        if methyl_args.combine_c_mods {
+                            control = combine_cytosine_motifs(control);
+                        }
+                        let control = control.remove(0);
+                        if !report_motifs.contains(&control) {
+                            bail!(
+                                "Background motif '{}' should also be given with '--motifs'",
+                                background_motif
+                            );
+                        }
+                        Some(MethylationBackground::Motif(control))
+                    } else {
+                        methyl_args.background_value.map(MethylationBackground::Value)
+                    };
+
+                    // The contigs are consumed by the extraction, so the motif sites are
+                    // found up front.
+                    let motif_sites = methyl_args.dump_motif_matches.as_ref().map(|_| {
+                        contigs
+                            .values()
+                            .flat_map(|contig| find_motif_sites(contig, &report_motifs))
+                            .collect::<Vec<_>>()
+                    });
+
                     info!("Finding methylation");
-                    let meth_pattern = extract_methylation_pattern(
+                    let call_threshold =
+                        methyl_args.call_threshold.unwrap_or(DEFAULT_CALL_THRESHOLD);
+                    let options = MethylationPatternOptions {
+                        threads,
+                        min_valid_read_coverage: methyl_args.min_valid_read_coverage,
+                        min_valid_cov_to_diff_fraction: methyl_args.min_valid_cov_to_diff_fraction,
+                        min_score: methyl_args.min_score,
+                        coverage_field: methyl_args.coverage_field.clone(),
+                        allow_mismatch: methyl_args.allow_mismatch,
+                        combine_c_mods: methyl_args.combine_c_mods,
+                        collapse_strands: methyl_args.collapse_strands,
+                        min_motif_observations: methyl_args.min_motif_observations,
+                        on_parse_error: methyl_args.on_parse_error,
+                        check_mod_type_consistency: methyl_args.check_mod_type_consistency,
+                        report_missing: methyl_args.report_missing,
+                        motif_occurrence_cache: methyl_args.motif_occurrence_cache.clone(),
+                        max_memory: methyl_args.max_memory_bytes(),
+                        allow_truncated: methyl_args.allow_truncated,
+                        output_type: extraction_output_type,
+                        call_threshold,
+                    };
+                    let meth_result = extract_methylation_pattern(
                         input,
                         contigs,
                         motifs,
-                        methyl_args.threads,
-                        methyl_args.min_valid_read_coverage,
-                        methyl_args.min_valid_cov_to_diff_fraction,
-                        methyl_args.allow_mismatch,
-                        &methyl_args.output_type,
-                    )?;
+                        &options,
+                        output_stream,
+                    );
+                    // A failed writer stops receiving, which fails the extraction with a
+                    // send error. The writer error says what went wrong, so it goes first.
+                    let streamed_rows = match output_writer {
+                        Some(writer) => Some(
+                            writer
+                                .join()
+                                .map_err(|_| anyhow!("The output writer thread panicked"))?
+                                .with_context(|| {
+                                    format!(
+                                        "Failed to write the output to: {}",
+                                        methyl_args.output.display()
+                                    )
+                                })?,
+                        ),
+                        None => None,
+                    };
+                    let meth_result = meth_result?;
+                    let stats = &meth_result.stats;
+                    info!(
+                        "Processed {} contigs ({} skipped with {} records, {} records filtered) in {}",
+                        stats.n_contigs_processed,
+                        stats.n_contigs_skipped,
+                        stats.n_records_skipped,
+                        stats.n_records_filtered,
+                        format_duration(stats.elapsed)
+                    );
+                    if stats.n_contigs_empty > 0 {
+                        info!(
+                            "{} contigs in the pileup index had no records{}",
+                            stats.n_contigs_empty,
+                            if methyl_args.report_missing {
+                                " and are reported with zero observations"
+                            } else {
+                                " and are left out. Use --report-missing to report them"
+                            }
+                        );
+                    }
+                    if stats.n_records_malformed > 0 {
+                        warn!(
+                            "Skipped {} malformed pileup records",
+                            stats.n_records_malformed
+                        );
+                    }
+                    if let Some(histogram_path) = &methyl_args.coverage_histogram {
+                        stats.coverage_histogram.write(
+                            histogram_path,
+                            methyl_args.coverage_histogram_bin_width,
+                        )?;
+                        info!(
+                            "Written coverage histogram of {} records to: {}",
+                            stats.coverage_histogram.n_records(),
+                            histogram_path.display()
+                        );
+                    }
+
+                    let mut meth_pattern = meth_result.pattern;
+                    if let (Some(report_path), MethylationPatternVariant::Raw(positions)) =
+                        (&methyl_args.motif_report, &meth_pattern)
+                    {
+                        let rows = positions.to_motif_report(
+                            &combine_bipartite_motifs(report_motifs.clone()),
+                            methyl_args.methylated_threshold,
+                            &methyl_args.output_type,
+                        );
+                        write_motif_report(&rows, report_path, methyl_args.motif_report_metrics)?;
+                        info!(
+                            "Written motif report ({} motifs) to: {}",
+                            rows.len(),
+                            report_path.display()
+                        );
+                    }
+                    if let (
+                        Some(dump_path),
+                        Some(motif_sites),
+                        MethylationPatternVariant::Raw(positions),
+                    ) = (&methyl_args.dump_motif_matches, motif_sites, &meth_pattern)
+                    {
+                        let matches = positions.to_motif_matches(motif_sites);
+                        write_motif_matches(&matches, dump_path, methyl_args.strand_format)?;
+                        info!(
+                            "Written {} motif matches ({} with a record) to: {}",
+                            matches.len(),
+                            matches.iter().filter(|m| m.had_record()).count(),
+                            dump_path.display()
+                        );
+                    }
+                    if let (Some(report_path), MethylationPatternVariant::Raw(positions)) =
+                        (&methyl_args.strand_bias_report, &meth_pattern)
+                    {
+                        let degrees = positions.to_strand_bias(methyl_args.bias_threshold);
+                        write_strand_bias_report(&degrees, report_path)?;
+                        info!(
+                            "Written strand bias report ({} biased of {} contig-motifs) to: {}",
+                            degrees.iter().filter(|d| d.biased).count(),
+                            degrees.len(),
+                            report_path.display()
+                        );
+                    }
+
+                    if let (Some(contig_groups), MethylationPatternVariant::Raw(positions)) =
+                        (&contig_groups, &meth_pattern)
+                    {
+                        let degrees =
+                            positions.to_group_degrees(contig_groups, &methyl_args.output_type)?;
+                        info!(
+                            "Writing methylation for {} group-motifs to: {}",
+                            degrees.len(),
+                            &methyl_args.output.display()
+                        );
+                        write_group_output(
+                            &degrees,
+                            &methyl_args.output,
+                            methyl_args.value_scale(),
+                            !methyl_args.no_sort,
+                        )?;
+                    } else if let (
+                        Some(contig_lengths),
+                        Some(window_size),
+                        MethylationPatternVariant::Raw(positions),
+                    ) = (&contig_lengths, methyl_args.window_size, &meth_pattern)
+                    {
+                        let window_step = methyl_args.window_step.unwrap_or(window_size);
+                        let degrees = positions.to_window_degrees(
+                            contig_lengths,
+                            window_size,
+                            window_step,
+                            &methyl_args.output_type,
+                        )?;
+                        info!(
+                            "Writing methylation in {} bp windows (step {} bp) to: {}",
+                            window_size,
+                            window_step,
+                            &methyl_args.output.display()
+                        );
+                        write_window_output(
+                            &degrees,
+                            &methyl_args.output,
+                            methyl_args.na_value.as_deref(),
+                            methyl_args.value_scale(),
+                            !methyl_args.no_sort,
+                        )?;
+                    } else if let (Some(format), MethylationPatternVariant::Raw(positions)) =
+                        (methyl_args.compat_format, &meth_pattern)
+                    {
+                        let counts = positions.to_site_counts(methyl_args.methylated_threshold);
+                        info!(
+                            "Writing {} contig-motifs in {:?} format to: {}",
+                            counts.len(),
+                            format,
+                            &methyl_args.output.display()
+                        );
+                        write_compat_output(format, &counts, &methyl_args.output)?;
+                    } else if let Some(n_rows) = streamed_rows {
+                        info!(
+                            "Written {} rows to: {}",
+                            n_rows,
+                            &methyl_args.output.display()
+                        );
+                    } else {
+                        if let MethylationPatternVariant::Raw(positions) = meth_pattern {
+                            meth_pattern =
+                                positions.into_variant(&methyl_args.output_type, call_threshold);
+                        }
+
+                        info!("Writing output to: {}", &methyl_args.output.display());
+                        match &meth_pattern {
+                            _ if methyl_args.format == OutputFormat::Json => {
+                                meth_pattern.write_json(&methyl_args.output)?;
+                            }
+                            MethylationPatternVariant::Raw(positions)
+                                if methyl_args.raw_strand_wide =>
+                            {
+                                write_raw_strand_wide(
+                                    &positions.to_strand_wide(),
+                                    &methyl_args.output,
+                                    !methyl_args.no_sort,
+                                )?;
+                            }
+                            MethylationPatternVariant::Raw(_)
+                                if methyl_args.raw_format == RawFormat::Bedmethyl =>
+                            {
+                                let n_records = meth_pattern.write_bedmethyl(&methyl_args.output)?;
+                                info!("Written {} bedMethyl records", n_records);
+                            }
+                            _ if background.is_some() => {
+                                let metadata = if methyl_args.emit_metadata_header {
+                                    methyl_args.metadata_header()
+                                } else {
+                                    Vec::new()
+                                };
+                                meth_pattern.write_background_adjusted_output(
+                                    &methyl_args.output,
+                                    background.as_ref().unwrap(),
+                                    methyl_args.value_scale(),
+                                    !methyl_args.no_sort,
+                                    methyl_args.include_stderr,
+                                    &metadata,
+                                )?;
+                            }
+                            _ => {
+                                let metadata = if methyl_args.emit_metadata_header {
+                                    methyl_args.metadata_header()
+                                } else {
+                                    Vec::new()
+                                };
+                                meth_pattern.write_output(
+                                    &methyl_args.output,
+                                    methyl_args.value_scale(),
+                                    !methyl_args.no_sort,
+                                    methyl_args.include_stderr,
+                                    methyl_args.strand_format,
+                                    &metadata,
+                                )?
+                            }
+                        }
+
+                        if let Some(checksum_path) = &methyl_args.emit_checksums {
+                            let n_contigs = meth_pattern.write_checksums(
+                                checksum_path,
+                                methyl_args.value_scale(),
+                                methyl_args.strand_format,
+                            )?;
+                            info!(
+                                "Written checksums for {} contigs to: {}",
+                                n_contigs,
+                                checksum_path.display()
+                            );
+                        }
+
+                        if let (Some(summary_path), Some(summaries)) = (
+                            &methyl_args.summary_output,
+                            summarize_methylation_pattern(
+                                &meth_pattern,
+                                methyl_args.summary_threshold,
+                            ),
+                        ) {
+                            write_contig_summary(&summaries, summary_path)?;
+                            info!(
+                                "Written summary for {} contigs to: {}",
+                                summaries.len(),
+                                summary_path.display()
+                            );
+                        }
 
-                    info!("Writing output to: {}", &methyl_args.output.display());
-                    meth_pattern.write_output(&methyl_args.output)?;
+                        if let Some(per_motif_dir) = &methyl_args.per_motif_output_dir {
+                            let metadata = if methyl_args.emit_metadata_header {
+                                methyl_args.metadata_header()
+                            } else {
+                                Vec::new()
+                            };
+                            let n_motifs = write_per_motif_output(
+                                meth_pattern,
+                                per_motif_dir,
+                                methyl_args.value_scale(),
+                                !methyl_args.no_sort,
+                                methyl_args.include_stderr,
+                                methyl_args.strand_format,
+                                &metadata,
+                            )?;
+                            info!(
+                                "Written {} per motif files to: {}",
+                                n_motifs,
+                                per_motif_dir.display()
+                            );
+                        }
+                    }
+
+                    if let Some((collection, warnings_path)) = &warnings {
+                        let n_warnings = collection.write_json(warnings_path)?;
+                        info!(
+                            "Written {} warnings to: {}",
+                            n_warnings,
+                            warnings_path.display()
+                        );
+                    }
                 }
                 SequenceCommand::ReadBam(methyl_args) => {
                     create_output_file(&methyl_args.output)?;
@@ -131,10 +690,14 @@ fn main() -> Result<()> {
                     info!("Extracting read methylation");
                     let _ = extract_read_methylation_pattern(
                         &methyl_args.bam,
+                        methyl_args.reference.as_deref(),
                         contigs,
                         motifs,
                         &methyl_args.output,
                         methyl_args.threads.clone(),
+                        methyl_args.max_reads_per_contig.map(NonZeroUsize::get),
+                        methyl_args.min_mapping_quality,
+                        methyl_args.strand_format,
                     )?;
 
                     info!(
@@ -167,6 +730,7 @@ fn main() -> Result<()> {
                         read_ids_filter,
                         motifs,
                         methyl_args.threads.clone(),
+                        methyl_args.strand_format,
                     )?;
 
                     info!("Writing methylation pattern");
@@ -213,7 +777,15 @@ fn main() -> Result<()> {
                     info!("Writing to stdout");
                 }
 
-                CompressorService::compress_pileup(input_reader, output.as_deref())?;
+                let progress_bar = compress_args.progress_bar()?;
+                let count_lines = compress_args.stdin;
+                CompressorService::compress_pileup_with_progress(
+                    input_reader,
+                    output.as_deref(),
+                    compress_args.level,
+                    |n_bytes| progress_bar.inc(if count_lines { 1 } else { n_bytes as u64 }),
+                )?;
+                progress_bar.finish_and_clear();
 
                 if compress_args.should_remove_input_file() {
                     info!(
@@ -229,9 +801,39 @@ fn main() -> Result<()> {
                     &decompress_args.input,
                     decompress_args.output.as_deref(),
                     decompress_args.ls,
+                    decompress_args.ls_lengths,
                     contigs,
+                    decompress_args.regions.as_deref(),
+                    decompress_args.allow_truncated,
                 )?;
             }
+            BgZipCommands::Reblock(reblock_args) => {
+                reblock_args.validate()?;
+
+                let n_blocks_in = count_bgzf_blocks(&reblock_args.input)?;
+                info!(
+                    "Reblocking {} ({} blocks) to: {}",
+                    reblock_args.input.display(),
+                    n_blocks_in,
+                    reblock_args.output.display()
+                );
+
+                CompressorService::reblock_pileup(&reblock_args.input, &reblock_args.output)?;
+
+                let n_blocks_out = count_bgzf_blocks(&reblock_args.output)?;
+                info!("Reblocked {} blocks into {}", n_blocks_in, n_blocks_out);
+            }
+            BgZipCommands::Index(index_args) => {
+                index_args.validate()?;
+
+                info!("Indexing: {}", index_args.input.display());
+                let n_records = CompressorService::index_pileup(&index_args.input)?;
+                info!(
+                    "Indexed {} records to: {}",
+                    n_records,
+                    index_args.index_path().display()
+                );
+            }
         },
     }
 
@@ -1,5 +1,15 @@
 use anyhow::{Context, Result, anyhow};
-use std::{fs, path::Path};
+use epimetheus_io::{
+    io::readers::bed::{InputReader, LineReader},
+    services::compression_service::CompressorService,
+};
+use log::info;
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
 
 pub fn create_output_file(outpath: &Path) -> Result<()> {
     if let Some(ext) = outpath.extension() {
@@ -17,6 +27,34 @@ pub fn create_output_file(outpath: &Path) -> Result<()> {
     }
 }
 
+/// Compresses a plain .bed pileup to .bed.gz (+ .tbi) so it can be queried by contig.
+///
+/// If `keep` is `None` the intermediate is written to a temporary directory, which is
+/// removed when the returned `TempDir` is dropped.
+pub fn compress_pileup_for_lookup(
+    pileup: &Path,
+    keep: Option<&Path>,
+) -> Result<(PathBuf, Option<TempDir>)> {
+    let (gz_path, tmp_dir) = match keep {
+        Some(path) => (path.to_path_buf(), None),
+        None => {
+            let tmp_dir = TempDir::new()?;
+            (tmp_dir.path().join("pileup.bed.gz"), Some(tmp_dir))
+        }
+    };
+
+    info!(
+        "Compressing pileup to allow contig lookup: {}",
+        gz_path.display()
+    );
+    let file = File::open(pileup)
+        .with_context(|| format!("Could not open pileup: {}", pileup.display()))?;
+    let reader = InputReader::File(LineReader::new(BufReader::new(file)));
+    CompressorService::compress_pileup(reader, Some(&gz_path))?;
+
+    Ok((gz_path, tmp_dir))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
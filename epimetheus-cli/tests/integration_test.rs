@@ -4,6 +4,8 @@ use std::{
     path::PathBuf,
     process::{Command, Stdio},
 };
+use epimetheus_orchestration::extract_methylation_pattern_service::PILEUP_COLUMNS;
+use polars::prelude::*;
 use tempfile::TempDir;
 
 #[test]
@@ -178,6 +180,203 @@ fn test_contig_methylation_pattern_raw() {
     );
 }
 
+#[test]
+fn test_contig_methylation_pattern_raw_strand_format_numeric() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+    let expected_out = data_dir.join("expected_out_raw.tsv");
+
+    let temp_dir = TempDir::new().expect("Could not create temp dir");
+    let out_file = temp_dir.path().join("raw_numeric_strand.tsv");
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--batch-size",
+            "2",
+            "--min-valid-read-coverage",
+            "3",
+            "--output-type",
+            "raw",
+            "--strand-format",
+            "numeric",
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        status.success(),
+        "Process ended with non-success status: {:?}",
+        status
+    );
+
+    let actual = fs::read_to_string(&out_file).expect("Could not read output file");
+    let expected = fs::read_to_string(&expected_out).expect("Could not read expected output file");
+
+    let expected_numeric: Vec<String> = expected
+        .trim()
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                return line.to_string();
+            }
+            let mut fields: Vec<&str> = line.split('\t').collect();
+            fields[2] = match fields[2] {
+                "+" => "1",
+                "-" => "-1",
+                other => panic!("Unexpected strand '{}'", other),
+            };
+            fields.join("\t")
+        })
+        .collect();
+
+    assert_eq!(
+        actual.trim().lines().collect::<Vec<_>>(),
+        expected_numeric,
+        "Output did not match expected with numeric strands"
+    );
+}
+
+#[test]
+fn test_contig_methylation_pattern_strand_asymmetry() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Could not create temp dir");
+    let out_file = temp_dir.path().join("strand_asymmetry.tsv");
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "TCCCG_m_1",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--min-valid-read-coverage",
+            "3",
+            "--output-type",
+            "strand-asymmetry",
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        status.success(),
+        "Process ended with non-success status: {:?}",
+        status
+    );
+
+    let actual = fs::read_to_string(&out_file).expect("Could not read output file");
+    let mut lines = actual.lines();
+    assert_eq!(
+        lines.next(),
+        Some("contig\tmotif\tmod_type\tmod_position\tmethylation_plus\tmethylation_minus\tmean_asymmetry\tn_sites")
+    );
+
+    let rows: Vec<Vec<&str>> = lines.map(|l| l.split('\t').collect()).collect();
+    assert!(!rows.is_empty(), "Expected strand asymmetry rows for GATC");
+    for row in rows {
+        // The non-palindromic motif is skipped.
+        assert_eq!(row[1], "GATC");
+        let asymmetry: f64 = row[6].parse().unwrap();
+        assert!((0.0..=1.0).contains(&asymmetry));
+        assert!(row[7].parse::<u32>().unwrap() > 0);
+    }
+}
+
+#[test]
+fn test_contig_methylation_pattern_compat_nanomotif() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Could not create temp dir");
+    let out_file = temp_dir.path().join("motifs-scored.tsv");
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1,TCCCG_m_1",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--min-valid-read-coverage",
+            "3",
+            "--compat-format",
+            "nanomotif",
+            "--methylated-threshold",
+            "0.7",
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        status.success(),
+        "Process ended with non-success status: {:?}",
+        status
+    );
+
+    let actual = fs::read_to_string(&out_file).expect("Could not read output file");
+    let mut lines = actual.lines();
+    assert_eq!(
+        lines.next(),
+        Some("contig\tmotif\tmod_position\tmod_type\tn_mod\tn_nomod\tmotif_type")
+    );
+
+    let rows: Vec<Vec<&str>> = lines.map(|l| l.split('\t').collect()).collect();
+    assert!(!rows.is_empty(), "Expected site counts");
+    for row in rows {
+        let expected_type = match row[1] {
+            "GATC" => "palindrome",
+            "TCCCG" => "non-palindrome",
+            motif => panic!("Unexpected motif {}", motif),
+        };
+        assert_eq!(row[6], expected_type);
+        let n_sites = row[4].parse::<u32>().unwrap() + row[5].parse::<u32>().unwrap();
+        assert!(n_sites > 0);
+    }
+}
+
 #[test]
 fn test_contig_methylation_pattern_median_gz() {
     let manifest_dir = env!("CARGO_MANIFEST_DIR");
@@ -453,6 +652,76 @@ fn test_compress_pileup_from_stdin() {
     );
 }
 
+#[test]
+fn test_decompress_pileup_region() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let compressed_pileup = temp_dir.path().join("geobacillus-plasmids.region.bed.gz");
+    let out_file = temp_dir.path().join("region.bed");
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "bgzip",
+            "compress",
+            "-i",
+            pileup.to_str().unwrap(),
+            "-o",
+            compressed_pileup.to_str().unwrap(),
+            "--keep",
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+    assert!(status.success(), "Compression failed: {:?}", status);
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "bgzip",
+            "decompress",
+            "-i",
+            compressed_pileup.to_str().unwrap(),
+            "-o",
+            out_file.to_str().unwrap(),
+            "--contigs",
+            "contig_2:101-200",
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+    assert!(status.success(), "Decompression failed: {:?}", status);
+
+    // The 1-based region 101-200 holds the records starting at 0-based 100 to 199.
+    // Records are compared on their position columns, since numbers are reformatted.
+    let key = |line: &str| line.split('\t').take(6).collect::<Vec<_>>().join("\t");
+    let mut expected: Vec<String> = fs::read_to_string(&pileup)
+        .expect("Failed to read pileup")
+        .lines()
+        .filter(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let start: u64 = fields[1].parse().unwrap();
+            fields[0] == "contig_2" && (100..200).contains(&start)
+        })
+        .map(key)
+        .collect();
+    let mut extracted: Vec<String> = fs::read_to_string(&out_file)
+        .expect("Failed to read extracted region")
+        .lines()
+        .map(key)
+        .collect();
+    extracted.sort();
+    expected.sort();
+
+    assert!(!expected.is_empty());
+    assert_eq!(extracted, expected);
+}
+
 #[test]
 fn test_verify_expected_outputs_from_raw() {
     use std::collections::HashMap;
@@ -696,3 +965,2101 @@ fn test_read_methylation_pattern_read() {
         status
     );
 }
+
+#[test]
+fn test_contig_methylation_pattern_bed_with_contig_filter() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+    let expected_out = data_dir.join("expected_out_median.tsv");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let intermediate = temp_dir.path().join("intermediate.bed.gz");
+    let out_file = temp_dir.path().join("test_out_contig_filter.tsv");
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--contigs",
+            "contig_2",
+            "--keep-intermediate-gz",
+            intermediate.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        status.success(),
+        "Process ended with non-success status: {:?}",
+        status
+    );
+
+    assert!(intermediate.exists(), "Intermediate .bed.gz was not kept");
+    assert!(
+        temp_dir.path().join("intermediate.bed.gz.tbi").exists(),
+        "Intermediate index was not kept"
+    );
+
+    let actual = fs::read_to_string(&out_file).expect("Could not read output file");
+    let expected = fs::read_to_string(&expected_out).expect("Could not read expected output file");
+    let expected_contig_2: Vec<&str> = expected
+        .lines()
+        .enumerate()
+        .filter(|(i, l)| *i == 0 || l.starts_with("contig_2\t"))
+        .map(|(_, l)| l)
+        .collect();
+
+    assert_eq!(
+        actual.trim().lines().collect::<Vec<&str>>(),
+        expected_contig_2,
+        "Output did not match expected"
+    );
+}
+
+#[test]
+fn test_contig_methylation_pattern_warnings_json() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus.bed.gz");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_warnings.tsv");
+    let warnings_file = temp_dir.path().join("warnings.jsonl");
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--min-valid-read-coverage",
+            "1000000",
+            "--warnings-json",
+            warnings_file.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        status.success(),
+        "Process ended with non-success status: {:?}",
+        status
+    );
+
+    let warnings = fs::read_to_string(&warnings_file).expect("Could not read warnings file");
+    let lines: Vec<&str> = warnings.lines().collect();
+    assert_eq!(lines.len(), 2, "Expected one warning per contig");
+    assert!(
+        lines
+            .iter()
+            .all(|l| l.starts_with(r#"{"type":"filtered_records""#))
+    );
+}
+
+#[test]
+fn test_contig_methylation_pattern_contigs_pattern() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus.bed.gz");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+    let expected_out = data_dir.join("expected_out_median.tsv");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_contigs_pattern.tsv");
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--contigs-pattern",
+            "^contig_3",
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        status.success(),
+        "Process ended with non-success status: {:?}",
+        status
+    );
+
+    let actual = fs::read_to_string(&out_file).expect("Could not read output file");
+    let expected = fs::read_to_string(&expected_out).expect("Could not read expected output file");
+    let expected_contig_3: Vec<&str> = expected
+        .lines()
+        .enumerate()
+        .filter(|(i, l)| *i == 0 || l.starts_with("contig_3\t"))
+        .map(|(_, l)| l)
+        .collect();
+
+    assert_eq!(
+        actual.trim().lines().collect::<Vec<&str>>(),
+        expected_contig_3,
+        "Output did not match expected"
+    );
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--contigs",
+            "contig_2",
+            "--contigs-pattern",
+            "^contig_3",
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        !status.success(),
+        "'--contigs' and '--contigs-pattern' should not be accepted together"
+    );
+}
+
+#[test]
+fn test_contig_methylation_pattern_parquet() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+    let expected_out = data_dir.join("expected_out_median.tsv");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let parquet = temp_dir.path().join("pileup.parquet");
+    let out_file = temp_dir.path().join("test_out_parquet.tsv");
+
+    let mut pileup_df = CsvReadOptions::default()
+        .with_has_header(false)
+        .with_parse_options(CsvParseOptions::default().with_separator(b'\t'))
+        .try_into_reader_with_file_path(Some(pileup))
+        .expect("Could not open pileup")
+        .finish()
+        .expect("Could not read pileup");
+    pileup_df
+        .set_column_names(PILEUP_COLUMNS)
+        .expect("Could not set column names");
+    ParquetWriter::new(fs::File::create(&parquet).expect("Could not create parquet"))
+        .finish(&mut pileup_df)
+        .expect("Could not write parquet");
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            parquet.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        status.success(),
+        "Process ended with non-success status: {:?}",
+        status
+    );
+
+    let actual = fs::read_to_string(&out_file).expect("Could not read output file");
+    let expected = fs::read_to_string(&expected_out).expect("Could not read expected output file");
+
+    let normalize = |s: &str| s.replace("\r\n", "\n");
+
+    assert_eq!(
+        normalize(actual.trim()),
+        normalize(expected.trim()),
+        "Output did not match expected"
+    );
+}
+
+#[test]
+fn test_contig_methylation_pattern_threads_auto() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus.bed.gz");
+    // The assembly holds 2 contigs.
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_threads_auto.tsv");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--threads",
+            "auto",
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let threads: usize = stderr
+        .lines()
+        .find_map(|l| {
+            l.split("Using ")
+                .nth(1)
+                .and_then(|rest| rest.strip_suffix(" threads"))
+        })
+        .expect("Resolved thread count was not logged")
+        .parse()
+        .expect("Could not parse thread count");
+
+    assert!(
+        (1..=2).contains(&threads),
+        "Expected at most 2 threads, got {}",
+        threads
+    );
+}
+
+#[test]
+fn test_contig_methylation_pattern_contig_groups() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+    let expected_out = data_dir.join("expected_out_weighted_mean.tsv");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let groups_file = temp_dir.path().join("contig_groups.tsv");
+    let out_file = temp_dir.path().join("test_out_contig_groups.tsv");
+    fs::write(&groups_file, "contig\tgroup\ncontig_2\tbin_1\ncontig_3\tbin_1\n")
+        .expect("Could not write contig groups");
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--min-valid-read-coverage",
+            "3",
+            "--output-type",
+            "weighted-mean",
+            "--contig-groups",
+            groups_file.to_str().unwrap(),
+        ])
+        .status()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        status.success(),
+        "Process ended with non-success status: {:?}",
+        status
+    );
+
+    // The pooled weighted mean is the per contig weighted mean weighted by the
+    // contig's total valid coverage (n_motif_obs * mean_read_cov).
+    let expected = fs::read_to_string(&expected_out).expect("Could not read expected output file");
+    let mut pooled: Vec<(String, f64, f64, u32, u32)> = Vec::new();
+    for line in expected.lines().skip(1) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let motif = format!("{}\t{}\t{}", fields[1], fields[2], fields[3]);
+        let w_mean: f64 = fields[4].parse().unwrap();
+        let mean_read_cov: f64 = fields[5].parse().unwrap();
+        let n_motif_obs: u32 = fields[6].parse().unwrap();
+        let motif_occurences_total: u32 = fields[7].parse().unwrap();
+        let total_cov = mean_read_cov * n_motif_obs as f64;
+
+        match pooled.iter_mut().find(|(m, ..)| *m == motif) {
+            Some(entry) => {
+                entry.1 += w_mean * total_cov;
+                entry.2 += total_cov;
+                entry.3 += n_motif_obs;
+                entry.4 += motif_occurences_total;
+            }
+            None => pooled.push((
+                motif,
+                w_mean * total_cov,
+                total_cov,
+                n_motif_obs,
+                motif_occurences_total,
+            )),
+        }
+    }
+
+    let actual = fs::read_to_string(&out_file).expect("Could not read output file");
+    let lines: Vec<&str> = actual.trim().lines().collect();
+    assert_eq!(
+        lines[0],
+        "group\tmotif\tmod_type\tmod_position\tmethylation_value\tmean_read_cov\tn_motif_obs\tmotif_occurences_total\tn_contigs"
+    );
+    assert_eq!(lines.len(), pooled.len() + 1);
+
+    for line in &lines[1..] {
+        let fields: Vec<&str> = line.split('\t').collect();
+        assert_eq!(fields[0], "bin_1");
+        let motif = format!("{}\t{}\t{}", fields[1], fields[2], fields[3]);
+        let (_, weighted_sum, total_cov, n_motif_obs, motif_occurences_total) = pooled
+            .iter()
+            .find(|(m, ..)| *m == motif)
+            .expect("Unexpected motif in grouped output");
+
+        let methylation_value: f64 = fields[4].parse().unwrap();
+        assert!((methylation_value - weighted_sum / total_cov).abs() < 1e-9);
+        assert_eq!(fields[6].parse::<u32>().unwrap(), *n_motif_obs);
+        assert_eq!(fields[7].parse::<u32>().unwrap(), *motif_occurences_total);
+        assert_eq!(fields[8], "2");
+    }
+}
+
+#[test]
+fn test_contig_methylation_pattern_check_motifs() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_check_motifs.tsv");
+
+    let run = |motifs: &[&str]| {
+        let mut args = vec![
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-o",
+            out_file.to_str().unwrap(),
+            "--check-motifs",
+            "-m",
+        ];
+        args.extend_from_slice(motifs);
+        Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to execute cargo run")
+    };
+
+    let output = run(&["GATC_a_1", "GATC_m_1", "RGATCY_a_2"]);
+    assert!(!output.status.success(), "GATC_m_1 should be flagged");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Motif 'GATC_m_1'"), "stderr: {}", stderr);
+    assert!(!stderr.contains("Motif 'GATC_a_1'"), "stderr: {}", stderr);
+
+    let output = run(&["GATC_a_1", "GATC_m_3"]);
+    assert!(output.status.success());
+    assert!(!out_file.exists(), "Checking motifs should not write output");
+}
+
+#[test]
+fn test_contig_methylation_pattern_metadata_header() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+    let run = |out_file: &PathBuf, extra: &[&str]| {
+        let mut args = vec![
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "-o",
+            out_file.to_str().unwrap(),
+        ];
+        args.extend_from_slice(extra);
+        let output = Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+        std::fs::read_to_string(out_file).expect("Failed to read output")
+    };
+
+    let plain = run(&temp_dir.path().join("plain.tsv"), &[]);
+    let with_header = run(
+        &temp_dir.path().join("with_header.tsv"),
+        &["--emit-metadata-header"],
+    );
+
+    let (comments, data): (Vec<&str>, Vec<&str>) =
+        with_header.lines().partition(|l| l.starts_with('#'));
+    assert!(with_header.starts_with("# command: "));
+    assert!(comments.contains(&"# motifs: GATC_a_1 GATC_m_3"));
+    assert!(
+        comments
+            .iter()
+            .any(|l| l.starts_with("# version: ") && l.len() > "# version: ".len())
+    );
+    assert_eq!(data, plain.lines().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_contig_methylation_pattern_weighted_mean_is_reproducible() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus.bed.gz");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+    let run = |out_file: &PathBuf| {
+        let output = Command::new("cargo")
+            .args(&[
+                "run",
+                "--quiet",
+                "--",
+                "methylation-pattern",
+                "contig",
+                "-p",
+                pileup.to_str().unwrap(),
+                "-a",
+                assembly.to_str().unwrap(),
+                "-m",
+                "GATC_a_1",
+                "GATC_m_3",
+                "RGATCY_a_2",
+                "-o",
+                out_file.to_str().unwrap(),
+                "--output-type",
+                "weighted-mean",
+                "--include-stderr",
+                "--threads",
+                "2",
+            ])
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+        std::fs::read(out_file).expect("Failed to read output")
+    };
+
+    let first = run(&temp_dir.path().join("run_1.tsv"));
+    for i in 2..=4 {
+        let other = run(&temp_dir.path().join(format!("run_{}.tsv", i)));
+        assert_eq!(first, other, "Run {} differs from the first run", i);
+    }
+}
+
+#[test]
+fn test_contig_methylation_pattern_motif_report() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_motif_report.tsv");
+    let report_file = temp_dir.path().join("motif_report.tsv");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "ACGTACGTACGTACGTACGT_a_0",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--motif-report",
+            report_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let report = std::fs::read_to_string(&report_file).expect("Failed to read motif report");
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(
+        lines[0],
+        "motif\tmod_type\tmod_position\tmotif_occurences_total\tn_motif_obs\tmethylation_fraction\tn_contigs_observed\tn_contigs_methylated"
+    );
+    assert_eq!(lines.len(), 4, "Expected one row per motif: {}", report);
+
+    let unobserved = lines
+        .iter()
+        .find(|l| l.starts_with("ACGTACGTACGTACGTACGT\t"))
+        .expect("Motif without observations should be reported");
+    assert!(unobserved.ends_with("\t0\t0\t0\t0"), "{}", unobserved);
+
+    let gatc = lines
+        .iter()
+        .find(|l| l.starts_with("GATC\ta\t1\t"))
+        .expect("GATC_a_1 should be reported");
+    let fields: Vec<&str> = gatc.split('\t').collect();
+    let n_obs: u64 = fields[4].parse().unwrap();
+    let n_contigs_observed: u32 = fields[6].parse().unwrap();
+    assert!(n_obs > 0);
+    assert!((1..=2).contains(&n_contigs_observed));
+}
+
+#[test]
+fn test_contig_methylation_pattern_motif_report_metrics() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_motif_report_metrics.tsv");
+    let report_file = temp_dir.path().join("motif_report_metrics.tsv");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--motif-report",
+            report_file.to_str().unwrap(),
+            "--motif-report-metrics",
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let report = std::fs::read_to_string(&report_file).expect("Failed to read motif report");
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(
+        lines[0],
+        "motif\tmod_type\tmod_position\tmotif_length\tgc_content\tdegeneracy\tmotif_occurences_total\tn_motif_obs\tmethylation_fraction\tn_contigs_observed\tn_contigs_methylated"
+    );
+    assert!(
+        lines.iter().any(|l| l.starts_with("GATC\ta\t1\t4\t0.5\t1\t")),
+        "{}",
+        report
+    );
+    assert!(
+        lines
+            .iter()
+            .any(|l| l.starts_with("RGATCY\ta\t2\t6\t0.5\t4\t")),
+        "{}",
+        report
+    );
+}
+
+#[test]
+fn test_contig_methylation_pattern_trimmed_mean() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_trimmed_mean.tsv");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--output-type",
+            "trimmed-mean",
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let out = std::fs::read_to_string(&out_file).expect("Failed to read output");
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(
+        lines[0],
+        "contig\tmotif\tmod_type\tmod_position\tmethylation_value\tmean_read_cov\tn_motif_obs\tmotif_occurences_total\tn_filtered_low_cov\tn_filtered_diff\ttrim_applied"
+    );
+    // One row per contig-motif, all with enough observations to trim.
+    assert_eq!(lines.len(), 5, "{}", out);
+    for line in &lines[1..] {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let value: f64 = fields[4].parse().unwrap();
+        assert!((0.0..=1.0).contains(&value));
+        assert_eq!(fields[10], "true");
+    }
+}
+
+#[test]
+fn test_contig_methylation_pattern_dump_motif_matches() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_dump_motif_matches.tsv");
+    let dump_file = temp_dir.path().join("motif_matches.tsv");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--min-valid-read-coverage",
+            "200",
+            "--dump-motif-matches",
+            dump_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let dump = std::fs::read_to_string(&dump_file).expect("Failed to read motif matches");
+    let lines: Vec<&str> = dump.lines().collect();
+    assert_eq!(
+        lines[0],
+        "contig\tstart\tstrand\tmotif\tmod_type\tmod_position\thad_record\tn_modified\tn_valid_cov\tfraction_modified\texclusion_reason"
+    );
+
+    // 380 GATC sites in contig_2 and 354 in contig_3, each on both strands.
+    let rows: Vec<Vec<&str>> = lines[1..].iter().map(|l| l.split('\t').collect()).collect();
+    assert_eq!(rows.len(), 2 * (380 + 354));
+
+    let (with_record, without_record): (Vec<_>, Vec<_>) =
+        rows.iter().partition(|fields| fields[6] == "true");
+    assert!(!with_record.is_empty());
+    assert!(!without_record.is_empty());
+    for fields in &with_record {
+        assert!(fields[8].parse::<u32>().unwrap() >= 200);
+        assert_eq!(fields[10], "");
+    }
+    for fields in &without_record {
+        assert_eq!(fields[8], "");
+        assert_eq!(fields[10], "no_passing_record");
+    }
+}
+
+#[test]
+fn test_read_methylation_pattern_bam_max_reads_per_contig() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let bam = data_dir.join("barcode01_5x_coverage.bam");
+    let assembly =
+        data_dir.join("NC_000913.3_escherichia_coli_str_K_12_substr_MG1655_complete_genome.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+    let read_ids = |extra: &[&str], name: &str| {
+        let out_file = temp_dir.path().join(name);
+        let mut args = vec![
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "read-bam",
+            "-b",
+            bam.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GGWCC_m_3",
+            "-o",
+            out_file.to_str().unwrap(),
+        ];
+        args.extend_from_slice(extra);
+        let output = Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+
+        let content = std::fs::read_to_string(&out_file).expect("Failed to read output");
+        let mut lines = content.lines();
+        let header: Vec<&str> = lines.next().unwrap().split('\t').collect();
+        let read_id_idx = header.iter().position(|h| *h == "read_id").unwrap();
+        lines
+            .map(|l| l.split('\t').nth(read_id_idx).unwrap().to_string())
+            .collect::<std::collections::HashSet<String>>()
+    };
+
+    let all_reads = read_ids(&[], "all.tsv");
+    let capped_reads = read_ids(&["--max-reads-per-contig", "2"], "capped.tsv");
+
+    assert!(all_reads.len() > 2);
+    assert!(!capped_reads.is_empty() && capped_reads.len() <= 2);
+    assert!(capped_reads.is_subset(&all_reads));
+}
+
+#[test]
+fn test_read_methylation_pattern_bam_min_mapping_quality() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let bam = data_dir.join("barcode01_5x_coverage.bam");
+    let assembly =
+        data_dir.join("NC_000913.3_escherichia_coli_str_K_12_substr_MG1655_complete_genome.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+    let mapping_qualities = |extra: &[&str], name: &str| {
+        let out_file = temp_dir.path().join(name);
+        let mut args = vec![
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "read-bam",
+            "-b",
+            bam.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GGWCC_m_3",
+            "-o",
+            out_file.to_str().unwrap(),
+        ];
+        args.extend_from_slice(extra);
+        let output = Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+
+        let content = std::fs::read_to_string(&out_file).expect("Failed to read output");
+        let mut lines = content.lines();
+        let header: Vec<&str> = lines.next().unwrap().split('\t').collect();
+        let mapq_idx = header.iter().position(|h| *h == "mapping_quality").unwrap();
+        lines
+            .map(|l| l.split('\t').nth(mapq_idx).unwrap().parse::<u8>().unwrap())
+            .collect::<Vec<u8>>()
+    };
+
+    let all = mapping_qualities(&[], "all.tsv");
+    let threshold = *all.iter().max().unwrap();
+    let filtered = mapping_qualities(
+        &["--min-mapping-quality", &threshold.to_string()],
+        "filtered.tsv",
+    );
+
+    assert!(all.iter().any(|&mapq| mapq < threshold));
+    assert!(!filtered.is_empty());
+    assert!(filtered.iter().all(|&mapq| mapq >= threshold));
+    assert_eq!(
+        filtered.len(),
+        all.iter().filter(|&&mapq| mapq >= threshold).count()
+    );
+}
+
+#[test]
+fn test_read_methylation_pattern_bam_cap_counts_retained_reads() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let bam = data_dir.join("barcode01_5x_coverage.bam");
+    let assembly =
+        data_dir.join("NC_000913.3_escherichia_coli_str_K_12_substr_MG1655_complete_genome.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+    let reads = |extra: &[&str], name: &str| {
+        let out_file = temp_dir.path().join(name);
+        let mut args = vec![
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "read-bam",
+            "-b",
+            bam.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GGWCC_m_3",
+            "-o",
+            out_file.to_str().unwrap(),
+        ];
+        args.extend_from_slice(extra);
+        let output = Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+
+        let content = std::fs::read_to_string(&out_file).expect("Failed to read output");
+        let mut lines = content.lines();
+        let header: Vec<&str> = lines.next().unwrap().split('\t').collect();
+        let read_id_idx = header.iter().position(|h| *h == "read_id").unwrap();
+        let mapq_idx = header.iter().position(|h| *h == "mapping_quality").unwrap();
+        lines
+            .map(|l| {
+                let fields: Vec<&str> = l.split('\t').collect();
+                (
+                    fields[read_id_idx].to_string(),
+                    fields[mapq_idx].parse::<u8>().unwrap(),
+                )
+            })
+            .collect::<std::collections::HashSet<(String, u8)>>()
+    };
+
+    let all = reads(&[], "all.tsv");
+    let threshold = all.iter().map(|(_, mapq)| *mapq).max().unwrap().to_string();
+    let retained = reads(&["--min-mapping-quality", &threshold], "retained.tsv");
+    let capped = reads(
+        &[
+            "--min-mapping-quality",
+            &threshold,
+            "--max-reads-per-contig",
+            "2",
+        ],
+        "capped.tsv",
+    );
+
+    // Skipped reads do not count towards the cap, so it is filled with retained reads.
+    assert!(all.len() > retained.len());
+    assert!(retained.len() > 2);
+    assert_eq!(capped.len(), 2);
+    assert!(capped.is_subset(&retained));
+}
+
+#[test]
+fn test_contig_methylation_pattern_streamed_matches_sorted() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus.bed.gz");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let sorted_file = temp_dir.path().join("test_out_sorted.tsv");
+    let streamed_file = temp_dir.path().join("test_out_streamed.tsv");
+    let unsorted_file = temp_dir.path().join("test_out_unsorted.tsv");
+
+    let run = |out_file: &PathBuf, extra_args: &[&str]| {
+        let mut args = vec![
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--output-type",
+            "weighted-mean",
+        ];
+        args.extend_from_slice(extra_args);
+        let output = Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+        std::fs::read_to_string(out_file).expect("Failed to read output")
+    };
+
+    let sorted = run(&sorted_file, &["--sorted"]);
+    let streamed = run(&streamed_file, &[]);
+    let unsorted = run(&unsorted_file, &["--no-sort"]);
+
+    // The contigs of a .bed.gz pileup are streamed in contig order.
+    assert_eq!(sorted, streamed);
+
+    let mut sorted_rows: Vec<&str> = sorted.lines().collect();
+    let mut unsorted_rows: Vec<&str> = unsorted.lines().collect();
+    assert_eq!(sorted_rows[0], unsorted_rows[0]);
+    assert!(sorted_rows.len() > 1);
+    sorted_rows.sort();
+    unsorted_rows.sort();
+    assert_eq!(sorted_rows, unsorted_rows);
+}
+
+#[test]
+fn test_contig_methylation_pattern_per_motif_output_dir() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_per_motif.tsv");
+    let per_motif_dir = temp_dir.path().join("per_motif");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--output-type",
+            "median",
+            "--per-motif-output-dir",
+            per_motif_dir.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let out = std::fs::read_to_string(&out_file).expect("Failed to read output");
+    let manifest = std::fs::read_to_string(per_motif_dir.join("manifest.tsv"))
+        .expect("Failed to read manifest");
+    let manifest_lines: Vec<&str> = manifest.lines().collect();
+    assert_eq!(manifest_lines[0], "motif\tmod_type\tmod_position\tfile\tn_rows");
+    assert_eq!(manifest_lines.len(), 3, "{}", manifest);
+
+    for (motif, mod_position) in [("GATC", 1), ("RGATCY", 2)] {
+        let file_name = format!("{}_a_{}.tsv", motif, mod_position);
+        let motif_out = std::fs::read_to_string(per_motif_dir.join(&file_name))
+            .expect("Failed to read per motif output");
+        let motif_rows: Vec<&str> = motif_out.lines().collect();
+        let expected_rows: Vec<&str> = out
+            .lines()
+            .skip(1)
+            .filter(|line| line.split('\t').nth(1) == Some(motif))
+            .collect();
+
+        assert_eq!(motif_rows[0], out.lines().next().unwrap());
+        assert!(!expected_rows.is_empty());
+        assert_eq!(&motif_rows[1..], expected_rows.as_slice());
+        let manifest_line = format!(
+            "{}\ta\t{}\t{}\t{}",
+            motif,
+            mod_position,
+            file_name,
+            expected_rows.len()
+        );
+        assert!(manifest_lines.contains(&manifest_line.as_str()), "{}", manifest);
+    }
+}
+
+#[test]
+fn test_contig_methylation_pattern_coverage_field() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+    let run = |out_file: &PathBuf, coverage_field: &str| {
+        let output = Command::new("cargo")
+            .args(&[
+                "run",
+                "--quiet",
+                "--",
+                "methylation-pattern",
+                "contig",
+                "-p",
+                pileup.to_str().unwrap(),
+                "-a",
+                assembly.to_str().unwrap(),
+                "-m",
+                "GATC_a_1",
+                "-o",
+                out_file.to_str().unwrap(),
+                "--output-type",
+                "raw",
+                "--coverage-field",
+                coverage_field,
+            ])
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+        std::fs::read_to_string(out_file).expect("Failed to read output")
+    };
+
+    let default_out = run(&temp_dir.path().join("default.tsv"), "n_valid_cov");
+    let custom_out = run(&temp_dir.path().join("custom.tsv"), "n_valid_cov+n_fail");
+
+    // Rows keyed by contig, start, strand, motif, mod_type and mod_position.
+    let rows = |out: &str| -> std::collections::HashMap<String, Vec<u32>> {
+        out.lines()
+            .skip(1)
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                let counts = fields[6..].iter().map(|v| v.parse().unwrap()).collect();
+                (fields[..6].join("\t"), counts)
+            })
+            .collect()
+    };
+    let default_rows = rows(&default_out);
+    let custom_rows = rows(&custom_out);
+
+    assert!(!default_rows.is_empty());
+    let mut n_changed = 0;
+    for (key, counts) in &default_rows {
+        // n_modified, n_valid_cov, n_diff, n_fail
+        let custom = custom_rows.get(key).expect("Row missing with custom coverage");
+        assert_eq!(custom[1], counts[1] + counts[3], "{}", key);
+        if counts[3] > 0 {
+            n_changed += 1;
+        }
+    }
+    assert!(n_changed > 0);
+
+    let invalid = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "-o",
+            temp_dir.path().join("invalid.tsv").to_str().unwrap(),
+            "--coverage-field",
+            "n_valid_cov+n_valid",
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+    assert!(!invalid.status.success());
+}
+
+#[test]
+fn test_contig_methylation_pattern_background_value() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("adjusted.tsv");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--background-value",
+            "0.1",
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let adjusted = std::fs::read_to_string(&out_file).expect("Failed to read output");
+    let mut lines = adjusted.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "contig\tmotif\tmod_type\tmod_position\tmethylation_value\tmean_read_cov\tn_motif_obs\tmotif_occurences_total\tn_filtered_low_cov\tn_filtered_diff\tbackground\tmethylation_adjusted"
+    );
+
+    let mut n_rows = 0;
+    for line in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let value: f64 = fields[4].parse().unwrap();
+        let background: f64 = fields[10].parse().unwrap();
+        let methylation_adjusted: f64 = fields[11].parse().unwrap();
+        assert_eq!(background, 0.1);
+        assert_eq!(methylation_adjusted, (value - 0.1).clamp(0.0, 1.0));
+        n_rows += 1;
+    }
+    assert!(n_rows > 0);
+}
+
+#[test]
+fn test_contig_methylation_pattern_bedmethyl_round_trip() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let bedmethyl_file = temp_dir.path().join("raw.bed");
+    let raw_file = temp_dir.path().join("raw.tsv");
+    let round_trip_file = temp_dir.path().join("round_trip.tsv");
+
+    let run = |pileup: &PathBuf, out_file: &PathBuf, extra_args: &[&str]| {
+        let output = Command::new("cargo")
+            .args(&[
+                "run",
+                "--quiet",
+                "--",
+                "methylation-pattern",
+                "contig",
+                "-p",
+                pileup.to_str().unwrap(),
+                "-a",
+                assembly.to_str().unwrap(),
+                "-m",
+                "GATC_a_1",
+                "GATC_m_3",
+                "-o",
+                out_file.to_str().unwrap(),
+                "--output-type",
+                "raw",
+            ])
+            .args(extra_args)
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+    };
+
+    run(&pileup, &bedmethyl_file, &["--raw-format", "bedmethyl"]);
+    run(&pileup, &raw_file, &[]);
+    // The bedMethyl records already passed the filters.
+    run(
+        &bedmethyl_file,
+        &round_trip_file,
+        &[
+            "--min-valid-read-coverage",
+            "0",
+            "--min-valid-cov-to-diff-fraction",
+            "0",
+        ],
+    );
+
+    let bedmethyl = std::fs::read_to_string(&bedmethyl_file).expect("Failed to read output");
+    let first = bedmethyl.lines().next().expect("Empty bedMethyl output");
+    assert_eq!(first.split('\t').count(), 18);
+
+    let raw = std::fs::read_to_string(&raw_file).expect("Failed to read output");
+    let round_trip = std::fs::read_to_string(&round_trip_file).expect("Failed to read output");
+    assert!(raw.lines().count() > 1);
+    assert_eq!(raw, round_trip);
+}
+
+#[test]
+fn test_contig_methylation_pattern_motif_occurrence_cache() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let cache_file = temp_dir.path().join("occurrences.tsv");
+
+    let run = |name: &str, extra_args: &[&str]| -> String {
+        let out_file = temp_dir.path().join(name);
+        let output = Command::new("cargo")
+            .args(&[
+                "run",
+                "--quiet",
+                "--",
+                "methylation-pattern",
+                "contig",
+                "-p",
+                pileup.to_str().unwrap(),
+                "-a",
+                assembly.to_str().unwrap(),
+                "-m",
+                "GATC_a_1",
+                "GATC_m_3",
+                "-o",
+                out_file.to_str().unwrap(),
+            ])
+            .args(extra_args)
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+        std::fs::read_to_string(&out_file).expect("Failed to read output")
+    };
+
+    let scanned = run("scanned.tsv", &[]);
+    let cache_args = ["--motif-occurrence-cache", cache_file.to_str().unwrap()];
+    // The first run writes the cache, the second loads it.
+    assert_eq!(run("written.tsv", &cache_args), scanned);
+    assert!(cache_file.exists());
+    assert_eq!(run("loaded.tsv", &cache_args), scanned);
+
+    // A corrupt cache is replaced with a warning instead of failing the run.
+    std::fs::write(&cache_file, "not a cache\n").expect("Failed to write cache");
+    assert_eq!(run("rescanned.tsv", &cache_args), scanned);
+    let cache = std::fs::read_to_string(&cache_file).expect("Failed to read cache");
+    assert!(cache.starts_with("# epimetheus motif occurrences v1 xxh3_64="));
+}
+
+#[test]
+fn test_contig_methylation_pattern_collapse_strands() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+    let run = |name: &str, extra_args: &[&str]| -> Vec<(String, u32)> {
+        let out_file = temp_dir.path().join(name);
+        let output = Command::new("cargo")
+            .args(&[
+                "run",
+                "--quiet",
+                "--",
+                "methylation-pattern",
+                "contig",
+                "-p",
+                pileup.to_str().unwrap(),
+                "-a",
+                assembly.to_str().unwrap(),
+                "-m",
+                "GATC_a_1",
+                "-o",
+                out_file.to_str().unwrap(),
+            ])
+            .args(extra_args)
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+
+        let out = std::fs::read_to_string(&out_file).expect("Failed to read output");
+        let mut rows: Vec<(String, u32)> = out
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                (fields[0].to_string(), fields[6].parse().unwrap())
+            })
+            .collect();
+        rows.sort();
+        rows
+    };
+
+    let stranded = run("stranded.tsv", &[]);
+    let collapsed = run("collapsed.tsv", &["--collapse-strands"]);
+    assert_eq!(stranded.len(), collapsed.len());
+
+    // Both strands of a GATC site are one observation once collapsed.
+    for ((contig, n_stranded), (collapsed_contig, n_collapsed)) in stranded.iter().zip(&collapsed) {
+        assert_eq!(contig, collapsed_contig);
+        assert!(n_collapsed <= n_stranded && 2 * n_collapsed >= *n_stranded);
+    }
+    let total = |rows: &[(String, u32)]| rows.iter().map(|(_, n)| n).sum::<u32>();
+    assert!(total(&collapsed) < total(&stranded));
+}
+
+#[test]
+fn test_contig_methylation_pattern_min_motif_observations() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+    let run = |name: &str, extra_args: &[&str]| -> Vec<(String, String, u32)> {
+        let out_file = temp_dir.path().join(name);
+        let output = Command::new("cargo")
+            .args(&[
+                "run",
+                "--quiet",
+                "--",
+                "methylation-pattern",
+                "contig",
+                "-p",
+                pileup.to_str().unwrap(),
+                "-a",
+                assembly.to_str().unwrap(),
+                "-m",
+                "GATC_a_1",
+                "GATC_m_3",
+                "RGATCY_a_2",
+                "-o",
+                out_file.to_str().unwrap(),
+            ])
+            .args(extra_args)
+            .output()
+            .expect("Failed to execute cargo run");
+        assert!(
+            output.status.success(),
+            "Process ended with non-success status: {:?}",
+            output.status
+        );
+
+        let out = std::fs::read_to_string(&out_file).expect("Failed to read output");
+        let mut rows: Vec<(String, String, u32)> = out
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                let motif = format!("{}_{}_{}", fields[1], fields[2], fields[3]);
+                (fields[0].to_string(), motif, fields[6].parse().unwrap())
+            })
+            .collect();
+        rows.sort();
+        rows
+    };
+
+    let all = run("all.tsv", &[]);
+    let threshold = all.iter().map(|(_, _, n)| *n).max().unwrap();
+    let filtered = run(
+        "filtered.tsv",
+        &["--min-motif-observations", &threshold.to_string()],
+    );
+
+    let expected: Vec<_> = all
+        .iter()
+        .filter(|(_, _, n)| *n >= threshold)
+        .cloned()
+        .collect();
+    assert!(expected.len() < all.len());
+    assert_eq!(filtered, expected);
+}
+
+#[test]
+fn test_contig_methylation_pattern_multiple_pileups_are_merged() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+    let compress = |name: &str, lines: &[&str]| -> String {
+        let bed = temp_dir.path().join(name);
+        fs::write(&bed, lines.join("\n") + "\n").expect("Failed to write pileup");
+        let gz = temp_dir.path().join(format!("{}.gz", name));
+        let status = Command::new("cargo")
+            .args(&[
+                "run",
+                "--quiet",
+                "--",
+                "bgzip",
+                "compress",
+                "-i",
+                bed.to_str().unwrap(),
+                "-o",
+                gz.to_str().unwrap(),
+            ])
+            .status()
+            .expect("Failed to execute cargo run");
+        assert!(status.success(), "Compression failed with status: {:?}", status);
+        gz.to_str().unwrap().to_string()
+    };
+
+    let run = |name: &str, pileups: &[String]| -> Vec<Vec<String>> {
+        let out_file = temp_dir.path().join(name);
+        let status = Command::new("cargo")
+            .args(&[
+                "run",
+                "--quiet",
+                "--",
+                "methylation-pattern",
+                "contig",
+                "-p",
+            ])
+            .args(pileups)
+            .args(&[
+                "-a",
+                assembly.to_str().unwrap(),
+                "-m",
+                "GATC_a_1",
+                "GATC_m_3",
+                "RGATCY_a_2",
+                "-o",
+                out_file.to_str().unwrap(),
+            ])
+            .status()
+            .expect("Failed to execute cargo run");
+        assert!(
+            status.success(),
+            "Process ended with non-success status: {:?}",
+            status
+        );
+
+        let out = fs::read_to_string(&out_file).expect("Failed to read output");
+        let mut rows: Vec<Vec<String>> = out
+            .lines()
+            .skip(1)
+            .map(|line| line.split('\t').map(String::from).collect())
+            .collect();
+        rows.sort();
+        rows
+    };
+
+    // One pileup per contig, so each index lists only one of the contigs.
+    let content = fs::read_to_string(&pileup).expect("Failed to read pileup");
+    let lines: Vec<&str> = content.lines().collect();
+    let (contig_2, contig_3): (Vec<&str>, Vec<&str>) = lines
+        .iter()
+        .copied()
+        .partition(|line| line.starts_with("contig_2\t"));
+    assert!(!contig_2.is_empty() && !contig_3.is_empty());
+
+    let single = run("single.tsv", &[compress("all.bed", &lines)]);
+    let merged = run(
+        "merged.tsv",
+        &[
+            compress("contig_2.bed", &contig_2),
+            compress("contig_3.bed", &contig_3),
+        ],
+    );
+
+    assert!(!single.is_empty());
+    assert_eq!(merged, single);
+}
+
+#[test]
+fn test_contig_methylation_pattern_weighted_median() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+    let expected_out = data_dir.join("expected_out_median.tsv");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_weighted_median.tsv");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--output-type",
+            "weighted-median",
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    // Only the methylation value differs from the median output.
+    let actual = fs::read_to_string(&out_file).expect("Failed to read output");
+    let expected = fs::read_to_string(&expected_out).expect("Could not read expected output file");
+    let actual_lines: Vec<&str> = actual.trim().lines().collect();
+    let expected_lines: Vec<&str> = expected.trim().lines().collect();
+    assert_eq!(actual_lines[0], expected_lines[0]);
+    assert_eq!(actual_lines.len(), expected_lines.len());
+    for (actual, expected) in actual_lines[1..].iter().zip(&expected_lines[1..]) {
+        let actual: Vec<&str> = actual.split('\t').collect();
+        let expected: Vec<&str> = expected.split('\t').collect();
+        assert_eq!(actual[..4], expected[..4]);
+        assert_eq!(actual[5..], expected[5..]);
+        let value: f64 = actual[4].parse().unwrap();
+        assert!((0.0..=1.0).contains(&value));
+    }
+}
+
+#[test]
+fn test_contig_methylation_pattern_summary_output() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out.tsv");
+    let summary_file = temp_dir.path().join("test_summary.tsv");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--output-type",
+            "median",
+            "--summary-output",
+            summary_file.to_str().unwrap(),
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let out = fs::read_to_string(&out_file).expect("Failed to read output");
+    let mut contigs: Vec<&str> = out
+        .trim()
+        .lines()
+        .skip(1)
+        .map(|line| line.split('\t').next().unwrap())
+        .collect();
+    contigs.dedup();
+
+    let summary = fs::read_to_string(&summary_file).expect("Failed to read summary");
+    let lines: Vec<&str> = summary.trim().lines().collect();
+    assert_eq!(
+        lines[0],
+        "contig\tn_motifs_above_threshold\tmean_methylation\ttotal_motif_obs"
+    );
+    assert_eq!(lines.len() - 1, contigs.len());
+    for line in &lines[1..] {
+        let fields: Vec<&str> = line.split('\t').collect();
+        assert!(contigs.contains(&fields[0]));
+        let n_above: usize = fields[1].parse().unwrap();
+        assert!(n_above <= 3);
+    }
+}
+
+#[test]
+fn test_compress_pileup_compression_level() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+    let compress = |level: &str| {
+        let compressed_pileup = temp_dir
+            .path()
+            .join(format!("geobacillus-plasmids.level{}.pileup.bed.gz", level));
+        let status = Command::new("cargo")
+            .args(&[
+                "run",
+                "--quiet",
+                "--",
+                "bgzip",
+                "compress",
+                "-i",
+                pileup.to_str().unwrap(),
+                "-o",
+                compressed_pileup.to_str().unwrap(),
+                "--keep",
+                "--no-progress",
+                "--level",
+                level,
+            ])
+            .status()
+            .expect("Failed to execute cargo run");
+        (status, compressed_pileup)
+    };
+
+    let (status, fast) = compress("1");
+    assert!(
+        status.success(),
+        "Compression failed with status: {:?}",
+        status
+    );
+    let (status, best) = compress("9");
+    assert!(
+        status.success(),
+        "Compression failed with status: {:?}",
+        status
+    );
+    assert!(
+        fs::metadata(&best).unwrap().len() <= fs::metadata(&fast).unwrap().len(),
+        "Level 9 should not be larger than level 1"
+    );
+
+    let (status, _) = compress("10");
+    assert!(!status.success(), "Level 10 should be rejected");
+}
+
+#[test]
+fn test_contig_methylation_pattern_count_only() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+    let expected_out = data_dir.join("expected_out_median.tsv");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_count_only.tsv");
+
+    // The pileup is never read, so a missing file is fine.
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            temp_dir.path().join("missing.bed").to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--count-only",
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let actual = fs::read_to_string(&out_file).expect("Failed to read output");
+    let lines: Vec<&str> = actual.trim().lines().collect();
+    assert_eq!(lines[0], "contig\tmotif\tmod_type\toccurrences");
+
+    // The occurrences match the motif_occurences_total column of the methylation output.
+    let expected = fs::read_to_string(&expected_out).expect("Could not read expected output file");
+    for line in expected.trim().lines().skip(1) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let row = format!("{}\t{}\t{}\t{}", fields[0], fields[1], fields[2], fields[7]);
+        assert!(lines.contains(&row.as_str()), "Missing row: {}", row);
+    }
+}
+
+#[test]
+fn test_contig_methylation_pattern_percentage() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+    let expected_out = data_dir.join("expected_out_median.tsv");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out_percentage.tsv");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--output-type",
+            "median",
+            "--percentage",
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let actual = fs::read_to_string(&out_file).expect("Failed to read output");
+    let expected = fs::read_to_string(&expected_out).expect("Could not read expected output file");
+    let actual_lines: Vec<&str> = actual.trim().lines().collect();
+    let expected_lines: Vec<&str> = expected.trim().lines().collect();
+
+    assert_eq!(
+        actual_lines[0],
+        expected_lines[0].replace("methylation_value", "methylation_percent")
+    );
+    assert_eq!(actual_lines.len(), expected_lines.len());
+    for (actual_line, expected_line) in actual_lines.iter().zip(&expected_lines).skip(1) {
+        let actual_fields: Vec<&str> = actual_line.split('\t').collect();
+        let expected_fields: Vec<&str> = expected_line.split('\t').collect();
+        let percent: f64 = actual_fields[4].parse().unwrap();
+        let fraction: f64 = expected_fields[4].parse().unwrap();
+        assert!(
+            (percent - fraction * 100.0).abs() < 1e-9,
+            "{} != {} * 100",
+            percent,
+            fraction
+        );
+        assert_eq!(actual_fields[..4], expected_fields[..4]);
+        assert_eq!(actual_fields[5..], expected_fields[5..]);
+    }
+}
+
+#[test]
+fn test_contig_methylation_pattern_json_format() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+    let expected_out = data_dir.join("expected_out_median.tsv");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out.json");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--output-type",
+            "median",
+            "--format",
+            "json",
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let actual: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&out_file).expect("Failed to read output"))
+            .expect("Output is not valid JSON");
+    let rows = actual.as_array().expect("Output is not a JSON array");
+
+    // The objects hold the TSV rows in the same order.
+    let expected = fs::read_to_string(&expected_out).expect("Could not read expected output file");
+    let mut expected_lines = expected.trim().lines();
+    let columns: Vec<&str> = expected_lines.next().unwrap().split('\t').collect();
+    let expected_lines: Vec<&str> = expected_lines.collect();
+    assert_eq!(rows.len(), expected_lines.len());
+    for (row, line) in rows.iter().zip(expected_lines) {
+        let row = row.as_object().expect("Row is not a JSON object");
+        assert_eq!(row.len(), columns.len());
+        for (column, value) in columns.iter().zip(line.split('\t')) {
+            let field = &row[*column];
+            match field.as_str() {
+                Some(text) => assert_eq!(text, value),
+                None => assert_eq!(field.as_f64().unwrap(), value.parse::<f64>().unwrap()),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_contig_methylation_pattern_coverage_histogram() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let data_dir = PathBuf::from(manifest_dir).join("tests/data");
+
+    let pileup = data_dir.join("geobacillus-plasmids.pileup.bed");
+    let assembly = data_dir.join("geobacillus-plasmids.assembly.fasta");
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let out_file = temp_dir.path().join("test_out.tsv");
+    let histogram_file = temp_dir.path().join("coverage_histogram.tsv");
+
+    let output = Command::new("cargo")
+        .args(&[
+            "run",
+            "--quiet",
+            "--",
+            "methylation-pattern",
+            "contig",
+            "-p",
+            pileup.to_str().unwrap(),
+            "-a",
+            assembly.to_str().unwrap(),
+            "-m",
+            "GATC_a_1",
+            "GATC_m_3",
+            "RGATCY_a_2",
+            "-o",
+            out_file.to_str().unwrap(),
+            "--output-type",
+            "median",
+            "--coverage-histogram",
+            histogram_file.to_str().unwrap(),
+            "--coverage-histogram-bin-width",
+            "10",
+        ])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(
+        output.status.success(),
+        "Process ended with non-success status: {:?}",
+        output.status
+    );
+
+    let histogram = fs::read_to_string(&histogram_file).expect("Failed to read histogram");
+    let mut lines = histogram.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "mod_type\tmin_coverage\tmax_coverage\tn_records\tn_records_at_least_min"
+    );
+
+    // Every record is counted once, before the coverage filters.
+    let n_pileup_records = fs::read_to_string(&pileup)
+        .expect("Failed to read pileup")
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .count() as u64;
+    let mut n_records = 0;
+    for line in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let min_coverage: u32 = fields[1].parse().unwrap();
+        let max_coverage: u32 = fields[2].parse().unwrap();
+        assert_eq!(min_coverage % 10, 0);
+        assert_eq!(max_coverage, min_coverage + 9);
+        n_records += fields[3].parse::<u64>().unwrap();
+        assert!(fields[4].parse::<u64>().unwrap() >= fields[3].parse::<u64>().unwrap());
+    }
+    assert_eq!(n_records, n_pileup_records);
+}
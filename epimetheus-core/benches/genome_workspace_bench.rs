@@ -0,0 +1,38 @@
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use epimetheus_core::models::{contig::Contig, genome_workspace::GenomeWorkspaceBuilder};
+
+fn benchmark_batch_building(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Genome workspace batch building");
+
+    let batch_sizes = [100, 1000, 10000];
+
+    for &batch_size in &batch_sizes {
+        let contigs: Vec<Contig> = (0..batch_size)
+            .map(|i| Contig::from_string(format!("contig_{}", i), "GATC".to_string()).unwrap())
+            .collect();
+
+        group.bench_function(format!("new_batch_{}", batch_size), |b| {
+            b.iter(|| {
+                let mut builder = GenomeWorkspaceBuilder::new();
+                for contig in &contigs {
+                    builder.add_contig(contig.clone()).unwrap();
+                }
+                black_box(builder.build());
+            });
+        });
+
+        group.bench_function(format!("with_capacity_batch_{}", batch_size), |b| {
+            b.iter(|| {
+                let mut builder = GenomeWorkspaceBuilder::with_capacity(batch_size);
+                for contig in &contigs {
+                    builder.add_contig(contig.clone()).unwrap();
+                }
+                black_box(builder.build());
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_batch_building);
+criterion_main!(benches);
@@ -1,44 +1,106 @@
 use ahash::{AHashMap, HashMap};
 use anyhow::Result;
 use log::error;
-use epimetheus_methylome::{Strand, find_motif_indices_in_sequence, motif::Motif};
+use epimetheus_methylome::{
+    Strand, count_motif_occurrences, find_motif_indices_in_sequence, motif::Motif,
+};
 use rayon::prelude::*;
+use std::borrow::Cow;
 
 use crate::models::{
     contig::{Contig, ContigId, Position as ContigPosition},
     genome_workspace::GenomeWorkspace,
-    methylation::{MethylationCoverage, MotifMethylationPositions},
+    methylation::{
+        CalledFrequencyMotifMethylationDegree, FilteredCounts, MedianMotifMethylationDegree,
+        MethylationCoverage, MethylationOutput, MethylationPatternVariant,
+        MotifMethylationPositions, TrimmedMeanMotifMethylationDegree,
+        WeightedMeanMotifMethylationDegree, WeightedMedianMotifMethylationDegree,
+    },
+    motif_occurrences::{MotifOccurrence, MotifOccurrenceTable},
+};
+use crate::services::domain::motif_processor::{
+    canonical_bipartite_motifs, combine_bipartite_motifs,
 };
 
 pub fn calculate_contig_read_methylation_single(
     contig: &Contig,
     motifs: Vec<Motif>,
+) -> Result<MotifMethylationPositions> {
+    calculate_contig_read_methylation_with_occurrences(contig, motifs, None)
+}
+
+/// Like [`calculate_contig_read_methylation_single`], but takes the motif occurrences
+/// from `occurrences` instead of scanning the contig when given.
+///
+/// A bipartite motif given together with its partner (see
+/// [`canonical_bipartite_motifs`]) is one signal: the positions of both motifs and their
+/// filtered counts are reported under the canonical motif of the two. Both motifs match
+/// the same sites, so the motif occurrences are not summed.
+pub fn calculate_contig_read_methylation_with_occurrences(
+    contig: &Contig,
+    motifs: Vec<Motif>,
+    occurrences: Option<&MotifOccurrenceTable>,
 ) -> Result<MotifMethylationPositions> {
     let contig_seq = &contig.sequence;
 
     let mut all_methylation_data = AHashMap::new();
     let mut motif_occurence_totals = AHashMap::new();
+    let mut filtered_counts: AHashMap<(ContigId, Motif), FilteredCounts> = AHashMap::new();
+    let canonical = canonical_bipartite_motifs(&motifs);
 
     for motif in motifs.iter() {
         let mod_type = motif.mod_type;
-
-        let fwd_indices: Vec<usize> = find_motif_indices_in_sequence(&contig_seq, motif);
-        let rev_indices: Vec<usize> =
-            find_motif_indices_in_sequence(&contig_seq, &motif.reverse_complement());
-
-        if fwd_indices.is_empty() && rev_indices.is_empty() {
+        let reported = canonical.get(motif).copied().unwrap_or(motif);
+
+        let occurrence = match occurrences {
+            Some(table) => table
+                .get(&contig.id, motif)
+                .map(Cow::Borrowed)
+                .unwrap_or_default(),
+            None => Cow::Owned(MotifOccurrence::scan(contig_seq, motif)),
+        };
+
+        // A motif absent from the contig gets no row.
+        if occurrence.is_empty() {
             continue;
         }
 
-        // This is the actual number of motifs in the contig
-        motif_occurence_totals.insert((contig.id.clone(), motif.clone(), Strand::Positive), fwd_indices.len() as u32);
-        motif_occurence_totals.insert((contig.id.clone(), motif.clone(), Strand::Negative), rev_indices.len() as u32);
-        // let motif_occurences_total = fwd_indices.len() as u32 + rev_indices.len() as u32;
+        // The number of motif occurrences in the contig. Palindromic sites match both
+        // strands but are counted once.
+        motif_occurence_totals.insert(
+            (contig.id.clone(), reported.clone()),
+            occurrence.n_occurrences,
+        );
+
+        if !contig.filtered_positions.is_empty() {
+            let mut counts = contig.count_filtered_positions(
+                &occurrence.plus_positions,
+                Strand::Positive,
+                mod_type,
+            );
+            let minus_counts = contig.count_filtered_positions(
+                &occurrence.minus_positions,
+                Strand::Negative,
+                mod_type,
+            );
+            counts.n_filtered_low_cov += minus_counts.n_filtered_low_cov;
+            counts.n_filtered_diff += minus_counts.n_filtered_diff;
+            if counts != FilteredCounts::default() {
+                let total = filtered_counts
+                    .entry((contig.id.clone(), reported.clone()))
+                    .or_default();
+                total.n_filtered_low_cov += counts.n_filtered_low_cov;
+                total.n_filtered_diff += counts.n_filtered_diff;
+            }
+        }
 
         let fwd_methylation =
-            contig.get_methylated_positions(&fwd_indices, epimetheus_methylome::Strand::Positive, mod_type);
-        let rev_methylation =
-            contig.get_methylated_positions(&rev_indices, epimetheus_methylome::Strand::Negative, mod_type);
+            contig.get_methylated_positions(&occurrence.plus_positions, Strand::Positive, mod_type);
+        let rev_methylation = contig.get_methylated_positions(
+            &occurrence.minus_positions,
+            Strand::Negative,
+            mod_type,
+        );
 
         let methylation_data_fwd: HashMap<
             (ContigId, Motif, ContigPosition, Strand),
@@ -48,7 +110,7 @@ pub fn calculate_contig_read_methylation_single(
             .filter_map(|(pos, maybe_cov)| {
                 maybe_cov.map(|meth| {
                     (
-                        (contig.id.clone(), motif.clone(), pos, Strand::Positive),
+                        (contig.id.clone(), reported.clone(), pos, Strand::Positive),
                         meth.clone(),
                     )
                 })
@@ -63,7 +125,7 @@ pub fn calculate_contig_read_methylation_single(
             .filter_map(|(pos, maybe_cov)| {
                 maybe_cov.map(|meth| {
                     (
-                        (contig.id.clone(), motif.clone(), pos, Strand::Negative),
+                        (contig.id.clone(), reported.clone(), pos, Strand::Negative),
                         meth.clone(),
                     )
                 })
@@ -81,41 +143,225 @@ pub fn calculate_contig_read_methylation_single(
     Ok(MotifMethylationPositions {
         methylation: all_methylation_data,
         motif_occurence_totals: motif_occurence_totals,
+        filtered_counts,
     })
 }
 
+/// Methylation pattern of a contig without methylation records, to report the contig
+/// instead of leaving it out. Each motif occurring in the contig gets a row with zero
+/// observations, a NaN methylation value and a mean read coverage of 0.
+///
+/// Raw and strand asymmetry patterns have a row per observation, so the raw pattern
+/// only holds the motif occurrence totals and the strand asymmetry pattern is empty.
+/// Bipartite partners get a single row like in
+/// [`calculate_contig_read_methylation_with_occurrences`].
+pub fn empty_contig_methylation_pattern(
+    contig: &Contig,
+    motifs: &[Motif],
+    output_type: &MethylationOutput,
+) -> MethylationPatternVariant {
+    let totals: Vec<(Motif, u32)> = combine_bipartite_motifs(motifs.to_vec())
+        .into_iter()
+        .map(|motif| {
+            let n_occurences = count_motif_occurrences(&contig.sequence, &motif) as u32;
+            (motif, n_occurences)
+        })
+        .filter(|(_, n_occurences)| *n_occurences > 0)
+        .collect();
+    let contig_id = &contig.id;
+
+    match output_type {
+        MethylationOutput::Raw => MethylationPatternVariant::Raw(MotifMethylationPositions::new(
+            AHashMap::new(),
+            totals
+                .into_iter()
+                .map(|(motif, total)| ((contig_id.clone(), motif), total))
+                .collect(),
+        )),
+        MethylationOutput::Median => MethylationPatternVariant::Median(
+            totals
+                .into_iter()
+                .map(|(motif, total)| MedianMotifMethylationDegree {
+                    contig: contig_id.clone(),
+                    motif,
+                    median: f64::NAN,
+                    mean_read_cov: 0.0,
+                    n_motif_obs: 0,
+                    motif_occurences_total: total,
+                    n_filtered_low_cov: 0,
+                    n_filtered_diff: 0,
+                })
+                .collect(),
+        ),
+        MethylationOutput::WeightedMean => MethylationPatternVariant::WeightedMean(
+            totals
+                .into_iter()
+                .map(|(motif, total)| WeightedMeanMotifMethylationDegree {
+                    contig: contig_id.clone(),
+                    motif,
+                    w_mean: f64::NAN,
+                    stderr: f64::NAN,
+                    mean_read_cov: 0.0,
+                    n_motif_obs: 0,
+                    motif_occurences_total: total,
+                    n_filtered_low_cov: 0,
+                    n_filtered_diff: 0,
+                })
+                .collect(),
+        ),
+        MethylationOutput::WeightedMedian => MethylationPatternVariant::WeightedMedian(
+            totals
+                .into_iter()
+                .map(|(motif, total)| WeightedMedianMotifMethylationDegree {
+                    contig: contig_id.clone(),
+                    motif,
+                    w_median: f64::NAN,
+                    mean_read_cov: 0.0,
+                    n_motif_obs: 0,
+                    motif_occurences_total: total,
+                    n_filtered_low_cov: 0,
+                    n_filtered_diff: 0,
+                })
+                .collect(),
+        ),
+        MethylationOutput::TrimmedMean => MethylationPatternVariant::TrimmedMean(
+            totals
+                .into_iter()
+                .map(|(motif, total)| TrimmedMeanMotifMethylationDegree {
+                    contig: contig_id.clone(),
+                    motif,
+                    trimmed_mean: f64::NAN,
+                    mean_read_cov: 0.0,
+                    n_motif_obs: 0,
+                    motif_occurences_total: total,
+                    n_filtered_low_cov: 0,
+                    n_filtered_diff: 0,
+                    trim_applied: false,
+                })
+                .collect(),
+        ),
+        MethylationOutput::CalledFrequency => MethylationPatternVariant::CalledFrequency(
+            totals
+                .into_iter()
+                .map(|(motif, total)| CalledFrequencyMotifMethylationDegree {
+                    contig: contig_id.clone(),
+                    motif,
+                    called_frequency: f64::NAN,
+                    mean_read_cov: 0.0,
+                    n_motif_obs: 0,
+                    motif_occurences_total: total,
+                    n_filtered_low_cov: 0,
+                    n_filtered_diff: 0,
+                    n_called_methylated: 0,
+                })
+                .collect(),
+        ),
+        MethylationOutput::StrandAsymmetry => {
+            MethylationPatternVariant::StrandAsymmetry(Vec::new())
+        }
+    }
+}
+
+/// Every match of `motifs` in the contig as (contig, motif, position, strand), sorted
+/// by motif, position and strand.
+///
+/// Positions are those of the modified base, as in the methylation pattern. Matches of
+/// the reverse complement are on the minus strand. Matches of bipartite partners are
+/// given under their canonical motif (see [`canonical_bipartite_motifs`]).
+pub fn find_motif_sites(
+    contig: &Contig,
+    motifs: &[Motif],
+) -> Vec<(ContigId, Motif, ContigPosition, Strand)> {
+    let canonical = canonical_bipartite_motifs(motifs);
+    let mut sites = Vec::new();
+    for motif in motifs {
+        let reported = canonical.get(motif).copied().unwrap_or(motif);
+        for position in find_motif_indices_in_sequence(&contig.sequence, motif) {
+            sites.push((
+                contig.id.clone(),
+                reported.clone(),
+                position,
+                Strand::Positive,
+            ));
+        }
+        let reverse_complement = motif.reverse_complement();
+        for position in find_motif_indices_in_sequence(&contig.sequence, &reverse_complement) {
+            sites.push((
+                contig.id.clone(),
+                reported.clone(),
+                position,
+                Strand::Negative,
+            ));
+        }
+    }
+
+    sites.sort_unstable();
+    sites.dedup();
+    sites
+}
+
+/// The number of occurrences of each motif in each contig as (contig, motif,
+/// occurrences), sorted by contig and motif. Motifs without occurrences are included.
+///
+/// Occurrences are counted as for the `motif_occurences_total` column. See
+/// [`count_motif_occurrences`].
+pub fn count_contig_motif_occurrences(
+    contigs: &AHashMap<ContigId, Contig>,
+    motifs: &[Motif],
+) -> Vec<(ContigId, Motif, u32)> {
+    let mut counts: Vec<(ContigId, Motif, u32)> = contigs
+        .par_iter()
+        .flat_map_iter(|(contig_id, contig)| {
+            motifs.iter().map(move |motif| {
+                let n_occurrences = count_motif_occurrences(&contig.sequence, motif) as u32;
+                (contig_id.clone(), motif.clone(), n_occurrences)
+            })
+        })
+        .collect();
+
+    counts.sort_unstable_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+    counts
+}
+
 pub fn calculate_contig_read_methylation_pattern(
     contigs: GenomeWorkspace,
     motifs: Vec<Motif>,
     num_threads: usize,
 ) -> Result<MotifMethylationPositions> {
-    rayon::ThreadPoolBuilder::new()
+    let pool = rayon::ThreadPoolBuilder::new()
         .num_threads(num_threads)
         .build()
         .expect("Could not initialize threadpool");
 
     let mut combined_contig_motif_methylation = AHashMap::new();
     let mut combined_contig_motif_occurences = AHashMap::new();
-    let results: Vec<MotifMethylationPositions> = contigs
-        .get_workspace()
-        .par_iter()
-        .map(|(contig_id, contig)| {
-            calculate_contig_read_methylation_single(contig, motifs.clone()).unwrap_or_else(|e| {
-                error!("Error processing contig {}: {}", contig_id, e);
-                MotifMethylationPositions::new(AHashMap::new(), AHashMap::new())
+    let mut combined_filtered_counts = AHashMap::new();
+    let results: Vec<MotifMethylationPositions> = pool.install(|| {
+        contigs
+            .get_workspace()
+            .par_iter()
+            .map(|(contig_id, contig)| {
+                calculate_contig_read_methylation_single(contig, motifs.clone()).unwrap_or_else(
+                    |e| {
+                        error!("Error processing contig {}: {}", contig_id, e);
+                        MotifMethylationPositions::new(AHashMap::new(), AHashMap::new())
+                    },
+                )
             })
-        })
-        .collect();
+            .collect()
+    });
 
     for res in results {
         combined_contig_motif_methylation.extend(res.methylation);
         combined_contig_motif_occurences.extend(res.motif_occurence_totals);
+        combined_filtered_counts.extend(res.filtered_counts);
     }
 
-    Ok(MotifMethylationPositions::new(
-        combined_contig_motif_methylation,
-        combined_contig_motif_occurences
-    ))
+    Ok(MotifMethylationPositions {
+        methylation: combined_contig_motif_methylation,
+        motif_occurence_totals: combined_contig_motif_occurences,
+        filtered_counts: combined_filtered_counts,
+    })
 }
 
 #[cfg(test)]
@@ -129,7 +375,7 @@ mod tests {
     use crate::models::{
         genome_workspace::GenomeWorkspaceBuilder,
         methylation::MethylationRecord,
-        pileup::{PileupRecord, PileupRecordString},
+        pileup::{CoverageField, PileupRecord, PileupRecordString},
     };
 
     use super::*;
@@ -173,7 +419,14 @@ mod tests {
         for res in reader.lines() {
             let record = res.unwrap();
             let pileup_record = PileupRecord::try_from(PileupRecordString::new(record)).unwrap();
-            let meth_record = MethylationRecord::try_from_with_filters(pileup_record, 1, 0.8)?;
+            let meth_record = MethylationRecord::try_from_with_filters(
+                pileup_record,
+                1,
+                0.8,
+                0,
+                &CoverageField::default(),
+                None,
+            )?;
             if let Some(meth) = meth_record {
                 workspace_builder.add_record(meth).unwrap();
             }
@@ -227,4 +480,283 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_motif_occurences_total_counts_palindromes_once() -> Result<()> {
+        // GATC at 0..4, 6..10 and 12..16. Each site matches on both strands.
+        let mut contig =
+            Contig::from_string("contig_1".to_string(), "GATCAAGATCTTGATC".to_string())?;
+        contig.add_methylation_record(
+            MethylationRecord::new(
+                "contig_1".to_string(),
+                1,
+                Strand::Positive,
+                epimetheus_methylome::ModType::SixMA,
+                MethylationCoverage::new(8, 10, 0, 0, 0)?,
+            ),
+            false,
+        )?;
+
+        let motifs = vec![Motif::new("GATC", "a", 1)?];
+        let methylation = calculate_contig_read_methylation_single(&contig, motifs)?;
+
+        let totals: Vec<u32> = methylation
+            .to_median_degrees()
+            .iter()
+            .map(|res| res.motif_occurences_total)
+            .collect();
+        assert_eq!(totals, vec![3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reverse_complement_hits_are_attributed_to_motif() -> Result<()> {
+        // GAAG at 2..6 on the plus strand and its reverse complement CTTC at 8..12.
+        let mut contig = Contig::from_string("contig_1".to_string(), "AAGAAGTTCTTCAA".to_string())?;
+        for (position, strand, n_modified) in [(3, Strand::Positive, 8), (10, Strand::Negative, 2)]
+        {
+            contig.add_methylation_record(
+                MethylationRecord::new(
+                    "contig_1".to_string(),
+                    position,
+                    strand,
+                    epimetheus_methylome::ModType::SixMA,
+                    MethylationCoverage::new(n_modified, 10, 0, 0, 0)?,
+                ),
+                false,
+            )?;
+        }
+
+        let gaag = Motif::new("GAAG", "a", 1)?;
+        let methylation = calculate_contig_read_methylation_single(&contig, vec![gaag.clone()])?;
+
+        let mut keys: Vec<(ContigPosition, Strand)> = methylation
+            .methylation
+            .keys()
+            .map(|(_, motif, position, strand)| {
+                assert_eq!(motif, &gaag);
+                (*position, *strand)
+            })
+            .collect();
+        keys.sort_by_key(|(position, _)| *position);
+        assert_eq!(keys, vec![(3, Strand::Positive), (10, Strand::Negative)]);
+
+        let degrees = methylation.to_median_degrees();
+        assert_eq!(degrees.len(), 1);
+        assert_eq!(degrees[0].n_motif_obs, 2);
+        assert_eq!(degrees[0].motif_occurences_total, 2);
+        assert_eq!(degrees[0].median, 0.5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bipartite_partners_are_combined() -> Result<()> {
+        // CAAYNNNNCCT at 1..12. Its partner AGGNNNNRTTG_a_0 marks the A on the minus
+        // strand opposite the T of CCT.
+        let mut contig = Contig::from_string("contig_1".to_string(), "TCAACGTACCCTT".to_string())?;
+        for (position, strand, n_modified) in [(3, Strand::Positive, 8), (11, Strand::Negative, 4)]
+        {
+            contig.add_methylation_record(
+                MethylationRecord::new(
+                    "contig_1".to_string(),
+                    position,
+                    strand,
+                    epimetheus_methylome::ModType::SixMA,
+                    MethylationCoverage::new(n_modified, 10, 0, 0, 0)?,
+                ),
+                false,
+            )?;
+        }
+
+        let partner = Motif::new("AGGNNNNRTTG", "a", 0)?;
+        let motifs = vec![Motif::new("CAAYNNNNCCT", "a", 2)?, partner.clone()];
+        let methylation = calculate_contig_read_methylation_single(&contig, motifs.clone())?;
+
+        let degrees = methylation.to_median_degrees();
+        assert_eq!(degrees.len(), 1);
+        assert_eq!(degrees[0].motif, partner);
+        assert_eq!(degrees[0].n_motif_obs, 2);
+        assert_eq!(degrees[0].motif_occurences_total, 1);
+        assert!((degrees[0].median - 0.6).abs() < 1e-12);
+
+        // Without the partner only the plus strand A of the site is observed.
+        let methylation = calculate_contig_read_methylation_single(&contig, motifs[..1].to_vec())?;
+        let degrees = methylation.to_median_degrees();
+        assert_eq!(degrees.len(), 1);
+        assert_eq!(degrees[0].n_motif_obs, 1);
+
+        let sites: Vec<(Motif, ContigPosition, Strand)> = find_motif_sites(&contig, &motifs)
+            .into_iter()
+            .map(|(_, motif, position, strand)| (motif, position, strand))
+            .collect();
+        assert_eq!(
+            sites,
+            vec![
+                (partner.clone(), 3, Strand::Positive),
+                (partner.clone(), 11, Strand::Negative),
+            ]
+        );
+
+        match empty_contig_methylation_pattern(&contig, &motifs, &MethylationOutput::Median) {
+            MethylationPatternVariant::Median(degrees) => {
+                assert_eq!(degrees.len(), 1);
+                assert_eq!(degrees[0].motif, partner);
+            }
+            _ => panic!("Expected a median pattern"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_absent_motif_has_no_row() -> Result<()> {
+        // GATC occurs once, CCWGG does not occur in the contig.
+        let mut contig = Contig::from_string("contig_1".to_string(), "AAGATCAA".to_string())?;
+        contig.add_methylation_record(
+            MethylationRecord::new(
+                "contig_1".to_string(),
+                3,
+                Strand::Positive,
+                epimetheus_methylome::ModType::SixMA,
+                MethylationCoverage::new(8, 10, 0, 0, 0)?,
+            ),
+            false,
+        )?;
+
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let motifs = vec![gatc.clone(), Motif::new("CCWGG", "m", 1)?];
+        let methylation = calculate_contig_read_methylation_single(&contig, motifs)?;
+
+        assert_eq!(methylation.motif_occurence_totals.len(), 1);
+        let degrees = methylation.to_median_degrees();
+        assert_eq!(degrees.len(), 1);
+        assert_eq!(degrees[0].motif, gatc);
+        assert!(degrees.iter().all(|d| d.motif_occurences_total > 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filtered_positions_are_counted_per_contig_motif() -> Result<()> {
+        use crate::models::methylation::FilterReason::{Diff, LowCoverage};
+        use epimetheus_methylome::ModType::{FiveMC, SixMA};
+
+        // GATC at 5..9 and 11..15. The 6mA is at 6 and 12 on the plus strand and at 7
+        // and 13 on the minus strand.
+        let mut contig =
+            Contig::from_string("contig_1".to_string(), "TGGACGATCCCGATC".to_string())?;
+        contig.add_methylation(
+            6,
+            Strand::Positive,
+            SixMA,
+            MethylationCoverage::new(8, 10, 0, 0, 0)?,
+        )?;
+        contig.filtered_positions.extend([
+            ((12, Strand::Positive, SixMA), LowCoverage),
+            ((7, Strand::Negative, SixMA), Diff),
+            ((13, Strand::Negative, SixMA), Diff),
+            // Not a motif position and another mod type at a motif position.
+            ((3, Strand::Positive, SixMA), LowCoverage),
+            ((12, Strand::Positive, FiveMC), LowCoverage),
+        ]);
+
+        let methylation =
+            calculate_contig_read_methylation_single(&contig, vec![Motif::new("GATC", "a", 1)?])?;
+        let degrees = methylation.to_weighted_mean_degress();
+        assert_eq!(degrees.len(), 1);
+        assert_eq!(degrees[0].n_motif_obs, 1);
+        assert_eq!(degrees[0].n_filtered_low_cov, 1);
+        assert_eq!(degrees[0].n_filtered_diff, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_contig_methylation_pattern() -> Result<()> {
+        let contig = Contig::from_string("contig_1".to_string(), "AAGATCAAGATC".to_string())?;
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let motifs = vec![gatc.clone(), Motif::new("CCWGG", "m", 1)?];
+
+        let MethylationPatternVariant::Median(degrees) =
+            empty_contig_methylation_pattern(&contig, &motifs, &MethylationOutput::Median)
+        else {
+            panic!("Expected a median pattern");
+        };
+        assert_eq!(degrees.len(), 1);
+        assert_eq!(degrees[0].motif, gatc);
+        assert!(degrees[0].median.is_nan());
+        assert_eq!(degrees[0].n_motif_obs, 0);
+        assert_eq!(degrees[0].motif_occurences_total, 2);
+
+        let MethylationPatternVariant::Raw(positions) =
+            empty_contig_methylation_pattern(&contig, &motifs, &MethylationOutput::Raw)
+        else {
+            panic!("Expected a raw pattern");
+        };
+        assert!(positions.methylation.is_empty());
+        assert_eq!(
+            positions
+                .motif_occurence_totals
+                .get(&("contig_1".to_string(), gatc)),
+            Some(&2)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_motif_sites() -> Result<()> {
+        // GATC at 0..4 matches both strands, TCC at 8..11 only the plus strand.
+        let contig = Contig::from_string("contig_1".to_string(), "GATCAAAATCCA".to_string())?;
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let tcc = Motif::new("TCC", "m", 2)?;
+
+        let sites = find_motif_sites(&contig, &[gatc.clone(), tcc.clone()]);
+        let sites: Vec<(Motif, ContigPosition, Strand)> = sites
+            .into_iter()
+            .map(|(_, motif, position, strand)| (motif, position, strand))
+            .collect();
+
+        assert_eq!(
+            sites,
+            vec![
+                (gatc.clone(), 1, Strand::Positive),
+                (gatc, 2, Strand::Negative),
+                (tcc, 10, Strand::Positive),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_contig_motif_occurrences() -> Result<()> {
+        let mut contigs = AHashMap::new();
+        contigs.insert(
+            "contig_2".to_string(),
+            Contig::from_string("contig_2".to_string(), "AAAAAAAA".to_string())?,
+        );
+        contigs.insert(
+            "contig_1".to_string(),
+            Contig::from_string("contig_1".to_string(), "GATCAAAATCCAGATC".to_string())?,
+        );
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let tcc = Motif::new("TCC", "m", 2)?;
+
+        let counts = count_contig_motif_occurrences(&contigs, &[tcc.clone(), gatc.clone()]);
+
+        assert_eq!(
+            counts,
+            vec![
+                ("contig_1".to_string(), gatc.clone(), 2),
+                ("contig_1".to_string(), tcc.clone(), 1),
+                ("contig_2".to_string(), gatc, 0),
+                ("contig_2".to_string(), tcc, 0),
+            ]
+        );
+
+        Ok(())
+    }
 }
@@ -117,4 +117,15 @@ mod tests {
         assert_eq!(motifs_to_keep[1], m3.clone());
         assert_eq!(motifs_to_keep[2], m5.clone());
     }
+
+    #[test]
+    fn test_collapse_motifs_keeps_spacer() {
+        let m1 = Motif::new("CAACNNNNCCT", "a", 2).unwrap();
+        let m2 = Motif::new("CAATNNNNCCT", "a", 2).unwrap();
+
+        let collapsed = collapse_motifs(&vec![m1, m2]).unwrap();
+
+        assert_eq!(collapsed.sequence_to_string(), "CAAYNNNNCCT");
+        assert_eq!(collapsed.spacer_range(), Some(4..8));
+    }
 }
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use std::{io::Write, path::Path};
+
+use crate::{io::writers::create_tsv_writer, models::coverage_histogram::CoverageHistogram};
+
+impl CoverageHistogram {
+    /// Writes the histogram binned by `bin_width` as TSV. `min_coverage` and
+    /// `max_coverage` are the inclusive coverages of a bin. `n_records_at_least_min` is
+    /// the number of records of the mod type with at least `min_coverage`, i.e. the
+    /// records kept with `min_valid_read_coverage` set to `min_coverage`.
+    pub fn write<P: AsRef<Path>>(&self, path: P, bin_width: u32) -> Result<()> {
+        let bins = self.binned(bin_width)?;
+        let mut writer = create_tsv_writer(
+            path,
+            &[],
+            "mod_type\tmin_coverage\tmax_coverage\tn_records\tn_records_at_least_min",
+        )?;
+
+        for (i, &(mod_type, bin_start, count)) in bins.iter().enumerate() {
+            let n_at_least_min: u64 = bins[i..]
+                .iter()
+                .take_while(|(other, _, _)| *other == mod_type)
+                .map(|(_, _, count)| count)
+                .sum();
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                mod_type.to_pileup_code(),
+                bin_start,
+                bin_start + (bin_width - 1),
+                count,
+                n_at_least_min
+            )?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epimetheus_methylome::ModType;
+
+    #[test]
+    fn test_write_counts_records_at_least_min() -> Result<()> {
+        let mut histogram = CoverageHistogram::new();
+        for n_valid_cov in [1, 2, 6, 11] {
+            histogram.add(ModType::SixMA, n_valid_cov);
+        }
+        histogram.add(ModType::FiveMC, 3);
+
+        let file = tempfile::NamedTempFile::new()?;
+        histogram.write(file.path(), 5)?;
+
+        let content = std::fs::read_to_string(file.path())?;
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "mod_type\tmin_coverage\tmax_coverage\tn_records\tn_records_at_least_min",
+                "a\t0\t4\t2\t4",
+                "a\t5\t9\t1\t2",
+                "a\t10\t14\t1\t1",
+                "m\t0\t4\t1\t1",
+            ]
+        );
+
+        Ok(())
+    }
+}
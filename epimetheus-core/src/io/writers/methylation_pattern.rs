@@ -0,0 +1,1521 @@
+use ahash::AHashMap;
+use anyhow::{Result, bail};
+use epimetheus_methylome::{ModType, Motif, Strand, StrandFormat};
+use serde::Serialize;
+use std::{collections::BTreeMap, io::Write, path::Path, sync::mpsc};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{
+    io::writers::{create_file_writer, create_tsv_writer, sorted_rows, write_tsv_header},
+    models::{
+        contig::Position as ContigPosition,
+        methylation::{
+            MethylationBackground, MethylationCoverage, MethylationOutput,
+            MethylationPatternVariant, MotifMethylationDegree, MotifMethylationPositions,
+            ValueScale, background_adjusted,
+        },
+        pileup::PileupRecord,
+    },
+};
+
+impl MethylationPatternVariant {
+    /// Writes the methylation pattern as TSV.
+    ///
+    /// The `methylation_value` column of the degree outputs is written in `value_scale`
+    /// and named after it (see [`ValueScale::value_column`]). The raw output only
+    /// contains counts and is unaffected.
+    ///
+    /// Rows are sorted unless `sort` is false, in which case they are written in
+    /// processing order. That order is not deterministic across runs. Raw rows are
+    /// sorted by contig, position, strand and motif.
+    ///
+    /// With `include_stderr` the weighted mean output gets a `stderr` column (see
+    /// [`weighted_mean_stderr`]). It is always a fraction, whatever the `value_scale`.
+    ///
+    /// `strand_format` sets how the `strand` column of the raw output is written.
+    ///
+    /// Each line in `metadata` is written before the column header, prefixed with
+    /// `# ` so TSV readers can skip it as a comment.
+    ///
+    /// [`weighted_mean_stderr`]: crate::models::methylation::weighted_mean_stderr
+    pub fn write_output<P: AsRef<Path>>(
+        &self,
+        path: P,
+        value_scale: ValueScale,
+        sort: bool,
+        include_stderr: bool,
+        strand_format: StrandFormat,
+        metadata: &[String],
+    ) -> Result<()> {
+        self.write_output_to(
+            create_file_writer(path)?,
+            value_scale,
+            sort,
+            include_stderr,
+            strand_format,
+            metadata,
+        )
+    }
+
+    /// Writes the methylation pattern as TSV to `writer`, as [`Self::write_output`]
+    /// does to a file. Used to write to writers that are not plain files, such as a
+    /// BGZF writer.
+    pub fn write_output_to<W: Write>(
+        &self,
+        mut writer: W,
+        value_scale: ValueScale,
+        sort: bool,
+        include_stderr: bool,
+        strand_format: StrandFormat,
+        metadata: &[String],
+    ) -> Result<()> {
+        write_tsv_header(
+            &mut writer,
+            metadata,
+            &output_header(&self.output_type(), include_stderr, value_scale),
+        )?;
+        self.write_rows(
+            &mut writer,
+            value_scale,
+            sort,
+            include_stderr,
+            strand_format,
+        )?;
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes a raw pattern as an 18 column bedMethyl pileup in the column order of
+    /// modkit, without header, so it can be compressed and read like a pileup.
+    ///
+    /// Only the counts kept in the pattern are known. `n_other_mod`, `n_delete` and
+    /// `n_no_call` are written as 0 and `n_canonical` as `n_valid_cov - n_modified`.
+    /// `n_valid_cov` is the coverage after removing `n_other_mod`, so reading the file
+    /// back gives the same methylation coverages. The score is `n_valid_cov` and the
+    /// fraction modified is a percentage with two decimals, as written by modkit.
+    ///
+    /// A position matched by several motifs with the same mod type is written once.
+    /// Records are sorted by contig, start and strand. Other patterns are an error.
+    /// Returns the number of records written.
+    pub fn write_bedmethyl<P: AsRef<Path>>(&self, path: P) -> Result<usize> {
+        let MethylationPatternVariant::Raw(meth_pos) = self else {
+            bail!(
+                "bedMethyl output requires a raw pattern. Got: {}",
+                self.output_type().to_string()
+            );
+        };
+
+        let mut records: BTreeMap<(&str, ContigPosition, Strand, ModType), &MethylationCoverage> =
+            BTreeMap::new();
+        for ((contig_id, motif, pos, strand), meth) in &meth_pos.methylation {
+            records.insert((contig_id.as_str(), *pos, *strand, motif.mod_type), meth);
+        }
+
+        let mut writer = create_file_writer(path)?;
+        for ((contig_id, pos, strand, mod_type), meth) in &records {
+            let start = *pos as u32;
+            let fraction_modified = if meth.get_n_valid_cov() == 0 {
+                0.0
+            } else {
+                (meth.fraction_modified() * 10_000.0).round() / 100.0
+            };
+            let record = PileupRecord::new(
+                contig_id.to_string(),
+                start,
+                start + 1,
+                *mod_type,
+                meth.get_n_valid_cov(),
+                *strand,
+                start,
+                start + 1,
+                "255,0,0".to_string(),
+                meth.get_n_valid_cov(),
+                fraction_modified,
+                meth.get_n_modified(),
+                meth.get_n_valid_cov() - meth.get_n_modified(),
+                0,
+                0,
+                meth.get_n_fail(),
+                meth.get_n_diff(),
+                0,
+            );
+            writeln!(writer, "{}", record)?;
+        }
+
+        writer.flush()?;
+        Ok(records.len())
+    }
+
+    /// Writes the methylation pattern as a JSON array of objects with the fields of the
+    /// TSV columns of [`Self::write_output`], sorted like it. Methylation values are
+    /// fractions and NaN values are written as `null`.
+    ///
+    /// The raw output has one object per contig-motif, sorted by contig and motif, with
+    /// the per position columns `start`, `strand`, `n_modified`, `n_valid_cov`, `n_diff`
+    /// and `n_fail` as arrays sorted by position and strand.
+    pub fn write_json<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = create_file_writer(path)?;
+
+        match self {
+            MethylationPatternVariant::Raw(meth_pos) => {
+                serde_json::to_writer(&mut writer, &raw_json_rows(meth_pos))?
+            }
+            MethylationPatternVariant::Median(degrees) => {
+                serde_json::to_writer(&mut writer, &degree_json_rows(degrees, |_, _| {}))?
+            }
+            MethylationPatternVariant::WeightedMean(degrees) => {
+                serde_json::to_writer(&mut writer, &degree_json_rows(degrees, |_, _| {}))?
+            }
+            MethylationPatternVariant::WeightedMedian(degrees) => {
+                serde_json::to_writer(&mut writer, &degree_json_rows(degrees, |_, _| {}))?
+            }
+            MethylationPatternVariant::TrimmedMean(degrees) => {
+                let rows = degree_json_rows(degrees, |row, deg| {
+                    row.trim_applied = Some(deg.trim_applied)
+                });
+                serde_json::to_writer(&mut writer, &rows)?
+            }
+            MethylationPatternVariant::CalledFrequency(degrees) => {
+                let rows = degree_json_rows(degrees, |row, deg| {
+                    row.n_called_methylated = Some(deg.n_called_methylated)
+                });
+                serde_json::to_writer(&mut writer, &rows)?
+            }
+            MethylationPatternVariant::StrandAsymmetry(degrees) => {
+                let rows: Vec<StrandAsymmetryJsonRow> = sorted_rows(degrees, true)
+                    .into_iter()
+                    .map(|deg| StrandAsymmetryJsonRow {
+                        contig: &deg.contig,
+                        motif: deg.motif.sequence_to_string(),
+                        mod_type: deg.motif.mod_type.to_pileup_code(),
+                        mod_position: deg.motif.mod_position,
+                        methylation_plus: deg.methylation_plus,
+                        methylation_minus: deg.methylation_minus,
+                        mean_asymmetry: deg.mean_asymmetry,
+                        n_sites: deg.n_sites,
+                    })
+                    .collect();
+                serde_json::to_writer(&mut writer, &rows)?
+            }
+        }
+        writeln!(writer)?;
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the pattern like [`Self::write_output`] with the columns `background` and
+    /// `methylation_adjusted` added. The adjusted value is the methylation value minus
+    /// the background, clamped to [0, 1] (see [`background_adjusted`]).
+    ///
+    /// With [`MethylationBackground::Motif`] the background of a contig is the
+    /// methylation value of the control motif in that contig, so the control motif
+    /// itself is adjusted to 0. Rows of contigs without the control motif get `NA` in
+    /// both columns. Both columns are scaled like `methylation_value`.
+    ///
+    /// Only per contig-motif values can be adjusted, so raw and strand asymmetry
+    /// patterns are an error. Returns the number of rows written.
+    pub fn write_background_adjusted_output<P: AsRef<Path>>(
+        &self,
+        path: P,
+        background: &MethylationBackground,
+        value_scale: ValueScale,
+        sort: bool,
+        include_stderr: bool,
+        metadata: &[String],
+    ) -> Result<usize> {
+        let Some(mut rows) = self.degree_rows(value_scale, include_stderr) else {
+            bail!(
+                "Background adjustment requires a median, weighted mean, trimmed mean or called frequency output. Got: {}",
+                self.output_type().to_string()
+            );
+        };
+        if sort {
+            rows.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        }
+
+        let control_values: AHashMap<&str, f64> = match background {
+            MethylationBackground::Motif(control) => rows
+                .iter()
+                .filter(|(_, motif, _, _)| *motif == control)
+                .map(|(contig, _, value, _)| (*contig, *value))
+                .collect(),
+            MethylationBackground::Value(_) => AHashMap::new(),
+        };
+        let format_value = |value: f64| value_scale.format(value);
+
+        let header = format!(
+            "{}\tbackground\tmethylation_adjusted",
+            output_header(&self.output_type(), include_stderr, value_scale)
+        );
+        let mut writer = create_tsv_writer(path, metadata, &header)?;
+
+        for (contig, _, value, line) in &rows {
+            let contig_background = match background {
+                MethylationBackground::Motif(_) => control_values.get(contig).copied(),
+                MethylationBackground::Value(value) => Some(*value),
+            };
+            match contig_background {
+                Some(contig_background) => writeln!(
+                    writer,
+                    "{}\t{}\t{}",
+                    line,
+                    format_value(contig_background),
+                    format_value(background_adjusted(*value, contig_background))
+                )?,
+                None => writeln!(writer, "{}\tNA\tNA", line)?,
+            }
+        }
+
+        writer.flush()?;
+        Ok(rows.len())
+    }
+
+    /// Contig, motif, methylation value and output line of each row of a per
+    /// contig-motif pattern, unsorted. `None` for raw and strand asymmetry patterns.
+    fn degree_rows(
+        &self,
+        value_scale: ValueScale,
+        include_stderr: bool,
+    ) -> Option<Vec<(&str, &Motif, f64, String)>> {
+        fn rows<T: MotifMethylationDegree>(
+            degrees: &[T],
+            line: impl Fn(&T) -> String,
+        ) -> Vec<(&str, &Motif, f64, String)> {
+            degrees
+                .iter()
+                .map(|deg| {
+                    (
+                        deg.get_contig(),
+                        deg.get_motif(),
+                        deg.get_methylation_value(),
+                        line(deg),
+                    )
+                })
+                .collect()
+        }
+
+        match self {
+            MethylationPatternVariant::Raw(_) | MethylationPatternVariant::StrandAsymmetry(_) => {
+                None
+            }
+            MethylationPatternVariant::Median(degrees) => {
+                Some(rows(degrees, |deg| deg.to_csv_line('\t', value_scale)))
+            }
+            MethylationPatternVariant::WeightedMean(degrees) => Some(rows(degrees, |deg| {
+                if include_stderr {
+                    format!("{}\t{}", deg.to_csv_line('\t', value_scale), deg.stderr)
+                } else {
+                    deg.to_csv_line('\t', value_scale)
+                }
+            })),
+            MethylationPatternVariant::WeightedMedian(degrees) => {
+                Some(rows(degrees, |deg| deg.to_csv_line('\t', value_scale)))
+            }
+            MethylationPatternVariant::TrimmedMean(degrees) => Some(rows(degrees, |deg| {
+                format!(
+                    "{}\t{}",
+                    deg.to_csv_line('\t', value_scale),
+                    deg.trim_applied
+                )
+            })),
+            MethylationPatternVariant::CalledFrequency(degrees) => Some(rows(degrees, |deg| {
+                format!(
+                    "{}\t{}",
+                    deg.to_csv_line('\t', value_scale),
+                    deg.n_called_methylated
+                )
+            })),
+        }
+    }
+
+    /// Writes the rows of the pattern without header. Returns the number of rows written.
+    fn write_rows<W: Write>(
+        &self,
+        writer: &mut W,
+        value_scale: ValueScale,
+        sort: bool,
+        include_stderr: bool,
+        strand_format: StrandFormat,
+    ) -> Result<usize> {
+        let mut n_rows = 0;
+        match self {
+            MethylationPatternVariant::Raw(meth_pos) => {
+                let mut sorted_entries: Vec<_> = meth_pos.methylation.iter().collect();
+                if sort {
+                    // Within a contig rows follow the reference, so a position
+                    // covered by several motifs is written as a block.
+                    sorted_entries.sort_by(|(a, _), (b, _)| {
+                        (&a.0, a.2, a.3, &a.1).cmp(&(&b.0, b.2, b.3, &b.1))
+                    });
+                }
+
+                for ((contig_id, motif, pos, strand), meth) in sorted_entries {
+                    writeln!(
+                        writer,
+                        "{}",
+                        raw_csv_line(contig_id, motif, *pos, strand, meth, strand_format, '\t')
+                    )?;
+                    n_rows += 1;
+                }
+            }
+            MethylationPatternVariant::Median(degrees) => {
+                for deg in sorted_rows(degrees, sort) {
+                    writeln!(writer, "{}", deg.to_csv_line('\t', value_scale))?;
+                    n_rows += 1;
+                }
+            }
+            MethylationPatternVariant::WeightedMean(degrees) => {
+                for deg in sorted_rows(degrees, sort) {
+                    if include_stderr {
+                        writeln!(
+                            writer,
+                            "{}\t{}",
+                            deg.to_csv_line('\t', value_scale),
+                            deg.stderr
+                        )?;
+                    } else {
+                        writeln!(writer, "{}", deg.to_csv_line('\t', value_scale))?;
+                    }
+                    n_rows += 1;
+                }
+            }
+            MethylationPatternVariant::WeightedMedian(degrees) => {
+                for deg in sorted_rows(degrees, sort) {
+                    writeln!(writer, "{}", deg.to_csv_line('\t', value_scale))?;
+                    n_rows += 1;
+                }
+            }
+            MethylationPatternVariant::TrimmedMean(degrees) => {
+                for deg in sorted_rows(degrees, sort) {
+                    writeln!(
+                        writer,
+                        "{}\t{}",
+                        deg.to_csv_line('\t', value_scale),
+                        deg.trim_applied
+                    )?;
+                    n_rows += 1;
+                }
+            }
+            MethylationPatternVariant::CalledFrequency(degrees) => {
+                for deg in sorted_rows(degrees, sort) {
+                    writeln!(
+                        writer,
+                        "{}\t{}",
+                        deg.to_csv_line('\t', value_scale),
+                        deg.n_called_methylated
+                    )?;
+                    n_rows += 1;
+                }
+            }
+            MethylationPatternVariant::StrandAsymmetry(degrees) => {
+                for deg in sorted_rows(degrees, sort) {
+                    writeln!(writer, "{}", deg.to_csv_line('\t', value_scale))?;
+                    n_rows += 1;
+                }
+            }
+        }
+
+        Ok(n_rows)
+    }
+
+    /// Writes a checksum per contig as TSV with the columns `contig`, `n_rows` and
+    /// `xxh3_64`.
+    ///
+    /// The checksum is the 64-bit XXH3 hash (seed 0, lowercase hex) of the contig's
+    /// output rows, exactly as written by [`Self::write_output`], sorted
+    /// lexicographically and each terminated by a newline. Contigs are written in
+    /// sorted order, so the file is deterministic for identical results.
+    pub fn write_checksums<P: AsRef<Path>>(
+        &self,
+        path: P,
+        value_scale: ValueScale,
+        strand_format: StrandFormat,
+    ) -> Result<usize> {
+        let mut rows_by_contig: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        match self {
+            MethylationPatternVariant::Raw(meth_pos) => {
+                for ((contig_id, motif, pos, strand), meth) in &meth_pos.methylation {
+                    rows_by_contig
+                        .entry(contig_id.as_str())
+                        .or_default()
+                        .push(raw_csv_line(
+                            contig_id,
+                            motif,
+                            *pos,
+                            strand,
+                            meth,
+                            strand_format,
+                            '\t',
+                        ));
+                }
+            }
+            MethylationPatternVariant::Median(degrees) => {
+                for deg in degrees {
+                    rows_by_contig
+                        .entry(deg.get_contig())
+                        .or_default()
+                        .push(deg.to_csv_line('\t', value_scale));
+                }
+            }
+            MethylationPatternVariant::WeightedMean(degrees) => {
+                for deg in degrees {
+                    rows_by_contig
+                        .entry(deg.get_contig())
+                        .or_default()
+                        .push(deg.to_csv_line('\t', value_scale));
+                }
+            }
+            MethylationPatternVariant::WeightedMedian(degrees) => {
+                for deg in degrees {
+                    rows_by_contig
+                        .entry(deg.get_contig())
+                        .or_default()
+                        .push(deg.to_csv_line('\t', value_scale));
+                }
+            }
+            MethylationPatternVariant::TrimmedMean(degrees) => {
+                for deg in degrees {
+                    rows_by_contig
+                        .entry(deg.get_contig())
+                        .or_default()
+                        .push(format!(
+                            "{}\t{}",
+                            deg.to_csv_line('\t', value_scale),
+                            deg.trim_applied
+                        ));
+                }
+            }
+            MethylationPatternVariant::CalledFrequency(degrees) => {
+                for deg in degrees {
+                    rows_by_contig
+                        .entry(deg.get_contig())
+                        .or_default()
+                        .push(format!(
+                            "{}\t{}",
+                            deg.to_csv_line('\t', value_scale),
+                            deg.n_called_methylated
+                        ));
+                }
+            }
+            MethylationPatternVariant::StrandAsymmetry(degrees) => {
+                for deg in degrees {
+                    rows_by_contig
+                        .entry(deg.get_contig())
+                        .or_default()
+                        .push(deg.to_csv_line('\t', value_scale));
+                }
+            }
+        }
+
+        let mut writer = create_tsv_writer(path, &[], "contig\tn_rows\txxh3_64")?;
+
+        for (contig, rows) in rows_by_contig.iter_mut() {
+            rows.sort();
+
+            let mut hasher = Xxh3::new();
+            for row in rows.iter() {
+                hasher.update(row.as_bytes());
+                hasher.update(b"\n");
+            }
+
+            writeln!(
+                writer,
+                "{}\t{}\t{:016x}",
+                contig,
+                rows.len(),
+                hasher.digest()
+            )?;
+        }
+
+        writer.flush()?;
+        Ok(rows_by_contig.len())
+    }
+}
+
+/// Column header of the TSV written by [`MethylationPatternVariant::write_output`] for
+/// `output_type`. The methylation value column is named after `value_scale`.
+pub fn output_header(
+    output_type: &MethylationOutput,
+    include_stderr: bool,
+    value_scale: ValueScale,
+) -> String {
+    const RAW_COLUMNS: &str = "contig\tstart\tstrand\tmotif\tmod_type\tmod_position\tn_modified\tn_valid_cov\tn_diff\tn_fail";
+    const ASYMMETRY_COLUMNS: &str = "contig\tmotif\tmod_type\tmod_position\tmethylation_plus\tmethylation_minus\tmean_asymmetry\tn_sites";
+    let degree_columns = format!(
+        "contig\tmotif\tmod_type\tmod_position\t{}\tmean_read_cov\tn_motif_obs\tmotif_occurences_total\tn_filtered_low_cov\tn_filtered_diff",
+        value_scale.value_column()
+    );
+
+    match output_type {
+        MethylationOutput::Raw => RAW_COLUMNS.to_string(),
+        MethylationOutput::Median => degree_columns,
+        MethylationOutput::WeightedMean if include_stderr => format!("{}\tstderr", degree_columns),
+        MethylationOutput::WeightedMean => degree_columns,
+        MethylationOutput::WeightedMedian => degree_columns,
+        MethylationOutput::TrimmedMean => format!("{}\ttrim_applied", degree_columns),
+        MethylationOutput::CalledFrequency => {
+            format!("{}\tn_called_methylated", degree_columns)
+        }
+        MethylationOutput::StrandAsymmetry => ASYMMETRY_COLUMNS.to_string(),
+    }
+}
+
+/// File name `<motif>_<mod_type>_<mod_position>.tsv` of a motif in the per motif output.
+/// Characters other than ASCII letters, digits, '-' and '_' are replaced with '_'.
+pub fn motif_file_name(motif: &Motif) -> String {
+    let name = format!(
+        "{}_{}_{}",
+        motif.sequence_to_string(),
+        motif.mod_type.to_pileup_code(),
+        motif.mod_position
+    );
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.tsv", name)
+}
+
+/// Writes one file per motif to `dir` (see [`motif_file_name`]) with the rows of that
+/// motif for all contigs, in the layout of [`MethylationPatternVariant::write_output`].
+///
+/// A `manifest.tsv` with the columns `motif`, `mod_type`, `mod_position`, `file` and
+/// `n_rows` lists the written files. The directory is created if missing. Returns the
+/// number of motif files.
+pub fn write_per_motif_output<P: AsRef<Path>>(
+    pattern: MethylationPatternVariant,
+    dir: P,
+    value_scale: ValueScale,
+    sort: bool,
+    include_stderr: bool,
+    strand_format: StrandFormat,
+    metadata: &[String],
+) -> Result<usize> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+
+    let mut manifest = create_tsv_writer(
+        dir.join("manifest.tsv"),
+        &[],
+        "motif\tmod_type\tmod_position\tfile\tn_rows",
+    )?;
+
+    let split = pattern.split_by_motif();
+    let n_motifs = split.len();
+    for (motif, motif_pattern) in split {
+        let file_name = motif_file_name(&motif);
+        motif_pattern.write_output(
+            dir.join(&file_name),
+            value_scale,
+            sort,
+            include_stderr,
+            strand_format,
+            metadata,
+        )?;
+        writeln!(
+            manifest,
+            "{}\t{}\t{}\t{}\t{}",
+            motif.sequence_to_string(),
+            motif.mod_type.to_pileup_code(),
+            motif.mod_position,
+            file_name,
+            motif_pattern.n_rows()
+        )?;
+    }
+
+    manifest.flush()?;
+    Ok(n_motifs)
+}
+
+/// Writes the patterns received on `receiver` as TSV while they arrive, so only the
+/// pattern being written is held in memory.
+///
+/// The header is written for `output_type` before the first pattern is received.
+/// Patterns are written in the order they are received. With `sort` the rows of each
+/// pattern are sorted, so patterns received in contig order give the same file as
+/// [`MethylationPatternVariant::write_output`] with `sort`. The file has the same
+/// columns and metadata lines. Writing stops when all senders are dropped. Returns the
+/// number of rows written.
+pub fn write_output_streaming<P: AsRef<Path>>(
+    receiver: mpsc::Receiver<MethylationPatternVariant>,
+    path: P,
+    output_type: &MethylationOutput,
+    value_scale: ValueScale,
+    sort: bool,
+    include_stderr: bool,
+    strand_format: StrandFormat,
+    metadata: &[String],
+) -> Result<usize> {
+    let header = output_header(output_type, include_stderr, value_scale);
+    let mut writer = create_tsv_writer(path, metadata, &header)?;
+
+    let mut n_rows = 0;
+    for pattern in receiver {
+        if pattern.output_type() != *output_type {
+            bail!(
+                "Received a '{}' pattern while writing '{}' output",
+                pattern.output_type().to_string(),
+                output_type.to_string()
+            );
+        }
+        n_rows += pattern.write_rows(
+            &mut writer,
+            value_scale,
+            sort,
+            include_stderr,
+            strand_format,
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(n_rows)
+}
+
+/// A contig-motif of a degree output in [`MethylationPatternVariant::write_json`].
+#[derive(Serialize)]
+struct DegreeJsonRow<'a> {
+    contig: &'a str,
+    motif: String,
+    mod_type: &'static str,
+    mod_position: u8,
+    methylation_value: f64,
+    mean_read_cov: f64,
+    n_motif_obs: u32,
+    motif_occurences_total: u32,
+    n_filtered_low_cov: u32,
+    n_filtered_diff: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trim_applied: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n_called_methylated: Option<u32>,
+}
+
+/// Sorted JSON rows of degrees. `extra` sets the fields of the output type specific
+/// columns.
+fn degree_json_rows<'a, D: MotifMethylationDegree + PartialOrd>(
+    degrees: &'a [D],
+    extra: impl Fn(&mut DegreeJsonRow<'a>, &'a D),
+) -> Vec<DegreeJsonRow<'a>> {
+    sorted_rows(degrees, true)
+        .into_iter()
+        .map(|deg| {
+            let mut row = DegreeJsonRow {
+                contig: deg.get_contig(),
+                motif: deg.get_motif().sequence_to_string(),
+                mod_type: deg.get_motif().mod_type.to_pileup_code(),
+                mod_position: deg.get_motif().mod_position,
+                methylation_value: deg.get_methylation_value(),
+                mean_read_cov: deg.get_mean_read_cov(),
+                n_motif_obs: deg.get_n_motif_obs(),
+                motif_occurences_total: deg.get_motif_occurences_total(),
+                n_filtered_low_cov: deg.get_n_filtered_low_cov(),
+                n_filtered_diff: deg.get_n_filtered_diff(),
+                trim_applied: None,
+                n_called_methylated: None,
+            };
+            extra(&mut row, deg);
+            row
+        })
+        .collect()
+}
+
+/// A contig-motif of the strand asymmetry output in
+/// [`MethylationPatternVariant::write_json`].
+#[derive(Serialize)]
+struct StrandAsymmetryJsonRow<'a> {
+    contig: &'a str,
+    motif: String,
+    mod_type: &'static str,
+    mod_position: u8,
+    methylation_plus: f64,
+    methylation_minus: f64,
+    mean_asymmetry: f64,
+    n_sites: u32,
+}
+
+/// The positions of a contig-motif of the raw output in
+/// [`MethylationPatternVariant::write_json`], one array element per position.
+#[derive(Serialize)]
+struct RawJsonRow<'a> {
+    contig: &'a str,
+    #[serde(skip)]
+    motif_key: &'a Motif,
+    motif: String,
+    mod_type: &'static str,
+    mod_position: u8,
+    start: Vec<ContigPosition>,
+    strand: Vec<&'static str>,
+    n_modified: Vec<u32>,
+    n_valid_cov: Vec<u32>,
+    n_diff: Vec<u32>,
+    n_fail: Vec<u32>,
+}
+
+/// JSON rows of the raw output sorted by contig and motif.
+fn raw_json_rows(meth_pos: &MotifMethylationPositions) -> Vec<RawJsonRow<'_>> {
+    let mut sorted_entries: Vec<_> = meth_pos.methylation.iter().collect();
+    sorted_entries.sort_by(|(a, _), (b, _)| (&a.0, &a.1, a.2, a.3).cmp(&(&b.0, &b.1, b.2, b.3)));
+
+    let mut rows: Vec<RawJsonRow> = Vec::new();
+    for ((contig_id, motif, pos, strand), meth) in sorted_entries {
+        let is_new_row = rows
+            .last()
+            .is_none_or(|row| row.contig != contig_id || row.motif_key != motif);
+        if is_new_row {
+            rows.push(RawJsonRow {
+                contig: contig_id,
+                motif_key: motif,
+                motif: motif.sequence_to_string(),
+                mod_type: motif.mod_type.to_pileup_code(),
+                mod_position: motif.mod_position,
+                start: Vec::new(),
+                strand: Vec::new(),
+                n_modified: Vec::new(),
+                n_valid_cov: Vec::new(),
+                n_diff: Vec::new(),
+                n_fail: Vec::new(),
+            });
+        }
+
+        let row = rows.last_mut().expect("A row was pushed");
+        row.start.push(*pos);
+        row.strand.push(strand.format(StrandFormat::Symbol));
+        row.n_modified.push(meth.get_n_modified());
+        row.n_valid_cov.push(meth.get_n_valid_cov());
+        row.n_diff.push(meth.get_n_diff());
+        row.n_fail.push(meth.get_n_fail());
+    }
+
+    rows
+}
+
+fn raw_csv_line(
+    contig_id: &str,
+    motif: &Motif,
+    position: ContigPosition,
+    strand: &Strand,
+    meth: &MethylationCoverage,
+    strand_format: StrandFormat,
+    delim: char,
+) -> String {
+    format!(
+        "{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}",
+        contig_id,
+        position,
+        strand.format(strand_format),
+        motif.sequence_to_string(),
+        motif.mod_type.to_pileup_code(),
+        motif.mod_position,
+        meth.get_n_modified(),
+        meth.get_n_valid_cov(),
+        meth.get_n_diff(),
+        meth.get_n_fail()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::methylation::*;
+
+    #[test]
+    fn test_write_output_percent() -> Result<()> {
+        let variant = MethylationPatternVariant::Median(vec![MedianMotifMethylationDegree {
+            contig: "contig_1".to_string(),
+            motif: Motif::new("GATC", "a", 1).unwrap(),
+            median: 0.25,
+            mean_read_cov: 10.0,
+            n_motif_obs: 2,
+            motif_occurences_total: 4,
+            n_filtered_low_cov: 0,
+            n_filtered_diff: 0,
+        }]);
+
+        let out = tempfile::NamedTempFile::new()?;
+        variant.write_output(
+            out.path(),
+            ValueScale::Percent,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &[],
+        )?;
+
+        let written = std::fs::read_to_string(out.path())?;
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(
+            lines[0],
+            output_header(&MethylationOutput::Median, false, ValueScale::Percent)
+        );
+        assert!(lines[0].contains("\tmethylation_percent\t"));
+        assert_eq!(lines[1], "contig_1\tGATC\ta\t1\t25\t10\t2\t4\t0\t0");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_json_degrees() -> Result<()> {
+        let degree = |contig: &str, trimmed_mean: f64| TrimmedMeanMotifMethylationDegree {
+            contig: contig.to_string(),
+            motif: Motif::new("GATC", "a", 1).unwrap(),
+            trimmed_mean,
+            mean_read_cov: 10.0,
+            n_motif_obs: 2,
+            motif_occurences_total: 4,
+            n_filtered_low_cov: 1,
+            n_filtered_diff: 0,
+            trim_applied: false,
+        };
+        let variant = MethylationPatternVariant::TrimmedMean(vec![
+            degree("contig_2", f64::NAN),
+            degree("contig_1", 0.25),
+        ]);
+
+        let out = tempfile::NamedTempFile::new()?;
+        variant.write_json(out.path())?;
+
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(out.path())?)?;
+        assert_eq!(
+            written,
+            serde_json::json!([
+                {
+                    "contig": "contig_1",
+                    "motif": "GATC",
+                    "mod_type": "a",
+                    "mod_position": 1,
+                    "methylation_value": 0.25,
+                    "mean_read_cov": 10.0,
+                    "n_motif_obs": 2,
+                    "motif_occurences_total": 4,
+                    "n_filtered_low_cov": 1,
+                    "n_filtered_diff": 0,
+                    "trim_applied": false
+                },
+                {
+                    "contig": "contig_2",
+                    "motif": "GATC",
+                    "mod_type": "a",
+                    "mod_position": 1,
+                    "methylation_value": null,
+                    "mean_read_cov": 10.0,
+                    "n_motif_obs": 2,
+                    "motif_occurences_total": 4,
+                    "n_filtered_low_cov": 1,
+                    "n_filtered_diff": 0,
+                    "trim_applied": false
+                }
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_json_raw_nests_positions() -> Result<()> {
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let coverage = |n_modified, n_valid_cov, n_diff, n_fail| {
+            MethylationCoverage::new(n_modified, n_valid_cov, 0, n_diff, n_fail).unwrap()
+        };
+        let mut methylation = AHashMap::new();
+        for (contig, position, strand, meth) in [
+            ("contig_1", 8, Strand::Positive, coverage(1, 4, 0, 0)),
+            ("contig_1", 3, Strand::Negative, coverage(2, 5, 1, 2)),
+            ("contig_1", 3, Strand::Positive, coverage(3, 6, 0, 1)),
+            ("contig_0", 5, Strand::Positive, coverage(0, 2, 0, 0)),
+        ] {
+            methylation.insert((contig.to_string(), gatc.clone(), position, strand), meth);
+        }
+        let variant = MethylationPatternVariant::Raw(MotifMethylationPositions::new(
+            methylation,
+            AHashMap::new(),
+        ));
+
+        let out = tempfile::NamedTempFile::new()?;
+        variant.write_json(out.path())?;
+
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(out.path())?)?;
+        assert_eq!(
+            written,
+            serde_json::json!([
+                {
+                    "contig": "contig_0",
+                    "motif": "GATC",
+                    "mod_type": "a",
+                    "mod_position": 1,
+                    "start": [5],
+                    "strand": ["+"],
+                    "n_modified": [0],
+                    "n_valid_cov": [2],
+                    "n_diff": [0],
+                    "n_fail": [0]
+                },
+                {
+                    "contig": "contig_1",
+                    "motif": "GATC",
+                    "mod_type": "a",
+                    "mod_position": 1,
+                    "start": [3, 3, 8],
+                    "strand": ["+", "-", "+"],
+                    "n_modified": [3, 2, 1],
+                    "n_valid_cov": [6, 5, 4],
+                    "n_diff": [0, 1, 0],
+                    "n_fail": [1, 2, 0]
+                }
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_output_no_sort_has_same_rows() -> Result<()> {
+        let degree = |contig: &str, median: f64| MedianMotifMethylationDegree {
+            contig: contig.to_string(),
+            motif: Motif::new("GATC", "a", 1).unwrap(),
+            median,
+            mean_read_cov: 10.0,
+            n_motif_obs: 2,
+            motif_occurences_total: 4,
+            n_filtered_low_cov: 0,
+            n_filtered_diff: 0,
+        };
+        let variant = MethylationPatternVariant::Median(vec![
+            degree("contig_3", 0.1),
+            degree("contig_1", 0.9),
+            degree("contig_2", 0.5),
+        ]);
+
+        let sorted_out = tempfile::NamedTempFile::new()?;
+        let unsorted_out = tempfile::NamedTempFile::new()?;
+        variant.write_output(
+            sorted_out.path(),
+            ValueScale::Fraction,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &[],
+        )?;
+        variant.write_output(
+            unsorted_out.path(),
+            ValueScale::Fraction,
+            false,
+            false,
+            StrandFormat::Symbol,
+            &[],
+        )?;
+
+        let sorted = std::fs::read_to_string(sorted_out.path())?;
+        let unsorted = std::fs::read_to_string(unsorted_out.path())?;
+
+        let contigs: Vec<&str> = sorted
+            .lines()
+            .skip(1)
+            .map(|l| l.split('\t').next().unwrap())
+            .collect();
+        assert_eq!(contigs, vec!["contig_1", "contig_2", "contig_3"]);
+
+        let mut sorted_rows: Vec<&str> = sorted.lines().collect();
+        let mut unsorted_rows: Vec<&str> = unsorted.lines().collect();
+        assert_eq!(sorted_rows[0], unsorted_rows[0]);
+        sorted_rows.sort();
+        unsorted_rows.sort();
+        assert_eq!(sorted_rows, unsorted_rows);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_output_streaming_matches_write_output() -> Result<()> {
+        let degree = |contig: &str, median: f64| MedianMotifMethylationDegree {
+            contig: contig.to_string(),
+            motif: Motif::new("GATC", "a", 1).unwrap(),
+            median,
+            mean_read_cov: 10.0,
+            n_motif_obs: 2,
+            motif_occurences_total: 4,
+            n_filtered_low_cov: 0,
+            n_filtered_diff: 0,
+        };
+        let metadata = vec!["epimetheus test".to_string()];
+
+        let (sender, receiver) = mpsc::channel();
+        sender.send(MethylationPatternVariant::Median(vec![degree("contig_1", 0.9)]))?;
+        sender.send(MethylationPatternVariant::Median(vec![
+            degree("contig_2", 0.5),
+            degree("contig_2", 0.1),
+        ]))?;
+        drop(sender);
+
+        let streamed_out = tempfile::NamedTempFile::new()?;
+        let n_rows = write_output_streaming(
+            receiver,
+            streamed_out.path(),
+            &MethylationOutput::Median,
+            ValueScale::Fraction,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &metadata,
+        )?;
+        assert_eq!(n_rows, 3);
+
+        // Patterns received in contig order with their rows sorted are the sorted output.
+        let buffered_out = tempfile::NamedTempFile::new()?;
+        MethylationPatternVariant::Median(vec![
+            degree("contig_2", 0.5),
+            degree("contig_1", 0.9),
+            degree("contig_2", 0.1),
+        ])
+        .write_output(
+            buffered_out.path(),
+            ValueScale::Fraction,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &metadata,
+        )?;
+
+        assert_eq!(
+            std::fs::read_to_string(streamed_out.path())?,
+            std::fs::read_to_string(buffered_out.path())?
+        );
+
+        let (sender, receiver) = mpsc::channel();
+        sender.send(MethylationPatternVariant::Median(vec![degree("contig_1", 0.9)]))?;
+        drop(sender);
+        let mismatch_out = tempfile::NamedTempFile::new()?;
+        assert!(
+            write_output_streaming(
+                receiver,
+                mismatch_out.path(),
+                &MethylationOutput::WeightedMean,
+                ValueScale::Fraction,
+                true,
+                false,
+                StrandFormat::Symbol,
+                &[],
+            )
+            .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_output_to_matches_write_output() -> Result<()> {
+        let pattern = MethylationPatternVariant::Median(vec![MedianMotifMethylationDegree {
+            contig: "contig_1".to_string(),
+            motif: Motif::new("GATC", "a", 1)?,
+            median: 0.9,
+            mean_read_cov: 10.0,
+            n_motif_obs: 2,
+            motif_occurences_total: 4,
+            n_filtered_low_cov: 0,
+            n_filtered_diff: 0,
+        }]);
+        let metadata = vec!["epimetheus test".to_string()];
+
+        let file_out = tempfile::NamedTempFile::new()?;
+        pattern.write_output(
+            file_out.path(),
+            ValueScale::Percent,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &metadata,
+        )?;
+        let mut buffer = Vec::new();
+        pattern.write_output_to(
+            &mut buffer,
+            ValueScale::Percent,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &metadata,
+        )?;
+
+        assert_eq!(
+            String::from_utf8(buffer)?,
+            std::fs::read_to_string(file_out.path())?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_per_motif_output() -> Result<()> {
+        let degree = |contig: &str, motif: &Motif| MedianMotifMethylationDegree {
+            contig: contig.to_string(),
+            motif: motif.clone(),
+            median: 0.5,
+            mean_read_cov: 10.0,
+            n_motif_obs: 2,
+            motif_occurences_total: 4,
+            n_filtered_low_cov: 0,
+            n_filtered_diff: 0,
+        };
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let ccwgg = Motif::new("CCWGG", "m", 1)?;
+        let pattern = MethylationPatternVariant::Median(vec![
+            degree("contig_1", &gatc),
+            degree("contig_1", &ccwgg),
+            degree("contig_2", &gatc),
+        ]);
+
+        let dir = tempfile::tempdir()?;
+        let n_motifs = write_per_motif_output(
+            pattern,
+            dir.path(),
+            ValueScale::Fraction,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &[],
+        )?;
+        assert_eq!(n_motifs, 2);
+
+        assert_eq!(motif_file_name(&gatc), "GATC_a_1.tsv");
+        let gatc_rows: Vec<String> = std::fs::read_to_string(dir.path().join("GATC_a_1.tsv"))?
+            .lines()
+            .skip(1)
+            .map(String::from)
+            .collect();
+        assert_eq!(gatc_rows.len(), 2);
+        assert!(gatc_rows.iter().all(|row| row.contains("\tGATC\ta\t1\t")));
+
+        let ccwgg_rows: Vec<String> = std::fs::read_to_string(dir.path().join("CCWGG_m_1.tsv"))?
+            .lines()
+            .skip(1)
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            ccwgg_rows,
+            vec!["contig_1\tCCWGG\tm\t1\t0.5\t10\t2\t4\t0\t0"]
+        );
+
+        let manifest = std::fs::read_to_string(dir.path().join("manifest.tsv"))?;
+        assert_eq!(
+            manifest,
+            "motif\tmod_type\tmod_position\tfile\tn_rows\n\
+             CCWGG\tm\t1\tCCWGG_m_1.tsv\t1\n\
+             GATC\ta\t1\tGATC_a_1.tsv\t2\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_bedmethyl_round_trips() -> Result<()> {
+        use crate::models::pileup::PileupRecordString;
+
+        let gatc_a = Motif::new("GATC", "a", 1)?;
+        let atc_a = Motif::new("ATC", "a", 0)?;
+        let gatc_m = Motif::new("GATC", "m", 3)?;
+        let mut methylation = AHashMap::new();
+        for (contig, motif, position, strand, meth) in [
+            ("contig_2", &gatc_a, 5, Strand::Positive, MethylationCoverage::new(1, 3, 0, 0, 0)?),
+            ("contig_1", &gatc_m, 12, Strand::Negative, MethylationCoverage::new(0, 8, 0, 1, 2)?),
+            ("contig_1", &gatc_a, 3, Strand::Positive, MethylationCoverage::new(2, 7, 1, 0, 4)?),
+            ("contig_1", &atc_a, 3, Strand::Positive, MethylationCoverage::new(2, 7, 1, 0, 4)?),
+        ] {
+            methylation.insert((contig.to_string(), motif.clone(), position, strand), meth);
+        }
+        let pattern = MethylationPatternVariant::Raw(MotifMethylationPositions::new(
+            methylation.clone(),
+            AHashMap::new(),
+        ));
+
+        let out = tempfile::NamedTempFile::new()?;
+        assert_eq!(pattern.write_bedmethyl(out.path())?, 3);
+
+        let content = std::fs::read_to_string(out.path())?;
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "contig_1\t3\t4\ta\t6\t+\t3\t4\t255,0,0\t6\t33.33\t2\t4\t0\t0\t4\t0\t0",
+                "contig_1\t12\t13\tm\t8\t-\t12\t13\t255,0,0\t8\t0\t0\t8\t0\t0\t2\t1\t0",
+                "contig_2\t5\t6\ta\t3\t+\t5\t6\t255,0,0\t3\t33.33\t1\t2\t0\t0\t0\t0\t0",
+            ]
+        );
+
+        for line in lines {
+            let record = PileupRecord::try_from(PileupRecordString::new(line.to_string()))?
+                .to_methylation_record()?;
+            let expected = methylation
+                .iter()
+                .find(|((contig, motif, position, strand), _)| {
+                    *contig == record.contig
+                        && *position == record.position
+                        && *strand == record.strand
+                        && motif.mod_type == record.mod_type
+                })
+                .map(|(_, meth)| *meth)
+                .unwrap();
+            assert_eq!(record.methylation, expected);
+        }
+
+        let median = MethylationPatternVariant::Median(Vec::new());
+        assert!(median.write_bedmethyl(out.path()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_background_adjusted_output() -> Result<()> {
+        let degree = |contig: &str, motif: &Motif, median: f64| MedianMotifMethylationDegree {
+            contig: contig.to_string(),
+            motif: motif.clone(),
+            median,
+            mean_read_cov: 10.0,
+            n_motif_obs: 2,
+            motif_occurences_total: 4,
+            n_filtered_low_cov: 0,
+            n_filtered_diff: 0,
+        };
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let control = Motif::new("CCWGG", "m", 1)?;
+        let pattern = MethylationPatternVariant::Median(vec![
+            degree("contig_2", &gatc, 0.9),
+            degree("contig_1", &gatc, 0.75),
+            degree("contig_1", &control, 0.25),
+        ]);
+
+        let out = tempfile::NamedTempFile::new()?;
+        let n_rows = pattern.write_background_adjusted_output(
+            out.path(),
+            &MethylationBackground::Value(0.8),
+            ValueScale::Fraction,
+            true,
+            false,
+            &[],
+        )?;
+        assert_eq!(n_rows, 3);
+        let rows: Vec<String> = std::fs::read_to_string(out.path())?
+            .lines()
+            .map(String::from)
+            .collect();
+        assert!(rows[0].ends_with("n_filtered_diff\tbackground\tmethylation_adjusted"));
+        let mut rows = rows[1..].to_vec();
+        rows.sort();
+        assert_eq!(
+            rows,
+            [
+                "contig_1\tCCWGG\tm\t1\t0.25\t10\t2\t4\t0\t0\t0.8\t0",
+                "contig_1\tGATC\ta\t1\t0.75\t10\t2\t4\t0\t0\t0.8\t0",
+                "contig_2\tGATC\ta\t1\t0.9\t10\t2\t4\t0\t0\t0.8\t0.09999999999999998",
+            ]
+        );
+
+        pattern.write_background_adjusted_output(
+            out.path(),
+            &MethylationBackground::Motif(control.clone()),
+            ValueScale::Fraction,
+            true,
+            false,
+            &[],
+        )?;
+        let mut rows: Vec<String> = std::fs::read_to_string(out.path())?
+            .lines()
+            .skip(1)
+            .map(String::from)
+            .collect();
+        rows.sort();
+        assert_eq!(
+            rows,
+            [
+                "contig_1\tCCWGG\tm\t1\t0.25\t10\t2\t4\t0\t0\t0.25\t0",
+                "contig_1\tGATC\ta\t1\t0.75\t10\t2\t4\t0\t0\t0.25\t0.5",
+                "contig_2\tGATC\ta\t1\t0.9\t10\t2\t4\t0\t0\tNA\tNA",
+            ]
+        );
+
+        assert_eq!(background_adjusted(0.1, -0.5), 0.6);
+        assert_eq!(background_adjusted(0.9, -0.5), 1.0);
+
+        let raw = MethylationPatternVariant::Raw(MotifMethylationPositions::new(
+            AHashMap::new(),
+            AHashMap::new(),
+        ));
+        assert!(
+            raw.write_background_adjusted_output(
+                out.path(),
+                &MethylationBackground::Value(0.1),
+                ValueScale::Fraction,
+                true,
+                false,
+                &[],
+            )
+            .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_output_raw_sorted_by_position() -> Result<()> {
+        let gatc_a = Motif::new("GATC", "a", 1)?;
+        let gatc_m = Motif::new("GATC", "m", 3)?;
+        let mut methylation = AHashMap::new();
+        for (contig, motif, position, strand) in [
+            ("contig_2", &gatc_a, 5, Strand::Positive),
+            ("contig_1", &gatc_m, 12, Strand::Positive),
+            ("contig_1", &gatc_a, 12, Strand::Negative),
+            ("contig_1", &gatc_a, 3, Strand::Positive),
+            ("contig_1", &gatc_a, 12, Strand::Positive),
+        ] {
+            methylation.insert(
+                (contig.to_string(), motif.clone(), position, strand),
+                MethylationCoverage::new(1, 2, 0, 0, 0)?,
+            );
+        }
+        let variant = MethylationPatternVariant::Raw(MotifMethylationPositions::new(
+            methylation,
+            AHashMap::new(),
+        ));
+
+        let out = tempfile::NamedTempFile::new()?;
+        variant.write_output(
+            out.path(),
+            ValueScale::Fraction,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &[],
+        )?;
+        let written = std::fs::read_to_string(out.path())?;
+
+        let keys: Vec<String> = written
+            .lines()
+            .skip(1)
+            .map(|l| l.split('\t').take(5).collect::<Vec<_>>().join(" "))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "contig_1 3 + GATC a",
+                "contig_1 12 + GATC a",
+                "contig_1 12 + GATC m",
+                "contig_1 12 - GATC a",
+                "contig_2 5 + GATC a",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_output_metadata_header_is_comment_prefixed() -> Result<()> {
+        let variant = MethylationPatternVariant::Median(vec![MedianMotifMethylationDegree {
+            contig: "contig_1".to_string(),
+            motif: Motif::new("GATC", "a", 1).unwrap(),
+            median: 0.5,
+            mean_read_cov: 10.0,
+            n_motif_obs: 2,
+            motif_occurences_total: 4,
+            n_filtered_low_cov: 0,
+            n_filtered_diff: 0,
+        }]);
+
+        let plain_out = tempfile::NamedTempFile::new()?;
+        let header_out = tempfile::NamedTempFile::new()?;
+        variant.write_output(
+            plain_out.path(),
+            ValueScale::Fraction,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &[],
+        )?;
+        variant.write_output(
+            header_out.path(),
+            ValueScale::Fraction,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &["version: 1.0".to_string(), "motifs: GATC_a_1\nsplit".to_string()],
+        )?;
+
+        let plain = std::fs::read_to_string(plain_out.path())?;
+        let with_header = std::fs::read_to_string(header_out.path())?;
+
+        let (comments, data): (Vec<&str>, Vec<&str>) =
+            with_header.lines().partition(|l| l.starts_with('#'));
+        assert_eq!(comments, vec!["# version: 1.0", "# motifs: GATC_a_1 split"]);
+        assert!(with_header.starts_with("# version: 1.0\n"));
+        assert_eq!(data, plain.lines().collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_checksums_is_order_independent() -> Result<()> {
+        let degree = |contig: &str, motif: &str, median: f64| MedianMotifMethylationDegree {
+            contig: contig.to_string(),
+            motif: Motif::new(motif, "a", 1).unwrap(),
+            median,
+            mean_read_cov: 10.0,
+            n_motif_obs: 2,
+            motif_occurences_total: 4,
+            n_filtered_low_cov: 0,
+            n_filtered_diff: 0,
+        };
+        let first = MethylationPatternVariant::Median(vec![
+            degree("contig_1", "GATC", 0.9),
+            degree("contig_1", "GANTC", 0.2),
+            degree("contig_2", "GATC", 0.5),
+        ]);
+        let second = MethylationPatternVariant::Median(vec![
+            degree("contig_2", "GATC", 0.5),
+            degree("contig_1", "GANTC", 0.2),
+            degree("contig_1", "GATC", 0.9),
+        ]);
+        let changed = MethylationPatternVariant::Median(vec![
+            degree("contig_1", "GATC", 0.8),
+            degree("contig_1", "GANTC", 0.2),
+            degree("contig_2", "GATC", 0.5),
+        ]);
+
+        let first_out = tempfile::NamedTempFile::new()?;
+        let second_out = tempfile::NamedTempFile::new()?;
+        let changed_out = tempfile::NamedTempFile::new()?;
+        assert_eq!(
+            first.write_checksums(first_out.path(), ValueScale::Fraction, StrandFormat::Symbol)?,
+            2
+        );
+        second.write_checksums(
+            second_out.path(),
+            ValueScale::Fraction,
+            StrandFormat::Symbol,
+        )?;
+        changed.write_checksums(
+            changed_out.path(),
+            ValueScale::Fraction,
+            StrandFormat::Symbol,
+        )?;
+
+        let first = std::fs::read_to_string(first_out.path())?;
+        let second = std::fs::read_to_string(second_out.path())?;
+        let changed = std::fs::read_to_string(changed_out.path())?;
+        assert_eq!(first, second);
+
+        let first_lines: Vec<&str> = first.lines().collect();
+        let changed_lines: Vec<&str> = changed.lines().collect();
+        assert_eq!(first_lines[0], "contig\tn_rows\txxh3_64");
+        assert!(first_lines[1].starts_with("contig_1\t2\t"));
+        assert_ne!(first_lines[1], changed_lines[1]);
+        assert_eq!(first_lines[2], changed_lines[2]);
+
+        Ok(())
+    }
+}
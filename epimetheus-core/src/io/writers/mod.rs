@@ -0,0 +1,80 @@
+pub mod coverage_histogram;
+pub mod methylation_pattern;
+pub mod reports;
+
+use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Creates the file at `path` for buffered writing.
+pub fn create_file_writer<P: AsRef<Path>>(path: P) -> Result<BufWriter<File>> {
+    let path = path.as_ref();
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create output file: {}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
+/// Writes each line in `metadata` prefixed with `# `, so TSV readers can skip it as a
+/// comment, followed by the column `header`.
+pub fn write_tsv_header<W: Write>(writer: &mut W, metadata: &[String], header: &str) -> Result<()> {
+    for line in metadata {
+        writeln!(writer, "# {}", line.replace('\n', " "))?;
+    }
+    writeln!(writer, "{}", header)?;
+    Ok(())
+}
+
+/// Creates the TSV file at `path` and writes its header. See [`write_tsv_header`].
+pub fn create_tsv_writer<P: AsRef<Path>>(
+    path: P,
+    metadata: &[String],
+    header: &str,
+) -> Result<BufWriter<File>> {
+    let mut writer = create_file_writer(path)?;
+    write_tsv_header(&mut writer, metadata, header)?;
+    Ok(writer)
+}
+
+/// The rows in their order, or sorted if `sort` is set.
+pub fn sorted_rows<T: PartialOrd>(rows: &[T], sort: bool) -> Vec<&T> {
+    let mut sorted: Vec<&T> = rows.iter().collect();
+    if sort {
+        sorted.sort_by(|a, b| a.partial_cmp(b).expect("Ordering failed"));
+    }
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_tsv_writer_prefixes_metadata() -> Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let mut writer = create_tsv_writer(
+            file.path(),
+            &["epimetheus 1.0\nsecond line".to_string()],
+            "contig\tn_rows",
+        )?;
+        writeln!(writer, "contig_1\t2")?;
+        writer.flush()?;
+
+        let content = std::fs::read_to_string(file.path())?;
+        assert_eq!(
+            content,
+            "# epimetheus 1.0 second line\ncontig\tn_rows\ncontig_1\t2\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sorted_rows_keeps_order_without_sort() {
+        let rows = [3.0, 1.0, 2.0];
+        assert_eq!(sorted_rows(&rows, false), vec![&3.0, &1.0, &2.0]);
+        assert_eq!(sorted_rows(&rows, true), vec![&1.0, &2.0, &3.0]);
+    }
+}
@@ -0,0 +1,295 @@
+use anyhow::Result;
+use epimetheus_methylome::{Motif, StrandFormat};
+use std::{io::Write, path::Path};
+
+use crate::{
+    io::writers::{create_tsv_writer, sorted_rows},
+    models::methylation::{
+        GroupMotifMethylationDegree, MotifMatch, MotifReportRow, StrandBiasDegree,
+        StrandWidePosition, ValueScale, WindowMethylationDegree,
+    },
+};
+
+/// Writes the methylation per contig group as TSV. See
+/// [`MotifMethylationPositions::to_group_degrees`].
+///
+/// [`MotifMethylationPositions::to_group_degrees`]:
+/// crate::models::methylation::MotifMethylationPositions::to_group_degrees
+pub fn write_group_output<P: AsRef<Path>>(
+    degrees: &[GroupMotifMethylationDegree],
+    path: P,
+    value_scale: ValueScale,
+    sort: bool,
+) -> Result<()> {
+    let header = format!(
+        "group\tmotif\tmod_type\tmod_position\t{}\tmean_read_cov\tn_motif_obs\tmotif_occurences_total\tn_contigs",
+        value_scale.value_column()
+    );
+    let mut writer = create_tsv_writer(path, &[], &header)?;
+
+    for deg in sorted_rows(degrees, sort) {
+        let methylation_value = value_scale.format(deg.methylation_value);
+
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            deg.group,
+            deg.motif.sequence_to_string(),
+            deg.motif.mod_type.to_pileup_code(),
+            deg.motif.mod_position,
+            methylation_value,
+            deg.mean_read_cov,
+            deg.n_motif_obs,
+            deg.motif_occurences_total,
+            deg.n_contigs,
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the window methylation as TSV. See
+/// [`MotifMethylationPositions::to_window_degrees`].
+///
+/// Windows without observations are skipped, unless `na_value` is given in which
+/// case they are written with `na_value` as methylation.
+///
+/// [`MotifMethylationPositions::to_window_degrees`]:
+/// crate::models::methylation::MotifMethylationPositions::to_window_degrees
+pub fn write_window_output<P: AsRef<Path>>(
+    degrees: &[WindowMethylationDegree],
+    path: P,
+    na_value: Option<&str>,
+    value_scale: ValueScale,
+    sort: bool,
+) -> Result<()> {
+    let methylation_column = match value_scale {
+        ValueScale::Percent => "methylation_percent",
+        ValueScale::Fraction | ValueScale::Scale255 => "methylation",
+    };
+    let header = format!(
+        "contig\twindow_start\twindow_end\tmotif\tmod_type\tmod_position\t{}\tn_obs",
+        methylation_column
+    );
+    let mut writer = create_tsv_writer(path, &[], &header)?;
+
+    let mut sorted_degrees: Vec<_> = degrees.iter().collect();
+    if sort {
+        sorted_degrees
+            .sort_by_key(|deg| (deg.contig.clone(), deg.motif.clone(), deg.window_start));
+    }
+
+    for deg in sorted_degrees {
+        let methylation = match (deg.methylation, na_value) {
+            (Some(value), _) => value_scale.format(value),
+            (None, Some(na_value)) => na_value.to_string(),
+            (None, None) => continue,
+        };
+
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            deg.contig,
+            deg.window_start,
+            deg.window_end,
+            deg.motif.sequence_to_string(),
+            deg.motif.mod_type.to_pileup_code(),
+            deg.motif.mod_position,
+            methylation,
+            deg.n_obs,
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the motif report as TSV, one row per motif. With `include_metrics` the motif
+/// length, GC content and degeneracy (see [`Motif::gc_content`] and
+/// [`Motif::degeneracy`]) are added after the mod position.
+pub fn write_motif_report<P: AsRef<Path>>(
+    rows: &[MotifReportRow],
+    path: P,
+    include_metrics: bool,
+) -> Result<()> {
+    let metrics_header = if include_metrics {
+        "\tmotif_length\tgc_content\tdegeneracy"
+    } else {
+        ""
+    };
+    let header = format!(
+        "motif\tmod_type\tmod_position{}\tmotif_occurences_total\tn_motif_obs\tmethylation_fraction\tn_contigs_observed\tn_contigs_methylated",
+        metrics_header
+    );
+    let mut writer = create_tsv_writer(path, &[], &header)?;
+
+    for row in rows {
+        let metrics = if include_metrics {
+            format!(
+                "\t{}\t{}\t{}",
+                row.motif.sequence.len(),
+                row.motif.gc_content(),
+                row.motif.degeneracy()
+            )
+        } else {
+            String::new()
+        };
+        writeln!(
+            writer,
+            "{}\t{}\t{}{}\t{}\t{}\t{}\t{}\t{}",
+            row.motif.sequence_to_string(),
+            row.motif.mod_type.to_pileup_code(),
+            row.motif.mod_position,
+            metrics,
+            row.motif_occurences_total,
+            row.n_motif_obs,
+            row.methylation_fraction,
+            row.n_contigs_observed,
+            row.n_contigs_methylated,
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the motif matches as TSV, one row per motif site. Coverage columns are empty
+/// for sites without a record.
+pub fn write_motif_matches<P: AsRef<Path>>(
+    matches: &[MotifMatch],
+    path: P,
+    strand_format: StrandFormat,
+) -> Result<()> {
+    let mut writer = create_tsv_writer(
+        path,
+        &[],
+        "contig\tstart\tstrand\tmotif\tmod_type\tmod_position\thad_record\tn_modified\tn_valid_cov\tfraction_modified\texclusion_reason",
+    )?;
+
+    for site in matches {
+        let (n_modified, n_valid_cov, fraction_modified) = match &site.coverage {
+            Some(coverage) => (
+                coverage.get_n_modified().to_string(),
+                coverage.get_n_valid_cov().to_string(),
+                coverage.fraction_modified().to_string(),
+            ),
+            None => (String::new(), String::new(), String::new()),
+        };
+
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            site.contig,
+            site.position,
+            site.strand.format(strand_format),
+            site.motif.sequence_to_string(),
+            site.motif.mod_type.to_pileup_code(),
+            site.motif.mod_position,
+            site.had_record(),
+            n_modified,
+            n_valid_cov,
+            fraction_modified,
+            site.exclusion_reason().unwrap_or(""),
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the motif occurrence counts of `--count-only` as TSV, one row per
+/// contig-motif.
+pub fn write_motif_occurrence_counts<P: AsRef<Path>>(
+    counts: &[(String, Motif, u32)],
+    path: P,
+) -> Result<()> {
+    let mut writer = create_tsv_writer(path, &[], "contig\tmotif\tmod_type\toccurrences")?;
+
+    for (contig, motif, n_occurrences) in counts {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}",
+            contig,
+            motif.sequence_to_string(),
+            motif.mod_type.to_pileup_code(),
+            n_occurrences,
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the strand-wide raw output as TSV. See
+/// [`MotifMethylationPositions::to_strand_wide`].
+///
+/// [`MotifMethylationPositions::to_strand_wide`]:
+/// crate::models::methylation::MotifMethylationPositions::to_strand_wide
+pub fn write_raw_strand_wide<P: AsRef<Path>>(
+    rows: &[StrandWidePosition],
+    path: P,
+    sort: bool,
+) -> Result<()> {
+    let mut writer = create_tsv_writer(
+        path,
+        &[],
+        "contig\tstart\tmotif\tmod_type\tmod_position\tn_modified_plus\tn_valid_cov_plus\tn_modified_minus\tn_valid_cov_minus",
+    )?;
+
+    let mut sorted_positions: Vec<_> = rows.iter().collect();
+    if sort {
+        sorted_positions.sort_by_key(|row| (row.contig.clone(), row.motif.clone(), row.start));
+    }
+
+    for row in sorted_positions {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            row.contig,
+            row.start,
+            row.motif.sequence_to_string(),
+            row.motif.mod_type.to_pileup_code(),
+            row.motif.mod_position,
+            row.n_modified_plus,
+            row.n_valid_cov_plus,
+            row.n_modified_minus,
+            row.n_valid_cov_minus,
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the strand bias report as TSV sorted by contig and motif.
+pub fn write_strand_bias_report<P: AsRef<Path>>(
+    degrees: &[StrandBiasDegree],
+    path: P,
+) -> Result<()> {
+    let mut writer = create_tsv_writer(
+        path,
+        &[],
+        "contig\tmotif\tmod_type\tmod_position\tmethylation_plus\tmethylation_minus\tbias\tn_motif_obs_plus\tn_motif_obs_minus\tbiased",
+    )?;
+
+    for deg in sorted_rows(degrees, true) {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            deg.contig,
+            deg.motif.sequence_to_string(),
+            deg.motif.mod_type.to_pileup_code(),
+            deg.motif.mod_position,
+            deg.methylation_plus,
+            deg.methylation_minus,
+            deg.bias,
+            deg.n_motif_obs_plus,
+            deg.n_motif_obs_minus,
+            deg.biased,
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
@@ -1,3 +1,4 @@
 pub mod algorithms;
+pub mod io;
 pub mod models;
 pub mod services;
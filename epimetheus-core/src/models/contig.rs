@@ -7,12 +7,18 @@ use epimetheus_methylome::{ModType, Strand, sequence::Sequence};
 pub type ContigId = String;
 pub type Position = usize;
 
+/// Rough upper bound of the bytes per base a contig takes while it is processed: the
+/// sequence, the pileup records and the methylation of both strands.
+pub const ESTIMATED_BYTES_PER_BASE: u64 = 128;
+
 #[derive(Clone)]
 pub struct Contig {
     pub id: ContigId,
     pub sequence: Sequence,
     sequence_len: usize,
     pub methylated_positions: AHashMap<(Position, Strand, ModType), MethylationCoverage>,
+    /// Positions with a pileup record removed by the coverage filters.
+    pub filtered_positions: FilteredPositions,
 }
 
 impl Contig {
@@ -24,6 +30,7 @@ impl Contig {
             sequence,
             sequence_len: sequence_length,
             methylated_positions: AHashMap::new(),
+            filtered_positions: FilteredPositions::new(),
         }
     }
 
@@ -36,9 +43,16 @@ impl Contig {
             sequence,
             sequence_len: sequence_length,
             methylated_positions: AHashMap::new(),
+            filtered_positions: FilteredPositions::new(),
         })
     }
 
+    /// Estimated memory in bytes of the contig with its methylation loaded. See
+    /// [`ESTIMATED_BYTES_PER_BASE`].
+    pub fn estimated_memory(&self) -> u64 {
+        self.sequence_len as u64 * ESTIMATED_BYTES_PER_BASE
+    }
+
     pub fn add_methylation(
         &mut self,
         position: usize,
@@ -61,7 +75,16 @@ impl Contig {
         Ok(())
     }
 
-    pub fn add_methylation_record(&mut self, record: MethylationRecord) -> anyhow::Result<()> {
+    /// Adds the methylation of a pileup record. Returns false if the record was skipped.
+    ///
+    /// A record at or past the end of the contig means the pileup was made against
+    /// another version of the assembly. This is an error, unless `allow_mismatch` is set,
+    /// in which case the record is skipped.
+    pub fn add_methylation_record(
+        &mut self,
+        record: MethylationRecord,
+        allow_mismatch: bool,
+    ) -> anyhow::Result<bool> {
         if self.id != record.contig {
             bail!(
                 "Contig id error: Methylation record id '{}'. Contig id: {}",
@@ -70,13 +93,25 @@ impl Contig {
             )
         }
 
+        if record.position >= self.sequence_len {
+            if allow_mismatch {
+                return Ok(false);
+            }
+            bail!(
+                "Pileup record at position {} is past the end of contig '{}' (length {}). The pileup may be made against another version of the assembly. Use --allow-mismatch to skip such records.",
+                record.position,
+                self.id,
+                self.sequence_len
+            )
+        }
+
         self.add_methylation(
             record.position,
             record.strand,
             record.mod_type,
             record.methylation,
         )?;
-        Ok(())
+        Ok(true)
     }
 
     pub fn get_methylated_positions(
@@ -90,6 +125,22 @@ impl Contig {
             .map(|&pos| (pos, self.methylated_positions.get(&(pos, strand, mod_type))))
             .collect()
     }
+
+    /// Counts the `positions` on `strand` removed by the coverage filters.
+    pub fn count_filtered_positions(
+        &self,
+        positions: &[Position],
+        strand: Strand,
+        mod_type: ModType,
+    ) -> FilteredCounts {
+        let mut counts = FilteredCounts::default();
+        for &pos in positions {
+            if let Some(&reason) = self.filtered_positions.get(&(pos, strand, mod_type)) {
+                counts.add(reason);
+            }
+        }
+        counts
+    }
 }
 
 #[cfg(test)]
@@ -171,4 +222,28 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_record_past_contig_end() {
+        let mut contig = Contig::from_string("contig_1".to_string(), "GATC".to_string()).unwrap();
+        let record = |position: usize| {
+            MethylationRecord::new(
+                "contig_1".to_string(),
+                position,
+                Strand::Positive,
+                ModType::SixMA,
+                MethylationCoverage::new(1, 1, 0, 0, 0).unwrap(),
+            )
+        };
+
+        let err = contig.add_methylation_record(record(4), false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("past the end of contig 'contig_1' (length 4)")
+        );
+
+        assert!(!contig.add_methylation_record(record(10), true).unwrap());
+        assert!(contig.add_methylation_record(record(3), true).unwrap());
+        assert_eq!(contig.methylated_positions.len(), 1);
+    }
 }
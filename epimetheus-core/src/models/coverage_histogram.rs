@@ -0,0 +1,91 @@
+use anyhow::{Result, bail};
+use epimetheus_methylome::ModType;
+use std::collections::BTreeMap;
+
+/// Bin width of the coverage histogram when none is given.
+pub const DEFAULT_COVERAGE_BIN_WIDTH: u32 = 5;
+
+/// Number of pileup records per mod type and valid coverage, to help choosing
+/// `min_valid_read_coverage`.
+///
+/// Coverages are counted exactly and only binned when written, so histograms of
+/// contigs processed in parallel can be merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageHistogram {
+    counts: BTreeMap<(ModType, u32), u64>,
+}
+
+impl CoverageHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, mod_type: ModType, n_valid_cov: u32) {
+        *self.counts.entry((mod_type, n_valid_cov)).or_default() += 1;
+    }
+
+    pub fn merge(&mut self, other: CoverageHistogram) {
+        for (key, count) in other.counts {
+            *self.counts.entry(key).or_default() += count;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Number of records counted.
+    pub fn n_records(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Counts per mod type and bin of `bin_width` coverages, sorted by mod type and
+    /// bin. A bin is given by its lowest coverage. Empty bins are left out.
+    pub fn binned(&self, bin_width: u32) -> Result<Vec<(ModType, u32, u64)>> {
+        if bin_width == 0 {
+            bail!("Coverage histogram bin width should be at least 1");
+        }
+
+        let mut bins: BTreeMap<(ModType, u32), u64> = BTreeMap::new();
+        for (&(mod_type, n_valid_cov), &count) in &self.counts {
+            let bin_start = n_valid_cov / bin_width * bin_width;
+            *bins.entry((mod_type, bin_start)).or_default() += count;
+        }
+
+        Ok(bins
+            .into_iter()
+            .map(|((mod_type, bin_start), count)| (mod_type, bin_start, count))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binned_counts_per_mod_type() -> Result<()> {
+        let mut histogram = CoverageHistogram::new();
+        for n_valid_cov in [0, 3, 4, 5, 12] {
+            histogram.add(ModType::SixMA, n_valid_cov);
+        }
+        let mut other = CoverageHistogram::new();
+        other.add(ModType::FiveMC, 7);
+        other.add(ModType::SixMA, 14);
+        histogram.merge(other);
+
+        assert_eq!(histogram.n_records(), 7);
+        assert_eq!(
+            histogram.binned(5)?,
+            vec![
+                (ModType::SixMA, 0, 3),
+                (ModType::SixMA, 5, 1),
+                (ModType::SixMA, 10, 2),
+                (ModType::FiveMC, 5, 1),
+            ]
+        );
+        assert!(histogram.binned(0).is_err());
+
+        Ok(())
+    }
+}
@@ -14,6 +14,14 @@ impl GenomeWorkspaceBuilder {
         }
     }
 
+    /// Pre-sizes the contig map for `capacity` contigs. Batch loaders know
+    /// their batch size, so this avoids rehashing while a batch is filled.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            workspace: GenomeWorkspace::with_capacity(capacity),
+        }
+    }
+
     pub fn add_contig(&mut self, contig: Contig) -> Result<&mut Self> {
         if self.workspace.contigs.contains_key(&contig.id) {
             bail!("Key error: '{}' already inserted", &contig.id)
@@ -56,6 +64,12 @@ impl GenomeWorkspace {
             contigs: AHashMap::new(),
         }
     }
+
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            contigs: AHashMap::with_capacity(capacity),
+        }
+    }
     pub fn get_workspace(&self) -> AHashMap<String, Contig> {
         self.contigs.clone()
     }
@@ -72,7 +86,7 @@ impl GenomeWorkspace {
 #[cfg(test)]
 mod tests {
     use crate::models::methylation::MethylationCoverage;
-    use crate::models::pileup::{PileupRecord, PileupRecordString};
+    use crate::models::pileup::{CoverageField, PileupRecord, PileupRecordString};
 
     use super::*;
     use anyhow::Result;
@@ -139,7 +153,14 @@ mod tests {
         for res in reader.lines() {
             let record = res.unwrap();
             let pileup_record = PileupRecord::try_from(PileupRecordString::new(record)).unwrap();
-            let meth_record = MethylationRecord::try_from_with_filters(pileup_record, 3, 0.8);
+            let meth_record = MethylationRecord::try_from_with_filters(
+                pileup_record,
+                3,
+                0.8,
+                0,
+                &CoverageField::default(),
+                None,
+            );
 
             let meth = match meth_record {
                 Ok(Some(m)) => m,
@@ -183,6 +204,31 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_with_capacity_builds_same_workspace() -> Result<()> {
+        let contigs = vec![
+            Contig::from_string("contig_1".to_string(), "GATC".to_string())?,
+            Contig::from_string("contig_2".to_string(), "TGGACGATCCCGATC".to_string())?,
+        ];
+
+        let mut default_builder = GenomeWorkspaceBuilder::new();
+        let mut sized_builder = GenomeWorkspaceBuilder::with_capacity(contigs.len());
+        for contig in contigs {
+            default_builder.add_contig(contig.clone())?;
+            sized_builder.add_contig(contig)?;
+        }
+
+        let default_ws = default_builder.build().get_workspace();
+        let sized_ws = sized_builder.build().get_workspace();
+
+        assert_eq!(default_ws.len(), sized_ws.len());
+        for (id, contig) in &default_ws {
+            assert_eq!(sized_ws.get(id).map(|c| &c.sequence), Some(&contig.sequence));
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_populate_methylation_missing_contig() {
         let mut workspace_builder = GenomeWorkspaceBuilder::new();
@@ -201,9 +247,16 @@ mod tests {
         for res in reader.lines() {
             let record = res.unwrap();
             let pileup_record = PileupRecord::try_from(PileupRecordString::new(record)).unwrap();
-            let meth_record = MethylationRecord::try_from_with_filters(pileup_record, 3, 0.8)
-                .unwrap()
-                .unwrap();
+            let meth_record = MethylationRecord::try_from_with_filters(
+                pileup_record,
+                3,
+                0.8,
+                0,
+                &CoverageField::default(),
+                None,
+            )
+            .unwrap()
+            .unwrap();
 
             let result = workspace_builder.add_record(meth_record);
             assert!(result.is_err());
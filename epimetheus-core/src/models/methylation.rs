@@ -1,16 +1,17 @@
-use std::{path::Path, str::FromStr};
+use std::{collections::BTreeMap, str::FromStr};
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::{Result, bail};
 use clap::ValueEnum;
 use epimetheus_methylome::{ModType, Motif, Strand};
+pub use epimetheus_methylome::{MotifType, StrandFormat};
 
 #[cfg(feature = "python")]
 use pyo3::{IntoPyObject, types::PyAnyMethods};
 
 use crate::models::{
     contig::{ContigId, Position as ContigPosition},
-    pileup::PileupRecord,
+    pileup::{CoverageField, PileupRecord},
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
@@ -64,6 +65,47 @@ impl MethylationCoverage {
     pub fn fraction_modified(&self) -> f64 {
         self.n_modified as f64 / self.n_valid_cov as f64
     }
+
+    /// Sums the counts of two coverages of the same site.
+    pub fn combined(&self, other: &Self) -> Self {
+        Self {
+            n_modified: self.n_modified + other.n_modified,
+            n_valid_cov: self.n_valid_cov + other.n_valid_cov,
+            n_diff: self.n_diff + other.n_diff,
+            n_fail: self.n_fail + other.n_fail,
+        }
+    }
+}
+
+/// Coverage filter of [`MethylationRecord::try_from_with_filters`] that removed a
+/// pileup record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FilterReason {
+    /// The valid coverage is below `min_valid_read_coverage`.
+    LowCoverage,
+    /// The fraction of valid coverage to valid plus diff coverage is below
+    /// `min_valid_cov_to_diff_fraction`.
+    Diff,
+}
+
+/// Positions of a contig removed by the coverage filters, keyed like
+/// [`crate::models::contig::Contig::methylated_positions`].
+pub type FilteredPositions = AHashMap<(ContigPosition, Strand, ModType), FilterReason>;
+
+/// Motif positions of a contig-motif removed by each coverage filter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FilteredCounts {
+    pub n_filtered_low_cov: u32,
+    pub n_filtered_diff: u32,
+}
+
+impl FilteredCounts {
+    pub fn add(&mut self, reason: FilterReason) {
+        match reason {
+            FilterReason::LowCoverage => self.n_filtered_low_cov += 1,
+            FilterReason::Diff => self.n_filtered_diff += 1,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -112,12 +154,37 @@ impl MethylationRecord {
         }
     }
 
+    /// Converts a pileup record, returning `None` if it is removed by the filters.
+    ///
+    /// Records with a `score` (column 5 of the pileup) below `min_score` are removed.
+    /// A `min_score` of 0 keeps all records.
+    ///
+    /// The valid coverage is read from `coverage_field` instead of the `n_valid_cov`
+    /// column (see [`CoverageField`]).
+    ///
+    /// Records removed by the valid coverage or diff filter are added to `filtered`
+    /// when given, so the removed motif positions can be counted per contig-motif.
     pub fn try_from_with_filters(
         value: PileupRecord,
         min_valid_read_coverage: u32,
         min_valid_cov_to_diff_fraction: f32,
+        min_score: u32,
+        coverage_field: &CoverageField,
+        mut filtered: Option<&mut FilteredPositions>,
     ) -> Result<Option<Self>> {
-        if value.n_valid_cov < min_valid_read_coverage {
+        let n_valid_cov = coverage_field.value(&value);
+        let mut record_filtered = |reason: FilterReason| {
+            if let Some(filtered) = filtered.as_mut() {
+                filtered.insert((value.start as usize, value.strand, value.mod_type), reason);
+            }
+        };
+
+        if n_valid_cov < min_valid_read_coverage {
+            record_filtered(FilterReason::LowCoverage);
+            return Ok(None);
+        }
+
+        if value.score < min_score {
             return Ok(None);
         }
 
@@ -125,15 +192,16 @@ impl MethylationRecord {
             return Ok(None);
         }
 
-        if (value.n_valid_cov as f32 / (value.n_diff as f32 + value.n_valid_cov as f32))
+        if (n_valid_cov as f32 / (value.n_diff as f32 + n_valid_cov as f32))
             < min_valid_cov_to_diff_fraction
         {
+            record_filtered(FilterReason::Diff);
             return Ok(None);
         }
 
         let meth = MethylationCoverage::new(
             value.n_modified,
-            value.n_valid_cov,
+            n_valid_cov,
             value.n_other_mod,
             value.n_diff,
             value.n_fail,
@@ -154,6 +222,45 @@ impl MethylationRecord {
     }
 }
 
+/// Scales a methylation fraction to the 0-255 bedMethyl score range.
+///
+/// The value is rounded to the nearest integer with ties rounded away from zero,
+/// so 0.5 becomes 128. Values outside [0, 1] are clamped.
+pub fn scale_to_255(fraction: f64) -> u8 {
+    (fraction.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// How methylation fractions are written in the outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValueScale {
+    /// Fractions in [0, 1].
+    #[default]
+    Fraction,
+    /// Integers in 0-255, see [`scale_to_255`].
+    Scale255,
+    /// Percentages in [0, 100].
+    Percent,
+}
+
+impl ValueScale {
+    pub fn format(&self, fraction: f64) -> String {
+        match self {
+            ValueScale::Fraction => fraction.to_string(),
+            ValueScale::Scale255 => scale_to_255(fraction).to_string(),
+            ValueScale::Percent => (fraction * 100.0).to_string(),
+        }
+    }
+
+    /// Name of the methylation value column. Percentages are written as
+    /// `methylation_percent`, so they can not be mistaken for fractions.
+    pub fn value_column(&self) -> &'static str {
+        match self {
+            ValueScale::Percent => "methylation_percent",
+            ValueScale::Fraction | ValueScale::Scale255 => "methylation_value",
+        }
+    }
+}
+
 pub trait MotifMethylationDegree {
     fn get_contig(&self) -> &str;
     fn get_motif(&self) -> &Motif;
@@ -161,26 +268,43 @@ pub trait MotifMethylationDegree {
     fn get_mean_read_cov(&self) -> f64;
     fn get_n_motif_obs(&self) -> u32;
     fn get_motif_occurences_total(&self) -> u32;
+    fn get_n_filtered_low_cov(&self) -> u32;
+    fn get_n_filtered_diff(&self) -> u32;
 
-    fn to_csv_line(&self, delim: char) -> String {
+    fn to_csv_line(&self, delim: char, value_scale: ValueScale) -> String {
         let motif_seq = self.get_motif().sequence_to_string();
         let mod_type = self.get_motif().mod_type.to_pileup_code();
         let mod_position = self.get_motif().mod_position;
+        let methylation_value = value_scale.format(self.get_methylation_value());
 
         format!(
-            "{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}",
+            "{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}",
             self.get_contig(),
             motif_seq,
             mod_type,
             mod_position,
-            self.get_methylation_value(),
+            methylation_value,
             self.get_mean_read_cov(),
             self.get_n_motif_obs(),
             self.get_motif_occurences_total(),
+            self.get_n_filtered_low_cov(),
+            self.get_n_filtered_diff(),
         )
     }
 }
 
+/// Median and weighted mean methylation of a contig-motif side by side.
+#[derive(PartialEq, Clone, PartialOrd)]
+pub struct MotifMethylationSummary {
+    pub contig: String,
+    pub motif: Motif,
+    pub median: f64,
+    pub weighted_mean: f64,
+    pub mean_read_cov: f64,
+    pub n_motif_obs: u32,
+    pub motif_occurences_total: u32,
+}
+
 #[derive(PartialEq, Clone, PartialOrd)]
 pub struct MedianMotifMethylationDegree {
     pub contig: String,
@@ -189,6 +313,10 @@ pub struct MedianMotifMethylationDegree {
     pub mean_read_cov: f64,
     pub n_motif_obs: u32,
     pub motif_occurences_total: u32,
+    /// Motif positions removed by the valid coverage filter.
+    pub n_filtered_low_cov: u32,
+    /// Motif positions removed by the valid coverage to diff fraction filter.
+    pub n_filtered_diff: u32,
 }
 
 impl MotifMethylationDegree for MedianMotifMethylationDegree {
@@ -215,6 +343,14 @@ impl MotifMethylationDegree for MedianMotifMethylationDegree {
     fn get_motif_occurences_total(&self) -> u32 {
         self.motif_occurences_total
     }
+
+    fn get_n_filtered_low_cov(&self) -> u32 {
+        self.n_filtered_low_cov
+    }
+
+    fn get_n_filtered_diff(&self) -> u32 {
+        self.n_filtered_diff
+    }
 }
 
 #[derive(PartialEq, Clone, PartialOrd)]
@@ -222,9 +358,15 @@ pub struct WeightedMeanMotifMethylationDegree {
     pub contig: String,
     pub motif: Motif,
     pub w_mean: f64,
+    /// Standard error of `w_mean`. See [`weighted_mean_stderr`].
+    pub stderr: f64,
     pub mean_read_cov: f64,
     pub n_motif_obs: u32,
     pub motif_occurences_total: u32,
+    /// Motif positions removed by the valid coverage filter.
+    pub n_filtered_low_cov: u32,
+    /// Motif positions removed by the valid coverage to diff fraction filter.
+    pub n_filtered_diff: u32,
 }
 
 impl MotifMethylationDegree for WeightedMeanMotifMethylationDegree {
@@ -251,28 +393,297 @@ impl MotifMethylationDegree for WeightedMeanMotifMethylationDegree {
     fn get_motif_occurences_total(&self) -> u32 {
         self.motif_occurences_total
     }
+
+    fn get_n_filtered_low_cov(&self) -> u32 {
+        self.n_filtered_low_cov
+    }
+
+    fn get_n_filtered_diff(&self) -> u32 {
+        self.n_filtered_diff
+    }
+}
+
+/// Median of the per position methylation fractions, each weighted by its valid
+/// coverage. See [`MotifMethylationPositions::to_weighted_median_degrees`].
+#[derive(PartialEq, Clone, PartialOrd)]
+pub struct WeightedMedianMotifMethylationDegree {
+    pub contig: String,
+    pub motif: Motif,
+    pub w_median: f64,
+    pub mean_read_cov: f64,
+    pub n_motif_obs: u32,
+    pub motif_occurences_total: u32,
+    /// Motif positions removed by the valid coverage filter.
+    pub n_filtered_low_cov: u32,
+    /// Motif positions removed by the valid coverage to diff fraction filter.
+    pub n_filtered_diff: u32,
+}
+
+impl MotifMethylationDegree for WeightedMedianMotifMethylationDegree {
+    fn get_contig(&self) -> &str {
+        self.contig.as_str()
+    }
+
+    fn get_motif(&self) -> &Motif {
+        &self.motif
+    }
+
+    fn get_methylation_value(&self) -> f64 {
+        self.w_median
+    }
+
+    fn get_mean_read_cov(&self) -> f64 {
+        self.mean_read_cov
+    }
+
+    fn get_n_motif_obs(&self) -> u32 {
+        self.n_motif_obs
+    }
+
+    fn get_motif_occurences_total(&self) -> u32 {
+        self.motif_occurences_total
+    }
+
+    fn get_n_filtered_low_cov(&self) -> u32 {
+        self.n_filtered_low_cov
+    }
+
+    fn get_n_filtered_diff(&self) -> u32 {
+        self.n_filtered_diff
+    }
+}
+
+/// Mean of the per position methylation fractions between the 20th and 80th
+/// percentile. See [`MotifMethylationPositions::to_trimmed_mean_degrees`].
+#[derive(PartialEq, Clone, PartialOrd)]
+pub struct TrimmedMeanMotifMethylationDegree {
+    pub contig: String,
+    pub motif: Motif,
+    pub trimmed_mean: f64,
+    pub mean_read_cov: f64,
+    pub n_motif_obs: u32,
+    pub motif_occurences_total: u32,
+    /// Motif positions removed by the valid coverage filter.
+    pub n_filtered_low_cov: u32,
+    /// Motif positions removed by the valid coverage to diff fraction filter.
+    pub n_filtered_diff: u32,
+    /// False when there were fewer than [`TRIMMED_MEAN_MIN_OBS`] observations and
+    /// `trimmed_mean` is the plain mean.
+    pub trim_applied: bool,
+}
+
+impl MotifMethylationDegree for TrimmedMeanMotifMethylationDegree {
+    fn get_contig(&self) -> &str {
+        self.contig.as_str()
+    }
+
+    fn get_motif(&self) -> &Motif {
+        &self.motif
+    }
+
+    fn get_methylation_value(&self) -> f64 {
+        self.trimmed_mean
+    }
+
+    fn get_mean_read_cov(&self) -> f64 {
+        self.mean_read_cov
+    }
+
+    fn get_n_motif_obs(&self) -> u32 {
+        self.n_motif_obs
+    }
+
+    fn get_motif_occurences_total(&self) -> u32 {
+        self.motif_occurences_total
+    }
+
+    fn get_n_filtered_low_cov(&self) -> u32 {
+        self.n_filtered_low_cov
+    }
+
+    fn get_n_filtered_diff(&self) -> u32 {
+        self.n_filtered_diff
+    }
+}
+
+/// Fraction of positions called methylated. See
+/// [`MotifMethylationPositions::to_called_frequency_degrees`].
+#[derive(PartialEq, Clone, PartialOrd)]
+pub struct CalledFrequencyMotifMethylationDegree {
+    pub contig: String,
+    pub motif: Motif,
+    pub called_frequency: f64,
+    pub mean_read_cov: f64,
+    pub n_motif_obs: u32,
+    pub motif_occurences_total: u32,
+    /// Motif positions removed by the valid coverage filter.
+    pub n_filtered_low_cov: u32,
+    /// Motif positions removed by the valid coverage to diff fraction filter.
+    pub n_filtered_diff: u32,
+    /// Positions with a methylation fraction at or above the call threshold.
+    pub n_called_methylated: u32,
+}
+
+impl MotifMethylationDegree for CalledFrequencyMotifMethylationDegree {
+    fn get_contig(&self) -> &str {
+        self.contig.as_str()
+    }
+
+    fn get_motif(&self) -> &Motif {
+        &self.motif
+    }
+
+    fn get_methylation_value(&self) -> f64 {
+        self.called_frequency
+    }
+
+    fn get_mean_read_cov(&self) -> f64 {
+        self.mean_read_cov
+    }
+
+    fn get_n_motif_obs(&self) -> u32 {
+        self.n_motif_obs
+    }
+
+    fn get_motif_occurences_total(&self) -> u32 {
+        self.motif_occurences_total
+    }
+
+    fn get_n_filtered_low_cov(&self) -> u32 {
+        self.n_filtered_low_cov
+    }
+
+    fn get_n_filtered_diff(&self) -> u32 {
+        self.n_filtered_diff
+    }
 }
 
 pub struct MotifMethylationPositions {
     pub methylation: AHashMap<(ContigId, Motif, ContigPosition, Strand), MethylationCoverage>,
-    pub motif_occurence_totals: AHashMap<(ContigId, Motif, Strand), u32>,
+    /// Occurrences of each motif in the contig sequence on both strands, with
+    /// palindromic sites counted once. See
+    /// [`epimetheus_methylome::count_motif_occurrences`].
+    pub motif_occurence_totals: AHashMap<(ContigId, Motif), u32>,
+    /// Motif positions removed by the coverage filters, counted per strand. Contig-motifs
+    /// without removed positions are left out.
+    pub filtered_counts: AHashMap<(ContigId, Motif), FilteredCounts>,
 }
 
 impl MotifMethylationPositions {
     pub fn new(
         methylation: AHashMap<(ContigId, Motif, ContigPosition, Strand), MethylationCoverage>,
-        motif_occurence_totals: AHashMap<(ContigId, Motif, Strand), u32>,
+        motif_occurence_totals: AHashMap<(ContigId, Motif), u32>,
     ) -> Self {
         Self {
             methylation,
             motif_occurence_totals,
+            filtered_counts: AHashMap::new(),
+        }
+    }
+
+    /// Motif positions of a contig-motif removed by the coverage filters.
+    fn get_filtered_counts(&self, contig_id: &ContigId, motif: &Motif) -> FilteredCounts {
+        self.filtered_counts
+            .get(&(contig_id.clone(), motif.clone()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Merges the plus and minus strand observations of each site of a palindromic
+    /// motif into one observation at the plus strand position, summing the coverages.
+    ///
+    /// Both strands of a palindromic site describe the same methylation event, which
+    /// would otherwise count as two observations. A site observed on the minus strand
+    /// only is moved to its plus strand position. Observations of motifs that are not
+    /// palindromic are kept as they are.
+    pub fn collapse_strands(self) -> Self {
+        let palindromic: AHashSet<Motif> = self
+            .methylation
+            .keys()
+            .map(|(_, motif, _, _)| motif)
+            .collect::<AHashSet<&Motif>>()
+            .into_iter()
+            .filter(|motif| motif.is_palindromic())
+            .cloned()
+            .collect();
+
+        let mut methylation = AHashMap::with_capacity(self.methylation.len());
+        for ((contig_id, motif, position, strand), coverage) in self.methylation {
+            let key = if strand == Strand::Negative && palindromic.contains(&motif) {
+                // The minus strand mod position of a site starting at `start` is
+                // start + len - 1 - mod_position and the plus strand one
+                // start + mod_position.
+                let mod_position = motif.mod_position as usize;
+                let plus_position = position + 2 * mod_position + 1 - motif.sequence.len();
+                (contig_id, motif, plus_position, Strand::Positive)
+            } else {
+                (contig_id, motif, position, strand)
+            };
+
+            methylation
+                .entry(key)
+                .and_modify(|combined: &mut MethylationCoverage| {
+                    *combined = combined.combined(&coverage)
+                })
+                .or_insert(coverage);
+        }
+
+        Self {
+            methylation,
+            motif_occurence_totals: self.motif_occurence_totals,
+            filtered_counts: self.filtered_counts,
+        }
+    }
+
+    /// Removes the observations of contig-motifs with fewer than
+    /// `min_motif_observations` observed positions, the `n_motif_obs` of the degrees.
+    /// Returns the number of contig-motifs removed.
+    ///
+    /// Estimates from one or two positions are unreliable, and removing the positions
+    /// leaves the contig-motif out of every output type.
+    pub fn retain_min_motif_observations(&mut self, min_motif_observations: u32) -> usize {
+        if min_motif_observations <= 1 {
+            return 0;
+        }
+
+        let mut n_motif_obs: AHashMap<(&ContigId, &Motif), u32> = AHashMap::new();
+        for (contig_id, motif, _, _) in self.methylation.keys() {
+            *n_motif_obs.entry((contig_id, motif)).or_default() += 1;
         }
+        let sparse: AHashSet<(ContigId, Motif)> = n_motif_obs
+            .into_iter()
+            .filter(|(_, n)| *n < min_motif_observations)
+            .map(|((contig_id, motif), _)| (contig_id.clone(), motif.clone()))
+            .collect();
+
+        if !sparse.is_empty() {
+            self.methylation.retain(|(contig_id, motif, _, _), _| {
+                !sparse.contains(&(contig_id.clone(), motif.clone()))
+            });
+        }
+        sparse.len()
+    }
+
+    /// Methylation entries sorted by contig, motif, position and strand.
+    ///
+    /// The hash map iterates in a different order every run. Pooled statistics sum
+    /// floating point values, so coverages are gathered in this order to make the
+    /// results bit-identical across runs.
+    fn sorted_methylation(
+        &self,
+    ) -> Vec<(
+        &(ContigId, Motif, ContigPosition, Strand),
+        &MethylationCoverage,
+    )> {
+        let mut entries: Vec<_> = self.methylation.iter().collect();
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        entries
     }
 
     fn group_by_motif(&self) -> AHashMap<(ContigId, Motif), Vec<&MethylationCoverage>> {
         let mut grouped: AHashMap<(ContigId, Motif), Vec<&MethylationCoverage>> = AHashMap::new();
 
-        for ((contig_id, motif, _position, _strand), coverage) in &self.methylation {
+        for ((contig_id, motif, _position, _strand), coverage) in self.sorted_methylation() {
             grouped
                 .entry((contig_id.clone(), motif.clone()))
                 .or_insert_with(Vec::new)
@@ -286,43 +697,16 @@ impl MotifMethylationPositions {
         self.group_by_motif()
             .into_iter()
             .map(|((contig_id, motif), coverages)| {
-                let mut fractions: Vec<f64> = coverages
-                    .iter()
-                    .map(|cov| cov.fraction_modified())
-                    .collect();
-
-                fractions.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-                let median = if fractions.len() % 2 == 0 {
-                    let mid = fractions.len() / 2;
-                    (fractions[mid - 1] + fractions[mid]) / 2.0
-                } else {
-                    fractions[fractions.len() / 2]
-                };
-
-                let mean_read_cov = {
-                    let total_cov: u64 = coverages
-                        .iter()
-                        .map(|cov| cov.get_n_valid_cov() as u64)
-                        .sum();
-
-                    total_cov as f64 / coverages.len() as f64
-                };
+                let median = median_fraction(&coverages);
+                let mean_read_cov = mean_read_cov(&coverages);
 
-                let motif_occurences_fwd = self
+                let motif_occurence_totals = self
                     .motif_occurence_totals
-                    .get(&(contig_id.clone(), motif.clone(), Strand::Positive))
-                    .cloned()
-                    .unwrap_or(0)
-                    .clone();
-                let motif_occurences_rev = self
-                    .motif_occurence_totals
-                    .get(&(contig_id.clone(), motif.clone(), Strand::Negative))
-                    .cloned()
-                    .unwrap_or(0)
-                    .clone();
+                    .get(&(contig_id.clone(), motif.clone()))
+                    .copied()
+                    .unwrap_or(0);
 
-                let motif_occurence_totals = motif_occurences_fwd + motif_occurences_rev;
+                let filtered_counts = self.get_filtered_counts(&contig_id, &motif);
 
                 MedianMotifMethylationDegree {
                     contig: contig_id,
@@ -331,103 +715,955 @@ impl MotifMethylationPositions {
                     mean_read_cov,
                     n_motif_obs: coverages.len() as u32,
                     motif_occurences_total: motif_occurence_totals,
+                    n_filtered_low_cov: filtered_counts.n_filtered_low_cov,
+                    n_filtered_diff: filtered_counts.n_filtered_diff,
                 }
             })
             .collect()
     }
 
-    pub fn to_weighted_mean_degress(&self) -> Vec<WeightedMeanMotifMethylationDegree> {
+    /// Median and weighted mean per contig-motif, computed from the same grouping of the
+    /// positions. The values equal those of [`Self::to_median_degrees`] and
+    /// [`Self::to_weighted_mean_degress`].
+    pub fn to_summary(&self) -> Vec<MotifMethylationSummary> {
         self.group_by_motif()
             .into_iter()
             .map(|((contig_id, motif), coverages)| {
-                let fraction_weight = coverages
-                    .iter()
-                    .map(|cov| cov.fraction_modified() * cov.get_n_valid_cov() as f64)
-                    .sum::<f64>();
-
-                let total_weights = coverages
-                    .iter()
-                    .map(|cov| cov.get_n_valid_cov())
-                    .sum::<u32>();
-
-                let weighted_mean = fraction_weight / total_weights as f64;
+                let motif_occurence_totals = self
+                    .motif_occurence_totals
+                    .get(&(contig_id.clone(), motif.clone()))
+                    .copied()
+                    .unwrap_or(0);
 
-                let mean_read_cov = {
-                    let total_cov: u64 = coverages
-                        .iter()
-                        .map(|cov| cov.get_n_valid_cov() as u64)
-                        .sum();
+                MotifMethylationSummary {
+                    contig: contig_id,
+                    motif,
+                    median: median_fraction(&coverages),
+                    weighted_mean: weighted_mean_fraction(&coverages),
+                    mean_read_cov: mean_read_cov(&coverages),
+                    n_motif_obs: coverages.len() as u32,
+                    motif_occurences_total: motif_occurence_totals,
+                }
+            })
+            .collect()
+    }
 
-                    total_cov as f64 / coverages.len() as f64
-                };
+    /// Methylation per contig-motif as the median of the per position fractions, each
+    /// weighted by its valid coverage. High coverage positions dominate like in the
+    /// weighted mean, but a few outlying positions cannot pull the value away.
+    ///
+    /// With equal coverages this is the median of [`Self::to_median_degrees`].
+    pub fn to_weighted_median_degrees(&self) -> Vec<WeightedMedianMotifMethylationDegree> {
+        self.group_by_motif()
+            .into_iter()
+            .map(|((contig_id, motif), coverages)| {
+                let w_median = weighted_median_fraction(&coverages);
+                let mean_read_cov = mean_read_cov(&coverages);
 
-                let motif_occurences_fwd = self
-                    .motif_occurence_totals
-                    .get(&(contig_id.clone(), motif.clone(), Strand::Positive))
-                    .cloned()
-                    .unwrap_or(0)
-                    .clone();
-                let motif_occurences_rev = self
+                let motif_occurence_totals = self
                     .motif_occurence_totals
-                    .get(&(contig_id.clone(), motif.clone(), Strand::Negative))
-                    .cloned()
-                    .unwrap_or(0)
-                    .clone();
-                let motif_occurence_totals = motif_occurences_fwd + motif_occurences_rev;
+                    .get(&(contig_id.clone(), motif.clone()))
+                    .copied()
+                    .unwrap_or(0);
 
-                WeightedMeanMotifMethylationDegree {
+                let filtered_counts = self.get_filtered_counts(&contig_id, &motif);
+
+                WeightedMedianMotifMethylationDegree {
                     contig: contig_id,
                     motif,
-                    w_mean: weighted_mean,
+                    w_median,
                     mean_read_cov,
                     n_motif_obs: coverages.len() as u32,
                     motif_occurences_total: motif_occurence_totals,
+                    n_filtered_low_cov: filtered_counts.n_filtered_low_cov,
+                    n_filtered_diff: filtered_counts.n_filtered_diff,
                 }
             })
             .collect()
     }
-}
 
-#[derive(Debug, Clone, ValueEnum)]
-#[cfg_attr(feature = "python", pyo3::pyclass(module = "epymetheus"))]
-pub enum MethylationOutput {
-    Raw,
-    Median,
-    WeightedMean,
-}
+    /// Methylation per contig-motif as the mean of the per position fractions between
+    /// the 20th and 80th percentile. This is less sensitive than the median to a
+    /// degenerate motif tipping the result to 0 or 1.
+    ///
+    /// With fewer than [`TRIMMED_MEAN_MIN_OBS`] observations the plain mean is used
+    /// and `trim_applied` is false.
+    pub fn to_trimmed_mean_degrees(&self) -> Vec<TrimmedMeanMotifMethylationDegree> {
+        self.group_by_motif()
+            .into_iter()
+            .map(|((contig_id, motif), coverages)| {
+                let (trimmed_mean, trim_applied) = trimmed_mean_fraction(&coverages);
+                let mean_read_cov = mean_read_cov(&coverages);
 
-impl ToString for MethylationOutput {
-    fn to_string(&self) -> String {
-        match self {
-            Self::Raw => "raw".to_string(),
-            Self::Median => "median".to_string(),
-            Self::WeightedMean => "weighted_mean".to_string(),
-        }
-    }
-}
+                let motif_occurence_totals = self
+                    .motif_occurence_totals
+                    .get(&(contig_id.clone(), motif.clone()))
+                    .copied()
+                    .unwrap_or(0);
 
-impl FromStr for MethylationOutput {
-    type Err = String;
+                let filtered_counts = self.get_filtered_counts(&contig_id, &motif);
 
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "raw" => Ok(Self::Raw),
-            "median" => Ok(Self::Median),
-            "weighted_mean" => Ok(Self::WeightedMean),
-            _ => Err(format!("Invalid output type: {}", s)),
-        }
+                TrimmedMeanMotifMethylationDegree {
+                    contig: contig_id,
+                    motif,
+                    trimmed_mean,
+                    mean_read_cov,
+                    n_motif_obs: coverages.len() as u32,
+                    motif_occurences_total: motif_occurence_totals,
+                    n_filtered_low_cov: filtered_counts.n_filtered_low_cov,
+                    n_filtered_diff: filtered_counts.n_filtered_diff,
+                    trim_applied,
+                }
+            })
+            .collect()
     }
-}
 
-#[cfg(feature = "python")]
-#[pyo3::pymethods]
-impl MethylationOutput {
+    /// Methylation per contig-motif as the fraction of positions called methylated.
+    ///
+    /// Each position is called methylated when its fraction of modified reads is at or
+    /// above `call_threshold`. Unlike the median, mean and weighted mean, which
+    /// summarise the continuous fractions, this is a methylation frequency over
+    /// discrete calls: a contig with all positions at 0.6 gets 1 with a threshold of
+    /// 0.5, not 0.6.
+    pub fn to_called_frequency_degrees(
+        &self,
+        call_threshold: f64,
+    ) -> Vec<CalledFrequencyMotifMethylationDegree> {
+        self.group_by_motif()
+            .into_iter()
+            .map(|((contig_id, motif), coverages)| {
+                let n_called_methylated = coverages
+                    .iter()
+                    .filter(|coverage| coverage.fraction_modified() >= call_threshold)
+                    .count() as u32;
+                let mean_read_cov = mean_read_cov(&coverages);
+
+                let motif_occurence_totals = self
+                    .motif_occurence_totals
+                    .get(&(contig_id.clone(), motif.clone()))
+                    .copied()
+                    .unwrap_or(0);
+
+                let filtered_counts = self.get_filtered_counts(&contig_id, &motif);
+
+                CalledFrequencyMotifMethylationDegree {
+                    contig: contig_id,
+                    motif,
+                    called_frequency: n_called_methylated as f64 / coverages.len() as f64,
+                    mean_read_cov,
+                    n_motif_obs: coverages.len() as u32,
+                    motif_occurences_total: motif_occurence_totals,
+                    n_filtered_low_cov: filtered_counts.n_filtered_low_cov,
+                    n_filtered_diff: filtered_counts.n_filtered_diff,
+                    n_called_methylated,
+                }
+            })
+            .collect()
+    }
+
+    /// The pattern of `output_type`. `call_threshold` is only used by
+    /// [`MethylationOutput::CalledFrequency`] (see [`Self::to_called_frequency_degrees`]).
+    pub fn into_variant(
+        self,
+        output_type: &MethylationOutput,
+        call_threshold: f64,
+    ) -> MethylationPatternVariant {
+        match output_type {
+            MethylationOutput::Raw => MethylationPatternVariant::Raw(self),
+            MethylationOutput::Median => MethylationPatternVariant::Median(self.to_median_degrees()),
+            MethylationOutput::WeightedMean => {
+                MethylationPatternVariant::WeightedMean(self.to_weighted_mean_degress())
+            }
+            MethylationOutput::WeightedMedian => {
+                MethylationPatternVariant::WeightedMedian(self.to_weighted_median_degrees())
+            }
+            MethylationOutput::TrimmedMean => {
+                MethylationPatternVariant::TrimmedMean(self.to_trimmed_mean_degrees())
+            }
+            MethylationOutput::CalledFrequency => MethylationPatternVariant::CalledFrequency(
+                self.to_called_frequency_degrees(call_threshold),
+            ),
+            MethylationOutput::StrandAsymmetry => {
+                MethylationPatternVariant::StrandAsymmetry(self.to_strand_asymmetry_degrees())
+            }
+        }
+    }
+
+    /// Reshapes the raw methylation to one row per motif occurrence with the plus and
+    /// minus strand counts side by side.
+    ///
+    /// Rows are keyed by the 0-based start of the motif occurrence on the reference,
+    /// so both strands of a palindromic site share a row. A strand without
+    /// methylation at the occurrence is zero-filled.
+    pub fn to_strand_wide(&self) -> Vec<StrandWidePosition> {
+        let mut rows: AHashMap<(ContigId, Motif, ContigPosition), StrandWidePosition> =
+            AHashMap::new();
+
+        for ((contig_id, motif, position, strand), coverage) in &self.methylation {
+            let start = motif_occurence_start(motif, *position, strand);
+
+            let row = rows
+                .entry((contig_id.clone(), motif.clone(), start))
+                .or_insert_with(|| StrandWidePosition {
+                    contig: contig_id.clone(),
+                    start,
+                    motif: motif.clone(),
+                    n_modified_plus: 0,
+                    n_valid_cov_plus: 0,
+                    n_modified_minus: 0,
+                    n_valid_cov_minus: 0,
+                });
+
+            match strand {
+                Strand::Positive => {
+                    row.n_modified_plus = coverage.get_n_modified();
+                    row.n_valid_cov_plus = coverage.get_n_valid_cov();
+                }
+                Strand::Negative => {
+                    row.n_modified_minus = coverage.get_n_modified();
+                    row.n_valid_cov_minus = coverage.get_n_valid_cov();
+                }
+            }
+        }
+
+        rows.into_values().collect()
+    }
+
+    /// Median methylation per strand for each contig-motif.
+    ///
+    /// `bias` is the absolute difference between the plus and minus strand medians.
+    /// Contig-motifs without observations on both strands are not reported.
+    pub fn to_strand_bias(&self, bias_threshold: f64) -> Vec<StrandBiasDegree> {
+        let mut grouped: AHashMap<
+            (ContigId, Motif),
+            (Vec<&MethylationCoverage>, Vec<&MethylationCoverage>),
+        > = AHashMap::new();
+
+        for ((contig_id, motif, _position, strand), coverage) in &self.methylation {
+            let (plus, minus) = grouped
+                .entry((contig_id.clone(), motif.clone()))
+                .or_default();
+            match strand {
+                Strand::Positive => plus.push(coverage),
+                Strand::Negative => minus.push(coverage),
+            }
+        }
+
+        grouped
+            .into_iter()
+            .filter(|(_, (plus, minus))| !plus.is_empty() && !minus.is_empty())
+            .map(|((contig_id, motif), (plus, minus))| {
+                let methylation_plus = median_fraction(&plus);
+                let methylation_minus = median_fraction(&minus);
+                let bias = (methylation_plus - methylation_minus).abs();
+
+                StrandBiasDegree {
+                    contig: contig_id,
+                    motif,
+                    methylation_plus,
+                    methylation_minus,
+                    bias,
+                    n_motif_obs_plus: plus.len() as u32,
+                    n_motif_obs_minus: minus.len() as u32,
+                    biased: bias > bias_threshold,
+                }
+            })
+            .collect()
+    }
+
+    /// Mean methylation difference between the strands of each palindromic
+    /// contig-motif.
+    ///
+    /// A site is a motif occurrence with methylation observed on both strands. Its
+    /// asymmetry is `|plus - minus|` of the strand fractions, and the per
+    /// contig-motif values are means over the sites. Only for palindromic motifs do
+    /// both strands represent the same site, so other motifs are skipped, as are
+    /// contig-motifs without any site.
+    pub fn to_strand_asymmetry_degrees(&self) -> Vec<StrandAsymmetryDegree> {
+        let mut sites: BTreeMap<(&ContigId, &Motif, ContigPosition), (Option<f64>, Option<f64>)> =
+            BTreeMap::new();
+        for ((contig_id, motif, position, strand), coverage) in &self.methylation {
+            if !motif.is_palindromic() || coverage.get_n_valid_cov() == 0 {
+                continue;
+            }
+            let start = motif_occurence_start(motif, *position, strand);
+            let (plus, minus) = sites.entry((contig_id, motif, start)).or_default();
+            match strand {
+                Strand::Positive => *plus = Some(coverage.fraction_modified()),
+                Strand::Negative => *minus = Some(coverage.fraction_modified()),
+            }
+        }
+
+        // Sites are visited in sorted order, so the sums are bit-identical across runs.
+        let mut grouped: BTreeMap<(&ContigId, &Motif), Vec<(f64, f64)>> = BTreeMap::new();
+        for ((contig_id, motif, _start), strands) in sites {
+            if let (Some(plus), Some(minus)) = strands {
+                grouped
+                    .entry((contig_id, motif))
+                    .or_default()
+                    .push((plus, minus));
+            }
+        }
+
+        grouped
+            .into_iter()
+            .map(|((contig_id, motif), site_fractions)| {
+                let n_sites = site_fractions.len() as f64;
+                let mean = |value: fn(&(f64, f64)) -> f64| {
+                    site_fractions.iter().map(value).sum::<f64>() / n_sites
+                };
+
+                StrandAsymmetryDegree {
+                    contig: contig_id.clone(),
+                    motif: motif.clone(),
+                    methylation_plus: mean(|(plus, _)| *plus),
+                    methylation_minus: mean(|(_, minus)| *minus),
+                    mean_asymmetry: mean(|(plus, minus)| (plus - minus).abs()),
+                    n_sites: site_fractions.len() as u32,
+                }
+            })
+            .collect()
+    }
+
+    /// Genome-wide summary per motif.
+    ///
+    /// `methylation_fraction` pools the counts of all positions of the motif
+    /// (`sum(n_modified) / sum(n_valid_cov)`). A contig counts as methylated when its
+    /// per-contig methylation, the median with [`MethylationOutput::Median`], the
+    /// weighted median with [`MethylationOutput::WeightedMedian`], the trimmed mean
+    /// with [`MethylationOutput::TrimmedMean`] and the weighted mean otherwise, is above
+    /// `methylated_threshold`.
+    ///
+    /// Every motif in `motifs` is reported, with zeros when it has no observations.
+    pub fn to_motif_report(
+        &self,
+        motifs: &[Motif],
+        methylated_threshold: f64,
+        output_type: &MethylationOutput,
+    ) -> Vec<MotifReportRow> {
+        let contig_methylation: fn(&[&MethylationCoverage]) -> f64 = match output_type {
+            MethylationOutput::Median => median_fraction,
+            MethylationOutput::WeightedMedian => weighted_median_fraction,
+            MethylationOutput::TrimmedMean => |coverages| trimmed_mean_fraction(coverages).0,
+            MethylationOutput::WeightedMean
+            | MethylationOutput::CalledFrequency
+            | MethylationOutput::Raw
+            | MethylationOutput::StrandAsymmetry => weighted_mean_fraction,
+        };
+
+        let mut rows: BTreeMap<Motif, MotifReportRow> = motifs
+            .iter()
+            .map(|motif| (motif.clone(), MotifReportRow::empty(motif.clone())))
+            .collect();
+        let mut pooled: BTreeMap<Motif, (u64, u64)> = BTreeMap::new();
+
+        for ((_contig_id, motif), n_occurences) in &self.motif_occurence_totals {
+            rows.entry(motif.clone())
+                .or_insert_with(|| MotifReportRow::empty(motif.clone()))
+                .motif_occurences_total += *n_occurences as u64;
+        }
+
+        let mut grouped: Vec<_> = self.group_by_motif().into_iter().collect();
+        grouped.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        for ((_contig_id, motif), coverages) in grouped {
+            let row = rows
+                .entry(motif.clone())
+                .or_insert_with(|| MotifReportRow::empty(motif.clone()));
+            row.n_motif_obs += coverages.len() as u64;
+            row.n_contigs_observed += 1;
+            if contig_methylation(&coverages) > methylated_threshold {
+                row.n_contigs_methylated += 1;
+            }
+
+            let (n_modified, n_valid_cov) = pooled.entry(motif).or_default();
+            for coverage in coverages {
+                *n_modified += coverage.get_n_modified() as u64;
+                *n_valid_cov += coverage.get_n_valid_cov() as u64;
+            }
+        }
+
+        for (motif, (n_modified, n_valid_cov)) in pooled {
+            if n_valid_cov > 0 {
+                if let Some(row) = rows.get_mut(&motif) {
+                    row.methylation_fraction = n_modified as f64 / n_valid_cov as f64;
+                }
+            }
+        }
+
+        rows.into_values().collect()
+    }
+
+    /// Counts the observed sites of each contig-motif with a methylation fraction at
+    /// or above `methylated_threshold` and below it.
+    ///
+    /// Each position and strand is a site. Rows are sorted by contig and motif.
+    pub fn to_site_counts(&self, methylated_threshold: f64) -> Vec<MotifSiteCounts> {
+        let mut counts: Vec<MotifSiteCounts> = self
+            .group_by_motif()
+            .into_iter()
+            .map(|((contig_id, motif), coverages)| {
+                let n_methylated = coverages
+                    .iter()
+                    .filter(|coverage| coverage.fraction_modified() >= methylated_threshold)
+                    .count() as u32;
+
+                MotifSiteCounts {
+                    contig: contig_id,
+                    motif,
+                    n_methylated,
+                    n_unmethylated: coverages.len() as u32 - n_methylated,
+                }
+            })
+            .collect();
+
+        counts.sort_unstable_by(|a, b| (&a.contig, &a.motif).cmp(&(&b.contig, &b.motif)));
+        counts
+    }
+
+    /// Joins every motif site with its methylation record.
+    ///
+    /// `sites` are all matches of the motifs in the assembly, see
+    /// [`crate::algorithms::methylation_pattern::find_motif_sites`]. Sites without a
+    /// record are kept with an exclusion reason. Rows are sorted by contig, position,
+    /// strand and motif.
+    pub fn to_motif_matches(
+        &self,
+        sites: Vec<(ContigId, Motif, ContigPosition, Strand)>,
+    ) -> Vec<MotifMatch> {
+        let mut matches: Vec<MotifMatch> = sites
+            .into_iter()
+            .map(|key| {
+                let coverage = self.methylation.get(&key).cloned();
+                let (contig, motif, position, strand) = key;
+                MotifMatch {
+                    contig,
+                    motif,
+                    position,
+                    strand,
+                    coverage,
+                }
+            })
+            .collect();
+
+        matches.sort_unstable_by(|a, b| {
+            (&a.contig, a.position, a.strand, &a.motif).cmp(&(
+                &b.contig,
+                b.position,
+                b.strand,
+                &b.motif,
+            ))
+        });
+        matches
+    }
+
+    /// Pools the positions of all contigs in a group and computes the methylation per
+    /// group and motif with the statistic of `output_type`.
+    ///
+    /// Contigs without a group are left out. `n_contigs` is the number of contigs in
+    /// the group with observations of the motif.
+    pub fn to_group_degrees(
+        &self,
+        contig_groups: &AHashMap<ContigId, String>,
+        output_type: &MethylationOutput,
+    ) -> Result<Vec<GroupMotifMethylationDegree>> {
+        let methylation_value: fn(&[&MethylationCoverage]) -> f64 = match output_type {
+            MethylationOutput::Median => median_fraction,
+            MethylationOutput::WeightedMean => weighted_mean_fraction,
+            MethylationOutput::WeightedMedian => weighted_median_fraction,
+            MethylationOutput::TrimmedMean => |coverages| trimmed_mean_fraction(coverages).0,
+            MethylationOutput::Raw
+            | MethylationOutput::CalledFrequency
+            | MethylationOutput::StrandAsymmetry => {
+                bail!(
+                    "Contig groups require the median, weighted-mean, weighted-median or trimmed-mean output type"
+                )
+            }
+        };
+
+        let mut grouped: AHashMap<
+            (String, Motif),
+            (Vec<&MethylationCoverage>, AHashSet<&ContigId>),
+        > = AHashMap::new();
+        for ((contig_id, motif, _position, _strand), coverage) in self.sorted_methylation() {
+            let Some(group) = contig_groups.get(contig_id) else {
+                continue;
+            };
+            let (coverages, contigs) = grouped
+                .entry((group.clone(), motif.clone()))
+                .or_default();
+            coverages.push(coverage);
+            contigs.insert(contig_id);
+        }
+
+        let mut occurence_totals: AHashMap<(String, Motif), u32> = AHashMap::new();
+        for ((contig_id, motif), n_occurences) in &self.motif_occurence_totals {
+            if let Some(group) = contig_groups.get(contig_id) {
+                *occurence_totals
+                    .entry((group.clone(), motif.clone()))
+                    .or_insert(0) += n_occurences;
+            }
+        }
+
+        Ok(grouped
+            .into_iter()
+            .map(|((group, motif), (coverages, contigs))| {
+                let motif_occurences_total = occurence_totals
+                    .get(&(group.clone(), motif.clone()))
+                    .cloned()
+                    .unwrap_or(0);
+
+                GroupMotifMethylationDegree {
+                    group,
+                    motif,
+                    methylation_value: methylation_value(&coverages),
+                    mean_read_cov: mean_read_cov(&coverages),
+                    n_motif_obs: coverages.len() as u32,
+                    motif_occurences_total,
+                    n_contigs: contigs.len() as u32,
+                }
+            })
+            .collect())
+    }
+
+    /// Methylation per motif in sliding windows along each contig.
+    ///
+    /// Windows start at 0 and every `window_step` bp after that and span
+    /// `window_size` bp, truncated at the contig end. A position counts towards every
+    /// window it falls in and both strands are pooled. The statistic follows
+    /// `output_type`. Windows without observations get `methylation: None`.
+    ///
+    /// Windows are created for every contig-motif with methylation or motif
+    /// occurrences, using the lengths in `contig_lengths`. Contigs without a length
+    /// are left out.
+    pub fn to_window_degrees(
+        &self,
+        contig_lengths: &AHashMap<ContigId, usize>,
+        window_size: usize,
+        window_step: usize,
+        output_type: &MethylationOutput,
+    ) -> Result<Vec<WindowMethylationDegree>> {
+        if window_size == 0 || window_step == 0 {
+            bail!("Window size and step should be at least 1");
+        }
+        let methylation_value: fn(&[&MethylationCoverage]) -> f64 = match output_type {
+            MethylationOutput::Median => median_fraction,
+            MethylationOutput::WeightedMean => weighted_mean_fraction,
+            MethylationOutput::WeightedMedian => weighted_median_fraction,
+            MethylationOutput::TrimmedMean => |coverages| trimmed_mean_fraction(coverages).0,
+            MethylationOutput::Raw
+            | MethylationOutput::CalledFrequency
+            | MethylationOutput::StrandAsymmetry => {
+                bail!(
+                    "Windows require the median, weighted-mean, weighted-median or trimmed-mean output type"
+                )
+            }
+        };
+
+        let mut grouped: AHashMap<(ContigId, Motif), Vec<(ContigPosition, &MethylationCoverage)>> =
+            AHashMap::new();
+        for (contig_id, motif) in self.motif_occurence_totals.keys() {
+            grouped
+                .entry((contig_id.clone(), motif.clone()))
+                .or_default();
+        }
+        // Pushed in sorted order, so the positions are sorted for the partition points.
+        for ((contig_id, motif, position, _strand), coverage) in self.sorted_methylation() {
+            grouped
+                .entry((contig_id.clone(), motif.clone()))
+                .or_default()
+                .push((*position, coverage));
+        }
+
+        let mut degrees = Vec::new();
+        for ((contig_id, motif), positions) in grouped {
+            let Some(&contig_length) = contig_lengths.get(&contig_id) else {
+                continue;
+            };
+
+            for window_start in (0..contig_length).step_by(window_step) {
+                let window_end = (window_start + window_size).min(contig_length);
+                let first = positions.partition_point(|(position, _)| *position < window_start);
+                let last = positions.partition_point(|(position, _)| *position < window_end);
+                let coverages: Vec<&MethylationCoverage> = positions[first..last]
+                    .iter()
+                    .map(|(_, coverage)| *coverage)
+                    .collect();
+
+                degrees.push(WindowMethylationDegree {
+                    contig: contig_id.clone(),
+                    window_start,
+                    window_end,
+                    motif: motif.clone(),
+                    methylation: (!coverages.is_empty()).then(|| methylation_value(&coverages)),
+                    n_obs: coverages.len() as u32,
+                });
+
+                if window_end == contig_length {
+                    break;
+                }
+            }
+        }
+
+        Ok(degrees)
+    }
+
+    pub fn to_weighted_mean_degress(&self) -> Vec<WeightedMeanMotifMethylationDegree> {
+        self.group_by_motif()
+            .into_iter()
+            .map(|((contig_id, motif), coverages)| {
+                let weighted_mean = weighted_mean_fraction(&coverages);
+                let mean_read_cov = mean_read_cov(&coverages);
+                let n_valid_cov_total = coverages
+                    .iter()
+                    .map(|cov| cov.get_n_valid_cov() as u64)
+                    .sum();
+
+                let motif_occurence_totals = self
+                    .motif_occurence_totals
+                    .get(&(contig_id.clone(), motif.clone()))
+                    .copied()
+                    .unwrap_or(0);
+
+                let filtered_counts = self.get_filtered_counts(&contig_id, &motif);
+
+                WeightedMeanMotifMethylationDegree {
+                    contig: contig_id,
+                    motif,
+                    w_mean: weighted_mean,
+                    stderr: weighted_mean_stderr(weighted_mean, n_valid_cov_total),
+                    mean_read_cov,
+                    n_motif_obs: coverages.len() as u32,
+                    motif_occurences_total: motif_occurence_totals,
+                    n_filtered_low_cov: filtered_counts.n_filtered_low_cov,
+                    n_filtered_diff: filtered_counts.n_filtered_diff,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Standard error of a weighted mean methylation `p` computed from pooled counts.
+///
+/// The weighted mean equals the pooled fraction `sum(n_modified) / n`, with `n` the
+/// pooled valid coverage `sum(n_valid_cov)` over the motif positions. The standard
+/// error is the binomial `sqrt(p * (1 - p) / n)`.
+pub fn weighted_mean_stderr(p: f64, n_valid_cov_total: u64) -> f64 {
+    (p * (1.0 - p) / n_valid_cov_total as f64).sqrt()
+}
+
+fn weighted_mean_fraction(coverages: &[&MethylationCoverage]) -> f64 {
+    let fraction_weight = coverages
+        .iter()
+        .map(|cov| cov.fraction_modified() * cov.get_n_valid_cov() as f64)
+        .sum::<f64>();
+
+    let total_weights = coverages
+        .iter()
+        .map(|cov| cov.get_n_valid_cov())
+        .sum::<u32>();
+
+    fraction_weight / total_weights as f64
+}
+
+fn mean_read_cov(coverages: &[&MethylationCoverage]) -> f64 {
+    let total_cov: u64 = coverages
+        .iter()
+        .map(|cov| cov.get_n_valid_cov() as u64)
+        .sum();
+
+    total_cov as f64 / coverages.len() as f64
+}
+
+fn median_fraction(coverages: &[&MethylationCoverage]) -> f64 {
+    let mut fractions: Vec<f64> = coverages
+        .iter()
+        .map(|cov| cov.fraction_modified())
+        .collect();
+
+    fractions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    if fractions.len() % 2 == 0 {
+        let mid = fractions.len() / 2;
+        (fractions[mid - 1] + fractions[mid]) / 2.0
+    } else {
+        fractions[fractions.len() / 2]
+    }
+}
+
+/// Median of the fractions weighted by the valid coverage: the smallest fraction at
+/// which the cumulative coverage of the sorted fractions reaches half of the total.
+/// When it is exactly half, the mean of that and the next fraction is used, so equal
+/// coverages give the plain median.
+fn weighted_median_fraction(coverages: &[&MethylationCoverage]) -> f64 {
+    let mut weighted: Vec<(f64, u64)> = coverages
+        .iter()
+        .map(|cov| (cov.fraction_modified(), cov.get_n_valid_cov() as u64))
+        .collect();
+
+    weighted.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap());
+
+    let total: u64 = weighted.iter().map(|(_, weight)| weight).sum();
+    if total == 0 {
+        return f64::NAN;
+    }
+
+    // Compare twice the cumulative coverage against the total to stay in integers.
+    let mut cumulative = 0;
+    for (i, (fraction, weight)) in weighted.iter().enumerate() {
+        cumulative += weight;
+        if 2 * cumulative == total && *weight > 0 {
+            let next = weighted[i + 1..]
+                .iter()
+                .find(|(_, weight)| *weight > 0)
+                .map(|(fraction, _)| *fraction)
+                .unwrap_or(*fraction);
+            return (fraction + next) / 2.0;
+        }
+        if 2 * cumulative > total {
+            return *fraction;
+        }
+    }
+
+    f64::NAN
+}
+
+/// Call threshold of [`MethylationOutput::CalledFrequency`] where none is given. See
+/// [`MotifMethylationPositions::to_called_frequency_degrees`].
+pub const DEFAULT_CALL_THRESHOLD: f64 = 0.5;
+
+/// Fewest observations for which the trimmed mean trims. See
+/// [`MotifMethylationPositions::to_trimmed_mean_degrees`].
+pub const TRIMMED_MEAN_MIN_OBS: usize = 5;
+
+/// Mean of the fractions between the 20th and 80th percentile (inclusive, linear
+/// interpolation between ranks), and whether trimming was applied. With fewer than
+/// [`TRIMMED_MEAN_MIN_OBS`] coverages this is the plain mean.
+fn trimmed_mean_fraction(coverages: &[&MethylationCoverage]) -> (f64, bool) {
+    let mut fractions: Vec<f64> = coverages
+        .iter()
+        .map(|cov| cov.fraction_modified())
+        .collect();
+
+    if fractions.len() < TRIMMED_MEAN_MIN_OBS {
+        let mean = fractions.iter().sum::<f64>() / fractions.len() as f64;
+        return (mean, false);
+    }
+
+    fractions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let lower = percentile(&fractions, 0.2);
+    let upper = percentile(&fractions, 0.8);
+
+    let kept: Vec<f64> = fractions
+        .into_iter()
+        .filter(|fraction| *fraction >= lower && *fraction <= upper)
+        .collect();
+    (kept.iter().sum::<f64>() / kept.len() as f64, true)
+}
+
+/// Percentile `q` in [0, 1] of sorted values, interpolating linearly between ranks.
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    let rank = q * (sorted.len() - 1) as f64;
+    let below = rank.floor() as usize;
+    let above = rank.ceil() as usize;
+    sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64)
+}
+
+#[derive(PartialEq, Clone, PartialOrd)]
+pub struct GroupMotifMethylationDegree {
+    pub group: String,
+    pub motif: Motif,
+    pub methylation_value: f64,
+    pub mean_read_cov: f64,
+    pub n_motif_obs: u32,
+    pub motif_occurences_total: u32,
+    pub n_contigs: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WindowMethylationDegree {
+    pub contig: String,
+    pub window_start: ContigPosition,
+    pub window_end: ContigPosition,
+    pub motif: Motif,
+    pub methylation: Option<f64>,
+    pub n_obs: u32,
+}
+
+#[derive(PartialEq, Clone, PartialOrd)]
+pub struct StrandBiasDegree {
+    pub contig: String,
+    pub motif: Motif,
+    pub methylation_plus: f64,
+    pub methylation_minus: f64,
+    pub bias: f64,
+    pub n_motif_obs_plus: u32,
+    pub n_motif_obs_minus: u32,
+    pub biased: bool,
+}
+
+/// Methylated and unmethylated site counts of a motif in a contig. See
+/// [`MotifMethylationPositions::to_site_counts`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct MotifSiteCounts {
+    pub contig: String,
+    pub motif: Motif,
+    pub n_methylated: u32,
+    pub n_unmethylated: u32,
+}
+
+/// A motif site in the assembly and its methylation record, if one passed the filters.
+/// See [`MotifMethylationPositions::to_motif_matches`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct MotifMatch {
+    pub contig: String,
+    pub motif: Motif,
+    pub position: ContigPosition,
+    pub strand: Strand,
+    pub coverage: Option<MethylationCoverage>,
+}
+
+impl MotifMatch {
+    pub fn had_record(&self) -> bool {
+        self.coverage.is_some()
+    }
+
+    /// Why the site is not in the methylation pattern. Records below
+    /// `--min-valid-read-coverage` or `--min-valid-cov-to-diff-fraction` are dropped
+    /// while reading the pileup, so they can not be told apart from missing records.
+    pub fn exclusion_reason(&self) -> Option<&'static str> {
+        match self.coverage {
+            Some(_) => None,
+            None => Some("no_passing_record"),
+        }
+    }
+}
+
+/// Strand asymmetry of a palindromic motif in a contig. See
+/// [`MotifMethylationPositions::to_strand_asymmetry_degrees`].
+#[derive(PartialEq, Clone, PartialOrd)]
+pub struct StrandAsymmetryDegree {
+    pub contig: String,
+    pub motif: Motif,
+    pub methylation_plus: f64,
+    pub methylation_minus: f64,
+    pub mean_asymmetry: f64,
+    pub n_sites: u32,
+}
+
+impl StrandAsymmetryDegree {
+    pub fn get_contig(&self) -> &str {
+        self.contig.as_str()
+    }
+
+    /// The methylation and asymmetry columns are written in `value_scale`.
+    pub fn to_csv_line(&self, delim: char, value_scale: ValueScale) -> String {
+        let value = |fraction: f64| value_scale.format(fraction);
+
+        format!(
+            "{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}{delim}{}",
+            self.contig,
+            self.motif.sequence_to_string(),
+            self.motif.mod_type.to_pileup_code(),
+            self.motif.mod_position,
+            value(self.methylation_plus),
+            value(self.methylation_minus),
+            value(self.mean_asymmetry),
+            self.n_sites,
+        )
+    }
+}
+
+/// Genome-wide summary of a motif. See [`MotifMethylationPositions::to_motif_report`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct MotifReportRow {
+    pub motif: Motif,
+    pub motif_occurences_total: u64,
+    pub n_motif_obs: u64,
+    pub methylation_fraction: f64,
+    pub n_contigs_observed: u32,
+    pub n_contigs_methylated: u32,
+}
+
+impl MotifReportRow {
+    fn empty(motif: Motif) -> Self {
+        Self {
+            motif,
+            motif_occurences_total: 0,
+            n_motif_obs: 0,
+            methylation_fraction: 0.0,
+            n_contigs_observed: 0,
+            n_contigs_methylated: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StrandWidePosition {
+    pub contig: String,
+    pub start: ContigPosition,
+    pub motif: Motif,
+    pub n_modified_plus: u32,
+    pub n_valid_cov_plus: u32,
+    pub n_modified_minus: u32,
+    pub n_valid_cov_minus: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(feature = "python", pyo3::pyclass(module = "epymetheus"))]
+pub enum MethylationOutput {
+    Raw,
+    Median,
+    WeightedMean,
+    WeightedMedian,
+    TrimmedMean,
+    CalledFrequency,
+    StrandAsymmetry,
+}
+
+impl ToString for MethylationOutput {
+    fn to_string(&self) -> String {
+        match self {
+            Self::Raw => "raw".to_string(),
+            Self::Median => "median".to_string(),
+            Self::WeightedMean => "weighted_mean".to_string(),
+            Self::WeightedMedian => "weighted_median".to_string(),
+            Self::TrimmedMean => "trimmed_mean".to_string(),
+            Self::CalledFrequency => "called_frequency".to_string(),
+            Self::StrandAsymmetry => "strand_asymmetry".to_string(),
+        }
+    }
+}
+
+impl FromStr for MethylationOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "raw" => Ok(Self::Raw),
+            "median" => Ok(Self::Median),
+            "weighted_mean" => Ok(Self::WeightedMean),
+            "weighted_median" => Ok(Self::WeightedMedian),
+            "trimmed_mean" => Ok(Self::TrimmedMean),
+            "called_frequency" => Ok(Self::CalledFrequency),
+            "strand_asymmetry" => Ok(Self::StrandAsymmetry),
+            _ => Err(format!("Invalid output type: {}", s)),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pyo3::pymethods]
+impl MethylationOutput {
     fn __reduce__(&self) -> pyo3::PyResult<(pyo3::PyObject, (String,))> {
         pyo3::Python::with_gil(|py| {
             let state = match self {
                 MethylationOutput::Median => "Median".to_string(),
                 MethylationOutput::WeightedMean => "WeightedMean".to_string(),
+                MethylationOutput::WeightedMedian => "WeightedMedian".to_string(),
+                MethylationOutput::TrimmedMean => "TrimmedMean".to_string(),
+                MethylationOutput::CalledFrequency => "CalledFrequency".to_string(),
                 MethylationOutput::Raw => "Raw".to_string(),
+                MethylationOutput::StrandAsymmetry => "StrandAsymmetry".to_string(),
             };
             let constructor = py.get_type::<MethylationOutput>().getattr("_from_state")?;
             Ok((constructor.into_pyobject(py)?.unbind(), (state,)))
@@ -442,7 +1678,11 @@ impl MethylationOutput {
         match state.as_str() {
             "Median" => Ok(MethylationOutput::Median),
             "WeightedMean" => Ok(MethylationOutput::WeightedMean),
+            "WeightedMedian" => Ok(MethylationOutput::WeightedMedian),
+            "TrimmedMean" => Ok(MethylationOutput::TrimmedMean),
+            "CalledFrequency" => Ok(MethylationOutput::CalledFrequency),
             "Raw" => Ok(MethylationOutput::Raw),
+            "StrandAsymmetry" => Ok(MethylationOutput::StrandAsymmetry),
             _ => Err(pyo3::PyErr::new::<pyo3::exceptions::PyValueError, _>(
                 "Invalid state",
             )),
@@ -458,79 +1698,167 @@ pub enum MethylationPatternVariant {
     Raw(MotifMethylationPositions),
     Median(Vec<MedianMotifMethylationDegree>),
     WeightedMean(Vec<WeightedMeanMotifMethylationDegree>),
+    WeightedMedian(Vec<WeightedMedianMotifMethylationDegree>),
+    TrimmedMean(Vec<TrimmedMeanMotifMethylationDegree>),
+    CalledFrequency(Vec<CalledFrequencyMotifMethylationDegree>),
+    StrandAsymmetry(Vec<StrandAsymmetryDegree>),
 }
 
 impl MethylationPatternVariant {
-    pub fn write_output<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        use std::fs::File;
-        use std::io::{BufWriter, Write};
-
-        let file = File::create(path)?;
-        let mut writer = BufWriter::new(file);
+    /// The output type this pattern was calculated for.
+    pub fn output_type(&self) -> MethylationOutput {
+        match self {
+            MethylationPatternVariant::Raw(_) => MethylationOutput::Raw,
+            MethylationPatternVariant::Median(_) => MethylationOutput::Median,
+            MethylationPatternVariant::WeightedMean(_) => MethylationOutput::WeightedMean,
+            MethylationPatternVariant::WeightedMedian(_) => MethylationOutput::WeightedMedian,
+            MethylationPatternVariant::TrimmedMean(_) => MethylationOutput::TrimmedMean,
+            MethylationPatternVariant::CalledFrequency(_) => MethylationOutput::CalledFrequency,
+            MethylationPatternVariant::StrandAsymmetry(_) => MethylationOutput::StrandAsymmetry,
+        }
+    }
 
+    /// Number of rows [`Self::write_output`] writes.
+    pub fn n_rows(&self) -> usize {
         match self {
-            MethylationPatternVariant::Raw(meth_pos) => {
-                writeln!(
-                    writer,
-                    "contig\tstart\tstrand\tmotif\tmod_type\tmod_position\tn_modified\tn_valid_cov\tn_diff\tn_fail"
-                )?;
-
-                let mut sorted_entries: Vec<_> = meth_pos.methylation.iter().collect();
-                sorted_entries.sort_by_key(|((contig_id, motif, pos, strand), _)| {
-                    (contig_id.clone(), motif.clone(), *pos, strand)
-                });
+            MethylationPatternVariant::Raw(positions) => positions.methylation.len(),
+            MethylationPatternVariant::Median(degrees) => degrees.len(),
+            MethylationPatternVariant::WeightedMean(degrees) => degrees.len(),
+            MethylationPatternVariant::WeightedMedian(degrees) => degrees.len(),
+            MethylationPatternVariant::TrimmedMean(degrees) => degrees.len(),
+            MethylationPatternVariant::CalledFrequency(degrees) => degrees.len(),
+            MethylationPatternVariant::StrandAsymmetry(degrees) => degrees.len(),
+        }
+    }
 
-                for ((contig_id, motif, pos, strand), meth) in sorted_entries {
-                    writeln!(
-                        writer,
-                        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-                        contig_id,
-                        pos,
-                        strand.to_string(),
-                        motif.sequence_to_string(),
-                        motif.mod_type.to_pileup_code(),
-                        motif.mod_position,
-                        meth.get_n_modified(),
-                        meth.get_n_valid_cov(),
-                        meth.get_n_diff(),
-                        meth.get_n_fail()
-                    )?;
-                }
+    /// Splits the pattern into one pattern per motif, ordered by motif.
+    ///
+    /// Raw patterns keep the motif occurrence totals of their motif, so a motif
+    /// occurring in a contig without methylation gets an empty pattern.
+    pub fn split_by_motif(self) -> BTreeMap<Motif, MethylationPatternVariant> {
+        fn group<T>(items: Vec<T>, motif: impl Fn(&T) -> &Motif) -> BTreeMap<Motif, Vec<T>> {
+            let mut groups: BTreeMap<Motif, Vec<T>> = BTreeMap::new();
+            for item in items {
+                groups.entry(motif(&item).clone()).or_default().push(item);
             }
-            MethylationPatternVariant::Median(degrees) => {
-                writeln!(
-                    writer,
-                    "contig\tmotif\tmod_type\tmod_position\tmethylation_value\tmean_read_cov\tn_motif_obs\tmotif_occurences_total"
-                )?;
-                let mut sorted_degrees = degrees.clone();
-                sorted_degrees.sort_by(|a, b| a.partial_cmp(b).expect("Ordering failed"));
-
-                for deg in sorted_degrees {
-                    writeln!(writer, "{}", deg.to_csv_line('\t'))?;
+            groups
+        }
+        fn wrap<T>(
+            groups: BTreeMap<Motif, Vec<T>>,
+            variant: impl Fn(Vec<T>) -> MethylationPatternVariant,
+        ) -> BTreeMap<Motif, MethylationPatternVariant> {
+            groups
+                .into_iter()
+                .map(|(motif, items)| (motif, variant(items)))
+                .collect()
+        }
+
+        match self {
+            MethylationPatternVariant::Raw(positions) => {
+                let mut split: BTreeMap<Motif, MotifMethylationPositions> = BTreeMap::new();
+                let empty = || MotifMethylationPositions::new(AHashMap::new(), AHashMap::new());
+                for (key, total) in positions.motif_occurence_totals {
+                    split
+                        .entry(key.1.clone())
+                        .or_insert_with(empty)
+                        .motif_occurence_totals
+                        .insert(key, total);
                 }
-            }
-            MethylationPatternVariant::WeightedMean(degrees) => {
-                writeln!(
-                    writer,
-                    "contig\tmotif\tmod_type\tmod_position\tmethylation_value\tmean_read_cov\tn_motif_obs\tmotif_occurences_total"
-                )?;
-                let mut sorted_degrees = degrees.clone();
-                sorted_degrees.sort_by(|a, b| a.partial_cmp(b).expect("Ordering failed"));
-
-                for deg in sorted_degrees {
-                    writeln!(writer, "{}", deg.to_csv_line('\t'))?;
+                for (key, meth) in positions.methylation {
+                    split
+                        .entry(key.1.clone())
+                        .or_insert_with(empty)
+                        .methylation
+                        .insert(key, meth);
                 }
+                split
+                    .into_iter()
+                    .map(|(motif, positions)| (motif, MethylationPatternVariant::Raw(positions)))
+                    .collect()
             }
+            MethylationPatternVariant::Median(degrees) => wrap(
+                group(degrees, |d| &d.motif),
+                MethylationPatternVariant::Median,
+            ),
+            MethylationPatternVariant::WeightedMean(degrees) => wrap(
+                group(degrees, |d| &d.motif),
+                MethylationPatternVariant::WeightedMean,
+            ),
+            MethylationPatternVariant::WeightedMedian(degrees) => wrap(
+                group(degrees, |d| &d.motif),
+                MethylationPatternVariant::WeightedMedian,
+            ),
+            MethylationPatternVariant::TrimmedMean(degrees) => wrap(
+                group(degrees, |d| &d.motif),
+                MethylationPatternVariant::TrimmedMean,
+            ),
+            MethylationPatternVariant::CalledFrequency(degrees) => wrap(
+                group(degrees, |d| &d.motif),
+                MethylationPatternVariant::CalledFrequency,
+            ),
+            MethylationPatternVariant::StrandAsymmetry(degrees) => wrap(
+                group(degrees, |d| &d.motif),
+                MethylationPatternVariant::StrandAsymmetry,
+            ),
         }
+    }
 
-        writer.flush()?;
-        Ok(())
+}
+
+/// Column layout of the raw output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum RawFormat {
+    /// One row per motif position, see [`MethylationPatternVariant::write_output`].
+    #[default]
+    Tsv,
+    /// An 18 column bedMethyl pileup, see [`MethylationPatternVariant::write_bedmethyl`].
+    Bedmethyl,
+}
+
+/// File format of the methylation pattern output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Tab separated columns, see [`MethylationPatternVariant::write_output`].
+    #[default]
+    Tsv,
+    /// An array of objects, see [`MethylationPatternVariant::write_json`].
+    Json,
+}
+
+/// Background methylation subtracted from each contig-motif methylation value by
+/// [`MethylationPatternVariant::write_background_adjusted_output`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MethylationBackground {
+    /// The methylation value of a control motif in the same contig.
+    Motif(Motif),
+    /// A fixed methylation value for all contigs.
+    Value(f64),
+}
+
+/// `value - background` clamped to [0, 1].
+pub fn background_adjusted(value: f64, background: f64) -> f64 {
+    (value - background).clamp(0.0, 1.0)
+}
+
+/// 0-based start of the motif occurrence on the reference holding the modified base
+/// at `position` on `strand`.
+fn motif_occurence_start(
+    motif: &Motif,
+    position: ContigPosition,
+    strand: &Strand,
+) -> ContigPosition {
+    let mod_position = motif.mod_position as usize;
+    match strand {
+        Strand::Positive => position.saturating_sub(mod_position),
+        Strand::Negative => position.saturating_sub(motif.sequence.len() - 1 - mod_position),
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::io::writers::{methylation_pattern::output_header, reports::write_window_output};
+
     #[test]
     fn test_methylation_coverage_valid() -> Result<()> {
         // Test valid inputs
@@ -545,6 +1873,952 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_methylation_coverage_accessors() -> Result<()> {
+        let coverage = MethylationCoverage::new(5, 10, 0, 2, 3)?;
+        assert_eq!(coverage.get_n_modified(), 5);
+        assert_eq!(coverage.get_n_valid_cov(), 10);
+        assert_eq!(coverage.get_n_diff(), 2);
+        assert_eq!(coverage.get_n_fail(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_from_with_filters_min_score() -> Result<()> {
+        let record = |score: u32| {
+            PileupRecord::new(
+                "contig_1".to_string(),
+                6,
+                7,
+                ModType::SixMA,
+                score,
+                Strand::Positive,
+                6,
+                7,
+                "255,0,0".to_string(),
+                15,
+                100.0,
+                15,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+            )
+        };
+
+        let default = CoverageField::default();
+        let kept: Vec<u32> = [0, 5, 10, 20]
+            .into_iter()
+            .filter_map(|score| {
+                MethylationRecord::try_from_with_filters(record(score), 3, 0.8, 10, &default, None)
+                    .unwrap()
+                    .map(|_| score)
+            })
+            .collect();
+        assert_eq!(kept, vec![10, 20]);
+
+        for score in [0, 5, 10, 20] {
+            let meth =
+                MethylationRecord::try_from_with_filters(record(score), 3, 0.8, 0, &default, None)?;
+            assert!(meth.is_some());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_from_with_filters_coverage_field() -> Result<()> {
+        // 6 modified and 4 canonical reads, 2 reads with another mod and 3 filtered
+        // reads that are not part of n_valid_cov.
+        let record = PileupRecord::new(
+            "contig_1".to_string(),
+            6,
+            7,
+            ModType::SixMA,
+            0,
+            Strand::Positive,
+            6,
+            7,
+            "255,0,0".to_string(),
+            12,
+            50.0,
+            6,
+            4,
+            2,
+            0,
+            3,
+            0,
+            0,
+        );
+
+        let default = MethylationRecord::try_from_with_filters(
+            record.clone(),
+            1,
+            0.8,
+            0,
+            &CoverageField::default(),
+            None,
+        )?
+        .unwrap();
+        assert_eq!(default.methylation.get_n_valid_cov(), 10);
+
+        let with_fail: CoverageField = "n_valid_cov + n_fail".parse()?;
+        assert_eq!(with_fail.to_string(), "n_valid_cov+n_fail");
+        let meth =
+            MethylationRecord::try_from_with_filters(record.clone(), 1, 0.8, 0, &with_fail, None)?
+                .unwrap();
+        assert_eq!(meth.methylation.get_n_valid_cov(), 13);
+        assert_eq!(meth.methylation.get_n_modified(), 6);
+
+        // The minimum coverage applies to the selected field.
+        assert!(
+            MethylationRecord::try_from_with_filters(record, 16, 0.8, 0, &with_fail, None)?
+                .is_none()
+        );
+
+        assert!("n_valid_cov+score".parse::<CoverageField>().is_err());
+        assert!("n_valid".parse::<CoverageField>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_from_with_filters_records_filter_reason() -> Result<()> {
+        let record = |start: u32, n_valid_cov: u32, n_diff: u32, score: u32| {
+            PileupRecord::new(
+                "contig_1".to_string(),
+                start,
+                start + 1,
+                ModType::SixMA,
+                score,
+                Strand::Negative,
+                start,
+                start + 1,
+                "255,0,0".to_string(),
+                n_valid_cov,
+                100.0,
+                n_valid_cov,
+                0,
+                0,
+                0,
+                0,
+                n_diff,
+                0,
+            )
+        };
+
+        let default = CoverageField::default();
+        let mut filtered = FilteredPositions::new();
+        for rec in [
+            record(1, 2, 0, 10),
+            record(2, 10, 10, 10),
+            record(3, 10, 0, 0),
+            record(4, 10, 0, 10),
+        ] {
+            MethylationRecord::try_from_with_filters(
+                rec,
+                3,
+                0.8,
+                5,
+                &default,
+                Some(&mut filtered),
+            )?;
+        }
+
+        // The score filter is not counted and kept records are not added.
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(
+            filtered.get(&(1, Strand::Negative, ModType::SixMA)),
+            Some(&FilterReason::LowCoverage)
+        );
+        assert_eq!(
+            filtered.get(&(2, Strand::Negative, ModType::SixMA)),
+            Some(&FilterReason::Diff)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scale_to_255() {
+        assert_eq!(scale_to_255(0.0), 0);
+        assert_eq!(scale_to_255(0.5), 128);
+        assert_eq!(scale_to_255(1.0), 255);
+        assert_eq!(scale_to_255(1.2), 255);
+    }
+
+    #[test]
+    fn test_to_csv_line_scale_255() {
+        let degree = MedianMotifMethylationDegree {
+            contig: "contig_1".to_string(),
+            motif: Motif::new("GATC", "a", 1).unwrap(),
+            median: 0.5,
+            mean_read_cov: 10.0,
+            n_motif_obs: 2,
+            motif_occurences_total: 4,
+            n_filtered_low_cov: 1,
+            n_filtered_diff: 0,
+        };
+
+        assert_eq!(
+            degree.to_csv_line('\t', ValueScale::Fraction),
+            "contig_1\tGATC\ta\t1\t0.5\t10\t2\t4\t1\t0"
+        );
+        assert_eq!(
+            degree.to_csv_line('\t', ValueScale::Scale255),
+            "contig_1\tGATC\ta\t1\t128\t10\t2\t4\t1\t0"
+        );
+    }
+
+    #[test]
+    fn test_to_strand_bias() -> Result<()> {
+        let motif = Motif::new("GATC", "a", 1)?;
+        let other_motif = Motif::new("GANTC", "a", 1)?;
+        let mut methylation = AHashMap::new();
+        let mut insert = |motif: &Motif, position: usize, strand: Strand, n_modified: u32| {
+            methylation.insert(
+                ("contig_1".to_string(), motif.clone(), position, strand),
+                MethylationCoverage::new(n_modified, 10, 0, 0, 0).unwrap(),
+            );
+        };
+        insert(&motif, 1, Strand::Positive, 9);
+        insert(&motif, 2, Strand::Negative, 1);
+        insert(&motif, 5, Strand::Negative, 3);
+        insert(&other_motif, 1, Strand::Positive, 5);
+        insert(&other_motif, 2, Strand::Negative, 4);
+
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+        let degrees = positions.to_strand_bias(0.5);
+
+        assert_eq!(degrees.len(), 2);
+
+        let gantc = degrees.iter().find(|d| d.motif == other_motif).unwrap();
+        assert!((gantc.bias - 0.1).abs() < 1e-9);
+        assert!(!gantc.biased);
+
+        let gatc = degrees.iter().find(|d| d.motif == motif).unwrap();
+        assert!((gatc.methylation_plus - 0.9).abs() < 1e-9);
+        assert!((gatc.methylation_minus - 0.2).abs() < 1e-9);
+        assert!((gatc.bias - 0.7).abs() < 1e-9);
+        assert_eq!(gatc.n_motif_obs_minus, 2);
+        assert!(gatc.biased);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_strand_asymmetry_degrees() -> Result<()> {
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let non_palindromic = Motif::new("TCCCG", "m", 1)?;
+
+        let mut methylation = AHashMap::new();
+        let mut insert = |motif: &Motif, position: usize, strand: Strand, n_modified: u32| {
+            methylation.insert(
+                ("contig_1".to_string(), motif.clone(), position, strand),
+                MethylationCoverage::new(n_modified, 10, 0, 0, 0).unwrap(),
+            );
+        };
+        // GATC at 10 and 20 is observed on both strands (A at +1 and T at +2).
+        insert(&gatc, 11, Strand::Positive, 9);
+        insert(&gatc, 12, Strand::Negative, 1);
+        insert(&gatc, 21, Strand::Positive, 5);
+        insert(&gatc, 22, Strand::Negative, 5);
+        // Only the plus strand is observed at 30, so it is not a site.
+        insert(&gatc, 31, Strand::Positive, 10);
+        insert(&non_palindromic, 41, Strand::Positive, 10);
+        insert(&non_palindromic, 43, Strand::Negative, 0);
+
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+        let degrees = positions.to_strand_asymmetry_degrees();
+
+        assert_eq!(degrees.len(), 1);
+        let degree = &degrees[0];
+        assert_eq!(degree.motif, gatc);
+        assert_eq!(degree.n_sites, 2);
+        assert!((degree.methylation_plus - 0.7).abs() < 1e-12);
+        assert!((degree.methylation_minus - 0.3).abs() < 1e-12);
+        assert!((degree.mean_asymmetry - 0.4).abs() < 1e-12);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_motif_report() -> Result<()> {
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let gantc = Motif::new("GANTC", "a", 1)?;
+        let unobserved = Motif::new("CCWGG", "m", 1)?;
+
+        let mut methylation = AHashMap::new();
+        let mut insert = |contig: &str, position: usize, n_modified: u32, n_valid_cov: u32| {
+            methylation.insert(
+                (contig.to_string(), gatc.clone(), position, Strand::Positive),
+                MethylationCoverage::new(n_modified, n_valid_cov, 0, 0, 0).unwrap(),
+            );
+        };
+        // contig_1 is methylated, contig_2 is not.
+        insert("contig_1", 1, 9, 10);
+        insert("contig_1", 5, 8, 10);
+        insert("contig_2", 1, 1, 20);
+
+        let occurences = AHashMap::from([
+            (("contig_1".to_string(), gatc.clone()), 3),
+            (("contig_2".to_string(), gatc.clone()), 2),
+            (("contig_1".to_string(), gantc.clone()), 4),
+        ]);
+        let positions = MotifMethylationPositions::new(methylation, occurences);
+
+        let rows = positions.to_motif_report(
+            &[gatc.clone(), gantc.clone(), unobserved.clone()],
+            0.5,
+            &MethylationOutput::WeightedMean,
+        );
+        assert_eq!(rows.len(), 3);
+
+        let row = |motif: &Motif| rows.iter().find(|r| &r.motif == motif).unwrap();
+        let gatc_row = row(&gatc);
+        assert_eq!(gatc_row.motif_occurences_total, 5);
+        assert_eq!(gatc_row.n_motif_obs, 3);
+        assert!((gatc_row.methylation_fraction - 18.0 / 40.0).abs() < 1e-12);
+        assert_eq!(gatc_row.n_contigs_observed, 2);
+        assert_eq!(gatc_row.n_contigs_methylated, 1);
+
+        assert_eq!(row(&gantc).motif_occurences_total, 4);
+        assert_eq!(row(&gantc).n_motif_obs, 0);
+        assert_eq!(row(&gantc).methylation_fraction, 0.0);
+        assert_eq!(row(&unobserved), &MotifReportRow::empty(unobserved.clone()));
+
+        let median_rows =
+            positions.to_motif_report(&[gatc.clone()], 0.95, &MethylationOutput::Median);
+        assert_eq!(median_rows.len(), 2, "Motifs with occurrences are reported too");
+        let gatc_row = median_rows.iter().find(|r| r.motif == gatc).unwrap();
+        assert_eq!(gatc_row.n_contigs_methylated, 0);
+        assert_eq!(gatc_row.n_contigs_observed, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_site_counts() -> Result<()> {
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let ccwgg = Motif::new("CCWGG", "m", 1)?;
+
+        let mut methylation = AHashMap::new();
+        let mut insert =
+            |contig: &str, motif: &Motif, position: usize, strand: Strand, n_modified: u32| {
+                methylation.insert(
+                    (contig.to_string(), motif.clone(), position, strand),
+                    MethylationCoverage::new(n_modified, 10, 0, 0, 0).unwrap(),
+                );
+            };
+        insert("contig_2", &gatc, 1, Strand::Positive, 1);
+        insert("contig_1", &gatc, 1, Strand::Positive, 7);
+        insert("contig_1", &gatc, 1, Strand::Negative, 9);
+        insert("contig_1", &gatc, 8, Strand::Positive, 6);
+        insert("contig_1", &ccwgg, 20, Strand::Positive, 0);
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+
+        let counts = positions.to_site_counts(0.7);
+        let summary: Vec<(&str, String, u32, u32)> = counts
+            .iter()
+            .map(|c| {
+                (
+                    c.contig.as_str(),
+                    c.motif.sequence_to_string(),
+                    c.n_methylated,
+                    c.n_unmethylated,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("contig_1", "CCWGG".to_string(), 0, 1),
+                ("contig_1", "GATC".to_string(), 2, 1),
+                ("contig_2", "GATC".to_string(), 0, 1),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_motif_matches() -> Result<()> {
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let mut methylation = AHashMap::new();
+        methylation.insert(
+            ("contig_1".to_string(), gatc.clone(), 8, Strand::Positive),
+            MethylationCoverage::new(6, 10, 0, 0, 0)?,
+        );
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+
+        let sites = vec![
+            ("contig_1".to_string(), gatc.clone(), 9, Strand::Negative),
+            ("contig_1".to_string(), gatc.clone(), 8, Strand::Positive),
+        ];
+        let matches = positions.to_motif_matches(sites);
+
+        let summary: Vec<(ContigPosition, Strand, bool, Option<&str>)> = matches
+            .iter()
+            .map(|m| (m.position, m.strand, m.had_record(), m.exclusion_reason()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (8, Strand::Positive, true, None),
+                (9, Strand::Negative, false, Some("no_passing_record")),
+            ]
+        );
+        assert_eq!(matches[0].coverage.as_ref().map(|c| c.get_n_modified()), Some(6));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_strand_wide_palindromic_motif() -> Result<()> {
+        // GATC occurrence at 1: 6mA at 2 on the plus strand and at 3 on the minus strand.
+        // A second occurrence at 7 only has plus strand methylation.
+        let motif = Motif::new("GATC", "a", 1)?;
+        let mut methylation = AHashMap::new();
+        methylation.insert(
+            ("contig_1".to_string(), motif.clone(), 2, Strand::Positive),
+            MethylationCoverage::new(8, 10, 0, 0, 0)?,
+        );
+        methylation.insert(
+            ("contig_1".to_string(), motif.clone(), 3, Strand::Negative),
+            MethylationCoverage::new(3, 12, 0, 0, 0)?,
+        );
+        methylation.insert(
+            ("contig_1".to_string(), motif.clone(), 8, Strand::Positive),
+            MethylationCoverage::new(5, 5, 0, 0, 0)?,
+        );
+
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+        let mut rows = positions.to_strand_wide();
+        rows.sort_by_key(|row| row.start);
+
+        assert_eq!(
+            rows,
+            vec![
+                StrandWidePosition {
+                    contig: "contig_1".to_string(),
+                    start: 1,
+                    motif: motif.clone(),
+                    n_modified_plus: 8,
+                    n_valid_cov_plus: 10,
+                    n_modified_minus: 3,
+                    n_valid_cov_minus: 12,
+                },
+                StrandWidePosition {
+                    contig: "contig_1".to_string(),
+                    start: 7,
+                    motif,
+                    n_modified_plus: 5,
+                    n_valid_cov_plus: 5,
+                    n_modified_minus: 0,
+                    n_valid_cov_minus: 0,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_strands() -> Result<()> {
+        // GATC site at 1 with 6mA at 2 on the plus strand and at 3 on the minus strand,
+        // and a site at 7 observed on the minus strand (9) only.
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let non_palindromic = Motif::new("TCCCG", "m", 1)?;
+        let mut methylation = AHashMap::new();
+        let mut insert = |motif: &Motif, position, strand, n_modified, n_valid_cov| {
+            methylation.insert(
+                ("contig_1".to_string(), motif.clone(), position, strand),
+                MethylationCoverage::new(n_modified, n_valid_cov, 0, 0, 0).unwrap(),
+            );
+        };
+        insert(&gatc, 2, Strand::Positive, 8, 10);
+        insert(&gatc, 3, Strand::Negative, 3, 12);
+        insert(&gatc, 9, Strand::Negative, 4, 6);
+        insert(&non_palindromic, 21, Strand::Positive, 1, 10);
+        insert(&non_palindromic, 23, Strand::Negative, 2, 10);
+
+        let collapsed =
+            MotifMethylationPositions::new(methylation, AHashMap::new()).collapse_strands();
+        let mut entries: Vec<_> = collapsed
+            .methylation
+            .iter()
+            .map(|((_, motif, position, strand), coverage)| {
+                (
+                    motif.sequence_to_string(),
+                    *position,
+                    *strand,
+                    coverage.get_n_modified(),
+                    coverage.get_n_valid_cov(),
+                )
+            })
+            .collect();
+        entries.sort();
+
+        assert_eq!(
+            entries,
+            vec![
+                ("GATC".to_string(), 2, Strand::Positive, 11, 22),
+                ("GATC".to_string(), 8, Strand::Positive, 4, 6),
+                ("TCCCG".to_string(), 21, Strand::Positive, 1, 10),
+                ("TCCCG".to_string(), 23, Strand::Negative, 2, 10),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_retain_min_motif_observations() -> Result<()> {
+        let gatc = Motif::new("GATC", "a", 1)?;
+        // contig_1 has three observations of GATC, contig_2 only one.
+        let positions = || {
+            let sites = [
+                ("contig_1", 1),
+                ("contig_1", 5),
+                ("contig_1", 9),
+                ("contig_2", 1),
+            ];
+            let methylation = sites
+                .into_iter()
+                .map(|(contig, position)| {
+                    (
+                        (contig.to_string(), gatc.clone(), position, Strand::Positive),
+                        MethylationCoverage::new(5, 10, 0, 0, 0).unwrap(),
+                    )
+                })
+                .collect();
+            MotifMethylationPositions::new(methylation, AHashMap::new())
+        };
+
+        let mut unfiltered = positions();
+        assert_eq!(unfiltered.retain_min_motif_observations(1), 0);
+        assert_eq!(unfiltered.to_median_degrees().len(), 2);
+
+        let mut filtered = positions();
+        assert_eq!(filtered.retain_min_motif_observations(2), 1);
+        let degrees = filtered.to_weighted_mean_degress();
+        assert_eq!(degrees.len(), 1);
+        assert_eq!(degrees[0].contig, "contig_1");
+        assert_eq!(degrees[0].n_motif_obs, 3);
+
+        let mut filtered = positions();
+        assert_eq!(filtered.retain_min_motif_observations(4), 2);
+        assert!(filtered.methylation.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_group_degrees_pools_contigs_in_group() -> Result<()> {
+        let motif = Motif::new("GATC", "a", 1)?;
+        let mut methylation = AHashMap::new();
+        let mut insert = |contig: &str, position: usize, n_modified: u32, n_valid_cov: u32| {
+            methylation.insert(
+                (contig.to_string(), motif.clone(), position, Strand::Positive),
+                MethylationCoverage::new(n_modified, n_valid_cov, 0, 0, 0).unwrap(),
+            );
+        };
+        // The same position on two contigs must not collapse when pooled.
+        insert("contig_1", 2, 9, 10);
+        insert("contig_1", 8, 1, 10);
+        insert("contig_2", 2, 6, 20);
+        insert("contig_3", 2, 0, 10);
+
+        let mut occurences = AHashMap::new();
+        occurences.insert(("contig_1".to_string(), motif.clone()), 3);
+        occurences.insert(("contig_2".to_string(), motif.clone()), 2);
+        occurences.insert(("contig_3".to_string(), motif.clone()), 4);
+
+        let positions = MotifMethylationPositions::new(methylation, occurences);
+        let contig_groups: AHashMap<ContigId, String> = [
+            ("contig_1".to_string(), "bin_1".to_string()),
+            ("contig_2".to_string(), "bin_1".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        let median = positions.to_group_degrees(&contig_groups, &MethylationOutput::Median)?;
+        assert_eq!(median.len(), 1);
+        let bin = &median[0];
+        assert_eq!(bin.group, "bin_1");
+        assert!((bin.methylation_value - 0.3).abs() < 1e-9);
+        assert!((bin.mean_read_cov - 40.0 / 3.0).abs() < 1e-9);
+        assert_eq!(bin.n_motif_obs, 3);
+        assert_eq!(bin.motif_occurences_total, 5);
+        assert_eq!(bin.n_contigs, 2);
+
+        let weighted_mean =
+            positions.to_group_degrees(&contig_groups, &MethylationOutput::WeightedMean)?;
+        assert!((weighted_mean[0].methylation_value - 16.0 / 40.0).abs() < 1e-9);
+
+        assert!(
+            positions
+                .to_group_degrees(&contig_groups, &MethylationOutput::Raw)
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_window_degrees_follows_methylation_domains() -> Result<()> {
+        // Contig of 100 bp: highly methylated in the first half, unmethylated in the
+        // second half and without observations between 40 and 60.
+        let motif = Motif::new("GATC", "a", 1)?;
+        let mut methylation = AHashMap::new();
+        for (position, n_modified) in [(5, 9), (15, 10), (35, 8), (65, 1), (75, 0), (95, 0)] {
+            methylation.insert(
+                ("contig_1".to_string(), motif.clone(), position, Strand::Positive),
+                MethylationCoverage::new(n_modified, 10, 0, 0, 0)?,
+            );
+        }
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+        let contig_lengths: AHashMap<ContigId, usize> =
+            [("contig_1".to_string(), 100)].into_iter().collect();
+
+        let mut windows =
+            positions.to_window_degrees(&contig_lengths, 40, 20, &MethylationOutput::Median)?;
+        windows.sort_by_key(|w| w.window_start);
+
+        let summary: Vec<(usize, usize, Option<f64>, u32)> = windows
+            .iter()
+            .map(|w| (w.window_start, w.window_end, w.methylation, w.n_obs))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 40, Some(0.9), 3),
+                (20, 60, Some(0.8), 1),
+                (40, 80, Some(0.05), 2),
+                (60, 100, Some(0.0), 3),
+            ]
+        );
+
+        let windows =
+            positions.to_window_degrees(&contig_lengths, 10, 10, &MethylationOutput::Median)?;
+        assert_eq!(windows.len(), 10);
+        let empty = windows.iter().find(|w| w.window_start == 40).unwrap();
+        assert_eq!(empty.methylation, None);
+        assert_eq!(empty.n_obs, 0);
+
+        let out = tempfile::NamedTempFile::new()?;
+        write_window_output(&windows, out.path(), None, ValueScale::Fraction, true)?;
+        assert_eq!(std::fs::read_to_string(out.path())?.lines().count(), 1 + 6);
+        write_window_output(&windows, out.path(), Some("NA"), ValueScale::Fraction, true)?;
+        let written = std::fs::read_to_string(out.path())?;
+        assert_eq!(written.lines().count(), 1 + 10);
+        assert!(written.contains("contig_1\t40\t50\tGATC\ta\t1\tNA\t0"));
+
+        assert!(
+            positions
+                .to_window_degrees(&contig_lengths, 0, 10, &MethylationOutput::Median)
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_weighted_mean_stderr_uses_pooled_valid_coverage() -> Result<()> {
+        // Pooled counts: 30 of 40 valid reads modified, so p = 0.75 and n = 40.
+        let motif = Motif::new("GATC", "a", 1)?;
+        let mut methylation = AHashMap::new();
+        for (position, n_modified, n_valid_cov) in [(2, 9, 10), (8, 21, 30)] {
+            methylation.insert(
+                ("contig_1".to_string(), motif.clone(), position, Strand::Positive),
+                MethylationCoverage::new(n_modified, n_valid_cov, 0, 0, 0)?,
+            );
+        }
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+
+        let degrees = positions.to_weighted_mean_degress();
+        assert_eq!(degrees.len(), 1);
+        assert!((degrees[0].w_mean - 0.75).abs() < 1e-9);
+        let expected_stderr = (0.75_f64 * 0.25 / 40.0).sqrt();
+        assert!((degrees[0].stderr - expected_stderr).abs() < 1e-12);
+
+        assert_eq!(weighted_mean_stderr(0.0, 10), 0.0);
+        assert_eq!(weighted_mean_stderr(1.0, 10), 0.0);
+
+        let variant = MethylationPatternVariant::WeightedMean(degrees);
+        let out = tempfile::NamedTempFile::new()?;
+        variant.write_output(
+            out.path(),
+            ValueScale::Fraction,
+            true,
+            true,
+            StrandFormat::Symbol,
+            &[],
+        )?;
+        let written = std::fs::read_to_string(out.path())?;
+        let lines: Vec<&str> = written.lines().collect();
+        assert!(lines[0].ends_with("\tn_filtered_diff\tstderr"));
+        let stderr: f64 = lines[1].rsplit('\t').next().unwrap().parse()?;
+        assert!((stderr - expected_stderr).abs() < 1e-12);
+
+        variant.write_output(
+            out.path(),
+            ValueScale::Fraction,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &[],
+        )?;
+        let written = std::fs::read_to_string(out.path())?;
+        assert!(!written.contains("stderr"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_trimmed_mean_degrees() -> Result<()> {
+        let motif = Motif::new("GATC", "a", 1)?;
+        let mut methylation = AHashMap::new();
+        // contig_1: fractions 0, 0.5, 0.6, 0.7 and 1. The 20th and 80th percentiles are
+        // 0.4 and 0.76, so 0.5, 0.6 and 0.7 are kept.
+        for (position, n_modified) in [(2, 0), (8, 5), (14, 6), (20, 7), (26, 10)] {
+            methylation.insert(
+                ("contig_1".to_string(), motif.clone(), position, Strand::Positive),
+                MethylationCoverage::new(n_modified, 10, 0, 0, 0)?,
+            );
+        }
+        // contig_2: too few observations to trim.
+        for (position, n_modified) in [(2, 0), (8, 10)] {
+            methylation.insert(
+                ("contig_2".to_string(), motif.clone(), position, Strand::Positive),
+                MethylationCoverage::new(n_modified, 10, 0, 0, 0)?,
+            );
+        }
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+
+        let mut degrees = positions.to_trimmed_mean_degrees();
+        degrees.sort_by(|a, b| a.contig.cmp(&b.contig));
+        assert!((degrees[0].trimmed_mean - 0.6).abs() < 1e-9);
+        assert!(degrees[0].trim_applied);
+        assert_eq!(degrees[0].n_motif_obs, 5);
+        assert_eq!(degrees[1].trimmed_mean, 0.5);
+        assert!(!degrees[1].trim_applied);
+
+        let variant = MethylationPatternVariant::TrimmedMean(degrees);
+        let out = tempfile::NamedTempFile::new()?;
+        variant.write_output(
+            out.path(),
+            ValueScale::Fraction,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &[],
+        )?;
+        let written = std::fs::read_to_string(out.path())?;
+        let lines: Vec<&str> = written.lines().collect();
+        assert!(lines[0].ends_with("\tn_filtered_diff\ttrim_applied"));
+        assert!(lines[1].starts_with("contig_1\t") && lines[1].ends_with("\ttrue"));
+        assert!(lines[2].starts_with("contig_2\t") && lines[2].ends_with("\tfalse"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_weighted_median_degrees() -> Result<()> {
+        let motif = Motif::new("GATC", "a", 1)?;
+        let mut methylation = AHashMap::new();
+        // contig_1: fractions 0, 0.1, 0.8 and 0.9 with coverages 5, 10, 10 and 50. The
+        // cumulative coverages are 5, 15, 25 and 75, so half of 75 is first reached at
+        // 0.9. The median is 0.45 and the weighted mean 54 / 75 = 0.72.
+        let contig_1 = [(2, 0, 5), (8, 1, 10), (14, 8, 10), (20, 45, 50)];
+        for (position, n_modified, n_valid_cov) in contig_1 {
+            methylation.insert(
+                ("contig_1".to_string(), motif.clone(), position, Strand::Positive),
+                MethylationCoverage::new(n_modified, n_valid_cov, 0, 0, 0)?,
+            );
+        }
+        // contig_2: equal coverages reach exactly half at 0.2, so the next fraction is
+        // averaged in like the median.
+        for (position, n_modified) in [(2, 2), (8, 6)] {
+            methylation.insert(
+                ("contig_2".to_string(), motif.clone(), position, Strand::Positive),
+                MethylationCoverage::new(n_modified, 10, 0, 0, 0)?,
+            );
+        }
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+
+        let mut degrees = positions.to_weighted_median_degrees();
+        degrees.sort_by(|a, b| a.contig.cmp(&b.contig));
+        assert_eq!(degrees[0].w_median, 0.9);
+        assert_eq!(degrees[0].n_motif_obs, 4);
+        assert!((degrees[0].mean_read_cov - 18.75).abs() < 1e-9);
+        assert!((degrees[1].w_median - 0.4).abs() < 1e-9);
+
+        let mut medians = positions.to_median_degrees();
+        medians.sort_by(|a, b| a.contig.cmp(&b.contig));
+        assert!((medians[0].median - 0.45).abs() < 1e-9);
+        assert_eq!(medians[1].median, degrees[1].w_median);
+
+        let variant = MethylationPatternVariant::WeightedMedian(degrees);
+        assert_eq!(variant.output_type(), MethylationOutput::WeightedMedian);
+        let out = tempfile::NamedTempFile::new()?;
+        variant.write_output(
+            out.path(),
+            ValueScale::Fraction,
+            true,
+            false,
+            StrandFormat::Symbol,
+            &[],
+        )?;
+        let written = std::fs::read_to_string(out.path())?;
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(
+            lines[0],
+            output_header(
+                &MethylationOutput::WeightedMedian,
+                false,
+                ValueScale::Fraction
+            )
+        );
+        assert!(lines[1].starts_with("contig_1\tGATC\ta\t1\t0.9\t"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_summary_matches_median_and_weighted_mean() -> Result<()> {
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let ccwgg = Motif::new("CCWGG", "m", 1)?;
+        let mut methylation = AHashMap::new();
+        for (contig, motif, position, n_modified, n_valid_cov) in [
+            ("contig_1", &gatc, 2, 6, 10),
+            ("contig_1", &gatc, 8, 6, 10),
+            ("contig_1", &gatc, 14, 3, 30),
+            ("contig_1", &ccwgg, 20, 1, 4),
+            ("contig_2", &gatc, 2, 9, 10),
+        ] {
+            methylation.insert(
+                (contig.to_string(), motif.clone(), position, Strand::Positive),
+                MethylationCoverage::new(n_modified, n_valid_cov, 0, 0, 0)?,
+            );
+        }
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+
+        let key = |contig: &str, motif: &Motif| (contig.to_string(), motif.clone());
+        let medians: AHashMap<_, _> = positions
+            .to_median_degrees()
+            .into_iter()
+            .map(|d| (key(&d.contig, &d.motif), d))
+            .collect();
+        let weighted_means: AHashMap<_, _> = positions
+            .to_weighted_mean_degress()
+            .into_iter()
+            .map(|d| (key(&d.contig, &d.motif), d))
+            .collect();
+
+        let summary = positions.to_summary();
+        assert_eq!(summary.len(), 3);
+        for row in summary {
+            let median = &medians[&key(&row.contig, &row.motif)];
+            let weighted_mean = &weighted_means[&key(&row.contig, &row.motif)];
+            assert_eq!(row.median, median.median);
+            assert_eq!(row.weighted_mean, weighted_mean.w_mean);
+            assert_eq!(row.mean_read_cov, median.mean_read_cov);
+            assert_eq!(row.n_motif_obs, median.n_motif_obs);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_called_frequency_differs_from_weighted_mean() -> Result<()> {
+        // Fractions 0.6, 0.6 and 0.1 with 10, 10 and 30 valid reads.
+        let motif = Motif::new("GATC", "a", 1)?;
+        let mut methylation = AHashMap::new();
+        for (position, n_modified, n_valid_cov) in [(2, 6, 10), (8, 6, 10), (14, 3, 30)] {
+            methylation.insert(
+                ("contig_1".to_string(), motif.clone(), position, Strand::Positive),
+                MethylationCoverage::new(n_modified, n_valid_cov, 0, 0, 0)?,
+            );
+        }
+        let positions = MotifMethylationPositions::new(methylation, AHashMap::new());
+
+        // The weighted mean pools the reads: 15 of 50 modified.
+        let weighted_mean = positions.to_weighted_mean_degress();
+        assert!((weighted_mean[0].w_mean - 0.3).abs() < 1e-9);
+
+        // Two of three positions are called methylated at 0.5.
+        let called = positions.to_called_frequency_degrees(0.5);
+        assert_eq!(called[0].n_called_methylated, 2);
+        assert!((called[0].called_frequency - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(called[0].n_motif_obs, 3);
+
+        // The threshold is inclusive.
+        let called = positions.to_called_frequency_degrees(0.6);
+        assert_eq!(called[0].n_called_methylated, 2);
+        let called = positions.to_called_frequency_degrees(0.7);
+        assert_eq!(called[0].called_frequency, 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pooled_statistics_are_bit_identical_across_maps() -> Result<()> {
+        // Every map gets its own hash seed and insertion order. Summing the fractions
+        // in map order would give results differing in the last bits.
+        let motif = Motif::new("GATC", "a", 1)?;
+        let build = |reverse: bool| -> Result<MotifMethylationPositions> {
+            let mut entries: Vec<_> = (0..500u32)
+                .map(|i| (i as usize * 4, (i * 7) % 13 + 1, (i * 11) % 17 + 13))
+                .collect();
+            if reverse {
+                entries.reverse();
+            }
+            let mut methylation = AHashMap::new();
+            for (position, n_modified, n_valid_cov) in entries {
+                for strand in [Strand::Positive, Strand::Negative] {
+                    methylation.insert(
+                        ("contig_1".to_string(), motif.clone(), position, strand),
+                        MethylationCoverage::new(n_modified, n_valid_cov, 0, 0, 0)?,
+                    );
+                }
+            }
+            Ok(MotifMethylationPositions::new(methylation, AHashMap::new()))
+        };
+
+        let reference = build(false)?.to_weighted_mean_degress();
+        let contig_lengths = AHashMap::from([("contig_1".to_string(), 2000)]);
+        let reference_windows = build(false)?.to_window_degrees(
+            &contig_lengths,
+            300,
+            100,
+            &MethylationOutput::WeightedMean,
+        )?;
+
+        for run in 0..20 {
+            let positions = build(run % 2 == 1)?;
+            let degrees = positions.to_weighted_mean_degress();
+            assert_eq!(degrees[0].w_mean.to_bits(), reference[0].w_mean.to_bits());
+            assert_eq!(degrees[0].stderr.to_bits(), reference[0].stderr.to_bits());
+
+            let windows = positions.to_window_degrees(
+                &contig_lengths,
+                300,
+                100,
+                &MethylationOutput::WeightedMean,
+            )?;
+            let bits = |w: &[WindowMethylationDegree]| -> Vec<Option<u64>> {
+                w.iter().map(|d| d.methylation.map(f64::to_bits)).collect()
+            };
+            assert_eq!(bits(&windows), bits(&reference_windows));
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_methylation_coverage_invalid() {
         // Test invalid input: n_valid_cov < n_modified
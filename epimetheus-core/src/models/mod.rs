@@ -1,4 +1,6 @@
 pub mod contig;
+pub mod coverage_histogram;
 pub mod genome_workspace;
 pub mod methylation;
+pub mod motif_occurrences;
 pub mod pileup;
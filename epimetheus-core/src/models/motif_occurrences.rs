@@ -0,0 +1,339 @@
+use ahash::AHashMap;
+use anyhow::{Context, Result, anyhow, bail};
+use epimetheus_methylome::{
+    Motif, count_motif_occurrences, find_motif_indices_in_sequence, sequence::Sequence,
+};
+use log::{info, warn};
+use rayon::prelude::*;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::models::contig::{Contig, ContigId};
+
+const CACHE_HEADER: &str = "# epimetheus motif occurrences v1";
+const CACHE_COLUMNS: &str = "contig\tmotif\tn_occurrences\tplus_positions\tminus_positions";
+
+/// Occurrences of a motif in a contig.
+///
+/// The plus and minus strand positions are the positions of the modified base, as
+/// returned by [`find_motif_indices_in_sequence`] for the motif and its reverse
+/// complement. `n_occurrences` is [`count_motif_occurrences`], which counts the
+/// sites of palindromic motifs once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MotifOccurrence {
+    pub plus_positions: Vec<usize>,
+    pub minus_positions: Vec<usize>,
+    pub n_occurrences: u32,
+}
+
+impl MotifOccurrence {
+    /// Scans `sequence` for the occurrences of `motif`.
+    pub fn scan(sequence: &Sequence, motif: &Motif) -> Self {
+        Self {
+            plus_positions: find_motif_indices_in_sequence(sequence, motif),
+            minus_positions: find_motif_indices_in_sequence(sequence, &motif.reverse_complement()),
+            n_occurrences: count_motif_occurrences(sequence, motif) as u32,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plus_positions.is_empty() && self.minus_positions.is_empty()
+    }
+}
+
+/// Motif occurrences of an assembly, to skip scanning the assembly on repeated runs
+/// with the same motifs.
+///
+/// The table is keyed by the checksum of [`Self::checksum`]. A cache file is only
+/// used if it was written for the same assembly and motifs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotifOccurrenceTable {
+    checksum: String,
+    occurrences: AHashMap<(ContigId, String), MotifOccurrence>,
+}
+
+impl MotifOccurrenceTable {
+    /// The 64-bit XXH3 hash (seed 0, lowercase hex) of the contigs (id and sequence,
+    /// sorted by id) and the motifs (`<sequence>_<mod_type>_<mod_position>`, sorted).
+    pub fn checksum(contigs: &AHashMap<String, Contig>, motifs: &[Motif]) -> String {
+        let mut hasher = Xxh3::new();
+
+        let mut contig_ids: Vec<&String> = contigs.keys().collect();
+        contig_ids.sort();
+        for contig_id in contig_ids {
+            hasher.update(contig_id.as_bytes());
+            hasher.update(b"\t");
+            hasher.update(contigs[contig_id].sequence.to_string().as_bytes());
+            hasher.update(b"\n");
+        }
+
+        let mut labels: Vec<String> = motifs.iter().map(motif_label).collect();
+        labels.sort();
+        labels.dedup();
+        for label in labels {
+            hasher.update(label.as_bytes());
+            hasher.update(b"\n");
+        }
+
+        format!("{:016x}", hasher.digest())
+    }
+
+    /// Scans all contigs for the occurrences of `motifs`.
+    pub fn scan(contigs: &AHashMap<String, Contig>, motifs: &[Motif]) -> Self {
+        let occurrences = contigs
+            .par_iter()
+            .flat_map_iter(|(contig_id, contig)| {
+                motifs.iter().filter_map(move |motif| {
+                    let occurrence = MotifOccurrence::scan(&contig.sequence, motif);
+                    (!occurrence.is_empty())
+                        .then(|| ((contig_id.clone(), motif_label(motif)), occurrence))
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect();
+
+        Self {
+            checksum: Self::checksum(contigs, motifs),
+            occurrences,
+        }
+    }
+
+    /// Loads the table from the cache at `path`, or scans the assembly and writes the
+    /// cache if it is missing, unreadable or written for other contigs or motifs.
+    pub fn load_or_scan<P: AsRef<Path>>(
+        path: P,
+        contigs: &AHashMap<String, Contig>,
+        motifs: &[Motif],
+    ) -> Result<Self> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            let checksum = Self::checksum(contigs, motifs);
+            match Self::read(path, motifs) {
+                Ok(table) if table.checksum == checksum => {
+                    info!("Loaded motif occurrences from: {}", path.display());
+                    return Ok(table);
+                }
+                Ok(_) => warn!(
+                    "Motif occurrence cache {} does not match the assembly and motifs. Scanning the assembly and rewriting the cache.",
+                    path.display()
+                ),
+                Err(e) => warn!(
+                    "Could not read motif occurrence cache {}: {:#}. Scanning the assembly and rewriting the cache.",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+
+        let table = Self::scan(contigs, motifs);
+        table.write(path)?;
+        info!("Written motif occurrences to: {}", path.display());
+
+        Ok(table)
+    }
+
+    /// Occurrences of `motif` in the contig. Motifs without occurrences are not stored
+    /// and return `None`.
+    pub fn get(&self, contig_id: &str, motif: &Motif) -> Option<&MotifOccurrence> {
+        self.occurrences
+            .get(&(contig_id.to_string(), motif_label(motif)))
+    }
+
+    /// Writes the table as TSV after a header line with the checksum. Rows are sorted by
+    /// contig and motif and positions are comma separated.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|| {
+            format!(
+                "Failed to create motif occurrence cache at: {}",
+                path.display()
+            )
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        writeln!(writer, "{} xxh3_64={}", CACHE_HEADER, self.checksum)?;
+        writeln!(writer, "{}", CACHE_COLUMNS)?;
+
+        let mut keys: Vec<&(ContigId, String)> = self.occurrences.keys().collect();
+        keys.sort();
+        for key in keys {
+            let occurrence = &self.occurrences[key];
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                key.0,
+                key.1,
+                occurrence.n_occurrences,
+                join_positions(&occurrence.plus_positions),
+                join_positions(&occurrence.minus_positions)
+            )?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a table written by [`Self::write`]. Rows of motifs not in `motifs` are an
+    /// error.
+    pub fn read<P: AsRef<Path>>(path: P, motifs: &[Motif]) -> Result<Self> {
+        let known_labels: Vec<String> = motifs.iter().map(motif_label).collect();
+        let reader = BufReader::new(File::open(path)?);
+        let mut lines = reader.lines();
+
+        let header = lines
+            .next()
+            .ok_or_else(|| anyhow!("The cache is empty"))??;
+        let checksum = header
+            .strip_prefix(CACHE_HEADER)
+            .and_then(|rest| rest.trim().strip_prefix("xxh3_64="))
+            .ok_or_else(|| anyhow!("Unexpected cache header '{}'", header))?
+            .to_string();
+        match lines.next() {
+            Some(Ok(columns)) if columns == CACHE_COLUMNS => {}
+            _ => bail!("Expected the columns '{}'", CACHE_COLUMNS),
+        }
+
+        let mut occurrences = AHashMap::new();
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 5 {
+                bail!(
+                    "Expected 5 fields in line {} but found {}",
+                    i + 3,
+                    fields.len()
+                );
+            }
+            if !known_labels.iter().any(|label| label == fields[1]) {
+                bail!("Unknown motif '{}' in line {}", fields[1], i + 3);
+            }
+
+            let occurrence = MotifOccurrence {
+                plus_positions: parse_positions(fields[3])
+                    .with_context(|| format!("Invalid plus positions in line {}", i + 3))?,
+                minus_positions: parse_positions(fields[4])
+                    .with_context(|| format!("Invalid minus positions in line {}", i + 3))?,
+                n_occurrences: fields[2]
+                    .parse()
+                    .with_context(|| format!("Invalid n_occurrences in line {}", i + 3))?,
+            };
+            occurrences.insert((fields[0].to_string(), fields[1].to_string()), occurrence);
+        }
+
+        Ok(Self {
+            checksum,
+            occurrences,
+        })
+    }
+}
+
+fn motif_label(motif: &Motif) -> String {
+    format!(
+        "{}_{}_{}",
+        motif.sequence_to_string(),
+        motif.mod_type.to_pileup_code(),
+        motif.mod_position
+    )
+}
+
+fn join_positions(positions: &[usize]) -> String {
+    positions
+        .iter()
+        .map(usize::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn parse_positions(field: &str) -> Result<Vec<usize>> {
+    if field.is_empty() {
+        return Ok(Vec::new());
+    }
+    field
+        .split(',')
+        .map(|position| Ok(position.parse()?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn contigs(sequence: &str) -> AHashMap<String, Contig> {
+        let mut contigs = AHashMap::new();
+        contigs.insert(
+            "contig_1".to_string(),
+            Contig::from_string("contig_1".to_string(), sequence.to_string()).unwrap(),
+        );
+        contigs
+    }
+
+    #[test]
+    fn test_motif_occurrence_table_round_trip() -> Result<()> {
+        let contigs = contigs("GGATCTCCATGATCAAGG");
+        let motifs = vec![
+            Motif::new("GATC", "a", 1)?,
+            Motif::new("CCATG", "m", 1)?,
+            Motif::new("AAAA", "a", 0)?,
+        ];
+
+        let table = MotifOccurrenceTable::scan(&contigs, &motifs);
+        let gatc = table.get("contig_1", &motifs[0]).unwrap();
+        assert_eq!(gatc.plus_positions, vec![2, 11]);
+        assert_eq!(gatc.minus_positions, vec![3, 12]);
+        assert_eq!(gatc.n_occurrences, 2);
+        assert!(table.get("contig_1", &motifs[2]).is_none());
+
+        let cache = NamedTempFile::new()?;
+        table.write(cache.path())?;
+        assert_eq!(MotifOccurrenceTable::read(cache.path(), &motifs)?, table);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_or_scan_rescans_on_mismatch() -> Result<()> {
+        let motifs = vec![Motif::new("GATC", "a", 1)?];
+        let cache = NamedTempFile::new()?;
+
+        let first = MotifOccurrenceTable::scan(&contigs("GATCAAAA"), &motifs);
+        first.write(cache.path())?;
+        let loaded =
+            MotifOccurrenceTable::load_or_scan(cache.path(), &contigs("GATCAAAA"), &motifs)?;
+        assert_eq!(loaded, first);
+
+        // A changed assembly does not match the cache, so it is scanned and rewritten.
+        let changed = contigs("AAAAGATC");
+        let rescanned = MotifOccurrenceTable::load_or_scan(cache.path(), &changed, &motifs)?;
+        assert_eq!(
+            rescanned
+                .get("contig_1", &motifs[0])
+                .unwrap()
+                .plus_positions,
+            vec![5]
+        );
+        assert_eq!(
+            MotifOccurrenceTable::read(cache.path(), &motifs)?,
+            rescanned
+        );
+
+        // Other motifs do not match the cache either.
+        let other_motifs = vec![Motif::new("GATC", "m", 3)?];
+        let rescanned = MotifOccurrenceTable::load_or_scan(cache.path(), &changed, &other_motifs)?;
+        assert_eq!(
+            rescanned
+                .get("contig_1", &other_motifs[0])
+                .unwrap()
+                .plus_positions,
+            vec![7]
+        );
+
+        Ok(())
+    }
+}
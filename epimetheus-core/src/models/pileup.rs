@@ -1,4 +1,5 @@
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
+use clap::ValueEnum;
 use epimetheus_methylome::{ModType, Strand};
 use std::{fmt, str::FromStr};
 
@@ -102,7 +103,84 @@ impl ToString for PileupColumn {
     }
 }
 
-#[derive(Clone)]
+/// Pileup count columns summed to the valid coverage of a record, e.g. `n_valid_cov`
+/// (the default) or `n_valid_cov+n_other_mod`.
+///
+/// The sum replaces the `n_valid_cov` column of the record in
+/// [`MethylationRecord::try_from_with_filters`], so the coverage filters and the
+/// subtraction of `n_other_mod` apply to it as they do to `n_valid_cov`.
+#[derive(Debug, Clone)]
+pub struct CoverageField(Vec<PileupColumn>);
+
+impl CoverageField {
+    /// Count columns that can be part of a coverage field.
+    pub const COUNT_COLUMNS: [&str; 8] = [
+        "n_valid_cov",
+        "n_modified",
+        "n_canonical",
+        "n_other_mod",
+        "n_delete",
+        "n_fail",
+        "n_diff",
+        "n_no_call",
+    ];
+
+    pub fn value(&self, record: &PileupRecord) -> u32 {
+        self.0
+            .iter()
+            .map(|column| match column {
+                PileupColumn::NValidCov => record.n_valid_cov,
+                PileupColumn::NModified => record.n_modified,
+                PileupColumn::NCanonical => record.n_canonical,
+                PileupColumn::NOtherMod => record.n_other_mod,
+                PileupColumn::NDelete => record.n_delete,
+                PileupColumn::NFail => record.n_fail,
+                PileupColumn::NDiff => record.n_diff,
+                PileupColumn::NNoCall => record.n_no_call,
+                _ => unreachable!("Coverage fields only contain count columns"),
+            })
+            .sum()
+    }
+}
+
+impl Default for CoverageField {
+    fn default() -> Self {
+        Self(vec![PileupColumn::NValidCov])
+    }
+}
+
+impl FromStr for CoverageField {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let columns = s
+            .split('+')
+            .map(|name| {
+                let name = name.trim();
+                if !Self::COUNT_COLUMNS.contains(&name) {
+                    bail!(
+                        "Unknown coverage field '{}' in '{}'. Use a count column or a sum of count columns joined by '+': {}",
+                        name,
+                        s,
+                        Self::COUNT_COLUMNS.join(", ")
+                    );
+                }
+                name.parse::<PileupColumn>()
+            })
+            .collect::<anyhow::Result<Vec<PileupColumn>>>()?;
+
+        Ok(Self(columns))
+    }
+}
+
+impl fmt::Display for CoverageField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self.0.iter().map(PileupColumn::to_string).collect();
+        write!(f, "{}", names.join("+"))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct PileupRecord {
     pub contig: String,
     pub start: u32,
@@ -186,17 +264,53 @@ impl PileupRecord {
     }
 }
 
+/// How malformed pileup records are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ParseErrorMode {
+    /// Abort on the first malformed record.
+    #[default]
+    Fail,
+    /// Log and count malformed records and continue.
+    Skip,
+}
+
 impl TryFrom<PileupRecordString> for PileupRecord {
     type Error = anyhow::Error;
 
     fn try_from(value: PileupRecordString) -> std::result::Result<Self, Self::Error> {
-        let fields: Vec<&str> = value.0.trim().split('\t').collect();
+        PileupRecord::try_from(&value)
+    }
+}
+
+impl TryFrom<&PileupRecordString> for PileupRecord {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &PileupRecordString) -> std::result::Result<Self, Self::Error> {
+        // Trimming also strips the carriage return of a pileup edited on Windows.
+        let line = value.0.trim();
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 18 {
+            bail!(
+                "Expected 18 pileup columns but found {} for contig '{}': '{}'",
+                fields.len(),
+                fields[0],
+                line
+            );
+        }
 
         Ok(Self {
             contig: fields[0].to_string(),
             start: fields[1].parse()?,
             end: fields[2].parse()?,
-            mod_type: fields[3].parse()?,
+            mod_type: fields[3].parse().map_err(|_| {
+                anyhow!(
+                    "Unknown mod type '{}' at {}:{}. Supported mod types: {}",
+                    fields[3],
+                    fields[0],
+                    fields[1],
+                    ModType::supported_pileup_codes()
+                )
+            })?,
             score: fields[4].parse()?,
             strand: fields[5].parse()?,
             start_pos: fields[6].parse()?,
@@ -241,3 +355,35 @@ impl fmt::Display for PileupRecord {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pileup_record_wrong_column_count() {
+        let record = PileupRecordString::new(
+            "contig_1\t3\t4\ta\t20\t+\t3\t4\t255,0,0\t20\t50.00\t10\t10\t0\t0\t0\t0".to_string(),
+        );
+
+        let err = PileupRecord::try_from(&record).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Expected 18 pileup columns but found 17 for contig 'contig_1': \
+             'contig_1\t3\t4\ta\t20\t+\t3\t4\t255,0,0\t20\t50.00\t10\t10\t0\t0\t0\t0'"
+        );
+    }
+
+    #[test]
+    fn test_pileup_record_crlf() -> anyhow::Result<()> {
+        let record = PileupRecordString::new(
+            "contig_1\t3\t4\ta\t20\t+\t3\t4\t255,0,0\t20\t50.00\t10\t10\t0\t0\t0\t0\t7\r\n"
+                .to_string(),
+        );
+
+        let record = PileupRecord::try_from(&record)?;
+        assert_eq!(record.n_no_call, 7);
+
+        Ok(())
+    }
+}
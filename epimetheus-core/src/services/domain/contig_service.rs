@@ -1,15 +1,313 @@
+use ahash::{AHashMap, AHashSet};
 use anyhow::Result;
+use epimetheus_methylome::{ModType, Motif, Strand, find_motif_indices_in_sequence};
+use log::warn;
 
-use crate::models::{contig::Contig, methylation::MethylationRecord};
+use crate::models::{
+    contig::{Contig, Position},
+    methylation::MethylationRecord,
+};
+use crate::services::domain::warning_collector::{WarningEvent, WarningType, record_warning};
 
+/// Adds the methylation records to a copy of `contig`.
+///
+/// Records past the end of the contig are an error, unless `allow_mismatch` is set, in
+/// which case they are skipped and counted in a single contig mismatch warning (see
+/// [`Contig::add_methylation_record`]).
+///
+/// With `consistency_motifs` the symmetric sites of the palindromic motifs among them
+/// are checked with [`find_inconsistent_mod_types`]. Each inconsistent site is
+/// recorded as a warning.
 pub fn populate_contig_with_methylation(
     contig: &Contig,
     records: Vec<MethylationRecord>,
+    consistency_motifs: Option<&[Motif]>,
+    allow_mismatch: bool,
 ) -> Result<Contig> {
     let mut contig = contig.clone();
 
+    let mut n_out_of_bounds = 0;
     for rec in records {
-        contig.add_methylation_record(rec)?;
+        if !contig.add_methylation_record(rec, allow_mismatch)? {
+            n_out_of_bounds += 1;
+        }
     }
+    if n_out_of_bounds > 0 {
+        let message = format!(
+            "Skipped {} pileup records past the end of '{}' (length {}). The pileup may be made against another version of the assembly.",
+            n_out_of_bounds,
+            contig.id,
+            contig.sequence.len()
+        );
+        warn!("{}", message);
+        record_warning(
+            WarningEvent::new(WarningType::ContigMismatch, message)
+                .with_contig(contig.id.as_str()),
+        );
+    }
+
+    if let Some(motifs) = consistency_motifs {
+        let sites = find_inconsistent_mod_types(&contig, motifs);
+        if !sites.is_empty() {
+            warn!(
+                "Found {} palindromic motif sites in '{}' with different mod types on the plus and minus strand",
+                sites.len(),
+                contig.id
+            );
+        }
+        for site in sites {
+            let message = format!(
+                "Mod types [{}] on the plus strand at {} and [{}] on the minus strand at {} have nothing in common",
+                pileup_codes(&site.plus_mod_types),
+                site.plus_position,
+                pileup_codes(&site.minus_mod_types),
+                site.minus_position,
+            );
+            record_warning(
+                WarningEvent::new(WarningType::InconsistentModType, message)
+                    .with_contig(contig.id.as_str())
+                    .with_position(site.plus_position),
+            );
+        }
+    }
+
     Ok(contig)
 }
+
+/// A symmetric site of a palindromic motif where the plus and minus strand records
+/// share no mod type.
+#[derive(Debug, PartialEq)]
+pub struct InconsistentModTypeSite {
+    pub plus_position: Position,
+    pub minus_position: Position,
+    pub plus_mod_types: Vec<ModType>,
+    pub minus_mod_types: Vec<ModType>,
+}
+
+/// Finds symmetric sites of palindromic motifs with records on both strands but no
+/// mod type in common, e.g. an `a` record on the plus strand of a GATC and an `m`
+/// record on the minus strand. This points to merged or mislabeled pileups.
+///
+/// Motifs that are not palindromic are skipped. Sites are sorted by plus strand
+/// position.
+pub fn find_inconsistent_mod_types(
+    contig: &Contig,
+    motifs: &[Motif],
+) -> Vec<InconsistentModTypeSite> {
+    let mut mod_types: AHashMap<(Position, Strand), Vec<ModType>> = AHashMap::new();
+    for (position, strand, mod_type) in contig.methylated_positions.keys() {
+        mod_types
+            .entry((*position, *strand))
+            .or_default()
+            .push(*mod_type);
+    }
+    for types in mod_types.values_mut() {
+        types.sort();
+    }
+
+    let mut checked = AHashSet::new();
+    let mut sites = Vec::new();
+    for motif in motifs.iter().filter(|motif| motif.is_palindromic()) {
+        let mod_position = motif.mod_position as usize;
+        let minus_offset = motif.sequence.len() - 1 - mod_position;
+
+        for plus_position in find_motif_indices_in_sequence(&contig.sequence, motif) {
+            let minus_position = plus_position - mod_position + minus_offset;
+            if !checked.insert((plus_position, minus_position)) {
+                continue;
+            }
+
+            let (Some(plus), Some(minus)) = (
+                mod_types.get(&(plus_position, Strand::Positive)),
+                mod_types.get(&(minus_position, Strand::Negative)),
+            ) else {
+                continue;
+            };
+
+            if !plus.iter().any(|mod_type| minus.contains(mod_type)) {
+                sites.push(InconsistentModTypeSite {
+                    plus_position,
+                    minus_position,
+                    plus_mod_types: plus.clone(),
+                    minus_mod_types: minus.clone(),
+                });
+            }
+        }
+    }
+
+    sites.sort_by_key(|site| (site.plus_position, site.minus_position));
+    sites
+}
+
+/// Splits contigs into consecutive batches whose estimated memory (see
+/// [`Contig::estimated_memory`]) stays within `max_memory` bytes.
+///
+/// Every batch has at least one contig. A contig that alone exceeds the limit gets a
+/// batch of its own and a warning.
+pub fn partition_contigs_by_memory<'a>(
+    contigs: &[(&'a String, &'a Contig)],
+    max_memory: u64,
+) -> Vec<Vec<(&'a String, &'a Contig)>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_memory = 0;
+
+    for &(contig_id, contig) in contigs {
+        let memory = contig.estimated_memory();
+        if memory > max_memory {
+            warn!(
+                "Contig '{}' alone is estimated to need {:.2} GB, above the memory limit of {:.2} GB. Processing it on its own.",
+                contig_id,
+                memory as f64 / 1e9,
+                max_memory as f64 / 1e9
+            );
+        }
+
+        if !batch.is_empty() && batch_memory + memory > max_memory {
+            batches.push(std::mem::take(&mut batch));
+            batch_memory = 0;
+        }
+        batch.push((contig_id, contig));
+        batch_memory += memory;
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+fn pileup_codes(mod_types: &[ModType]) -> String {
+    mod_types
+        .iter()
+        .map(|mod_type| mod_type.to_pileup_code())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{contig::ESTIMATED_BYTES_PER_BASE, methylation::MethylationCoverage};
+    use crate::services::domain::warning_collector::enable_warning_collection;
+    use tempfile::NamedTempFile;
+
+    fn record(position: usize, strand: Strand, mod_type: ModType) -> MethylationRecord {
+        MethylationRecord::new(
+            "contig_1".to_string(),
+            position,
+            strand,
+            mod_type,
+            MethylationCoverage::new(5, 10, 0, 0, 0).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_populate_contig_skips_records_past_end_with_allow_mismatch() -> Result<()> {
+        let contig = Contig::from_string("contig_1".to_string(), "TGATCA".to_string())?;
+        let records = || {
+            vec![
+                record(2, Strand::Positive, ModType::SixMA),
+                record(6, Strand::Positive, ModType::SixMA),
+            ]
+        };
+
+        assert!(populate_contig_with_methylation(&contig, records(), None, false).is_err());
+
+        let populated = populate_contig_with_methylation(&contig, records(), None, true)?;
+        assert_eq!(populated.methylated_positions.len(), 1);
+        assert!(populated.methylated_positions.contains_key(&(
+            2,
+            Strand::Positive,
+            ModType::SixMA
+        )));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_populate_contig_warns_once_for_records_past_end() -> Result<()> {
+        // Other tests record warnings concurrently, so only the warnings of this contig
+        // are looked at.
+        let contig_id = "out_of_bounds_warning_test";
+        let contig = Contig::from_string(contig_id.to_string(), "TGATCA".to_string())?;
+        let records = (6..9)
+            .map(|position| record(position, Strand::Positive, ModType::SixMA))
+            .collect();
+
+        let collection = enable_warning_collection();
+        populate_contig_with_methylation(&contig, records, None, true)?;
+
+        let warnings_file = NamedTempFile::new()?;
+        collection.write_json(warnings_file.path())?;
+        let warnings: Vec<String> = std::fs::read_to_string(warnings_file.path())?
+            .lines()
+            .filter(|line| line.contains(contig_id))
+            .map(String::from)
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("Skipped 3 pileup records"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_inconsistent_mod_types() -> Result<()> {
+        // GATC at 1..5 and 7..11, TCCCG at 9..14.
+        let contig = Contig::from_string("contig_1".to_string(), "TGATCAGGATCCCGA".to_string())?;
+        let records = vec![
+            // Consistent: 6mA on both strands of the first GATC.
+            record(2, Strand::Positive, ModType::SixMA),
+            record(3, Strand::Negative, ModType::SixMA),
+            // Inconsistent: 6mA on plus, 5mC on minus of the second GATC.
+            record(8, Strand::Positive, ModType::SixMA),
+            record(9, Strand::Negative, ModType::FiveMC),
+            // TCCCG is not palindromic, so it is never checked.
+            record(10, Strand::Positive, ModType::FiveMC),
+            record(12, Strand::Negative, ModType::SixMA),
+        ];
+        let motifs = vec![Motif::new("GATC", "a", 1)?, Motif::new("TCCCG", "m", 1)?];
+
+        let contig =
+            populate_contig_with_methylation(&contig, records, Some(motifs.as_slice()), false)?;
+        let sites = find_inconsistent_mod_types(&contig, &motifs);
+
+        assert_eq!(
+            sites,
+            vec![InconsistentModTypeSite {
+                plus_position: 8,
+                minus_position: 9,
+                plus_mod_types: vec![ModType::SixMA],
+                minus_mod_types: vec![ModType::FiveMC],
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition_contigs_by_memory() -> Result<()> {
+        let ids: Vec<String> = (1..=4).map(|i| format!("contig_{}", i)).collect();
+        let contigs = [
+            Contig::from_string(ids[0].clone(), "A".repeat(10))?,
+            Contig::from_string(ids[1].clone(), "A".repeat(10))?,
+            Contig::from_string(ids[2].clone(), "A".repeat(50))?,
+            Contig::from_string(ids[3].clone(), "A".repeat(5))?,
+        ];
+        let pairs: Vec<(&String, &Contig)> = ids.iter().zip(contigs.iter()).collect();
+
+        let batches = partition_contigs_by_memory(&pairs, 25 * ESTIMATED_BYTES_PER_BASE);
+        let batch_ids: Vec<Vec<&str>> = batches
+            .iter()
+            .map(|batch| batch.iter().map(|(id, _)| id.as_str()).collect())
+            .collect();
+
+        // contig_3 exceeds the limit on its own and still gets a batch.
+        assert_eq!(
+            batch_ids,
+            vec![vec!["contig_1", "contig_2"], vec!["contig_3"], vec!["contig_4"]]
+        );
+
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
 pub mod contig_service;
 pub mod motif_processor;
+pub mod pileup_service;
 pub mod sequential_processer;
+pub mod warning_collector;
@@ -1,30 +1,240 @@
+pub use epimetheus_methylome::ModBaseMap;
 use epimetheus_methylome::Motif;
 use anyhow::Context;
-use std::str::FromStr;
+use log::{info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use crate::services::domain::warning_collector::{WarningEvent, WarningType, record_warning};
 
 
 pub fn create_motifs(motifs_str: &Vec<String>) -> anyhow::Result<Vec<Motif>> {
-    motifs_str.into_iter().map(|motif| {
-        let parts: Vec<&str> = motif.split("_").collect();
+    create_motifs_with_mod_base_map(motifs_str, &ModBaseMap::default())
+}
 
-        if parts.len() != 3 {
-            anyhow::bail!(
-                "Invalid motif format '{}' encountered. Expected format: '<sequence>_<mod_type>_<mod_position>'",
-                motif
+/// Like [`create_motifs`], but checks the base at each mod position against
+/// `mod_base_map` instead of the default bases.
+///
+/// Motifs given more than once (same sequence, mod type and mod position) are kept only
+/// at their first occurrence.
+pub fn create_motifs_with_mod_base_map(
+    motifs_str: &Vec<String>,
+    mod_base_map: &ModBaseMap,
+) -> anyhow::Result<Vec<Motif>> {
+    split_motif_args(motifs_str)
+    .iter()
+    .map(|motif| parse_motif(motif, mod_base_map))
+    .collect::<anyhow::Result<Vec<Motif>>>()
+    .map(dedup_motifs)
+    .map(|motifs| {
+        for (first, second) in find_reverse_complement_pairs(&motifs) {
+            let message = format!(
+                "Motif {}_{}_{} is the reverse complement of {}_{}_{}. Both describe the same sites. Use '--dedup-revcomp-motifs' to keep only the first.",
+                motifs[second].sequence_to_string(),
+                motifs[second].mod_type.to_pileup_code(),
+                motifs[second].mod_position,
+                motifs[first].sequence_to_string(),
+                motifs[first].mod_type.to_pileup_code(),
+                motifs[first].mod_position,
+            );
+            warn!("{}", message);
+            record_warning(WarningEvent::new(WarningType::InvalidArgument, message));
+        }
+        let mut partners: Vec<(&Motif, &Motif)> = canonical_bipartite_motifs(&motifs)
+            .into_iter()
+            .filter(|(motif, canonical)| motif != canonical)
+            .collect();
+        partners.sort();
+        for (motif, canonical) in partners {
+            info!(
+                "Motif {}_{}_{} is the bipartite partner of {}_{}_{}. Their methylation is combined and reported under {}_{}_{}.",
+                motif.sequence_to_string(),
+                motif.mod_type.to_pileup_code(),
+                motif.mod_position,
+                canonical.sequence_to_string(),
+                canonical.mod_type.to_pileup_code(),
+                canonical.mod_position,
+                canonical.sequence_to_string(),
+                canonical.mod_type.to_pileup_code(),
+                canonical.mod_position,
             );
         }
+        motifs
+    })
+}
+
+/// Drops motifs that were already seen, keeping the order of first occurrence.
+fn dedup_motifs(motifs: Vec<Motif>) -> Vec<Motif> {
+    let mut seen = HashSet::new();
+    motifs
+        .into_iter()
+        .filter(|motif| {
+            let first = seen.insert(motif.clone());
+            if !first {
+                warn!(
+                    "Motif {}_{}_{} was given more than once. Dropping the duplicate.",
+                    motif.sequence_to_string(),
+                    motif.mod_type.to_pileup_code(),
+                    motif.mod_position,
+                );
+            }
+            first
+        })
+        .collect()
+}
+
+/// Splits comma-separated motif arguments, so `["GATC_a_1,RGATCY_a_2", "CCWGG_m_1"]`
+/// gives three motifs. Whitespace around each motif and empty entries are dropped.
+pub fn split_motif_args(motifs_str: &[String]) -> Vec<String> {
+    motifs_str
+        .iter()
+        .flat_map(|arg| arg.split(','))
+        .map(str::trim)
+        .filter(|motif| !motif.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses a motif given as `<sequence>_<mod_type>_<mod_position>`.
+fn parse_motif(motif: &str, mod_base_map: &ModBaseMap) -> anyhow::Result<Motif> {
+    let parts: Vec<&str> = motif.split("_").collect();
+
+    if parts.len() != 3 {
+        anyhow::bail!(
+            "Invalid motif format '{}' encountered. Expected format: '<sequence>_<mod_type>_<mod_position>'",
+            motif
+        );
+    }
+
+    let sequence = parts[0];
+    let mod_type = parts[1];
+    let raw_position = i16::from_str(parts[2]).with_context(|| {
+        format!("Failed to parse mod_position '{}' in motif '{}'.", parts[2], motif)
+    })?;
+    let mod_position = resolve_mod_position(raw_position, sequence.len())
+        .with_context(|| format!("Invalid mod_position in motif '{}'", motif))?;
+
+    Motif::new_with_mod_base(sequence, mod_type, mod_position, mod_base_map)
+        .with_context(|| format!("Failed to create motif from '{}'", motif))
+}
+
+/// Checks every motif and returns the problems found as (motif, problem).
+///
+/// Unlike [`create_motifs`] this does not stop at the first invalid motif. A motif
+/// is invalid if it cannot match at all (a base that is not an IUPAC code), if the
+/// mod position is out of bounds or if the base at the mod position cannot carry the
+/// mod type according to `mod_base_map` (by default A for `a`, C for `m`, `h` and
+/// `21839`).
+pub fn check_motifs(motifs_str: &[String], mod_base_map: &ModBaseMap) -> Vec<(String, String)> {
+    split_motif_args(motifs_str)
+        .iter()
+        .filter_map(|motif| {
+            parse_motif(motif, mod_base_map)
+                .err()
+                .map(|e| (motif.clone(), format!("{:#}", e)))
+        })
+        .collect()
+}
+
+/// Finds pairs of motifs where one is the reverse complement of the other, including
+/// the mod position, so both measure the same modified base from opposite strands.
+/// Palindromic motifs are their own reverse complement and are never reported. Motifs
+/// with reverse complement sequences but another mod position, like bipartite partners
+/// (see [`Motif::is_bipartite_partner`]), measure different bases and are not pairs.
+///
+/// Returns the indices of each pair as (first occurrence, later occurrence).
+pub fn find_reverse_complement_pairs(motifs: &[Motif]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+
+    for (i, first) in motifs.iter().enumerate() {
+        if first.is_palindromic() {
+            continue;
+        }
+        let first_revcomp = first.reverse_complement();
+
+        for (j, second) in motifs.iter().enumerate().skip(i + 1) {
+            if *second == first_revcomp {
+                pairs.push((i, j));
+            }
+        }
+    }
+
+    pairs
+}
+
+/// Removes the later motif of each reverse complement pair. See [`find_reverse_complement_pairs`].
+pub fn dedup_reverse_complement_motifs(motifs: Vec<Motif>) -> Vec<Motif> {
+    let drop: HashSet<usize> = find_reverse_complement_pairs(&motifs)
+        .into_iter()
+        .map(|(_, later)| later)
+        .collect();
+
+    motifs
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !drop.contains(i))
+        .map(|(_, motif)| motif)
+        .collect()
+}
+
+/// Maps each bipartite motif given together with its partner (see
+/// [`Motif::is_bipartite_partner`]) to the motif their combined methylation is reported
+/// under: the one with the lexicographically smaller sequence. Other motifs are left out.
+pub fn canonical_bipartite_motifs(motifs: &[Motif]) -> HashMap<&Motif, &Motif> {
+    let mut canonical = HashMap::new();
+
+    for (i, first) in motifs.iter().enumerate() {
+        for second in motifs.iter().skip(i + 1) {
+            if first.is_bipartite_partner(second) {
+                let reported = if second.sequence_to_string() < first.sequence_to_string() {
+                    second
+                } else {
+                    first
+                };
+                canonical.insert(first, reported);
+                canonical.insert(second, reported);
+            }
+        }
+    }
 
-            let sequence = parts[0];
-            let mod_type = parts[1];
-            let mod_position = u8::from_str(parts[2]).with_context(|| {
-                format!("Failed to parse mod_position '{}' in motif '{}'.", parts[2], motif)
-            })?;
+    canonical
+}
+
+/// Replaces bipartite partners by the motif they are reported under (see
+/// [`canonical_bipartite_motifs`]), keeping the order of first occurrence.
+pub fn combine_bipartite_motifs(motifs: Vec<Motif>) -> Vec<Motif> {
+    let canonical = canonical_bipartite_motifs(&motifs);
+    let mut seen = HashSet::new();
 
-            Motif::new(sequence, mod_type, mod_position).with_context(|| {
-                format!("Failed to create motif from '{}'", motif)
-            })
-        
-    }).collect()
+    motifs
+        .iter()
+        .map(|motif| canonical.get(motif).copied().unwrap_or(motif))
+        .filter(|motif| seen.insert(*motif))
+        .cloned()
+        .collect()
+}
+
+/// Resolves a mod_position to a 0-based index from the 5' end.
+///
+/// Negative positions are counted from the 3' end, so `-1` is the last base of the motif.
+fn resolve_mod_position(position: i16, motif_length: usize) -> anyhow::Result<u8> {
+    let resolved = if position < 0 {
+        motif_length as i64 + position as i64
+    } else {
+        position as i64
+    };
+
+    if resolved < 0 || resolved >= motif_length as i64 {
+        anyhow::bail!(
+            "mod_position {} is out of bounds for motif of length {}",
+            position,
+            motif_length
+        );
+    }
+
+    u8::try_from(resolved).with_context(|| format!("mod_position {} does not fit in u8", resolved))
 }
 
 #[cfg(test)]
@@ -41,6 +251,170 @@ mod tests {
             result.err()
         );
     }
+
+    #[test]
+    fn test_create_motifs_five_hmc() {
+        let motifs = create_motifs(&vec!["GATC_h_3".to_string()]).unwrap();
+        assert_eq!(motifs[0].mod_type, epimetheus_methylome::ModType::FiveHMC);
+        assert_eq!(motifs[0].mod_position, 3);
+
+        let err = create_motifs(&vec!["GATC_x_3".to_string()]).unwrap_err();
+        assert!(format!("{:#}", err).contains("Supported mod types: a, m, 21839, h, C"));
+    }
+
+    #[test]
+    fn test_create_motifs_comma_separated() {
+        let motifs_args = vec!["GATC_a_1, RGATCY_a_2,".to_string(), "CCWGG_m_1".to_string()];
+        let motifs = create_motifs(&motifs_args).unwrap();
+        let motifs: Vec<String> = motifs
+            .iter()
+            .map(|m| format!("{}_{}_{}", m.sequence_to_string(), m.mod_type.to_pileup_code(), m.mod_position))
+            .collect();
+        assert_eq!(motifs, vec!["GATC_a_1", "RGATCY_a_2", "CCWGG_m_1"]);
+    }
+
+    #[test]
+    fn test_create_motifs_dedups_identical_motifs() {
+        let motifs = create_motifs(&vec!["GATC_a_1".to_string(), "GATC_a_1".to_string()]).unwrap();
+        assert_eq!(motifs, vec![Motif::new("GATC", "a", 1).unwrap()]);
+
+        // GATC_a_-3 resolves to GATC_a_1. Order of first occurrence is kept.
+        let motifs = create_motifs(&vec![
+            "CCWGG_m_1".to_string(),
+            "GATC_a_1,GATC_a_-3".to_string(),
+            "CCWGG_m_1".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            motifs,
+            vec![
+                Motif::new("CCWGG", "m", 1).unwrap(),
+                Motif::new("GATC", "a", 1).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_create_motifs_negative_mod_position() {
+        let motifs_args = vec!["GATC_a_-3".to_string(), "GATC_m_-1".to_string()];
+        let motifs = create_motifs(&motifs_args).unwrap();
+
+        assert_eq!(motifs[0], Motif::new("GATC", "a", 1).unwrap());
+        assert_eq!(motifs[1].mod_position, 3);
+    }
+
+    #[test]
+    fn test_resolve_mod_position() {
+        // GATC_a_-1 resolves to the last base, position 3.
+        assert_eq!(resolve_mod_position(-1, 4).unwrap(), 3);
+        assert_eq!(resolve_mod_position(-4, 4).unwrap(), 0);
+        assert_eq!(resolve_mod_position(2, 4).unwrap(), 2);
+        assert!(resolve_mod_position(4, 4).is_err());
+        assert!(resolve_mod_position(-5, 4).is_err());
+    }
+
+    #[test]
+    fn test_create_motifs_negative_mod_position_out_of_range() {
+        let motifs_args = vec!["GATC_a_-5".to_string()];
+        let result = create_motifs(&motifs_args);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_reverse_complement_pairs() {
+        let gatgc = Motif::new("GATGC", "a", 1).unwrap();
+        let motifs = vec![
+            gatgc.clone(),
+            Motif::new("GATC", "a", 1).unwrap(),
+            Motif::new("GCATC", "a", 2).unwrap(),
+            gatgc.reverse_complement(),
+            Motif::new("GCATC", "m", 1).unwrap(),
+        ];
+
+        // GCATC_a_2 has the reverse complement sequence of GATGC_a_1 but measures the A
+        // on the other strand. GATC is palindromic and the 5mC motif has another mod type.
+        assert_eq!(find_reverse_complement_pairs(&motifs), vec![(0, 3)]);
+
+        let deduped = dedup_reverse_complement_motifs(motifs);
+        assert_eq!(
+            deduped,
+            vec![
+                Motif::new("GATGC", "a", 1).unwrap(),
+                Motif::new("GATC", "a", 1).unwrap(),
+                Motif::new("GCATC", "a", 2).unwrap(),
+                Motif::new("GCATC", "m", 1).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bipartite_partners_are_combined() {
+        let motifs = create_motifs(&vec![
+            "CAAYNNNNCCT_a_2".to_string(),
+            "GATC_a_1".to_string(),
+            "AGGNNNNRTTG_a_0".to_string(),
+            "GATGC_a_1".to_string(),
+            "GCATC_a_2".to_string(),
+        ])
+        .unwrap();
+
+        // Neither the bipartite partners nor GATGC_a_1 and GCATC_a_2 measure the same
+        // base, so there are no reverse complement pairs.
+        assert!(find_reverse_complement_pairs(&motifs).is_empty());
+
+        let canonical = canonical_bipartite_motifs(&motifs);
+        assert_eq!(canonical.len(), 2);
+        assert_eq!(canonical[&motifs[0]], &motifs[2]);
+        assert_eq!(canonical[&motifs[2]], &motifs[2]);
+
+        assert_eq!(
+            combine_bipartite_motifs(motifs),
+            vec![
+                Motif::new("AGGNNNNRTTG", "a", 0).unwrap(),
+                Motif::new("GATC", "a", 1).unwrap(),
+                Motif::new("GATGC", "a", 1).unwrap(),
+                Motif::new("GCATC", "a", 2).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_motifs() {
+        let motifs = [
+            "GATC_a_1".to_string(),
+            "GATC_m_1".to_string(),
+            "GAXC_a_1".to_string(),
+            "GATC_a_7".to_string(),
+        ];
+        let problems = check_motifs(&motifs, &ModBaseMap::default());
+
+        let flagged: Vec<&str> = problems.iter().map(|(motif, _)| motif.as_str()).collect();
+        assert_eq!(flagged, vec!["GATC_m_1", "GAXC_a_1", "GATC_a_7"]);
+        assert!(problems[0].1.contains("points to base 'A' which is invalid for"));
+
+        // Mapping 'm' to A accepts GATC_m_1 and rejects 5mC on C.
+        let mod_base_map: ModBaseMap = "m:A".parse().unwrap();
+        let problems = check_motifs(
+            &["GATC_m_1".to_string(), "GATC_m_3".to_string()],
+            &mod_base_map,
+        );
+        let flagged: Vec<&str> = problems.iter().map(|(motif, _)| motif.as_str()).collect();
+        assert_eq!(flagged, vec!["GATC_m_3"]);
+
+        let motifs =
+            create_motifs_with_mod_base_map(&vec!["GATC_m_1".to_string()], &mod_base_map).unwrap();
+        assert_eq!(motifs[0].mod_position, 1);
+    }
+
+    #[test]
+    fn test_create_motifs_mod_position_past_end() {
+        let motifs_args = vec!["GATC_m_3".to_string(), "GATC_m_9".to_string()];
+        let err = create_motifs(&motifs_args).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("'GATC_m_9'"), "{}", message);
+        assert!(message.contains("out of bounds"), "{}", message);
+    }
+
     #[test]
     fn test_create_motifs_failure() {
         let motifs_args = vec!["GATC_a_3".to_string()];
@@ -0,0 +1,301 @@
+use ahash::AHashMap;
+use anyhow::Result;
+use epimetheus_methylome::{ModType, Motif, Strand};
+use log::warn;
+
+use crate::{
+    models::pileup::{ParseErrorMode, PileupRecord, PileupRecordString},
+    services::domain::warning_collector::{WarningEvent, WarningType, record_warning},
+};
+
+/// Parses a pileup line according to `mode`.
+///
+/// With [`ParseErrorMode::Fail`] a malformed line is returned as an error that
+/// includes the line. With [`ParseErrorMode::Skip`] it is logged and recorded as a
+/// warning, and `None` is returned so the caller can count it and continue.
+pub fn parse_pileup_record(
+    record: &PileupRecordString,
+    mode: ParseErrorMode,
+) -> Result<Option<PileupRecord>> {
+    match PileupRecord::try_from(record) {
+        Ok(pileup_record) => Ok(Some(pileup_record)),
+        Err(e) => {
+            let line = record.0.trim_end();
+            match mode {
+                ParseErrorMode::Fail => {
+                    Err(e.context(format!("Malformed pileup record: '{}'", line)))
+                }
+                ParseErrorMode::Skip => {
+                    let message = format!("Skipping malformed pileup record '{}': {}", line, e);
+                    warn!("{}", message);
+
+                    let mut event = WarningEvent::new(WarningType::MalformedRecord, message);
+                    if let Some(contig) = line.split('\t').next().filter(|c| !c.is_empty()) {
+                        event = event.with_contig(contig);
+                    }
+                    record_warning(event);
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+/// Pools 5mC (`m`) and 5hmC (`h`) records at the same cytosine into a single
+/// `C` record.
+///
+/// Both records share the valid coverage of the position, so the combined record
+/// keeps the valid coverage once and sums the modified counts. Calls of the
+/// pooled modification are removed from `n_other_mod`. Other modification types
+/// are returned unchanged. Record order is preserved.
+pub fn combine_cytosine_modifications(records: Vec<PileupRecord>) -> Vec<PileupRecord> {
+    let mut combined: Vec<PileupRecord> = Vec::with_capacity(records.len());
+    let mut index: AHashMap<(String, u32, Strand), usize> = AHashMap::new();
+
+    for mut record in records {
+        if !matches!(record.mod_type, ModType::FiveMC | ModType::FiveHMC) {
+            combined.push(record);
+            continue;
+        }
+
+        let key = (record.contig.clone(), record.start, record.strand);
+        match index.get(&key) {
+            Some(&i) => {
+                let existing = &mut combined[i];
+                existing.n_other_mod = existing.n_other_mod.saturating_sub(record.n_modified);
+                existing.n_modified += record.n_modified;
+                existing.n_valid_cov = existing.n_valid_cov.max(record.n_valid_cov);
+                existing.fraction_modified = percent_modified(existing);
+            }
+            None => {
+                record.mod_type = ModType::CombinedC;
+                index.insert(key, combined.len());
+                combined.push(record);
+            }
+        }
+    }
+
+    combined
+}
+
+/// Merges records of the same position, strand and mod type from several pileups
+/// (e.g. one per flowcell) into one record by summing the counts.
+///
+/// The score and valid coverage are summed as well and the fraction modified is
+/// recalculated. Positions present in only one pileup are kept unchanged. Record order
+/// is the order of first occurrence.
+pub fn merge_pileup_records(records: Vec<PileupRecord>) -> Vec<PileupRecord> {
+    let mut merged: Vec<PileupRecord> = Vec::with_capacity(records.len());
+    let mut index: AHashMap<(String, u32, Strand, ModType), usize> = AHashMap::new();
+
+    for record in records {
+        let key = (
+            record.contig.clone(),
+            record.start,
+            record.strand,
+            record.mod_type,
+        );
+        match index.get(&key) {
+            Some(&i) => {
+                let existing = &mut merged[i];
+                existing.score += record.score;
+                existing.n_valid_cov += record.n_valid_cov;
+                existing.n_modified += record.n_modified;
+                existing.n_canonical += record.n_canonical;
+                existing.n_other_mod += record.n_other_mod;
+                existing.n_delete += record.n_delete;
+                existing.n_fail += record.n_fail;
+                existing.n_diff += record.n_diff;
+                existing.n_no_call += record.n_no_call;
+                existing.fraction_modified = percent_modified(existing);
+            }
+            None => {
+                index.insert(key, merged.len());
+                merged.push(record);
+            }
+        }
+    }
+
+    merged
+}
+
+/// Maps 5mC and 5hmC motifs to the pooled `C` modification type, removing
+/// duplicates that result from the mapping.
+pub fn combine_cytosine_motifs(motifs: Vec<Motif>) -> Vec<Motif> {
+    let mut combined: Vec<Motif> = Vec::with_capacity(motifs.len());
+
+    for mut motif in motifs {
+        if matches!(motif.mod_type, ModType::FiveMC | ModType::FiveHMC) {
+            motif.mod_type = ModType::CombinedC;
+        }
+        if !combined.contains(&motif) {
+            combined.push(motif);
+        }
+    }
+
+    combined
+}
+
+fn percent_modified(record: &PileupRecord) -> f64 {
+    if record.n_valid_cov == 0 {
+        0.0
+    } else {
+        record.n_modified as f64 / record.n_valid_cov as f64 * 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(mod_type: ModType, start: u32, n_modified: u32, n_other_mod: u32) -> PileupRecord {
+        PileupRecord::new(
+            "contig_1".to_string(),
+            start,
+            start + 1,
+            mod_type,
+            20,
+            Strand::Positive,
+            start,
+            start + 1,
+            "255,0,0".to_string(),
+            20,
+            n_modified as f64 / 20.0 * 100.0,
+            n_modified,
+            20 - n_modified - n_other_mod,
+            n_other_mod,
+            0,
+            0,
+            0,
+            0,
+        )
+    }
+
+    #[test]
+    fn test_combine_cytosine_modifications() {
+        let records = vec![
+            record(ModType::FiveMC, 3, 8, 4),
+            record(ModType::FiveHMC, 3, 4, 8),
+            record(ModType::SixMA, 5, 10, 0),
+            record(ModType::FiveMC, 7, 6, 0),
+        ];
+
+        let combined = combine_cytosine_modifications(records);
+
+        assert_eq!(combined.len(), 3);
+
+        let both = &combined[0];
+        assert_eq!(both.mod_type, ModType::CombinedC);
+        assert_eq!(both.start, 3);
+        assert_eq!(both.n_modified, 12);
+        assert_eq!(both.n_valid_cov, 20);
+        assert_eq!(both.n_other_mod, 0);
+        assert_eq!(both.n_canonical, 8);
+        assert!((both.fraction_modified - 60.0).abs() < 1e-9);
+
+        assert_eq!(combined[1].mod_type, ModType::SixMA);
+        assert_eq!(combined[1].n_modified, 10);
+
+        assert_eq!(combined[2].mod_type, ModType::CombinedC);
+        assert_eq!(combined[2].n_modified, 6);
+    }
+
+    #[test]
+    fn test_merge_pileup_records() {
+        let records = vec![
+            record(ModType::SixMA, 3, 8, 0),
+            record(ModType::SixMA, 5, 10, 0),
+            record(ModType::SixMA, 3, 4, 2),
+            record(ModType::FiveMC, 3, 6, 0),
+        ];
+
+        let merged = merge_pileup_records(records);
+
+        assert_eq!(merged.len(), 3);
+
+        let both = &merged[0];
+        assert_eq!(both.mod_type, ModType::SixMA);
+        assert_eq!(both.start, 3);
+        assert_eq!(both.n_valid_cov, 40);
+        assert_eq!(both.n_modified, 12);
+        assert_eq!(both.n_canonical, 26);
+        assert_eq!(both.n_other_mod, 2);
+        assert_eq!(both.score, 40);
+        assert!((both.fraction_modified - 30.0).abs() < 1e-9);
+
+        assert_eq!(merged[1].start, 5);
+        assert_eq!(merged[1].n_valid_cov, 20);
+        assert_eq!(merged[2].mod_type, ModType::FiveMC);
+        assert_eq!(merged[2].n_modified, 6);
+    }
+
+    #[test]
+    fn test_parse_pileup_record_modes() {
+        let valid = PileupRecordString::new(
+            "contig_1\t3\t4\ta\t20\t+\t3\t4\t255,0,0\t20\t50.00\t10\t10\t0\t0\t0\t0\t0"
+                .to_string(),
+        );
+        let bad_strand = PileupRecordString::new(
+            "contig_1\t3\t4\ta\t20\tx\t3\t4\t255,0,0\t20\t50.00\t10\t10\t0\t0\t0\t0\t0"
+                .to_string(),
+        );
+        let truncated = PileupRecordString::new("contig_1\t3\t4\ta".to_string());
+
+        for mode in [ParseErrorMode::Fail, ParseErrorMode::Skip] {
+            let record = parse_pileup_record(&valid, mode).unwrap().unwrap();
+            assert_eq!(record.n_modified, 10);
+        }
+
+        let err = parse_pileup_record(&bad_strand, ParseErrorMode::Fail).unwrap_err();
+        assert!(err.to_string().contains("contig_1\t3\t4\ta\t20\tx"));
+        assert!(parse_pileup_record(&truncated, ParseErrorMode::Fail).is_err());
+
+        assert!(
+            parse_pileup_record(&bad_strand, ParseErrorMode::Skip)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            parse_pileup_record(&truncated, ParseErrorMode::Skip)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_parse_pileup_record_unknown_mod_type() {
+        let record = PileupRecordString::new(
+            "contig_1\t3\t4\tz\t20\t+\t3\t4\t255,0,0\t20\t50.00\t10\t10\t0\t0\t0\t0\t0"
+                .to_string(),
+        );
+
+        let err = parse_pileup_record(&record, ParseErrorMode::Fail).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(
+            message.contains(
+                "Unknown mod type 'z' at contig_1:3. Supported mod types: a, m, 21839, h, C"
+            ),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_combine_cytosine_motifs() {
+        let motifs = vec![
+            Motif::new("GATC", "m", 3).unwrap(),
+            Motif::new("GATC", "h", 3).unwrap(),
+            Motif::new("GATC", "a", 1).unwrap(),
+        ];
+
+        let combined = combine_cytosine_motifs(motifs);
+
+        assert_eq!(
+            combined,
+            vec![
+                Motif::new("GATC", "C", 3).unwrap(),
+                Motif::new("GATC", "a", 1).unwrap(),
+            ]
+        );
+    }
+}
@@ -10,7 +10,9 @@ use crate::{
     algorithms::methylation_pattern::calculate_contig_read_methylation_pattern,
     models::{
         genome_workspace::GenomeWorkspace,
-        methylation::{MethylationOutput, MethylationPatternVariant, MotifMethylationPositions},
+        methylation::{
+            MethylationOutput, MethylationPatternVariant, MotifMethylationPositions,
+        },
     },
     services::traits::BatchLoader,
 };
@@ -20,6 +22,7 @@ pub fn sequential_processer<L: BatchLoader<GenomeWorkspace>>(
     motifs: Vec<Motif>,
     threads: usize,
     output: &MethylationOutput,
+    call_threshold: f64,
 ) -> Result<MethylationPatternVariant> {
     let mut methylation_pattern_results: Vec<MethylationPatternVariant> = Vec::new();
 
@@ -37,18 +40,7 @@ pub fn sequential_processer<L: BatchLoader<GenomeWorkspace>>(
                         threads,
                     )?;
 
-                    let merged_results = match output {
-                        MethylationOutput::Raw => {
-                            MethylationPatternVariant::Raw(methylation_pattern)
-                        }
-                        MethylationOutput::Median => MethylationPatternVariant::Median(
-                            methylation_pattern.to_median_degrees(),
-                        ),
-
-                        MethylationOutput::WeightedMean => MethylationPatternVariant::WeightedMean(
-                            methylation_pattern.to_weighted_mean_degress(),
-                        ),
-                    };
+                    let merged_results = methylation_pattern.into_variant(output, call_threshold);
                     methylation_pattern_results.push(merged_results);
 
                     contigs_processed += contigs_in_batch;
@@ -113,6 +105,66 @@ pub fn sequential_processer<L: BatchLoader<GenomeWorkspace>>(
 
             MethylationPatternVariant::WeightedMean(collected)
         }
+
+        MethylationOutput::WeightedMedian => {
+            let collected = methylation_pattern_results
+                .into_par_iter()
+                .flat_map(|meth| {
+                    if let MethylationPatternVariant::WeightedMedian(weighted_median) = meth {
+                        weighted_median
+                    } else {
+                        Vec::new()
+                    }
+                })
+                .collect();
+
+            MethylationPatternVariant::WeightedMedian(collected)
+        }
+
+        MethylationOutput::TrimmedMean => {
+            let collected = methylation_pattern_results
+                .into_par_iter()
+                .flat_map(|meth| {
+                    if let MethylationPatternVariant::TrimmedMean(trimmed_mean) = meth {
+                        trimmed_mean
+                    } else {
+                        Vec::new()
+                    }
+                })
+                .collect();
+
+            MethylationPatternVariant::TrimmedMean(collected)
+        }
+
+        MethylationOutput::CalledFrequency => {
+            let collected = methylation_pattern_results
+                .into_par_iter()
+                .flat_map(|meth| {
+                    if let MethylationPatternVariant::CalledFrequency(called_frequency) = meth {
+                        called_frequency
+                    } else {
+                        Vec::new()
+                    }
+                })
+                .collect();
+
+            MethylationPatternVariant::CalledFrequency(collected)
+        }
+
+        MethylationOutput::StrandAsymmetry => {
+            let collected = methylation_pattern_results
+                .into_par_iter()
+                .flat_map(|meth| {
+                    if let MethylationPatternVariant::StrandAsymmetry(asymmetry) = meth {
+                        asymmetry
+                    } else {
+                        Vec::new()
+                    }
+                })
+                .collect();
+
+            MethylationPatternVariant::StrandAsymmetry(collected)
+        }
     };
 
     Ok(merged_results)
@@ -0,0 +1,184 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// Collected warnings. `None` means collection is disabled and events are dropped.
+static COLLECTOR: Mutex<Option<Vec<WarningEvent>>> = Mutex::new(None);
+/// Held by the active [`WarningCollection`], so only one collects at a time.
+static COLLECTION_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningType {
+    ContigMismatch,
+    FilteredRecords,
+    MalformedRecord,
+    InvalidArgument,
+    InconsistentModType,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WarningEvent {
+    #[serde(rename = "type")]
+    pub warning_type: WarningType,
+    pub message: String,
+    pub contig: Option<String>,
+    pub position: Option<usize>,
+}
+
+impl WarningEvent {
+    pub fn new(warning_type: WarningType, message: impl Into<String>) -> Self {
+        Self {
+            warning_type,
+            message: message.into(),
+            contig: None,
+            position: None,
+        }
+    }
+
+    pub fn with_contig(mut self, contig: impl Into<String>) -> Self {
+        self.contig = Some(contig.into());
+        self
+    }
+
+    pub fn with_position(mut self, position: usize) -> Self {
+        self.position = Some(position);
+        self
+    }
+}
+
+/// Handle of an active warning collection, returned by [`enable_warning_collection`].
+///
+/// Dropping the handle stops the collection and discards the warnings not written yet,
+/// so a later run in the same process (e.g. from Python) starts empty. A second
+/// collection waits until the first handle is dropped.
+pub struct WarningCollection {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl WarningCollection {
+    /// Write the warnings collected so far as JSON lines and clear them.
+    pub fn write_json(&self, path: &Path) -> Result<usize> {
+        let events = {
+            let mut collector = COLLECTOR.lock().expect("Warning collector lock poisoned");
+            collector.as_mut().map(std::mem::take).unwrap_or_default()
+        };
+        write_warnings_json(&events, path)?;
+
+        Ok(events.len())
+    }
+}
+
+impl Drop for WarningCollection {
+    fn drop(&mut self) {
+        *COLLECTOR.lock().expect("Warning collector lock poisoned") = None;
+    }
+}
+
+/// Start collecting warning events until the returned handle is dropped. Without an
+/// active collection `record_warning` is a no-op.
+pub fn enable_warning_collection() -> WarningCollection {
+    // A panic while a handle is alive poisons the lock. The collection is reset below,
+    // so the next one can start anyway.
+    let lock = COLLECTION_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    *COLLECTOR.lock().expect("Warning collector lock poisoned") = Some(Vec::new());
+
+    WarningCollection { _lock: lock }
+}
+
+/// Record a structured warning. This does not log anything, so call sites keep
+/// their regular `warn!` to leave the human readable log unchanged.
+pub fn record_warning(event: WarningEvent) {
+    let mut collector = COLLECTOR.lock().expect("Warning collector lock poisoned");
+    if let Some(events) = collector.as_mut() {
+        events.push(event);
+    }
+}
+
+/// Write warning events as JSON lines.
+pub fn write_warnings_json(events: &[WarningEvent], path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Could not create warnings file: {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    for event in events {
+        serde_json::to_writer(&mut writer, event)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_write_warnings_json() -> Result<()> {
+        let events = vec![
+            WarningEvent::new(WarningType::ContigMismatch, "Contig not found in assembly")
+                .with_contig("contig_5"),
+            WarningEvent::new(WarningType::MalformedRecord, "Could not parse line")
+                .with_contig("contig_3")
+                .with_position(12),
+        ];
+
+        let out = NamedTempFile::new()?;
+        write_warnings_json(&events, out.path())?;
+
+        let content = std::fs::read_to_string(out.path())?;
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"type":"contig_mismatch","message":"Contig not found in assembly","contig":"contig_5","position":null}"#
+        );
+        assert_eq!(
+            lines[1],
+            r#"{"type":"malformed_record","message":"Could not parse line","contig":"contig_3","position":12}"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_warnings_are_only_collected_while_the_handle_lives() -> Result<()> {
+        // Other tests record warnings concurrently, so only the events of this test
+        // are looked at.
+        let contig = "warning_collection_test";
+        let collected = |collection: &WarningCollection| -> Result<Vec<String>> {
+            let out = NamedTempFile::new()?;
+            collection.write_json(out.path())?;
+            Ok(std::fs::read_to_string(out.path())?
+                .lines()
+                .filter(|line| line.contains(contig))
+                .map(String::from)
+                .collect())
+        };
+        let record = |message: &str| {
+            record_warning(
+                WarningEvent::new(WarningType::InvalidArgument, message).with_contig(contig),
+            )
+        };
+
+        let collection = enable_warning_collection();
+        record("first run");
+        drop(collection);
+        record("between runs");
+
+        let collection = enable_warning_collection();
+        record("second run");
+        let lines = collected(&collection)?;
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("second run"));
+        assert!(collected(&collection)?.is_empty());
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use ahash::AHashMap;
 use anyhow::Result;
 
-use crate::models::contig::Contig;
+use crate::models::{contig::Contig, coverage_histogram::CoverageHistogram};
 
 pub trait BatchLoader<T> {
     fn new(
@@ -13,4 +13,36 @@ pub trait BatchLoader<T> {
         allow_mismatch: bool,
     ) -> Self;
     fn next_batch(&mut self) -> Option<Result<T>>;
+
+    /// Number of pileup contigs skipped because they are not in the assembly.
+    fn n_skipped_contigs(&self) -> usize {
+        0
+    }
+
+    /// Sorted ids of the pileup contigs skipped because they are not in the assembly.
+    fn skipped_contigs(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Number of pileup records of the contigs skipped because they are not in the
+    /// assembly.
+    fn n_skipped_records(&self) -> usize {
+        0
+    }
+
+    /// Number of pileup records removed by the coverage filters.
+    fn n_filtered_records(&self) -> usize {
+        0
+    }
+
+    /// Number of malformed pileup records skipped.
+    fn n_malformed_records(&self) -> usize {
+        0
+    }
+
+    /// Valid coverages of the pileup records of the assembly contigs, counted before the
+    /// coverage filters.
+    fn coverage_histogram(&self) -> CoverageHistogram {
+        CoverageHistogram::default()
+    }
 }
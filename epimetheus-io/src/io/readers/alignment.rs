@@ -0,0 +1,193 @@
+use anyhow::{Result, bail};
+use epimetheus_core::models::contig::ContigId;
+use epimetheus_methylome::read::Read;
+use std::path::Path;
+
+use crate::io::readers::{
+    bam::{BamReaderIndexed, ContigReads},
+    cram::CramReaderIndexed,
+};
+
+/// Indexed reader over aligned reads, either BAM or CRAM.
+pub enum AlignmentReader {
+    Bam(BamReaderIndexed),
+    Cram(CramReaderIndexed),
+}
+
+impl AlignmentReader {
+    /// Opens `path` as CRAM when it has a `.cram` extension and as BAM otherwise.
+    ///
+    /// CRAM input needs the `reference` FASTA to decode the read sequences.
+    pub fn from_path(path: &Path, reference: Option<&Path>) -> Result<Self> {
+        if !is_cram(path) {
+            return Ok(Self::Bam(BamReaderIndexed::new(path)?));
+        }
+
+        match reference {
+            Some(reference) => Ok(Self::Cram(CramReaderIndexed::new(path, reference)?)),
+            None => bail!(
+                "A reference FASTA is required to decode CRAM input: {}",
+                path.display()
+            ),
+        }
+    }
+
+    pub fn query_contigs(&mut self) -> Result<Vec<String>> {
+        match self {
+            Self::Bam(reader) => reader.query_contigs(),
+            Self::Cram(reader) => reader.query_contigs(),
+        }
+    }
+
+    pub fn query_contig_reads(&mut self, id: &ContigId) -> Result<Vec<Read>> {
+        match self {
+            Self::Bam(reader) => reader.query_contig_reads(id),
+            Self::Cram(reader) => reader.query_contig_reads(id),
+        }
+    }
+
+    /// Reads of a contig, skipping reads below `min_mapping_quality` before they count
+    /// towards `max_reads`. See [`BamReaderIndexed::query_contig_reads_capped`].
+    pub fn query_contig_reads_capped(
+        &mut self,
+        id: &ContigId,
+        max_reads: Option<usize>,
+        min_mapping_quality: u8,
+    ) -> Result<ContigReads> {
+        match self {
+            Self::Bam(reader) => {
+                reader.query_contig_reads_capped(id, max_reads, min_mapping_quality)
+            }
+            Self::Cram(reader) => {
+                reader.query_contig_reads_capped(id, max_reads, min_mapping_quality)
+            }
+        }
+    }
+}
+
+fn is_cram(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("cram"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epimetheus_methylome::read::MethBase;
+    use noodles_bam as bam;
+    use noodles_cram as cram;
+    use noodles_fasta as fasta;
+    use noodles_sam::alignment::io::Write as _;
+    use std::{fs::File, path::PathBuf};
+    use tempfile::TempDir;
+
+    const REFERENCE: &str =
+        "NC_000913.3_escherichia_coli_str_K_12_substr_MG1655_complete_genome.fasta";
+
+    /// Writes the first `n_records` of the BAM fixture as an indexed CRAM, next to an
+    /// indexed copy of the reference. Returns the CRAM and reference paths.
+    fn write_cram_fixture(dir: &Path, n_records: usize) -> Result<(PathBuf, PathBuf)> {
+        let data = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../epimetheus-cli/tests/data");
+
+        let reference = dir.join(REFERENCE);
+        std::fs::copy(data.join(REFERENCE), &reference)?;
+        let fai = fasta::fs::index(&reference)?;
+        let fai_path = dir.join(format!("{}.fai", REFERENCE));
+        let mut fai_writer = fasta::fai::io::Writer::new(File::create(fai_path)?);
+        fai_writer.write_index(&fai)?;
+
+        let repository = fasta::io::indexed_reader::Builder::default()
+            .build_from_path(&reference)
+            .map(fasta::repository::adapters::IndexedReader::new)
+            .map(fasta::Repository::new)?;
+
+        let mut bam_reader = File::open(data.join("barcode01_5x_coverage.bam"))
+            .map(bam::io::Reader::new)?;
+        let header = bam_reader.read_header()?;
+
+        let cram_path = dir.join("barcode01_5x_coverage.cram");
+        let mut writer = cram::io::writer::Builder::default()
+            .set_reference_sequence_repository(repository)
+            .build_from_path(&cram_path)?;
+        writer.write_alignment_header(&header)?;
+        for result in bam_reader.records().take(n_records) {
+            writer.write_alignment_record(&header, &result?)?;
+        }
+        writer.finish(&header)?;
+
+        let crai = cram::fs::index(&cram_path)?;
+        let crai_path = dir.join("barcode01_5x_coverage.cram.crai");
+        let mut crai_writer = cram::crai::io::Writer::new(File::create(crai_path)?);
+        crai_writer.write_index(&crai)?;
+
+        Ok((cram_path, reference))
+    }
+
+    /// Name, sequence and modifications by read position of each read.
+    fn decoded(reads: &[Read]) -> Vec<(String, String, Vec<(usize, MethBase)>)> {
+        reads
+            .iter()
+            .map(|read| {
+                let mut modifications: Vec<(usize, MethBase)> = read
+                    .get_modifications()
+                    .0
+                    .iter()
+                    .map(|(position, meth_base)| (*position, *meth_base))
+                    .collect();
+                modifications.sort_by_key(|(position, _)| *position);
+                (
+                    read.get_name().to_string(),
+                    read.get_sequence().to_string(),
+                    modifications,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cram_reads_match_bam_reads() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (cram_path, reference) = write_cram_fixture(temp_dir.path(), 200)?;
+
+        let mut cram_reader = AlignmentReader::from_path(&cram_path, Some(&reference))?;
+        let contigs = cram_reader.query_contigs()?;
+        assert_eq!(contigs, vec!["NC_000913.3".to_string()]);
+        let cram_reads = cram_reader.query_contig_reads(&contigs[0])?;
+        assert!(!cram_reads.is_empty());
+
+        // The CRAM holds the first records of the BAM, so the BAM reads are capped to match.
+        let bam_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../epimetheus-cli/tests/data/barcode01_5x_coverage.bam");
+        let mut bam_reader = AlignmentReader::from_path(&bam_path, None)?;
+        let bam_reads = bam_reader
+            .query_contig_reads_capped(&contigs[0], Some(cram_reads.len()), 0)?
+            .reads;
+
+        let cram_decoded = decoded(&cram_reads);
+        assert_eq!(cram_decoded, decoded(&bam_reads));
+        assert!(
+            cram_decoded
+                .iter()
+                .any(|(_, _, modifications)| !modifications.is_empty())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_cram() {
+        assert!(is_cram(Path::new("reads.cram")));
+        assert!(is_cram(Path::new("dir/reads.CRAM")));
+        assert!(!is_cram(Path::new("reads.bam")));
+        assert!(!is_cram(Path::new("reads")));
+    }
+
+    #[test]
+    fn test_cram_without_reference_errors() {
+        let err = AlignmentReader::from_path(Path::new("reads.cram"), None)
+            .err()
+            .expect("CRAM without a reference should fail");
+        assert!(err.to_string().contains("reference FASTA is required"));
+    }
+}
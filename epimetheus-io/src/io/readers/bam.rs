@@ -9,7 +9,7 @@ use epimetheus_methylome::{
     },
     sequence::Sequence,
 };
-use noodles_bam::{self as bam, record::Data};
+use noodles_bam as bam;
 use noodles_bgzf::{self as bgzf};
 use noodles_sam::Header;
 use noodles_sam::alignment::record::data::field::Tag;
@@ -43,91 +43,167 @@ impl BamReaderIndexed {
     }
 
     pub fn query_contig_reads(&mut self, id: &ContigId) -> Result<Vec<Read>> {
+        Ok(self.query_contig_reads_capped(id, None, 0)?.reads)
+    }
+
+    /// Reads of a contig, stopping once `max_reads` reads are collected.
+    ///
+    /// Secondary alignments and reads with a mapping quality below
+    /// `min_mapping_quality` are skipped before counting, so the cap applies to the
+    /// retained reads.
+    pub fn query_contig_reads_capped(
+        &mut self,
+        id: &ContigId,
+        max_reads: Option<usize>,
+        min_mapping_quality: u8,
+    ) -> Result<ContigReads> {
         let header = self.reader.read_header()?;
         let region = id.parse()?;
         let query = self.reader.query(&header, &region)?;
 
-        let mut reads = Vec::new();
-        for result in query.records() {
-            let record = result?;
-            let flags = record.flags();
-
-            if flags.is_secondary() {
-                continue;
-            }
-
-            let read_id = record.name().unwrap().to_string();
-
-            let strand = if flags.is_reverse_complemented() {
-                Strand::Negative
-            } else {
-                Strand::Positive
-            };
-            let bases: Vec<u8> = record.sequence().iter().collect();
-            let mut sequence = Sequence::from_u8(&bases).with_context(|| {
-                format!(
-                    "Could not parse sequence: {}",
-                    String::from_utf8_lossy(&bases)
-                )
-            })?;
-
-            sequence = match strand {
-                Strand::Positive => sequence,
-                Strand::Negative => sequence.reverse_complement(),
-            };
-
-            let alignment_start = if let Some(pos) = record.alignment_start() {
-                if let Ok(pos_ok) = pos {
-                    pos_ok.get() - 1
-                } else {
-                    0
-                }
-            } else {
-                return Err(anyhow!("{} not mapped to contig: {}", read_id, id));
-            };
-
-            let cigar = record.cigar();
-            let cigar_ops: Vec<Op> = cigar.iter().filter_map(|o| o.ok()).collect();
-            let mapping_quality = record.mapping_quality().map(|mq| mq.get()).unwrap_or(0);
-
-            let mapping = Some(ReadMapping::new(
-                id.clone(),
-                alignment_start,
-                strand,
-                cigar_ops,
-                mapping_quality,
-            ));
-
-            let data = record.data();
-            let mm_tags = extract_mm_tags(&data);
-            let ml_tag = extract_ml_tag(&data);
-
-            let meth_qualities = if let Some(ml) = ml_tag {
-                ml.iter()
-                    .map(|&s| MethQual::new(s))
-                    .collect::<Vec<MethQual>>()
-            } else {
-                Vec::new()
-            };
-
-            let modifications = if let Some(mm) = mm_tags {
-                let skip_distances =
-                    MethSkipDistances::from_meth_tags(mm.to_str()?, meth_qualities)?;
-                let modifications = convert_skip_distances_to_positions(&sequence, skip_distances)?;
-                modifications
-            } else {
-                BaseModifications::new()
-            };
-            let read = Read::new_with_mapping(read_id, sequence, modifications, mapping);
-
-            reads.push(read);
+        collect_contig_reads(query.records(), id, max_reads, min_mapping_quality)
+    }
+}
+
+/// The retained reads of a contig query. See [`collect_contig_reads`].
+#[derive(Debug)]
+pub struct ContigReads {
+    pub reads: Vec<Read>,
+    /// Whether the contig had more retained reads than the cap.
+    pub capped: bool,
+    /// Reads skipped for a mapping quality below the minimum, up to the cap.
+    pub n_low_mapping_quality: usize,
+}
+
+/// Collects the reads of a contig query, shared by the BAM and CRAM readers.
+///
+/// Secondary alignments and reads with a mapping quality below `min_mapping_quality`
+/// are skipped before counting towards `max_reads`. A missing mapping quality counts
+/// as 0. Reading stops at the first retained read past the cap.
+pub(crate) fn collect_contig_reads<R, I>(
+    records: I,
+    id: &ContigId,
+    max_reads: Option<usize>,
+    min_mapping_quality: u8,
+) -> Result<ContigReads>
+where
+    R: sam::alignment::Record,
+    I: IntoIterator<Item = std::io::Result<R>>,
+{
+    let mut contig_reads = ContigReads {
+        reads: Vec::new(),
+        capped: false,
+        n_low_mapping_quality: 0,
+    };
+    for result in records {
+        let record = result?;
+
+        if record.flags()?.is_secondary() {
+            continue;
+        }
+
+        if mapping_quality(&record) < min_mapping_quality {
+            contig_reads.n_low_mapping_quality += 1;
+            continue;
+        }
+
+        if max_reads.is_some_and(|max_reads| contig_reads.reads.len() >= max_reads) {
+            contig_reads.capped = true;
+            break;
         }
 
-        Ok(reads)
+        contig_reads.reads.push(read_from_record(&record, id)?);
     }
+
+    Ok(contig_reads)
+}
+
+fn mapping_quality<R: sam::alignment::Record + ?Sized>(record: &R) -> u8 {
+    record
+        .mapping_quality()
+        .and_then(|mq| mq.ok())
+        .map(|mq| mq.get())
+        .unwrap_or(0)
+}
+
+/// Builds a [`Read`] mapped to contig `id` from an alignment record, decoding the MM/ML tags.
+fn read_from_record<R: sam::alignment::Record + ?Sized>(record: &R, id: &ContigId) -> Result<Read> {
+    let read_id = record
+        .name()
+        .ok_or_else(|| anyhow!("Missing read id in record mapped to contig: {}", id))?
+        .to_string();
+
+    let strand = if record.flags()?.is_reverse_complemented() {
+        Strand::Negative
+    } else {
+        Strand::Positive
+    };
+    let bases: Vec<u8> = record.sequence().iter().collect();
+    let mut sequence = Sequence::from_u8(&bases).with_context(|| {
+        format!(
+            "Could not parse sequence: {}",
+            String::from_utf8_lossy(&bases)
+        )
+    })?;
+
+    sequence = match strand {
+        Strand::Positive => sequence,
+        Strand::Negative => sequence.reverse_complement(),
+    };
+
+    let alignment_start = if let Some(pos) = record.alignment_start() {
+        if let Ok(pos_ok) = pos {
+            pos_ok.get() - 1
+        } else {
+            0
+        }
+    } else {
+        return Err(anyhow!("{} not mapped to contig: {}", read_id, id));
+    };
+
+    let cigar = record.cigar();
+    let cigar_ops: Vec<Op> = cigar.iter().filter_map(|o| o.ok()).collect();
+    let mapping_quality = mapping_quality(record);
+
+    let mapping = Some(ReadMapping::new(
+        id.clone(),
+        alignment_start,
+        strand,
+        cigar_ops,
+        mapping_quality,
+    ));
+
+    let data = record.data();
+    let mm_tags = extract_mm_tags(&*data);
+    let ml_tag = extract_ml_tag(&*data);
+
+    let meth_qualities = if let Some(ml) = ml_tag {
+        ml.iter()
+            .map(|&s| MethQual::new(s))
+            .collect::<Vec<MethQual>>()
+    } else {
+        Vec::new()
+    };
+
+    let modifications = if let Some(mm) = mm_tags {
+        let skip_distances = MethSkipDistances::from_meth_tags(mm.to_str()?, meth_qualities)?;
+        convert_skip_distances_to_positions(&sequence, skip_distances)?
+    } else {
+        BaseModifications::new()
+    };
+
+    Ok(Read::new_with_mapping(
+        read_id,
+        sequence,
+        modifications,
+        mapping,
+    ))
 }
 
-pub fn extract_mm_tags<'a>(data: &'a Data) -> Option<&'a BStr> {
+pub fn extract_mm_tags<'a, D>(data: &'a D) -> Option<&'a BStr>
+where
+    D: sam::alignment::record::Data + ?Sized,
+{
     let mm_tags = data.get(&Tag::BASE_MODIFICATIONS).and_then(|value| {
         if let Ok(sam::alignment::record::data::field::Value::String(s)) = value {
             // Some(s.to_string())
@@ -140,7 +216,10 @@ pub fn extract_mm_tags<'a>(data: &'a Data) -> Option<&'a BStr> {
     mm_tags
 }
 
-pub fn extract_ml_tag(data: &Data) -> Option<Vec<u8>> {
+pub fn extract_ml_tag<D>(data: &D) -> Option<Vec<u8>>
+where
+    D: sam::alignment::record::Data + ?Sized,
+{
     let ml_tag = data
         .get(&Tag::BASE_MODIFICATION_PROBABILITIES)
         .and_then(|value| match value {
@@ -175,3 +254,62 @@ impl BamReader {
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use noodles_core::Position;
+    use noodles_sam::alignment::{
+        RecordBuf,
+        record::{Flags, MappingQuality, cigar::op::Kind},
+        record_buf::{Cigar, Sequence as SequenceBuf},
+    };
+
+    fn record(name: &str, mapping_quality: u8) -> std::io::Result<RecordBuf> {
+        Ok(RecordBuf::builder()
+            .set_name(name)
+            .set_flags(Flags::empty())
+            .set_alignment_start(Position::MIN)
+            .set_mapping_quality(MappingQuality::new(mapping_quality).unwrap())
+            .set_cigar(Cigar::from(vec![Op::new(Kind::Match, 4)]))
+            .set_sequence(SequenceBuf::from(b"GATC".to_vec()))
+            .build())
+    }
+
+    #[test]
+    fn test_cap_is_filled_with_reads_above_min_mapping_quality() -> Result<()> {
+        let records = vec![
+            record("low_1", 5),
+            record("high_1", 60),
+            record("low_2", 0),
+            record("high_2", 30),
+            record("high_3", 60),
+        ];
+
+        let contig_reads = collect_contig_reads(records, &"contig_1".to_string(), Some(2), 20)?;
+
+        let names: Vec<&str> = contig_reads
+            .reads
+            .iter()
+            .map(|read| read.get_name().as_str())
+            .collect();
+        assert_eq!(names, vec!["high_1", "high_2"]);
+        assert!(contig_reads.capped);
+        assert_eq!(contig_reads.n_low_mapping_quality, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_low_mapping_quality_reads_past_the_cap_do_not_cap() -> Result<()> {
+        let records = vec![record("high_1", 60), record("low_1", 5)];
+
+        let contig_reads = collect_contig_reads(records, &"contig_1".to_string(), Some(1), 20)?;
+
+        assert_eq!(contig_reads.reads.len(), 1);
+        assert!(!contig_reads.capped);
+        assert_eq!(contig_reads.n_low_mapping_quality, 1);
+
+        Ok(())
+    }
+}
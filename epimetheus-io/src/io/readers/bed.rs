@@ -1,9 +1,11 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use csv::ReaderBuilder;
+use epimetheus_core::models::pileup::{PileupRecord, PileupRecordString};
 use std::{
     fs::File,
     io::{BufRead, BufReader},
     ops::{Deref, DerefMut},
+    path::Path,
 };
 
 pub enum InputReader {
@@ -31,13 +33,33 @@ impl<R: BufRead> DerefMut for BedReader<R> {
 }
 
 impl<R: BufRead> BedReader<R> {
+    /// Reads a tab-delimited pileup without a header. Every record must have the same
+    /// number of columns.
     pub fn new(reader: R) -> Result<Self> {
         let csv_reader = ReaderBuilder::new()
             .delimiter(b'\t')
             .has_headers(false)
+            .flexible(false)
             .from_reader(reader);
         Ok(Self { inner: csv_reader })
     }
+
+    /// Iterates over the records parsed as [`PileupRecord`]s.
+    pub fn pileup_records(&mut self) -> impl Iterator<Item = Result<PileupRecord>> + '_ {
+        self.inner.records().map(|record| {
+            let record = record?;
+            let line = record.iter().collect::<Vec<&str>>().join("\t");
+            PileupRecord::try_from(PileupRecordString::new(line))
+        })
+    }
+}
+
+impl BedReader<BufReader<File>> {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open pileup at: {}", path.display()))?;
+        Self::new(BufReader::new(file))
+    }
 }
 
 pub struct LineReader<R: BufRead> {
@@ -53,3 +75,59 @@ impl<R: BufRead> LineReader<R> {
         self.inner.read_line(buf).map_err(Into::into)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epimetheus_methylome::{ModType, Strand};
+    use std::io::Write;
+
+    #[test]
+    fn test_bed_reader_from_path() -> Result<()> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        writeln!(
+            file,
+            "contig_3\t6\t7\ta\t133\t+\t6\t7\t255,0,0\t15\t40.00\t6\t9\t0\t0\t6\t0\t0"
+        )?;
+        writeln!(
+            file,
+            "contig_3\t8\t9\tm\t20\t-\t8\t9\t255,0,0\t20\t5.00\t1\t19\t0\t0\t0\t0\t0"
+        )?;
+        file.flush()?;
+
+        let mut reader = BedReader::from_path(file.path())?;
+        let records = reader.pileup_records().collect::<Result<Vec<_>>>()?;
+        assert_eq!(records.len(), 2);
+
+        let first = &records[0];
+        assert_eq!(first.contig, "contig_3");
+        assert_eq!(first.start, 6);
+        assert_eq!(first.end, 7);
+        assert_eq!(first.mod_type, ModType::SixMA);
+        assert_eq!(first.strand, Strand::Positive);
+        assert_eq!(first.n_valid_cov, 15);
+        assert_eq!(first.n_modified, 6);
+        assert_eq!(first.n_canonical, 9);
+        assert_eq!(first.n_fail, 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bed_reader_rejects_ragged_records() -> Result<()> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        writeln!(
+            file,
+            "contig_3\t6\t7\ta\t133\t+\t6\t7\t255,0,0\t15\t40.00\t6\t9\t0\t0\t6\t0\t0"
+        )?;
+        writeln!(file, "contig_3\t8\t9\tm")?;
+        file.flush()?;
+
+        let mut reader = BedReader::from_path(file.path())?;
+        let records: Vec<Result<PileupRecord>> = reader.pileup_records().collect();
+        assert!(records[0].is_ok());
+        assert!(records[1].is_err());
+
+        Ok(())
+    }
+}
@@ -1,26 +1,213 @@
-use anyhow::{Result, anyhow};
-use epimetheus_core::models::pileup::PileupRecordString;
+use anyhow::{Result, anyhow, bail};
+use epimetheus_core::{
+    models::pileup::PileupRecordString,
+    services::domain::warning_collector::{WarningEvent, WarningType, record_warning},
+};
+use log::warn;
 use noodles_bgzf::VirtualPosition;
 use noodles_bgzf::io::Reader as BgzfReader;
-use noodles_core::Region;
+use noodles_core::{Position, Region};
 use noodles_csi::io::IndexedReader;
 use noodles_csi::{BinningIndex, binning_index::Index};
 use std::{
+    collections::HashSet,
     fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
     path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
 };
 
-use crate::io::traits::PileupReader;
+use crate::io::traits::{PileupReader, record_start};
 
 pub struct Reader {
     reader: IndexedReader<BgzfReader<File>, Index<Vec<VirtualPosition>>>,
     records: Vec<PileupRecordString>,
     file_path: PathBuf,
+    allow_truncated: bool,
 }
 
 impl Clone for Reader {
     fn clone(&self) -> Self {
-        Self::from_path(&self.file_path).unwrap()
+        Self::from_path_with_truncation(&self.file_path, self.allow_truncated).unwrap()
+    }
+}
+
+/// Returns true when the tabix index of a .bed.gz was modified before the data file.
+pub fn index_is_stale(path: &Path) -> Result<bool> {
+    let index_path = PathBuf::from(format!("{}.tbi", path.display()));
+
+    let data_modified = std::fs::metadata(path)?.modified()?;
+    let index_modified = std::fs::metadata(&index_path)?.modified()?;
+
+    Ok(index_modified < data_modified)
+}
+
+/// Warns when the tabix index is older than the data file, or errors when `strict`.
+///
+/// A stale index can point to the wrong blocks and silently return incorrect
+/// records. Without `strict` each path is only warned about once.
+pub fn check_index_age(path: &Path, strict: bool) -> Result<()> {
+    static WARNED: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+
+    if !index_is_stale(path)? {
+        return Ok(());
+    }
+
+    let message = format!(
+        "The index of '{}' is older than the data file. Rebuild it with 'epimetheus bgzip compress'.",
+        path.display()
+    );
+    if strict {
+        bail!(message);
+    }
+
+    let newly_warned = WARNED
+        .get_or_init(|| Mutex::new(HashSet::new()))
+        .lock()
+        .map(|mut warned| warned.insert(path.to_path_buf()))
+        .unwrap_or(false);
+    if newly_warned {
+        warn!("{}", message);
+        record_warning(WarningEvent::new(WarningType::InvalidArgument, message));
+    }
+
+    Ok(())
+}
+
+/// The empty block every complete BGZF file ends with.
+pub const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02,
+    0x00, 0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Returns true when the file ends with the BGZF end-of-file block.
+pub fn has_bgzf_eof(path: &Path) -> Result<bool> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    if len < BGZF_EOF.len() as u64 {
+        return Ok(false);
+    }
+
+    let mut tail = [0u8; 28];
+    file.seek(SeekFrom::End(-(BGZF_EOF.len() as i64)))?;
+    file.read_exact(&mut tail)?;
+
+    Ok(tail == BGZF_EOF)
+}
+
+/// Errors when the BGZF end-of-file block is missing, or warns when `allow_truncated`.
+///
+/// A missing end-of-file block means the compression was interrupted, and queries
+/// would silently miss the records of the lost blocks. With `allow_truncated` each
+/// path is only warned about once.
+pub fn check_bgzf_eof(path: &Path, allow_truncated: bool) -> Result<()> {
+    static WARNED: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+
+    if has_bgzf_eof(path)? {
+        return Ok(());
+    }
+
+    let message = format!(
+        "'{}' has no BGZF end-of-file block. The file may be incomplete, e.g. from an interrupted compression. Use '--allow-truncated' to read it anyway.",
+        path.display()
+    );
+    if !allow_truncated {
+        bail!(message);
+    }
+
+    let newly_warned = WARNED
+        .get_or_init(|| Mutex::new(HashSet::new()))
+        .lock()
+        .map(|mut warned| warned.insert(path.to_path_buf()))
+        .unwrap_or(false);
+    if newly_warned {
+        warn!("{}", message);
+        record_warning(WarningEvent::new(WarningType::InvalidArgument, message));
+    }
+
+    Ok(())
+}
+
+/// Counts the BGZF blocks of a file, including the empty end-of-file block.
+///
+/// Only the block headers are read. Files produced by tools writing small blocks
+/// have many more blocks than needed, which slows down random access.
+pub fn count_bgzf_blocks(path: &Path) -> Result<usize> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut n_blocks = 0;
+
+    loop {
+        // ID1 ID2 CM FLG MTIME(4) XFL OS XLEN(2)
+        let mut header = [0u8; 12];
+        match file.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        if header[..2] != [0x1f, 0x8b] || header[3] & 0x04 == 0 {
+            bail!(
+                "'{}' is not a BGZF file (invalid block header at block {})",
+                path.display(),
+                n_blocks
+            );
+        }
+
+        let xlen = u16::from_le_bytes([header[10], header[11]]) as usize;
+        let mut extra = vec![0u8; xlen];
+        file.read_exact(&mut extra)?;
+
+        // The block size is stored in the 'BC' extra subfield.
+        let mut block_size = None;
+        let mut i = 0;
+        while i + 4 <= extra.len() {
+            let slen = u16::from_le_bytes([extra[i + 2], extra[i + 3]]) as usize;
+            if extra[i] == b'B' && extra[i + 1] == b'C' && slen == 2 && i + 6 <= extra.len() {
+                block_size = Some(u16::from_le_bytes([extra[i + 4], extra[i + 5]]) as usize + 1);
+            }
+            i += 4 + slen;
+        }
+        let Some(block_size) = block_size else {
+            bail!(
+                "'{}' is not a BGZF file (missing block size at block {})",
+                path.display(),
+                n_blocks
+            );
+        };
+
+        let remaining = block_size
+            .checked_sub(header.len() + xlen)
+            .ok_or_else(|| anyhow!("Invalid BGZF block size in '{}'", path.display()))?;
+        file.seek_relative(remaining as i64)?;
+        n_blocks += 1;
+    }
+
+    Ok(n_blocks)
+}
+
+/// Size of the tabix linear index windows (2^14).
+const LINEAR_INDEX_WINDOW: usize = 1 << 14;
+
+impl Reader {
+    fn max_end_from(&mut self, contig: &str, start: usize) -> Result<Option<u64>> {
+        let start = Position::try_from(start)?;
+        let region = Region::new(contig, start..);
+        let query = self
+            .reader
+            .query(&region)
+            .map_err(|e| anyhow!("Failed to fetch contig '{}': {}", contig, e.to_string()))?;
+
+        let mut max_end = None;
+        for record in query {
+            let record = record?;
+            let end = record
+                .as_ref()
+                .split('\t')
+                .nth(2)
+                .and_then(|end| end.parse::<u64>().ok());
+            max_end = max_end.max(end);
+        }
+
+        Ok(max_end)
     }
 }
 
@@ -66,6 +253,38 @@ impl PileupReader for Reader {
         Ok(std::mem::take(&mut self.records))
     }
 
+    /// Fetches only the index bins overlapping the region instead of the whole contig.
+    fn query_region(
+        &mut self,
+        contig: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<PileupRecordString>> {
+        let region = Region::new(
+            contig,
+            Position::try_from(start as usize + 1)?..=Position::try_from(end as usize)?,
+        );
+        let query = self.reader.query(&region).map_err(|e| {
+            anyhow!(
+                "Failed to fetch region '{}:{}-{}': {}",
+                contig,
+                start,
+                end,
+                e.to_string()
+            )
+        })?;
+
+        let mut records = Vec::new();
+        for record in query {
+            let record = PileupRecordString::new(record?.as_ref().to_string());
+            if record_start(&record).is_some_and(|s| s >= start && s < end) {
+                records.push(record);
+            }
+        }
+
+        Ok(records)
+    }
+
     fn available_contigs(&self) -> Vec<String> {
         let index = self
             .reader
@@ -79,10 +298,46 @@ impl PileupReader for Reader {
             .collect::<Vec<String>>()
     }
 
+    /// The tabix index does not store contig lengths. Instead the last linear index
+    /// window of each contig is queried and the largest record end is returned.
+    fn available_contigs_with_lengths(&mut self) -> Result<Vec<(String, Option<u64>)>> {
+        let n_windows: Vec<usize> = self
+            .reader
+            .index()
+            .reference_sequences()
+            .iter()
+            .map(|reference_sequence| reference_sequence.index().len())
+            .collect();
+
+        let mut contigs = Vec::new();
+        for (contig, n_windows) in self.available_contigs().into_iter().zip(n_windows) {
+            let max_end = if n_windows == 0 {
+                None
+            } else {
+                self.max_end_from(&contig, (n_windows - 1) * LINEAR_INDEX_WINDOW + 1)?
+            };
+            contigs.push((contig, max_end));
+        }
+
+        Ok(contigs)
+    }
+
     fn from_path(path: &Path) -> Result<Self>
     where
         Self: Sized,
     {
+        Self::from_path_with_truncation(path, false)
+    }
+
+    /// With `allow_truncated` a file without the BGZF end-of-file block is opened with
+    /// a warning instead of an error (see [`check_bgzf_eof`]).
+    fn from_path_with_truncation(path: &Path, allow_truncated: bool) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        check_index_age(path, false)?;
+        check_bgzf_eof(path, allow_truncated)?;
+
         let reader = noodles_tabix::io::indexed_reader::Builder::default()
             .build_from_path(path)
             .map_err(|e| anyhow!("Could not open file: {:?}. Error: {}", path, e.to_string()))?;
@@ -94,6 +349,84 @@ impl PileupReader for Reader {
             reader,
             records: Vec::with_capacity(500_000),
             file_path: path.to_path_buf(),
+            allow_truncated,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{io::readers::bed::InputReader, services::compression_service::CompressorService};
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_stale_index_is_detected() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let output_path = temp_dir.path().join("stale.bed.gz");
+
+        let lines = vec![
+            "contig_3\t0\t1\ta\t133\t+\t0\t1\t255,0,0\t15\t0.00\t15\t123\t0\t0\t6\t0\t0"
+                .to_string(),
+        ];
+        CompressorService::compress_pileup(
+            InputReader::Lines(lines.into_iter()),
+            Some(&output_path),
+        )?;
+
+        assert!(!index_is_stale(&output_path)?);
+        check_index_age(&output_path, true)?;
+
+        // Simulate the data being modified after the index was built.
+        File::options()
+            .write(true)
+            .open(&output_path)?
+            .set_modified(SystemTime::now() + Duration::from_secs(60))?;
+
+        assert!(index_is_stale(&output_path)?);
+        assert!(check_index_age(&output_path, true).is_err());
+        assert!(check_index_age(&output_path, false).is_ok());
+        assert!(Reader::from_path(&output_path).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_bgzf_is_detected() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let output_path = temp_dir.path().join("truncated.bed.gz");
+
+        let lines = vec![
+            "contig_3\t0\t1\ta\t133\t+\t0\t1\t255,0,0\t15\t0.00\t15\t123\t0\t0\t6\t0\t0"
+                .to_string(),
+        ];
+        CompressorService::compress_pileup(
+            InputReader::Lines(lines.into_iter()),
+            Some(&output_path),
+        )?;
+
+        assert!(has_bgzf_eof(&output_path)?);
+        check_bgzf_eof(&output_path, false)?;
+
+        // Simulate an interrupted compression by dropping the end-of-file block.
+        let len = std::fs::metadata(&output_path)?.len();
+        File::options()
+            .write(true)
+            .open(&output_path)?
+            .set_len(len - BGZF_EOF.len() as u64)?;
+
+        assert!(!has_bgzf_eof(&output_path)?);
+        let err = check_bgzf_eof(&output_path, false).unwrap_err();
+        assert!(err.to_string().contains("may be incomplete"));
+        assert!(check_bgzf_eof(&output_path, true).is_ok());
+        assert!(Reader::from_path(&output_path).is_err());
+
+        let mut reader = Reader::from_path_with_truncation(&output_path, true)?;
+        assert_eq!(reader.query_contig("contig_3")?.len(), 1);
+        assert_eq!(reader.clone().available_contigs(), vec!["contig_3".to_string()]);
+        // Allowing it for one reader does not allow it for the next.
+        assert!(Reader::from_path(&output_path).is_err());
+
+        Ok(())
+    }
+}
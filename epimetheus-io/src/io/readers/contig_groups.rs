@@ -0,0 +1,73 @@
+use ahash::AHashMap;
+use anyhow::{Context, Result, bail};
+use csv::ReaderBuilder;
+use std::path::Path;
+
+/// Reads a contig to group mapping from a TSV with the header columns `contig` and
+/// `group`. Additional columns are ignored.
+///
+/// A contig assigned to two different groups is an error.
+pub fn read_contig_groups(path: &Path) -> Result<AHashMap<String, String>> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(true)
+        .from_path(path)
+        .with_context(|| format!("Failed to open contig groups at: {:?}", path))?;
+
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .with_context(|| format!("Contig groups file is missing the '{}' column", name))
+    };
+    let contig_idx = column("contig")?;
+    let group_idx = column("group")?;
+
+    let mut groups = AHashMap::new();
+    for (line, result) in reader.records().enumerate() {
+        let record = result.with_context(|| "Error reading contig groups file")?;
+        let (Some(contig), Some(group)) = (record.get(contig_idx), record.get(group_idx)) else {
+            bail!("Missing contig or group on line {} of contig groups", line + 2);
+        };
+
+        if let Some(existing) = groups.insert(contig.to_string(), group.to_string()) {
+            if existing != group {
+                bail!(
+                    "Contig '{}' is assigned to both group '{}' and '{}'",
+                    contig,
+                    existing,
+                    group
+                );
+            }
+        }
+    }
+
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_read_contig_groups() -> Result<()> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        writeln!(file, "group\tcontig")?;
+        writeln!(file, "bin_1\tcontig_1")?;
+        writeln!(file, "bin_1\tcontig_2")?;
+        writeln!(file, "bin_2\tcontig_3")?;
+
+        let groups = read_contig_groups(file.path())?;
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups.get("contig_2").map(String::as_str), Some("bin_1"));
+        assert_eq!(groups.get("contig_3").map(String::as_str), Some("bin_2"));
+
+        writeln!(file, "bin_2\tcontig_1")?;
+        assert!(read_contig_groups(file.path()).is_err());
+
+        Ok(())
+    }
+}
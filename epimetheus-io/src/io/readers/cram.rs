@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use epimetheus_core::models::contig::ContigId;
+use epimetheus_methylome::read::Read;
+use noodles_cram as cram;
+use noodles_fasta as fasta;
+use std::{fs::File, path::Path};
+
+use crate::io::readers::bam::{ContigReads, collect_contig_reads};
+
+pub struct CramReaderIndexed {
+    reader: cram::io::IndexedReader<File>,
+}
+
+impl CramReaderIndexed {
+    /// Opens an indexed CRAM, decoding sequences against the indexed `reference` FASTA.
+    pub fn new(cram_path: &Path, reference: &Path) -> Result<Self> {
+        let fasta_reader = fasta::io::indexed_reader::Builder::default()
+            .build_from_path(reference)
+            .with_context(|| {
+                format!(
+                    "Could not read reference: {}. Did you remember to create the .fai index?",
+                    reference.display()
+                )
+            })?;
+        let repository = fasta::Repository::new(fasta::repository::adapters::IndexedReader::new(
+            fasta_reader,
+        ));
+
+        let reader = cram::io::indexed_reader::Builder::default()
+            .set_reference_sequence_repository(repository)
+            .build_from_path(cram_path)
+            .context("Could not build cram reader. Did you remember to create the index file?")?;
+
+        Ok(Self { reader })
+    }
+
+    pub fn query_contigs(&mut self) -> Result<Vec<String>> {
+        let header = self.reader.read_header()?;
+        let reference_sequences = header.reference_sequences();
+
+        let contigs = reference_sequences
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        Ok(contigs)
+    }
+
+    pub fn query_contig_reads(&mut self, id: &ContigId) -> Result<Vec<Read>> {
+        Ok(self.query_contig_reads_capped(id, None, 0)?.reads)
+    }
+
+    /// Reads of a contig, stopping once `max_reads` reads are collected.
+    ///
+    /// Secondary alignments and reads with a mapping quality below
+    /// `min_mapping_quality` are skipped before counting, as for BAM input.
+    pub fn query_contig_reads_capped(
+        &mut self,
+        id: &ContigId,
+        max_reads: Option<usize>,
+        min_mapping_quality: u8,
+    ) -> Result<ContigReads> {
+        let header = self.reader.read_header()?;
+        let region = id.parse()?;
+        let query = self.reader.query(&header, &region)?;
+
+        collect_contig_reads(query, id, max_reads, min_mapping_quality)
+    }
+}
@@ -1,25 +1,42 @@
 use anyhow::Context;
-use flate2::read::GzDecoder;
+use flate2::read::MultiGzDecoder;
 use epimetheus_methylome::read::Read;
 use noodles_fastq::{self as fastq};
 
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
 
 use crate::io::traits::FastqReader;
 
+/// Magic bytes starting every gzip member.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
 pub struct Reader;
 
+impl Reader {
+    /// Opens a fastq, decompressing it when it starts with the gzip magic bytes
+    /// regardless of the extension. Multi-member gzip (e.g. bgzip or concatenated
+    /// files) is read to the end.
+    fn open(path: &Path) -> anyhow::Result<Box<dyn BufRead>> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open fastq file: {}", path.display()))?;
+        let mut reader = BufReader::new(file);
+
+        let is_gzip = reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+        if is_gzip {
+            Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+        } else {
+            Ok(Box::new(reader))
+        }
+    }
+}
+
 impl FastqReader for Reader {
     fn read_fastq(path: &Path, read_filter: Option<Vec<String>>) -> anyhow::Result<Vec<Read>> {
-        let file = File::open(path)?;
-
-        let file: Box<dyn std::io::Read> =
-            if path.extension().and_then(|s| s.to_str()) == Some("gz") {
-                Box::new(GzDecoder::new(file))
-            } else {
-                Box::new(file)
-            };
-        let mut reader = fastq::io::Reader::new(BufReader::new(file));
+        let mut reader = fastq::io::Reader::new(Self::open(path)?);
 
         let mut reads = Vec::new();
         let num_reads_in_filter = if let Some(f) = &read_filter {
@@ -55,3 +72,56 @@ impl FastqReader for Reader {
         Ok(reads)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::{Read as _, Write};
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn names_and_sequences(reads: &[Read]) -> Vec<(String, String)> {
+        reads
+            .iter()
+            .map(|r| (r.get_name().clone(), r.get_sequence().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_read_gzipped_fastq_matches_uncompressed() -> anyhow::Result<()> {
+        let gz_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../epimetheus-cli/tests/data/barcode01_5x_coverage.fastq.gz");
+        let mut plain = Vec::new();
+        MultiGzDecoder::new(File::open(&gz_path)?).read_to_end(&mut plain)?;
+
+        let temp_dir = tempfile::tempdir()?;
+        let plain_path = temp_dir.path().join("reads.fastq");
+        std::fs::write(&plain_path, &plain)?;
+
+        // Gzip without a .gz extension, split over two gzip members at a record boundary.
+        let lines: Vec<&[u8]> = plain.split_inclusive(|b| *b == b'\n').collect();
+        let split = (lines.len() / 8) * 4;
+        let mut multi_member = gzip(&lines[..split].concat());
+        multi_member.extend(gzip(&lines[split..].concat()));
+        let multi_member_path = temp_dir.path().join("reads.fq");
+        std::fs::write(&multi_member_path, multi_member)?;
+
+        let expected = names_and_sequences(&Reader::read_fastq(&plain_path, None)?);
+        assert!(!expected.is_empty());
+        assert_eq!(
+            names_and_sequences(&Reader::read_fastq(&gz_path, None)?),
+            expected
+        );
+        assert_eq!(
+            names_and_sequences(&Reader::read_fastq(&multi_member_path, None)?),
+            expected
+        );
+
+        Ok(())
+    }
+}
@@ -1,5 +1,9 @@
+pub mod alignment;
 pub mod bam;
 pub mod bed;
 pub mod bgzf_bed;
+pub mod contig_groups;
+pub mod cram;
 pub mod fasta;
 pub mod fastq;
+pub mod regions;
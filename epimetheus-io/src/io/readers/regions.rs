@@ -0,0 +1,219 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    str::FromStr,
+};
+
+/// A 0-based, half-open region `[start, end)` on a contig, as in BED.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BedRegion {
+    pub contig: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// A whole contig or a region of a contig to extract from a pileup.
+///
+/// Parsed from `contig:start-end` as a region with 1-based, inclusive coordinates, as
+/// in samtools. Anything else is a contig name, so contigs whose name ends in
+/// `:<number>-<number>` can only be queried as regions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PileupQuery {
+    Contig(String),
+    Region(BedRegion),
+}
+
+impl FromStr for PileupQuery {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((contig, range)) = s.rsplit_once(':') else {
+            return Ok(Self::Contig(s.to_string()));
+        };
+        let Some((start, end)) = range.split_once('-') else {
+            return Ok(Self::Contig(s.to_string()));
+        };
+        let (Ok(start), Ok(end)) = (start.parse::<u64>(), end.parse::<u64>()) else {
+            return Ok(Self::Contig(s.to_string()));
+        };
+
+        if contig.is_empty() {
+            bail!("Missing contig in region '{}'", s);
+        }
+        if start == 0 || start > end {
+            bail!(
+                "Invalid region '{}'. Expected 1-based coordinates with start <= end",
+                s
+            );
+        }
+
+        Ok(Self::Region(BedRegion {
+            contig: contig.to_string(),
+            start: start - 1,
+            end,
+        }))
+    }
+}
+
+/// Reads regions from the first three columns of a BED file. Additional columns are
+/// ignored, as are empty lines and `#`, `track` and `browser` header lines.
+pub fn read_regions(path: &Path) -> Result<Vec<BedRegion>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open regions file at: {:?}", path))?;
+
+    let mut regions = Vec::new();
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let trimmed = line.trim();
+        if trimmed.is_empty()
+            || trimmed.starts_with('#')
+            || trimmed.starts_with("track")
+            || trimmed.starts_with("browser")
+        {
+            continue;
+        }
+
+        let fields: Vec<&str> = trimmed.split('\t').collect();
+        if fields.len() < 3 {
+            bail!(
+                "Expected at least 3 columns on line {} of regions file, got {}",
+                line_number + 1,
+                fields.len()
+            );
+        }
+        let parse = |value: &str, column: &str| {
+            value.parse::<u64>().with_context(|| {
+                format!(
+                    "Invalid {} '{}' on line {} of regions file",
+                    column,
+                    value,
+                    line_number + 1
+                )
+            })
+        };
+        let start = parse(fields[1], "start")?;
+        let end = parse(fields[2], "end")?;
+        if start >= end {
+            bail!(
+                "Region start {} must be before end {} on line {} of regions file",
+                start,
+                end,
+                line_number + 1
+            );
+        }
+
+        regions.push(BedRegion {
+            contig: fields[0].to_string(),
+            start,
+            end,
+        });
+    }
+
+    Ok(regions)
+}
+
+/// Sorts regions by contig and start and merges overlapping or touching regions on
+/// the same contig, so each position is covered by at most one region.
+pub fn merge_regions(mut regions: Vec<BedRegion>) -> Vec<BedRegion> {
+    regions.sort_by(|a, b| (&a.contig, a.start).cmp(&(&b.contig, b.start)));
+
+    let mut merged: Vec<BedRegion> = Vec::with_capacity(regions.len());
+    for region in regions {
+        match merged.last_mut() {
+            Some(last) if last.contig == region.contig && region.start <= last.end => {
+                last.end = last.end.max(region.end);
+            }
+            _ => merged.push(region),
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn region(contig: &str, start: u64, end: u64) -> BedRegion {
+        BedRegion {
+            contig: contig.to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_read_regions() -> Result<()> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        writeln!(file, "track name=targets")?;
+        writeln!(file, "# comment")?;
+        writeln!(file, "contig_1\t10\t20\tname_1")?;
+        writeln!(file)?;
+        writeln!(file, "contig_2\t0\t5")?;
+        file.flush()?;
+
+        let regions = read_regions(file.path())?;
+        assert_eq!(regions, vec![region("contig_1", 10, 20), region("contig_2", 0, 5)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_regions_rejects_empty_region() -> Result<()> {
+        let mut file = tempfile::NamedTempFile::new()?;
+        writeln!(file, "contig_1\t20\t20")?;
+        file.flush()?;
+
+        let err = read_regions(file.path()).unwrap_err();
+        assert!(err.to_string().contains("must be before end"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_pileup_query() -> Result<()> {
+        assert_eq!(
+            "contig_1".parse::<PileupQuery>()?,
+            PileupQuery::Contig("contig_1".to_string())
+        );
+        assert_eq!(
+            "contig_1:11-20".parse::<PileupQuery>()?,
+            PileupQuery::Region(region("contig_1", 10, 20))
+        );
+        assert_eq!(
+            "HLA:A:1-5".parse::<PileupQuery>()?,
+            PileupQuery::Region(region("HLA:A", 0, 5))
+        );
+        assert_eq!(
+            "contig:x-20".parse::<PileupQuery>()?,
+            PileupQuery::Contig("contig:x-20".to_string())
+        );
+        assert!("contig_1:0-20".parse::<PileupQuery>().is_err());
+        assert!("contig_1:20-10".parse::<PileupQuery>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_regions() {
+        let merged = merge_regions(vec![
+            region("contig_2", 0, 5),
+            region("contig_1", 15, 30),
+            region("contig_1", 10, 20),
+            region("contig_1", 30, 40),
+            region("contig_1", 50, 60),
+        ]);
+
+        assert_eq!(
+            merged,
+            vec![
+                region("contig_1", 10, 40),
+                region("contig_1", 50, 60),
+                region("contig_2", 0, 5),
+            ]
+        );
+    }
+}
@@ -9,8 +9,44 @@ pub trait PileupReader {
     fn from_path(path: &Path) -> Result<Self>
     where
         Self: Sized;
+
+    /// Opens the pileup like [`PileupReader::from_path`]. With `allow_truncated` a
+    /// pileup without its end-of-file marker is read with a warning instead of an
+    /// error, for formats that have one. By default the flag is ignored.
+    fn from_path_with_truncation(path: &Path, allow_truncated: bool) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let _ = allow_truncated;
+        Self::from_path(path)
+    }
+
     fn query_contig(&mut self, contig: &str) -> Result<Vec<PileupRecordString>>;
     fn available_contigs(&self) -> Vec<String>;
+
+    /// Records starting within the 0-based, half-open region `[start, end)` of a
+    /// contig. By default the whole contig is queried and filtered.
+    fn query_region(
+        &mut self,
+        contig: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<PileupRecordString>> {
+        Ok(self
+            .query_contig(contig)?
+            .into_iter()
+            .filter(|record| record_start(record).is_some_and(|s| s >= start && s < end))
+            .collect())
+    }
+
+    /// Contig names with their max indexed end coordinate, when the index allows it.
+    fn available_contigs_with_lengths(&mut self) -> Result<Vec<(String, Option<u64>)>> {
+        Ok(self
+            .available_contigs()
+            .into_iter()
+            .map(|contig| (contig, None))
+            .collect())
+    }
 }
 
 impl PileupReader for Box<dyn PileupReader> {
@@ -28,6 +64,28 @@ impl PileupReader for Box<dyn PileupReader> {
     fn available_contigs(&self) -> Vec<String> {
         (**self).available_contigs()
     }
+
+    fn query_region(
+        &mut self,
+        contig: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<PileupRecordString>> {
+        (**self).query_region(contig, start, end)
+    }
+
+    fn available_contigs_with_lengths(&mut self) -> Result<Vec<(String, Option<u64>)>> {
+        (**self).available_contigs_with_lengths()
+    }
+}
+
+/// The 0-based start coordinate (second column) of a pileup record.
+pub(crate) fn record_start(record: &PileupRecordString) -> Option<u64> {
+    record
+        .0
+        .split('\t')
+        .nth(1)
+        .and_then(|start| start.parse().ok())
 }
 
 pub trait FastaReader {
@@ -1,16 +1,40 @@
-use anyhow::Result;
-use epimetheus_core::models::pileup::{PileupRecord, PileupRecordString};
-use noodles_bgzf::{self as bgzf};
+use anyhow::{Context, Result, anyhow, bail};
+use epimetheus_core::{
+    models::pileup::{PileupRecord, PileupRecordString},
+    services::domain::warning_collector::{WarningEvent, WarningType, record_warning},
+};
+use log::warn;
+use noodles_bgzf::{self as bgzf, VirtualPosition};
 use noodles_core::Position;
 use noodles_csi::{self as csi, binning_index::index::reference_sequence::bin::Chunk};
 use noodles_tabix as tabix;
 use std::{
     fs::File,
-    io::{BufRead, BufWriter, Write},
-    path::Path,
+    io::{BufRead, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::io::readers::{
+    bed::LineReader,
+    bgzf_bed::{BGZF_EOF, has_bgzf_eof},
 };
 
-use crate::io::readers::bed::LineReader;
+/// BGZF compression level used when none is given. Level 6 is the deflate default.
+pub const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
+
+/// Maps a compression level from 0 (no compression) to 9 (best compression) to the
+/// BGZF compression level.
+fn compression_level(level: u32) -> Result<bgzf::io::writer::CompressionLevel> {
+    if level > 9 {
+        bail!(
+            "Compression level should be between 0 and 9. Got: {}",
+            level
+        );
+    }
+    bgzf::io::writer::CompressionLevel::try_from(level as u8)
+        .map_err(|e| anyhow!("Invalid compression level {}: {}", level, e))
+}
 
 pub enum WriterType {
     File(Writer<File>),
@@ -19,16 +43,32 @@ pub enum WriterType {
 
 impl WriterType {
     pub fn compress_from_reader<R: BufRead>(&mut self, reader: LineReader<R>) -> Result<()> {
+        self.compress_from_reader_with_progress(reader, |_| {})
+    }
+
+    pub fn compress_from_reader_with_progress<R: BufRead, F: FnMut(usize)>(
+        &mut self,
+        reader: LineReader<R>,
+        on_line: F,
+    ) -> Result<()> {
         match self {
-            WriterType::File(w) => w.compress_from_reader(reader),
-            WriterType::StdOut(w) => w.compress_from_reader(reader),
+            WriterType::File(w) => w.compress_from_reader_with_progress(reader, on_line),
+            WriterType::StdOut(w) => w.compress_from_reader_with_progress(reader, on_line),
         }
     }
 
     pub fn compress_from_lines(&mut self, lines: std::vec::IntoIter<String>) -> Result<()> {
+        self.compress_from_lines_with_progress(lines, |_| {})
+    }
+
+    pub fn compress_from_lines_with_progress<F: FnMut(usize)>(
+        &mut self,
+        lines: std::vec::IntoIter<String>,
+        on_line: F,
+    ) -> Result<()> {
         match self {
-            WriterType::File(w) => w.compress_from_lines(lines),
-            WriterType::StdOut(w) => w.compress_from_lines(lines),
+            WriterType::File(w) => w.compress_from_lines_with_progress(lines, on_line),
+            WriterType::StdOut(w) => w.compress_from_lines_with_progress(lines, on_line),
         }
     }
 
@@ -47,48 +87,179 @@ impl WriterType {
     }
 }
 
+/// Builds a tabix index from pileup records and the virtual positions of their lines
+/// in a BGZF file.
+pub struct PileupIndexer {
+    indexer: tabix::index::Indexer,
+    zero_length_records: usize,
+}
+
+impl PileupIndexer {
+    pub fn new() -> Self {
+        let mut indexer = tabix::index::Indexer::default();
+        indexer.set_header(csi::binning_index::index::header::Builder::bed().build());
+
+        Self {
+            indexer,
+            zero_length_records: 0,
+        }
+    }
+
+    /// Adds a record stored between the virtual positions `start_position` and
+    /// `end_position`.
+    pub fn add_record(
+        &mut self,
+        record: &PileupRecord,
+        start_position: VirtualPosition,
+        end_position: VirtualPosition,
+    ) -> Result<()> {
+        let start_val = record.start as usize;
+        let start = if start_val == 0 {
+            Position::MIN
+        } else {
+            Position::try_from(start_val)?
+        };
+
+        // A zero-length feature breaks the half-open BED interval. Index it as the
+        // single base [start, start + 1).
+        let end_val = if record.end == record.start {
+            self.zero_length_records += 1;
+            start_val + 1
+        } else {
+            record.end as usize
+        };
+        let end = Position::try_from(end_val)?;
+
+        let chunk = Chunk::new(start_position, end_position);
+
+        self.indexer.add_record(&record.contig, start, end, chunk)?;
+        Ok(())
+    }
+
+    /// Adds the records read from `reader` at their virtual positions. Blank lines are
+    /// skipped. Returns the number of added records.
+    pub fn add_records_from_reader<R: Read>(
+        &mut self,
+        reader: &mut bgzf::io::Reader<R>,
+    ) -> Result<usize> {
+        let mut line = String::new();
+        let mut n_lines = 0;
+        let mut n_records = 0;
+        loop {
+            let start_position = reader.virtual_position();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let end_position = reader.virtual_position();
+            n_lines += 1;
+
+            if !line.trim().is_empty() {
+                let record = PileupRecord::try_from(PileupRecordString::new(line.clone()))
+                    .with_context(|| format!("Invalid pileup record on line {}", n_lines))?;
+                self.add_record(&record, start_position, end_position)?;
+                n_records += 1;
+            }
+            line.clear();
+        }
+
+        Ok(n_records)
+    }
+
+    pub fn write(self, path: &Path) -> Result<()> {
+        assert_eq!(path.extension().unwrap(), "tbi");
+        let mut tabix_writer = File::create(path).map(tabix::io::Writer::new)?;
+
+        if self.zero_length_records > 0 {
+            let message = format!(
+                "Indexed {} records with start == end as single-base features",
+                self.zero_length_records
+            );
+            warn!("{}", message);
+            record_warning(WarningEvent::new(WarningType::MalformedRecord, message));
+        }
+
+        let index = self.indexer.build();
+        tabix_writer.write_index(&index)?;
+
+        Ok(())
+    }
+}
+
+impl Default for PileupIndexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct Writer<W: Write> {
     writer: bgzf::io::Writer<W>,
-    indexer: Option<tabix::index::Indexer>,
+    indexer: Option<PileupIndexer>,
+    tabix_path: Option<PathBuf>,
+    /// Compressed size of the data written before the writer was opened, see
+    /// [`Writer::reopen`].
+    offset: u64,
 }
 
 impl<W: Write> Writer<W> {
+    /// The virtual position in the output, including the data written before the writer
+    /// was opened.
+    fn virtual_position(&self) -> Result<VirtualPosition> {
+        let position = self.writer.virtual_position();
+        Ok(VirtualPosition::try_from((
+            self.offset + position.compressed(),
+            position.uncompressed(),
+        ))?)
+    }
+
     pub fn write_pileup_record(&mut self, record: &PileupRecord) -> Result<()> {
-        let line = format!("{}\n", record);
+        // A zero-length record is written with the end it is indexed with, so the
+        // tabix query can read it back.
+        let line = if record.end == record.start {
+            let record = PileupRecord {
+                end: record.start + 1,
+                ..record.clone()
+            };
+            format!("{}\n", record)
+        } else {
+            format!("{}\n", record)
+        };
         let bytes = line.as_bytes();
 
-        let start_position = self.writer.virtual_position();
+        let start_position = self.virtual_position()?;
 
         self.writer.write_all(bytes)?;
-        let end_position = self.writer.virtual_position();
+        let end_position = self.virtual_position()?;
 
         if let Some(ref mut indexer) = self.indexer {
-            let start_val = record.start as usize;
-            let start = if start_val == 0 {
-                Position::MIN
-            } else {
-                Position::try_from(start_val)?
-            };
-
-            let end_val = record.end as usize;
-            let end = Position::try_from(end_val)?;
-
-            let chunk = Chunk::new(start_position, end_position);
-
-            indexer.add_record(&record.contig, start, end, chunk)?;
+            indexer.add_record(record, start_position, end_position)?;
         }
 
         Ok(())
     }
 
-    pub fn compress_from_reader<R: BufRead>(&mut self, mut reader: LineReader<R>) -> Result<()> {
+    pub fn compress_from_reader<R: BufRead>(&mut self, reader: LineReader<R>) -> Result<()> {
+        self.compress_from_reader_with_progress(reader, |_| {})
+    }
+
+    /// Like [`Self::compress_from_reader`], calling `on_line` with the number of bytes
+    /// read after each line is written.
+    pub fn compress_from_reader_with_progress<R: BufRead, F: FnMut(usize)>(
+        &mut self,
+        mut reader: LineReader<R>,
+        mut on_line: F,
+    ) -> Result<()> {
         let mut line = String::new();
 
-        while reader.read_line(&mut line)? > 0 {
+        loop {
+            let n_bytes = reader.read_line(&mut line)?;
+            if n_bytes == 0 {
+                break;
+            }
             let record_string = PileupRecordString::new(line.clone());
             let record = PileupRecord::try_from(record_string)?;
 
             self.write_pileup_record(&record)?;
+            on_line(n_bytes);
             line.clear();
         }
 
@@ -96,55 +267,135 @@ impl<W: Write> Writer<W> {
     }
 
     pub fn compress_from_lines(&mut self, lines: std::vec::IntoIter<String>) -> Result<()> {
+        self.compress_from_lines_with_progress(lines, |_| {})
+    }
+
+    /// Like [`Self::compress_from_lines`], calling `on_line` with the length of each
+    /// line after it is written.
+    pub fn compress_from_lines_with_progress<F: FnMut(usize)>(
+        &mut self,
+        lines: std::vec::IntoIter<String>,
+        mut on_line: F,
+    ) -> Result<()> {
         for line in lines {
+            let n_bytes = line.len();
             let record_string = PileupRecordString::new(line);
             let record = PileupRecord::try_from(record_string)?;
 
             self.write_pileup_record(&record)?;
+            on_line(n_bytes);
         }
         Ok(())
     }
 
     pub fn write_tabix(&mut self, path: &Path) -> Result<()> {
-        assert_eq!(path.extension().unwrap(), "tbi");
-        let mut tabix_writer = File::create(path).map(tabix::io::Writer::new)?;
-
         if let Some(indexer) = self.indexer.take() {
-            let index = indexer.build();
-
-            tabix_writer.write_index(&index)?;
+            indexer.write(path)?;
         }
+        self.tabix_path = Some(path.to_path_buf());
 
         Ok(())
     }
 
     pub fn finish(self) -> Result<()> {
         self.writer.finish()?;
+
+        // Finishing writes the last block after the index, so touch the index to keep
+        // it newer than the data. Readers treat an older index as stale.
+        if let Some(tabix_path) = self.tabix_path {
+            File::options()
+                .write(true)
+                .open(tabix_path)?
+                .set_modified(SystemTime::now())?;
+        }
+
         Ok(())
     }
 }
 
+/// Writes bytes that are not pileup records, such as a TSV table, to the BGZF stream.
+/// They are not added to the index, so no tabix index should be written for them.
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
 impl Writer<File> {
-    pub fn from_path(output: &Path) -> Result<Self> {
-        let writer = File::create(output).map(bgzf::io::Writer::new)?;
-        let mut indexer = tabix::index::Indexer::default();
-        indexer.set_header(csi::binning_index::index::header::Builder::bed().build());
+    /// Creates a BGZF writer to `output` with a compression level from 0 to 9.
+    pub fn from_path(output: &Path, level: u32) -> Result<Self> {
+        let compression_level = compression_level(level)?;
+        let writer = File::create(output).map(|file| {
+            bgzf::io::writer::Builder::default()
+                .set_compression_level(compression_level)
+                .build_from_writer(file)
+        })?;
+
+        Ok(Self {
+            writer,
+            indexer: Some(PileupIndexer::new()),
+            tabix_path: None,
+            offset: 0,
+        })
+    }
+
+    /// Opens the finished BGZF pileup at `path` to append records with a compression
+    /// level from 0 to 9.
+    ///
+    /// The writer starts at the EOF block, which the first appended block overwrites and
+    /// [`Self::finish`] writes again. Until a block is written the file is left as it
+    /// was, so an append that fails early keeps the file readable. The existing records
+    /// are read, without recompressing them, to rebuild the index, which
+    /// [`Self::write_tabix`] writes with the appended records. The appended records must
+    /// keep the file sorted. A file without an EOF block is an error, as it may be
+    /// truncated.
+    pub fn reopen(path: &Path, level: u32) -> Result<Self> {
+        let compression_level = compression_level(level)?;
+        if !has_bgzf_eof(path)? {
+            bail!(
+                "Cannot append to {}: File does not end with a BGZF EOF block. It may be truncated.",
+                path.display()
+            );
+        }
+        let mut file = File::options().read(true).write(true).open(path)?;
+        let data_end = file.metadata()?.len() - BGZF_EOF.len() as u64;
+
+        let mut indexer = PileupIndexer::new();
+        indexer
+            .add_records_from_reader(&mut bgzf::io::Reader::new((&file).take(data_end)))
+            .with_context(|| format!("Cannot index {}", path.display()))?;
+
+        file.seek(SeekFrom::Start(data_end))?;
+        let writer = bgzf::io::writer::Builder::default()
+            .set_compression_level(compression_level)
+            .build_from_writer(file);
 
         Ok(Self {
             writer,
             indexer: Some(indexer),
+            tabix_path: None,
+            offset: data_end,
         })
     }
 }
 
 impl Writer<BufWriter<std::io::Stdout>> {
-    pub fn to_stdout() -> Result<Self> {
+    /// Creates a BGZF writer to stdout with a compression level from 0 to 9.
+    pub fn to_stdout(level: u32) -> Result<Self> {
         let stdout = BufWriter::new(std::io::stdout());
-        let writer = bgzf::io::Writer::new(stdout);
+        let writer = bgzf::io::writer::Builder::default()
+            .set_compression_level(compression_level(level)?)
+            .build_from_writer(stdout);
 
         Ok(Self {
             writer,
             indexer: None,
+            tabix_path: None,
+            offset: 0,
         })
     }
 }
@@ -1,15 +1,22 @@
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::anyhow;
 use epimetheus_core::{
     models::{
         contig::Contig,
+        coverage_histogram::CoverageHistogram,
         genome_workspace::{GenomeWorkspace, GenomeWorkspaceBuilder},
         methylation::MethylationRecord,
-        pileup::{PileupRecord, PileupRecordString},
+        pileup::{CoverageField, ParseErrorMode, PileupRecordString},
+    },
+    services::{
+        domain::{
+            pileup_service::parse_pileup_record,
+            warning_collector::{WarningEvent, WarningType, record_warning},
+        },
+        traits::BatchLoader,
     },
-    services::traits::BatchLoader,
 };
-use log::{debug, warn};
+use log::{debug, info, warn};
 use std::{
     fs::File,
     io::{BufRead, BufReader},
@@ -21,12 +28,23 @@ pub struct SequentialBatchLoader<R: BufRead> {
     batch_size: usize,
     min_valid_read_coverage: u32,
     min_valid_cov_to_diff_fraction: f32,
+    min_score: u32,
+    coverage_field: CoverageField,
     allow_mismatch: bool,
 
     current_contig_id: Option<String>,
     current_contig: Option<Contig>,
+    out_of_bounds_records: usize,
     pending_record: Option<Result<PileupRecordString, anyhow::Error>>,
     contigs_loaded_in_batch: usize,
+    skipped_contigs: AHashSet<String>,
+    skipped_records: usize,
+    filtered_records: usize,
+    coverage_histogram: CoverageHistogram,
+    on_parse_error: ParseErrorMode,
+    malformed_records: usize,
+    max_memory: Option<u64>,
+    memory_in_batch: u64,
 }
 
 impl<R: BufRead> SequentialBatchLoader<R> {
@@ -40,6 +58,10 @@ impl<R: BufRead> SequentialBatchLoader<R> {
     ) -> Self {
         let size = if batch_size == 0 {
             warn!("Batch size cannot be zero. Defaulting to 1.");
+            record_warning(WarningEvent::new(
+                WarningType::InvalidArgument,
+                "Batch size cannot be zero. Defaulting to 1.",
+            ));
             1
         } else {
             batch_size
@@ -51,13 +73,69 @@ impl<R: BufRead> SequentialBatchLoader<R> {
             batch_size: size,
             min_valid_read_coverage,
             min_valid_cov_to_diff_fraction,
+            min_score: 0,
+            coverage_field: CoverageField::default(),
             allow_mismatch,
             current_contig_id: None,
             current_contig: None,
+            out_of_bounds_records: 0,
             pending_record: None,
             contigs_loaded_in_batch: 0,
+            skipped_contigs: AHashSet::new(),
+            skipped_records: 0,
+            filtered_records: 0,
+            coverage_histogram: CoverageHistogram::new(),
+            on_parse_error: ParseErrorMode::default(),
+            malformed_records: 0,
+            max_memory: None,
+            memory_in_batch: 0,
         }
     }
+
+    /// Sets how malformed pileup records are handled. Defaults to [`ParseErrorMode::Fail`].
+    pub fn with_parse_error_mode(mut self, on_parse_error: ParseErrorMode) -> Self {
+        self.on_parse_error = on_parse_error;
+        self
+    }
+
+    /// Removes records with a pileup score below `min_score`. Defaults to 0, which keeps
+    /// all records.
+    pub fn with_min_score(mut self, min_score: u32) -> Self {
+        self.min_score = min_score;
+        self
+    }
+
+    /// Reads the valid coverage of each record from `coverage_field`. Defaults to the
+    /// `n_valid_cov` column.
+    pub fn with_coverage_field(mut self, coverage_field: CoverageField) -> Self {
+        self.coverage_field = coverage_field;
+        self
+    }
+
+    /// Ends a batch early when the estimated memory of its contigs (see
+    /// [`Contig::estimated_memory`]) would exceed `max_memory` bytes. A batch always
+    /// has at least one contig, even if it exceeds the limit on its own.
+    pub fn with_max_memory(mut self, max_memory: Option<u64>) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+}
+
+/// Warns once for the `n_records` records of `contig` skipped for lying past its end,
+/// instead of once per record.
+fn report_out_of_bounds_records(contig: &Contig, n_records: usize) {
+    if n_records == 0 {
+        return;
+    }
+
+    let message = format!(
+        "Skipped {} pileup records past the end of '{}' (length {}). The pileup may be made against another version of the assembly.",
+        n_records,
+        contig.id,
+        contig.sequence.len()
+    );
+    warn!("{}", message);
+    record_warning(WarningEvent::new(WarningType::ContigMismatch, message).with_contig(&contig.id));
 }
 
 impl BatchLoader<GenomeWorkspace> for SequentialBatchLoader<BufReader<File>> {
@@ -65,6 +143,32 @@ impl BatchLoader<GenomeWorkspace> for SequentialBatchLoader<BufReader<File>> {
         self.next()
     }
 
+    fn n_skipped_contigs(&self) -> usize {
+        self.skipped_contigs.len()
+    }
+
+    fn skipped_contigs(&self) -> Vec<String> {
+        let mut skipped: Vec<String> = self.skipped_contigs.iter().cloned().collect();
+        skipped.sort();
+        skipped
+    }
+
+    fn n_skipped_records(&self) -> usize {
+        self.skipped_records
+    }
+
+    fn n_filtered_records(&self) -> usize {
+        self.filtered_records
+    }
+
+    fn n_malformed_records(&self) -> usize {
+        self.malformed_records
+    }
+
+    fn coverage_histogram(&self) -> CoverageHistogram {
+        self.coverage_histogram.clone()
+    }
+
     fn new(
         reader: BufReader<File>,
         assembly: AHashMap<String, Contig>,
@@ -88,7 +192,7 @@ impl<R: BufRead> Iterator for SequentialBatchLoader<R> {
     type Item = Result<GenomeWorkspace, anyhow::Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut builder = GenomeWorkspaceBuilder::new();
+        let mut builder = GenomeWorkspaceBuilder::with_capacity(self.batch_size);
 
         let record_iter = self
             .pending_record
@@ -116,9 +220,12 @@ impl<R: BufRead> Iterator for SequentialBatchLoader<R> {
                 Err(e) => return Some(Err(e)),
             };
 
-            let record_for_pending = record.clone();
-            let pileup_record = match PileupRecord::try_from(record) {
-                Ok(p) => p,
+            let pileup_record = match parse_pileup_record(&record, self.on_parse_error) {
+                Ok(Some(p)) => p,
+                Ok(None) => {
+                    self.malformed_records += 1;
+                    continue;
+                }
                 Err(e) => return Some(Err(e)),
             };
 
@@ -137,6 +244,10 @@ impl<R: BufRead> Iterator for SequentialBatchLoader<R> {
                 match self.assembly.get(&contig_id) {
                     Some(found) => {
                         if let Some(old_contig) = self.current_contig.take() {
+                            report_out_of_bounds_records(
+                                &old_contig,
+                                std::mem::take(&mut self.out_of_bounds_records),
+                            );
                             debug!("Adding contig to builder");
                             if let Err(e) = builder.add_contig(old_contig) {
                                 return Some(Err(e));
@@ -147,14 +258,39 @@ impl<R: BufRead> Iterator for SequentialBatchLoader<R> {
                                 self.contigs_loaded_in_batch, self.batch_size
                             );
 
-                            if self.contigs_loaded_in_batch == self.batch_size {
-                                self.pending_record = Some(Ok(record_for_pending));
+                            let exceeds_memory = self.max_memory.is_some_and(|max_memory| {
+                                self.memory_in_batch + found.estimated_memory() > max_memory
+                            });
+                            if exceeds_memory && self.contigs_loaded_in_batch < self.batch_size {
+                                info!(
+                                    "Ending batch at {} contigs instead of {} to stay within the memory limit (~{:.2} GB estimated)",
+                                    self.contigs_loaded_in_batch,
+                                    self.batch_size,
+                                    self.memory_in_batch as f64 / 1e9
+                                );
+                            }
+
+                            if self.contigs_loaded_in_batch == self.batch_size || exceeds_memory {
+                                self.pending_record = Some(Ok(record));
                                 self.contigs_loaded_in_batch = 0;
+                                self.memory_in_batch = 0;
                                 return Some(Ok(builder.build()));
                             }
                         };
 
+                        if let Some(max_memory) = self.max_memory {
+                            if found.estimated_memory() > max_memory {
+                                warn!(
+                                    "Contig '{}' alone is estimated to need {:.2} GB, above the memory limit of {:.2} GB. Loading it anyway.",
+                                    contig_id,
+                                    found.estimated_memory() as f64 / 1e9,
+                                    max_memory as f64 / 1e9
+                                );
+                            }
+                        }
+
                         // Add the current contig to builder.
+                        self.memory_in_batch += found.estimated_memory();
                         self.current_contig_id = Some(contig_id.clone());
                         self.current_contig = Some(found.clone());
                     }
@@ -166,26 +302,53 @@ impl<R: BufRead> Iterator for SequentialBatchLoader<R> {
 
                     // Skip records if mismatches are allowed
                     None => {
+                        self.skipped_records += 1;
+                        if self.skipped_contigs.insert(contig_id.clone()) {
+                            record_warning(
+                                WarningEvent::new(
+                                    WarningType::ContigMismatch,
+                                    "Contig in pileup not found in assembly. Skipping records.",
+                                )
+                                .with_contig(contig_id),
+                            );
+                        }
                         continue;
                     }
                 }
             }
+            self.coverage_histogram.add(
+                pileup_record.mod_type,
+                self.coverage_field.value(&pileup_record),
+            );
+            let filtered = self
+                .current_contig
+                .as_mut()
+                .map(|c| &mut c.filtered_positions);
             let meth = match MethylationRecord::try_from_with_filters(
                 pileup_record.clone(),
                 self.min_valid_read_coverage,
                 self.min_valid_cov_to_diff_fraction,
+                self.min_score,
+                &self.coverage_field,
+                filtered,
             ) {
                 Ok(Some(m)) => m,
-                Ok(None) => continue,
+                Ok(None) => {
+                    self.filtered_records += 1;
+                    continue;
+                }
                 Err(e) => return Some(Err(e)),
             };
             if let Some(ref mut c) = self.current_contig {
-                if let Err(e) = c.add_methylation_record(meth) {
-                    return Some(Err(e));
+                match c.add_methylation_record(meth, self.allow_mismatch) {
+                    Ok(true) => {}
+                    Ok(false) => self.out_of_bounds_records += 1,
+                    Err(e) => return Some(Err(e)),
                 }
             }
         }
         if let Some(last) = self.current_contig.take() {
+            report_out_of_bounds_records(&last, std::mem::take(&mut self.out_of_bounds_records));
             builder.add_contig(last).ok()?;
         }
 
@@ -202,7 +365,10 @@ impl<R: BufRead> Iterator for SequentialBatchLoader<R> {
 mod tests {
 
     use super::*;
-    use epimetheus_core::models::methylation::MethylationCoverage;
+    use epimetheus_core::{
+        models::methylation::MethylationCoverage,
+        services::domain::warning_collector::enable_warning_collection,
+    };
     use std::{
         fs::File,
         io::{BufReader, Write},
@@ -331,6 +497,120 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_max_memory_splits_batches() -> anyhow::Result<()> {
+        let mut pileup_file = NamedTempFile::new()?;
+        for contig in ["contig_3", "contig_4", "contig_5"] {
+            writeln!(
+                pileup_file,
+                "{}\t6\t1\ta\t133\t+\t0\t1\t255,0,0\t15\t0.00\t15\t123\t0\t0\t6\t0\t0",
+                contig
+            )?;
+        }
+
+        let mut assembly = AHashMap::new();
+        for contig in ["contig_3", "contig_4", "contig_5"] {
+            assembly.insert(
+                contig.to_string(),
+                Contig::from_string(contig.to_string(), "TGGACGATCCCGATC".to_string())?,
+            );
+        }
+        let contig_memory = assembly.get("contig_3").unwrap().estimated_memory();
+        let reader = BufReader::new(File::open(pileup_file.path())?);
+
+        // Room for two contigs per batch although the batch size allows all three.
+        let batch_sizes: Vec<usize> =
+            SequentialBatchLoader::new(reader, assembly.clone(), 3, 1, 0.8, false)
+                .with_max_memory(Some(2 * contig_memory))
+                .map(|ws| ws.map(|ws| ws.get_workspace().len()))
+                .collect::<anyhow::Result<_>>()?;
+        assert_eq!(batch_sizes, vec![2, 1]);
+
+        // A limit below a single contig still loads one contig per batch.
+        let reader = BufReader::new(File::open(pileup_file.path())?);
+        let batch_sizes: Vec<usize> =
+            SequentialBatchLoader::new(reader, assembly, 3, 1, 0.8, false)
+                .with_max_memory(Some(1))
+                .map(|ws| ws.map(|ws| ws.get_workspace().len()))
+                .collect::<anyhow::Result<_>>()?;
+        assert_eq!(batch_sizes, vec![1, 1, 1]);
+
+        Ok(())
+    }
+
+    fn write_pileup_with_malformed_line() -> anyhow::Result<NamedTempFile> {
+        let mut pileup_file = NamedTempFile::new()?;
+        writeln!(
+            pileup_file,
+            "contig_3\t6\t1\ta\t133\t+\t0\t1\t255,0,0\t15\t0.00\t15\t123\t0\t0\t6\t0\t0"
+        )?;
+        writeln!(
+            pileup_file,
+            "contig_3\t8\t1\ta\t133\t+\t0\t1\t255,0,0\tmany\t0.00\t20\t123\t0\t0\t6\t0\t0"
+        )?;
+        writeln!(
+            pileup_file,
+            "contig_3\t12\t1\ta\t133\t+\t0\t1\t255,0,0\t20\t0.00\t5\t123\t0\t0\t6\t0\t0"
+        )?;
+        Ok(pileup_file)
+    }
+
+    fn assembly_contig_3() -> AHashMap<String, Contig> {
+        let mut assembly = AHashMap::new();
+        assembly.insert(
+            "contig_3".to_string(),
+            Contig::from_string("contig_3".to_string(), "TGGACGATCCCGATC".to_string()).unwrap(),
+        );
+        assembly
+    }
+
+    #[test]
+    fn test_malformed_record_fails() -> anyhow::Result<()> {
+        let pileup_file = write_pileup_with_malformed_line()?;
+        let reader = BufReader::new(File::open(pileup_file.path())?);
+
+        let mut batch_loader =
+            SequentialBatchLoader::new(reader, assembly_contig_3(), 1, 1, 0.8, false)
+                .with_parse_error_mode(ParseErrorMode::Fail);
+
+        let err = match batch_loader.next_batch() {
+            Some(Err(e)) => e,
+            _ => panic!("Expected the malformed record to fail the batch"),
+        };
+        assert!(err.to_string().contains("\tmany\t"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_record_is_skipped() -> anyhow::Result<()> {
+        let pileup_file = write_pileup_with_malformed_line()?;
+        let reader = BufReader::new(File::open(pileup_file.path())?);
+
+        let mut batch_loader =
+            SequentialBatchLoader::new(reader, assembly_contig_3(), 1, 1, 0.8, false)
+                .with_parse_error_mode(ParseErrorMode::Skip);
+
+        let workspace = batch_loader.next_batch().unwrap()?.get_workspace();
+        let contig_3 = workspace.get("contig_3").unwrap();
+        let positions = contig_3.get_methylated_positions(
+            &[6, 8, 12],
+            epimetheus_methylome::Strand::Positive,
+            epimetheus_methylome::ModType::SixMA,
+        );
+        let found: Vec<usize> = positions
+            .into_iter()
+            .filter(|(_, rec)| rec.is_some())
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(found, vec![6, 12]);
+
+        assert!(batch_loader.next_batch().is_none());
+        assert_eq!(batch_loader.n_malformed_records(), 1);
+
+        Ok(())
+    }
+
     #[test]
     fn test_contig_missing_error() -> anyhow::Result<()> {
         let mut pileup_file = NamedTempFile::new().unwrap();
@@ -456,11 +736,54 @@ mod tests {
         let file = File::open(pileup_file).unwrap();
         let reader = BufReader::new(file);
 
-        let batch_loader = SequentialBatchLoader::new(reader, assembly, 3, 1, 0.8, true);
+        let mut batch_loader = SequentialBatchLoader::new(reader, assembly, 3, 1, 0.8, true);
 
-        for ws in batch_loader {
+        for ws in batch_loader.by_ref() {
             assert_eq!(ws.unwrap().get_workspace().len(), 2);
         }
+        assert_eq!(batch_loader.skipped_contigs(), vec!["contig_5".to_string()]);
+        assert_eq!(batch_loader.n_skipped_contigs(), 1);
+        assert_eq!(batch_loader.n_skipped_records(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_records_past_contig_end_are_warned_once_per_contig() -> anyhow::Result<()> {
+        // Other tests record warnings concurrently, so only the warnings of this contig
+        // are looked at.
+        let contig_id = "out_of_bounds_loader_test";
+        let mut pileup_file = NamedTempFile::new()?;
+        for position in [6, 20, 21, 22] {
+            writeln!(
+                pileup_file,
+                "{}\t{}\t1\ta\t133\t+\t0\t1\t255,0,0\t15\t0.00\t15\t123\t0\t0\t6\t0\t0",
+                contig_id, position
+            )?;
+        }
+
+        let mut assembly = AHashMap::new();
+        assembly.insert(
+            contig_id.to_string(),
+            Contig::from_string(contig_id.to_string(), "TGGACGATCCCGATC".to_string())?,
+        );
+        let reader = BufReader::new(File::open(pileup_file.path())?);
+
+        let collection = enable_warning_collection();
+        let mut batch_loader = SequentialBatchLoader::new(reader, assembly, 3, 1, 0.8, true);
+        for ws in batch_loader.by_ref() {
+            assert_eq!(ws?.get_workspace().len(), 1);
+        }
+
+        let warnings_file = NamedTempFile::new()?;
+        collection.write_json(warnings_file.path())?;
+        let warnings: Vec<String> = std::fs::read_to_string(warnings_file.path())?
+            .lines()
+            .filter(|line| line.contains(contig_id))
+            .map(String::from)
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("Skipped 3 pileup records"));
 
         Ok(())
     }
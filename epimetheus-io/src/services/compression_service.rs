@@ -1,24 +1,43 @@
 use anyhow::Result;
-use std::path::Path;
+use noodles_bgzf as bgzf;
+use std::{fs::File, path::Path};
 
 use crate::io::{
-    readers::bed::InputReader,
-    writers::bgzip::{Writer, WriterType},
+    readers::bed::{InputReader, LineReader},
+    writers::bgzip::{DEFAULT_COMPRESSION_LEVEL, PileupIndexer, Writer, WriterType},
 };
 
 pub struct CompressorService;
 
 impl CompressorService {
+    /// Compresses the pileup with [`DEFAULT_COMPRESSION_LEVEL`].
     pub fn compress_pileup(input_reader: InputReader, output: Option<&Path>) -> Result<()> {
+        Self::compress_pileup_with_progress(input_reader, output, DEFAULT_COMPRESSION_LEVEL, |_| {})
+    }
+
+    /// Compresses the pileup like [`Self::compress_pileup`] with a compression `level`
+    /// from 0 to 9, calling `on_line` with the number of input bytes of each record once
+    /// it is written. Used to drive a progress bar without tying the service to a
+    /// particular one.
+    pub fn compress_pileup_with_progress<F: FnMut(usize)>(
+        input_reader: InputReader,
+        output: Option<&Path>,
+        level: u32,
+        on_line: F,
+    ) -> Result<()> {
         let mut writer = match output {
-            Some(path) => WriterType::File(Writer::from_path(path)?),
-            None => WriterType::StdOut(Writer::to_stdout()?),
+            Some(path) => WriterType::File(Writer::from_path(path, level)?),
+            None => WriterType::StdOut(Writer::to_stdout(level)?),
         };
 
         match input_reader {
-            InputReader::File(reader) => writer.compress_from_reader(reader)?,
-            InputReader::StdIn(reader) => writer.compress_from_reader(reader)?,
-            InputReader::Lines(lines) => writer.compress_from_lines(lines)?,
+            InputReader::File(reader) => {
+                writer.compress_from_reader_with_progress(reader, on_line)?
+            }
+            InputReader::StdIn(reader) => {
+                writer.compress_from_reader_with_progress(reader, on_line)?
+            }
+            InputReader::Lines(lines) => writer.compress_from_lines_with_progress(lines, on_line)?,
         }
 
         if let Some(path) = output {
@@ -30,17 +49,47 @@ impl CompressorService {
 
         Ok(())
     }
+
+    /// Recompresses a BGZF pileup with full-sized blocks and rebuilds the tabix
+    /// index. The input is read sequentially, so record order is preserved and the
+    /// input does not need an index.
+    pub fn reblock_pileup(input: &Path, output: &Path) -> Result<()> {
+        let reader = File::open(input).map(bgzf::io::Reader::new)?;
+        let mut writer = WriterType::File(Writer::from_path(output, DEFAULT_COMPRESSION_LEVEL)?);
+
+        writer.compress_from_reader(LineReader::new(reader))?;
+
+        let tbx_path = format!("{}.tbi", output.display());
+        writer.write_tabix(Path::new(&tbx_path))?;
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    /// Builds the tabix index of an existing BGZF pileup and writes it next to the
+    /// file as `<input>.tbi`. The records are read in place, so the data file is not
+    /// rewritten. Returns the number of indexed records.
+    pub fn index_pileup(input: &Path) -> Result<usize> {
+        let mut reader = File::open(input).map(bgzf::io::Reader::new)?;
+        let mut indexer = PileupIndexer::new();
+        let n_records = indexer.add_records_from_reader(&mut reader)?;
+
+        let tbx_path = format!("{}.tbi", input.display());
+        indexer.write(Path::new(&tbx_path))?;
+
+        Ok(n_records)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::io::readers::bed::{InputReader, LineReader};
-    use noodles_bgzf as bgzf;
-    use std::{
-        fs::File,
-        io::{BufRead, BufReader, Write},
+    use crate::io::{
+        readers::bgzf_bed::{Reader, count_bgzf_blocks, has_bgzf_eof},
+        traits::PileupReader,
     };
+    use std::io::{BufRead, BufReader, Write};
     use tempfile::NamedTempFile;
 
     fn create_test_bed_data() -> NamedTempFile {
@@ -100,6 +149,69 @@ mod tests {
         assert_eq!(line_count, 3, "Compressed file should contain 3 lines");
     }
 
+    #[test]
+    fn test_compress_pileup_with_progress_reports_all_bytes() {
+        let input_file = create_test_bed_data();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("test_output.bed.gz");
+
+        let file = File::open(input_file.path()).unwrap();
+        let input_reader = InputReader::File(LineReader::new(BufReader::new(file)));
+
+        let mut n_lines = 0;
+        let mut n_bytes = 0;
+        CompressorService::compress_pileup_with_progress(
+            input_reader,
+            Some(&output_path),
+            DEFAULT_COMPRESSION_LEVEL,
+            |n| {
+                n_lines += 1;
+                n_bytes += n;
+            },
+        )
+        .unwrap();
+
+        let input_size = std::fs::metadata(input_file.path()).unwrap().len() as usize;
+        assert_eq!(n_lines, 3);
+        assert_eq!(n_bytes, input_size);
+    }
+
+    #[test]
+    fn test_compress_pileup_with_compression_levels() {
+        let input_file = create_test_bed_data();
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        for level in [0, 1, 9] {
+            let output_path = temp_dir.path().join(format!("level_{}.bed.gz", level));
+            let file = File::open(input_file.path()).unwrap();
+            let input_reader = InputReader::File(LineReader::new(BufReader::new(file)));
+
+            CompressorService::compress_pileup_with_progress(
+                input_reader,
+                Some(&output_path),
+                level,
+                |_| {},
+            )
+            .unwrap();
+
+            let mut reader = Reader::from_path(&output_path).unwrap();
+            let records = reader.query_contig("contig_3").unwrap();
+            assert_eq!(records.len(), 3, "Level {} should keep all records", level);
+        }
+
+        let file = File::open(input_file.path()).unwrap();
+        let input_reader = InputReader::File(LineReader::new(BufReader::new(file)));
+        let output_path = temp_dir.path().join("level_10.bed.gz");
+        let err = CompressorService::compress_pileup_with_progress(
+            input_reader,
+            Some(&output_path),
+            10,
+            |_| {},
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("between 0 and 9"));
+    }
+
     #[test]
     fn test_compress_pileup_to_stdout() {
         let input_file = create_test_bed_data();
@@ -151,6 +263,51 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compress_handles_start_equal_to_end() {
+        let mut input_file = NamedTempFile::new().unwrap();
+        writeln!(
+            input_file,
+            "contig_3\t0\t0\ta\t133\t+\t0\t0\t255,0,0\t15\t0.00\t15\t123\t0\t0\t6\t0\t0"
+        )
+        .unwrap();
+        writeln!(
+            input_file,
+            "contig_3\t6\t6\ta\t133\t+\t6\t6\t255,0,0\t15\t0.00\t15\t123\t0\t0\t6\t0\t0"
+        )
+        .unwrap();
+        writeln!(
+            input_file,
+            "contig_3\t10\t11\ta\t133\t+\t10\t11\t255,0,0\t15\t0.00\t15\t123\t0\t0\t6\t0\t0"
+        )
+        .unwrap();
+        input_file.flush().unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("zero_length.bed.gz");
+
+        let file = File::open(input_file.path()).unwrap();
+        let line_reader = LineReader::new(BufReader::new(file));
+        let input_reader = InputReader::File(line_reader);
+
+        let result = CompressorService::compress_pileup(input_reader, Some(&output_path));
+        assert!(
+            result.is_ok(),
+            "Should handle start == end: {:?}",
+            result.err()
+        );
+
+        let mut reader = Reader::from_path(&output_path).unwrap();
+        let records = reader.query_contig("contig_3").unwrap();
+        assert_eq!(records.len(), 3, "All records should be queryable");
+
+        let ends: Vec<&str> = records
+            .iter()
+            .map(|record| record.0.split('\t').nth(2).unwrap())
+            .collect();
+        assert_eq!(ends, vec!["1", "7", "11"]);
+    }
+
     #[test]
     fn test_compress_from_memory_data() {
         // Create a temporary file with test data instead of using Cursor
@@ -178,4 +335,201 @@ mod tests {
 
         assert!(output_path.exists(), "Output file should be created");
     }
+
+    #[test]
+    fn test_reblock_pileup_merges_small_blocks() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let small_blocks = temp_dir.path().join("small_blocks.bed.gz");
+        let reblocked = temp_dir.path().join("reblocked.bed.gz");
+
+        let lines: Vec<String> = ["contig_3", "contig_1"]
+            .iter()
+            .flat_map(|contig| {
+                (0..50).map(move |i| {
+                    format!(
+                        "{}\t{}\t{}\ta\t133\t+\t{}\t{}\t255,0,0\t15\t0.4\t6\t9\t0\t0\t6\t0\t0",
+                        contig,
+                        i * 10,
+                        i * 10 + 1,
+                        i * 10,
+                        i * 10 + 1
+                    )
+                })
+            })
+            .collect();
+
+        // Flushing after each line ends the block, giving one block per record.
+        let mut writer = File::create(&small_blocks).map(bgzf::io::Writer::new)?;
+        for line in &lines {
+            writeln!(writer, "{}", line)?;
+            writer.flush()?;
+        }
+        writer.finish()?;
+
+        CompressorService::reblock_pileup(&small_blocks, &reblocked)?;
+
+        let n_small = count_bgzf_blocks(&small_blocks)?;
+        let n_reblocked = count_bgzf_blocks(&reblocked)?;
+        assert_eq!(n_small, lines.len() + 1);
+        assert!(n_reblocked < n_small, "{} >= {}", n_reblocked, n_small);
+
+        let decompressed: Vec<String> =
+            BufReader::new(File::open(&reblocked).map(bgzf::io::Reader::new)?)
+                .lines()
+                .collect::<std::io::Result<_>>()?;
+        assert_eq!(decompressed, lines, "Record order should be preserved");
+
+        let mut reader = Reader::from_path(&reblocked)?;
+        for contig in ["contig_1", "contig_3"] {
+            let expected: Vec<&String> = lines
+                .iter()
+                .filter(|l| l.starts_with(&format!("{}\t", contig)))
+                .collect();
+            let records = reader.query_contig(contig)?;
+            assert_eq!(records.len(), expected.len());
+            for (record, line) in records.iter().zip(expected) {
+                assert_eq!(record.0.trim_end(), line);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reopen_appends_records_and_extends_index() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let output = temp_dir.path().join("appended.bed.gz");
+        let tbx_path = temp_dir.path().join("appended.bed.gz.tbi");
+
+        let lines: Vec<String> = ["contig_1", "contig_3"]
+            .iter()
+            .flat_map(|contig| {
+                (0..20).map(move |i| {
+                    format!(
+                        "{}\t{}\t{}\ta\t133\t+\t{}\t{}\t255,0,0\t15\t0.4\t6\t9\t0\t0\t6\t0\t0",
+                        contig,
+                        i * 10,
+                        i * 10 + 1,
+                        i * 10,
+                        i * 10 + 1
+                    )
+                })
+            })
+            .collect();
+        let (first, second) = lines.split_at(20);
+
+        CompressorService::compress_pileup(
+            InputReader::Lines(first.to_vec().into_iter()),
+            Some(&output),
+        )?;
+
+        let mut writer = Writer::reopen(&output, DEFAULT_COMPRESSION_LEVEL)?;
+        writer.compress_from_lines(second.to_vec().into_iter())?;
+        writer.write_tabix(&tbx_path)?;
+        writer.finish()?;
+
+        let decompressed: Vec<String> =
+            BufReader::new(File::open(&output).map(bgzf::io::Reader::new)?)
+                .lines()
+                .collect::<std::io::Result<_>>()?;
+        assert_eq!(decompressed, lines);
+
+        let mut reader = Reader::from_path(&output)?;
+        for (contig, expected) in [("contig_1", first), ("contig_3", second)] {
+            let records: Vec<String> = reader
+                .query_contig(contig)?
+                .into_iter()
+                .map(|r| r.0.trim_end().to_string())
+                .collect();
+            assert_eq!(records, expected, "Records differ for {}", contig);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reopen_keeps_eof_block_until_a_block_is_written() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let output = temp_dir.path().join("interrupted.bed.gz");
+        let input_file = create_test_bed_data();
+        let input_reader = InputReader::File(LineReader::new(BufReader::new(File::open(
+            input_file.path(),
+        )?)));
+        CompressorService::compress_pileup(input_reader, Some(&output))?;
+        let len = std::fs::metadata(&output)?.len();
+
+        // Leaking the writer stands in for an append that stops before a block is
+        // flushed, e.g. on a crash.
+        let writer = Writer::reopen(&output, DEFAULT_COMPRESSION_LEVEL)?;
+        std::mem::forget(writer);
+
+        assert_eq!(std::fs::metadata(&output)?.len(), len);
+        assert!(has_bgzf_eof(&output)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reopen_rejects_file_without_eof_block() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let output = temp_dir.path().join("truncated.bed.gz");
+        let input_file = create_test_bed_data();
+        let input_reader = InputReader::File(LineReader::new(BufReader::new(File::open(
+            input_file.path(),
+        )?)));
+        CompressorService::compress_pileup(input_reader, Some(&output))?;
+
+        let len = std::fs::metadata(&output)?.len();
+        File::options()
+            .write(true)
+            .open(&output)?
+            .set_len(len - 1)?;
+
+        let err = Writer::reopen(&output, DEFAULT_COMPRESSION_LEVEL)
+            .err()
+            .expect("Reopening a truncated file should fail");
+        assert!(format!("{:#}", err).contains("EOF block"), "{:#}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_pileup_for_precompressed_fixture() -> Result<()> {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../epimetheus-cli/tests/data/geobacillus.bed.gz");
+        let temp_dir = tempfile::tempdir()?;
+        let unindexed = temp_dir.path().join("geobacillus.bed.gz");
+        std::fs::copy(&fixture, &unindexed)?;
+        assert!(!temp_dir.path().join("geobacillus.bed.gz.tbi").exists());
+
+        let n_records = CompressorService::index_pileup(&unindexed)?;
+        assert!(temp_dir.path().join("geobacillus.bed.gz.tbi").exists());
+
+        let n_lines = BufReader::new(File::open(&unindexed).map(bgzf::io::Reader::new)?)
+            .lines()
+            .count();
+        assert_eq!(n_records, n_lines);
+
+        let mut expected_reader = Reader::from_path(&fixture)?;
+        let mut reader = Reader::from_path(&unindexed)?;
+        let contigs = reader.available_contigs();
+        assert_eq!(contigs, expected_reader.available_contigs());
+        assert!(!contigs.is_empty());
+        for contig in contigs {
+            let records: Vec<String> = reader
+                .query_contig(&contig)?
+                .into_iter()
+                .map(|r| r.0)
+                .collect();
+            let expected: Vec<String> = expected_reader
+                .query_contig(&contig)?
+                .into_iter()
+                .map(|r| r.0)
+                .collect();
+            assert!(!records.is_empty());
+            assert_eq!(records, expected, "Records differ for {}", contig);
+        }
+
+        Ok(())
+    }
 }
@@ -3,22 +3,39 @@ use std::path::Path;
 use ahash::AHashMap;
 use anyhow::Result;
 use epimetheus_core::{
-    models::{contig::Contig, genome_workspace::GenomeWorkspace, pileup::PileupRecord},
-    services::traits::BatchLoader,
+    models::{
+        contig::Contig,
+        genome_workspace::GenomeWorkspace,
+        pileup::{ParseErrorMode, PileupRecord},
+    },
+    services::{domain::pileup_service::parse_pileup_record, traits::BatchLoader},
 };
 
 use crate::io::traits::PileupReader;
 
+/// Loads and parses the pileup records of a contig. Returns the records together
+/// with the number of malformed records skipped under [`ParseErrorMode::Skip`].
+///
+/// `allow_truncated` is passed to [`PileupReader::from_path_with_truncation`].
 pub fn load_pileup_records_for_contig<R: PileupReader>(
     pileup_path: &Path,
     contig_id: &str,
-) -> anyhow::Result<Vec<PileupRecord>> {
-    let mut reader = R::from_path(pileup_path)?;
+    on_parse_error: ParseErrorMode,
+    allow_truncated: bool,
+) -> anyhow::Result<(Vec<PileupRecord>, usize)> {
+    let mut reader = R::from_path_with_truncation(pileup_path, allow_truncated)?;
     let pileup_record_strings = reader.query_contig(contig_id)?;
-    pileup_record_strings
-        .into_iter()
-        .map(PileupRecord::try_from)
-        .collect::<anyhow::Result<Vec<PileupRecord>>>()
+
+    let mut records = Vec::with_capacity(pileup_record_strings.len());
+    let mut n_malformed = 0;
+    for record_string in &pileup_record_strings {
+        match parse_pileup_record(record_string, on_parse_error)? {
+            Some(record) => records.push(record),
+            None => n_malformed += 1,
+        }
+    }
+
+    Ok((records, n_malformed))
 }
 
 pub fn process_batches_from_loader<L: BatchLoader<GenomeWorkspace>>(
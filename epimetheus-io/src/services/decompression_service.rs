@@ -1,4 +1,5 @@
 use anyhow::Result;
+use epimetheus_core::models::pileup::PileupRecord;
 use log::info;
 use std::{
     fs::File,
@@ -7,7 +8,13 @@ use std::{
 };
 
 use crate::{
-    io::{readers::bgzf_bed::Reader, traits::PileupReader},
+    io::{
+        readers::{
+            bgzf_bed::Reader,
+            regions::{PileupQuery, merge_regions, read_regions},
+        },
+        traits::PileupReader,
+    },
     services::file_processing_service::query_pileup,
 };
 
@@ -15,9 +22,22 @@ pub fn extract_from_pileup(
     input: &Path,
     output: Option<&Path>,
     ls: bool,
-    contigs: Vec<String>,
+    ls_lengths: bool,
+    contigs: Vec<PileupQuery>,
+    regions: Option<&Path>,
+    allow_truncated: bool,
 ) -> Result<()> {
-    let mut reader = Reader::from_path(input)?;
+    let mut reader = Reader::from_path_with_truncation(input, allow_truncated)?;
+
+    if ls_lengths {
+        for (contig, length) in reader.available_contigs_with_lengths()? {
+            match length {
+                Some(length) => println!("{}\t{}", contig, length),
+                None => println!("{}\t.", contig),
+            }
+        }
+        return Ok(());
+    }
 
     if ls {
         let contigs_available = reader.available_contigs();
@@ -35,13 +55,135 @@ pub fn extract_from_pileup(
         None => Box::new(BufWriter::new(std::io::stdout())),
     };
 
+    if let Some(regions_path) = regions {
+        // Overlapping regions are merged so no record is written twice.
+        let regions = merge_regions(read_regions(regions_path)?);
+        info!("Writing {} regions.", regions.len());
+        for region in regions {
+            let records = reader.query_region(&region.contig, region.start, region.end)?;
+            for r in records {
+                writeln!(writer, "{}", PileupRecord::try_from(r)?)?;
+            }
+        }
+        return Ok(());
+    }
+
     info!("Writing {} contigs.", &contigs.len());
-    for contig in contigs {
-        let records = query_pileup(&mut reader, &[contig])?;
-        for r in records {
-            writeln!(writer, "{}", r)?;
+    for query in contigs {
+        match query {
+            PileupQuery::Contig(contig) => {
+                for r in query_pileup(&mut reader, &[contig])? {
+                    writeln!(writer, "{}", r)?;
+                }
+            }
+            PileupQuery::Region(region) => {
+                for r in reader.query_region(&region.contig, region.start, region.end)? {
+                    writeln!(writer, "{}", PileupRecord::try_from(r)?)?;
+                }
+            }
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{io::readers::bed::InputReader, services::compression_service::CompressorService};
+
+    #[test]
+    fn test_extract_two_regions_from_one_contig() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let pileup_path = temp_dir.path().join("pileup.bed.gz");
+        let regions_path = temp_dir.path().join("regions.bed");
+        let output_path = temp_dir.path().join("extracted.bed");
+
+        let line = |contig: &str, start: u64| {
+            format!(
+                "{}\t{}\t{}\ta\t133\t+\t0\t1\t255,0,0\t15\t0.4\t15\t123\t0\t0\t6\t0\t0",
+                contig,
+                start,
+                start + 1
+            )
+        };
+        let lines: Vec<String> = [0, 10, 20, 30, 40, 50]
+            .iter()
+            .map(|start| line("contig_1", *start))
+            .chain([line("contig_2", 10)])
+            .collect();
+        CompressorService::compress_pileup(
+            InputReader::Lines(lines.clone().into_iter()),
+            Some(&pileup_path),
+        )?;
+
+        // The first two regions overlap and must not duplicate the record at 20.
+        std::fs::write(
+            &regions_path,
+            "contig_1\t10\t21\ncontig_1\t15\t25\ncontig_1\t40\t50\n",
+        )?;
+
+        extract_from_pileup(
+            &pileup_path,
+            Some(&output_path),
+            false,
+            false,
+            Vec::new(),
+            Some(&regions_path),
+            false,
+        )?;
+
+        let extracted = std::fs::read_to_string(&output_path)?;
+        assert_eq!(
+            extracted.lines().collect::<Vec<_>>(),
+            vec![lines[1].as_str(), lines[2].as_str(), lines[4].as_str()]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_region_query() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let pileup_path = temp_dir.path().join("pileup.bed.gz");
+        let output_path = temp_dir.path().join("extracted.bed");
+
+        let line = |contig: &str, start: u64| {
+            format!(
+                "{}\t{}\t{}\ta\t133\t+\t0\t1\t255,0,0\t15\t0.4\t15\t123\t0\t0\t6\t0\t0",
+                contig,
+                start,
+                start + 1
+            )
+        };
+        let lines = vec![
+            line("contig_1", 0),
+            line("contig_1", 10),
+            line("contig_1", 20),
+            line("contig_2", 10),
+        ];
+        CompressorService::compress_pileup(
+            InputReader::Lines(lines.clone().into_iter()),
+            Some(&pileup_path),
+        )?;
+
+        // contig_1:11-20 is the 0-based [10, 20), so only the record at 10 is in it.
+        extract_from_pileup(
+            &pileup_path,
+            Some(&output_path),
+            false,
+            false,
+            vec!["contig_1:11-20".parse()?, "contig_2".parse()?],
+            None,
+            false,
+        )?;
+
+        let extracted = std::fs::read_to_string(&output_path)?;
+        assert_eq!(
+            extracted.lines().collect::<Vec<_>>(),
+            vec![lines[1].as_str(), lines[3].as_str()]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use epimetheus_core::{
+    io::writers::create_tsv_writer,
+    models::methylation::{MethylationPatternVariant, MotifMethylationDegree},
+};
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+/// Methylation of a contig summarized over its motifs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ContigMethylationSummary {
+    pub contig: String,
+    /// Motifs with a methylation value above the threshold.
+    pub n_motifs_above_threshold: u32,
+    /// Mean methylation value of the motifs. NaN values are left out and a contig
+    /// without values is NaN.
+    pub mean_methylation: f64,
+    /// Observed motif positions summed over the motifs.
+    pub total_motif_obs: u32,
+}
+
+/// Summarizes the contig-motif degrees per contig, sorted by contig.
+pub fn summarize_contigs<D: MotifMethylationDegree>(
+    results: &[D],
+    threshold: f64,
+) -> Vec<ContigMethylationSummary> {
+    let mut contigs: BTreeMap<&str, Vec<&D>> = BTreeMap::new();
+    for degree in results {
+        contigs.entry(degree.get_contig()).or_default().push(degree);
+    }
+
+    contigs
+        .into_iter()
+        .map(|(contig, degrees)| {
+            let values: Vec<f64> = degrees
+                .iter()
+                .map(|d| d.get_methylation_value())
+                .filter(|v| !v.is_nan())
+                .collect();
+            let mean_methylation = if values.is_empty() {
+                f64::NAN
+            } else {
+                values.iter().sum::<f64>() / values.len() as f64
+            };
+
+            ContigMethylationSummary {
+                contig: contig.to_string(),
+                n_motifs_above_threshold: values.iter().filter(|&&v| v > threshold).count() as u32,
+                mean_methylation,
+                total_motif_obs: degrees.iter().map(|d| d.get_n_motif_obs()).sum(),
+            }
+        })
+        .collect()
+}
+
+/// Summarizes a degree output per contig. Returns `None` for the raw and strand asymmetry
+/// outputs, which have no single methylation value per contig-motif.
+pub fn summarize_methylation_pattern(
+    pattern: &MethylationPatternVariant,
+    threshold: f64,
+) -> Option<Vec<ContigMethylationSummary>> {
+    match pattern {
+        MethylationPatternVariant::Median(degrees) => Some(summarize_contigs(degrees, threshold)),
+        MethylationPatternVariant::WeightedMean(degrees) => {
+            Some(summarize_contigs(degrees, threshold))
+        }
+        MethylationPatternVariant::WeightedMedian(degrees) => {
+            Some(summarize_contigs(degrees, threshold))
+        }
+        MethylationPatternVariant::TrimmedMean(degrees) => {
+            Some(summarize_contigs(degrees, threshold))
+        }
+        MethylationPatternVariant::CalledFrequency(degrees) => {
+            Some(summarize_contigs(degrees, threshold))
+        }
+        MethylationPatternVariant::Raw(_) | MethylationPatternVariant::StrandAsymmetry(_) => None,
+    }
+}
+
+/// Writes the contig summaries as TSV.
+pub fn write_contig_summary<P: AsRef<Path>>(
+    summaries: &[ContigMethylationSummary],
+    path: P,
+) -> Result<()> {
+    let mut writer = create_tsv_writer(
+        path,
+        &[],
+        "contig\tn_motifs_above_threshold\tmean_methylation\ttotal_motif_obs",
+    )?;
+    for summary in summaries {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}",
+            summary.contig,
+            summary.n_motifs_above_threshold,
+            summary.mean_methylation,
+            summary.total_motif_obs,
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epimetheus_core::models::methylation::MedianMotifMethylationDegree;
+    use epimetheus_methylome::Motif;
+
+    fn degree(
+        contig: &str,
+        motif: &str,
+        median: f64,
+        n_motif_obs: u32,
+    ) -> MedianMotifMethylationDegree {
+        MedianMotifMethylationDegree {
+            contig: contig.to_string(),
+            motif: Motif::new(motif, "a", 1).unwrap(),
+            median,
+            mean_read_cov: 10.0,
+            n_motif_obs,
+            motif_occurences_total: n_motif_obs,
+            n_filtered_low_cov: 0,
+            n_filtered_diff: 0,
+        }
+    }
+
+    #[test]
+    fn test_summarize_contigs() {
+        let degrees = vec![
+            degree("contig_2", "GATC", 0.95, 4),
+            degree("contig_1", "GATC", 0.95, 10),
+            degree("contig_1", "GAAC", 0.5, 5),
+            degree("contig_1", "CATG", 0.91, 3),
+            degree("contig_3", "GATC", f64::NAN, 0),
+        ];
+
+        let summaries = summarize_contigs(&degrees, 0.9);
+
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[0].contig, "contig_1");
+        assert_eq!(summaries[0].n_motifs_above_threshold, 2);
+        assert!((summaries[0].mean_methylation - (0.95 + 0.5 + 0.91) / 3.0).abs() < 1e-12);
+        assert_eq!(summaries[0].total_motif_obs, 18);
+
+        assert_eq!(summaries[1].contig, "contig_2");
+        assert_eq!(summaries[1].n_motifs_above_threshold, 1);
+        assert_eq!(summaries[1].mean_methylation, 0.95);
+
+        assert_eq!(summaries[2].contig, "contig_3");
+        assert_eq!(summaries[2].n_motifs_above_threshold, 0);
+        assert!(summaries[2].mean_methylation.is_nan());
+        assert_eq!(summaries[2].total_motif_obs, 0);
+    }
+}
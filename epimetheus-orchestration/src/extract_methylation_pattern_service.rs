@@ -1,29 +1,51 @@
 use ahash::AHashMap;
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use epimetheus_core::{
-    algorithms::methylation_pattern::calculate_contig_read_methylation_single,
+    algorithms::methylation_pattern::{
+        calculate_contig_read_methylation_with_occurrences, empty_contig_methylation_pattern,
+    },
     models::{
         contig::Contig,
+        coverage_histogram::CoverageHistogram,
         genome_workspace::GenomeWorkspace,
         methylation::{
-            MethylationOutput, MethylationPatternVariant, MethylationRecord,
-            MotifMethylationPositions,
+            DEFAULT_CALL_THRESHOLD, FilteredPositions, MethylationOutput,
+            MethylationPatternVariant, MethylationRecord, MotifMethylationPositions, StrandFormat,
+            ValueScale,
+        },
+        motif_occurrences::MotifOccurrenceTable,
+        pileup::{CoverageField, ParseErrorMode, PileupRecord},
+    },
+    services::{
+        domain::{
+            contig_service::{partition_contigs_by_memory, populate_contig_with_methylation},
+            pileup_service::{
+                combine_cytosine_modifications, combine_cytosine_motifs, merge_pileup_records,
+            },
+            warning_collector::{WarningEvent, WarningType, record_warning},
         },
-        pileup::PileupRecord,
+        traits::BatchLoader,
     },
-    services::{domain::contig_service::populate_contig_with_methylation, traits::BatchLoader},
 };
 use epimetheus_io::{
     io::traits::PileupReader, loaders::sequential_batch_loader::SequentialBatchLoader,
     services::data_loading_service::load_pileup_records_for_contig,
 };
 use humantime::format_duration;
-use indicatif::ProgressBar;
-use log::{debug, info};
+use indicatif::{HumanCount, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{debug, info, warn};
 use epimetheus_methylome::Motif;
 use polars::prelude::*;
 use rayon::prelude::*;
-use std::{collections::HashSet, io::BufReader, time::Instant};
+use std::{
+    collections::HashSet,
+    io::{BufReader, IsTerminal},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
 use std::{
     fs::File,
     path::{Path, PathBuf},
@@ -32,10 +54,128 @@ use std::{
 #[derive(Debug)]
 pub enum MethylationInput {
     GzFile(PathBuf),
+    /// Several indexed .bed.gz pileups of the same assembly, e.g. one per flowcell.
+    /// Records of the same position, strand and mod type are summed (see
+    /// [`merge_pileup_records`]) before the filters are applied.
+    GzFiles(Vec<PathBuf>),
     BedFile(PathBuf, usize),
     DataFrame(DataFrame),
 }
 
+/// Statistics of a methylation pattern run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MethylationRunStats {
+    /// Contigs for which methylation was calculated.
+    pub n_contigs_processed: usize,
+    /// Contigs skipped because they were only found in the assembly or the pileup.
+    pub n_contigs_skipped: usize,
+    /// Pileup records of contigs skipped because they are not in the assembly. Only
+    /// counted for .bed pileups.
+    pub n_records_skipped: usize,
+    /// Pileup records removed by the coverage filters.
+    pub n_records_filtered: usize,
+    /// Malformed pileup records skipped with [`ParseErrorMode::Skip`].
+    pub n_records_malformed: usize,
+    /// Contigs in the index of a .bed.gz pileup without any pileup records.
+    pub n_contigs_empty: usize,
+    /// Contig-motifs dropped for having fewer observed positions than
+    /// `min_motif_observations`.
+    pub n_contig_motifs_below_min_observations: usize,
+    /// Valid coverages of the pileup records of the assembly contigs, counted before the
+    /// coverage filters.
+    pub coverage_histogram: CoverageHistogram,
+    pub elapsed: Duration,
+}
+
+/// Methylation pattern together with the statistics of the run producing it.
+pub struct MethylationResult {
+    pub pattern: MethylationPatternVariant,
+    pub stats: MethylationRunStats,
+}
+
+impl MethylationResult {
+    pub fn write_output<P: AsRef<Path>>(
+        &self,
+        path: P,
+        value_scale: ValueScale,
+        sort: bool,
+        include_stderr: bool,
+        strand_format: StrandFormat,
+        metadata: &[String],
+    ) -> Result<()> {
+        self.pattern
+            .write_output(path, value_scale, sort, include_stderr, strand_format, metadata)
+    }
+}
+
+/// Column names of a pileup stored in a DataFrame, in bedMethyl order.
+pub const PILEUP_COLUMNS: [&str; 18] = [
+    "contig",
+    "start",
+    "end",
+    "mod_type",
+    "score",
+    "strand",
+    "start_pos",
+    "end_pos",
+    "color",
+    "n_valid_cov",
+    "fraction_modified",
+    "n_modified",
+    "n_canonical",
+    "n_other_mod",
+    "n_delete",
+    "n_fail",
+    "n_diff",
+    "n_no_call",
+];
+
+/// Reads a pileup stored as Parquet for use with [`MethylationInput::DataFrame`].
+pub fn read_parquet_pileup(path: &Path) -> Result<DataFrame> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open parquet pileup at: {}", path.display()))?;
+    let df = ParquetReader::new(file).finish()?;
+
+    validate_pileup_schema(&df)?;
+
+    Ok(df)
+}
+
+fn validate_pileup_schema(df: &DataFrame) -> Result<()> {
+    let columns: Vec<&str> = df
+        .get_column_names()
+        .into_iter()
+        .map(|name| name.as_str())
+        .collect();
+
+    if columns.len() != PILEUP_COLUMNS.len() {
+        bail!(
+            "Expected {} pileup columns but found {}: {:?}",
+            PILEUP_COLUMNS.len(),
+            columns.len(),
+            columns
+        );
+    }
+
+    for (found, expected) in columns.iter().zip(PILEUP_COLUMNS.iter()) {
+        if found != expected {
+            bail!(
+                "Invalid pileup schema. Expected column '{}' but found '{}'. Columns should be: {:?}",
+                expected,
+                found,
+                PILEUP_COLUMNS
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Merges the per-contig results into one pattern.
+///
+/// Aggregated rows are ordered by contig id, keeping the order
+/// within a contig, so the merged result does not depend on the order the
+/// contigs finished in.
 fn merge_methylation_results(
     results: Vec<MethylationPatternVariant>,
     output_type: &MethylationOutput,
@@ -44,17 +184,22 @@ fn merge_methylation_results(
         MethylationOutput::Raw => {
             let mut all_meth_results = AHashMap::new();
             let mut all_occurences_results = AHashMap::new();
+            let mut all_filtered_counts = AHashMap::new();
             for res in results {
                 if let MethylationPatternVariant::Raw(positions) = res {
                     all_meth_results.extend(positions.methylation);
                     all_occurences_results.extend(positions.motif_occurence_totals);
+                    all_filtered_counts.extend(positions.filtered_counts);
                 }
             }
-            MethylationPatternVariant::Raw(MotifMethylationPositions::new(all_meth_results, all_occurences_results))
+            let mut merged =
+                MotifMethylationPositions::new(all_meth_results, all_occurences_results);
+            merged.filtered_counts = all_filtered_counts;
+            MethylationPatternVariant::Raw(merged)
         }
         MethylationOutput::Median => {
-            let collected = results
-                .into_par_iter()
+            let mut collected: Vec<_> = results
+                .into_iter()
                 .flat_map(|meth| {
                     if let MethylationPatternVariant::Median(median) = meth {
                         median
@@ -63,13 +208,14 @@ fn merge_methylation_results(
                     }
                 })
                 .collect();
+            collected.sort_by(|a, b| a.contig.cmp(&b.contig));
 
             MethylationPatternVariant::Median(collected)
         }
 
         MethylationOutput::WeightedMean => {
-            let collected = results
-                .into_par_iter()
+            let mut collected: Vec<_> = results
+                .into_iter()
                 .flat_map(|meth| {
                     if let MethylationPatternVariant::WeightedMean(weighted_mean) = meth {
                         weighted_mean
@@ -78,35 +224,232 @@ fn merge_methylation_results(
                     }
                 })
                 .collect();
+            collected.sort_by(|a, b| a.contig.cmp(&b.contig));
 
             MethylationPatternVariant::WeightedMean(collected)
         }
+
+        MethylationOutput::WeightedMedian => {
+            let mut collected: Vec<_> = results
+                .into_iter()
+                .flat_map(|meth| {
+                    if let MethylationPatternVariant::WeightedMedian(weighted_median) = meth {
+                        weighted_median
+                    } else {
+                        Vec::new()
+                    }
+                })
+                .collect();
+            collected.sort_by(|a, b| a.contig.cmp(&b.contig));
+
+            MethylationPatternVariant::WeightedMedian(collected)
+        }
+
+        MethylationOutput::TrimmedMean => {
+            let mut collected: Vec<_> = results
+                .into_iter()
+                .flat_map(|meth| {
+                    if let MethylationPatternVariant::TrimmedMean(trimmed_mean) = meth {
+                        trimmed_mean
+                    } else {
+                        Vec::new()
+                    }
+                })
+                .collect();
+            collected.sort_by(|a, b| a.contig.cmp(&b.contig));
+
+            MethylationPatternVariant::TrimmedMean(collected)
+        }
+
+        MethylationOutput::CalledFrequency => {
+            let mut collected: Vec<_> = results
+                .into_iter()
+                .flat_map(|meth| {
+                    if let MethylationPatternVariant::CalledFrequency(called_frequency) = meth {
+                        called_frequency
+                    } else {
+                        Vec::new()
+                    }
+                })
+                .collect();
+            collected.sort_by(|a, b| a.contig.cmp(&b.contig));
+
+            MethylationPatternVariant::CalledFrequency(collected)
+        }
+
+        MethylationOutput::StrandAsymmetry => {
+            let mut collected: Vec<_> = results
+                .into_iter()
+                .flat_map(|meth| {
+                    if let MethylationPatternVariant::StrandAsymmetry(asymmetry) = meth {
+                        asymmetry
+                    } else {
+                        Vec::new()
+                    }
+                })
+                .collect();
+            collected.sort_by(|a, b| a.contig.cmp(&b.contig));
+
+            MethylationPatternVariant::StrandAsymmetry(collected)
+        }
     }
 }
 
+/// Contigs of a .bed.gz pileup processed per thread before their results are streamed
+/// (see [`extract_methylation_pattern`]).
+const STREAM_CHUNK_CONTIGS_PER_THREAD: usize = 16;
+
+/// Sends `pattern` to `output_stream` when streaming, otherwise returns it to be merged
+/// with the other results.
+fn stream_or_keep(
+    pattern: MethylationPatternVariant,
+    output_stream: Option<&mpsc::Sender<MethylationPatternVariant>>,
+) -> Result<Option<MethylationPatternVariant>> {
+    match output_stream {
+        Some(sender) => {
+            sender
+                .send(pattern)
+                .map_err(|_| anyhow!("The output writer stopped before all results were sent"))?;
+            Ok(None)
+        }
+        None => Ok(Some(pattern)),
+    }
+}
+
+/// Settings of a methylation pattern run (see [`extract_methylation_pattern`]).
+#[derive(Debug, Clone)]
+pub struct MethylationPatternOptions {
+    pub threads: usize,
+    pub min_valid_read_coverage: u32,
+    pub min_valid_cov_to_diff_fraction: f32,
+    /// Records with a pileup score below `min_score` are removed like records failing
+    /// the coverage filters. A `min_score` of 0 keeps all records.
+    pub min_score: u32,
+    /// Pileup columns read as the valid coverage of a record.
+    pub coverage_field: CoverageField,
+    pub allow_mismatch: bool,
+    /// 5mC and 5hmC records at the same position are pooled into a single `C` record
+    /// before filtering, and 5mC/5hmC motifs are reported as `C`. Pooling needs all
+    /// records of a contig at once and is therefore not supported for streamed .bed
+    /// input.
+    pub combine_c_mods: bool,
+    /// The plus and minus strand observations of each site of a palindromic motif are
+    /// merged into one (see [`MotifMethylationPositions::collapse_strands`]).
+    pub collapse_strands: bool,
+    /// Contig-motifs with fewer observed positions are left out of every output type
+    /// and counted in [`MethylationRunStats::n_contig_motifs_below_min_observations`].
+    /// A value of 1 keeps all of them. Contigs reported by `report_missing` are not
+    /// affected.
+    pub min_motif_observations: u32,
+    /// Whether a malformed pileup record aborts the run or is skipped and counted in
+    /// [`MethylationRunStats::n_records_malformed`].
+    pub on_parse_error: ParseErrorMode,
+    /// Symmetric sites of palindromic motifs with different mod types on the two
+    /// strands are recorded as warnings. Like `combine_c_mods` this needs all records
+    /// of a contig at once.
+    pub check_mod_type_consistency: bool,
+    /// Contigs in the index of a .bed.gz pileup without records are reported with zero
+    /// observations (see [`empty_contig_methylation_pattern`]) instead of only being
+    /// counted in [`MethylationRunStats::n_contigs_empty`].
+    pub report_missing: bool,
+    /// The motif occurrences are loaded from this cache file instead of scanning the
+    /// assembly, if the cache matches the assembly and motifs. Otherwise the assembly
+    /// is scanned and the cache is (re)written (see
+    /// [`MotifOccurrenceTable::load_or_scan`]).
+    pub motif_occurrence_cache: Option<PathBuf>,
+    /// Contigs of .bed.gz and .bed pileups are processed in batches whose estimated
+    /// memory (bytes) stays within the limit. A batch always has at least one contig.
+    pub max_memory: Option<u64>,
+    /// .bed.gz pileups without the BGZF end-of-file block are read with a warning
+    /// instead of an error (see [`PileupReader::from_path_with_truncation`]).
+    pub allow_truncated: bool,
+    pub output_type: MethylationOutput,
+    /// Fraction of modified reads at or above which a position is called methylated
+    /// for [`MethylationOutput::CalledFrequency`]. Must be between 0 and 1.
+    pub call_threshold: f64,
+}
+
+impl Default for MethylationPatternOptions {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            min_valid_read_coverage: 3,
+            min_valid_cov_to_diff_fraction: 0.8,
+            min_score: 0,
+            coverage_field: CoverageField::default(),
+            allow_mismatch: false,
+            combine_c_mods: false,
+            collapse_strands: false,
+            min_motif_observations: 1,
+            on_parse_error: ParseErrorMode::default(),
+            check_mod_type_consistency: false,
+            report_missing: false,
+            motif_occurrence_cache: None,
+            max_memory: None,
+            allow_truncated: false,
+            output_type: MethylationOutput::Median,
+            call_threshold: DEFAULT_CALL_THRESHOLD,
+        }
+    }
+}
+
+/// Calculates the methylation pattern of `motifs` in `contigs` from `input` with the
+/// settings in `options`.
+///
+/// With `output_stream` the results are sent to the channel as soon as their chunk of
+/// contigs (.bed.gz) or batch (.bed) is finished instead of being collected, so memory
+/// does not grow with the number of contigs. The returned pattern is then empty. The
+/// results of a .bed.gz pileup are sent in contig order, those of a .bed pileup in the
+/// order its contigs appear, sorted within each batch. A DataFrame pileup is already in
+/// memory and its merged result is sent once.
 pub fn extract_methylation_pattern(
     input: MethylationInput,
     contigs: AHashMap<String, Contig>,
     motifs: Vec<Motif>,
-    threads: usize,
-    min_valid_read_coverage: u32,
-    min_valid_cov_to_diff_fraction: f32,
-    allow_mismatch: bool,
-    output_type: &MethylationOutput,
-) -> Result<MethylationPatternVariant> {
-    match input {
-        MethylationInput::GzFile(path) => {
+    options: &MethylationPatternOptions,
+    output_stream: Option<mpsc::Sender<MethylationPatternVariant>>,
+) -> Result<MethylationResult> {
+    if !(0.0..=1.0).contains(&options.call_threshold) {
+        bail!(
+            "The call threshold must be between 0 and 1, got {}",
+            options.call_threshold
+        );
+    }
+
+    let start = Instant::now();
+    let motifs = if options.combine_c_mods {
+        combine_cytosine_motifs(motifs)
+    } else {
+        motifs
+    };
+    let occurrences = options
+        .motif_occurrence_cache
+        .as_deref()
+        .map(|path| MotifOccurrenceTable::load_or_scan(path, &contigs, &motifs))
+        .transpose()?;
+
+    let input = match input {
+        MethylationInput::GzFile(path) => MethylationInput::GzFiles(vec![path]),
+        input => input,
+    };
+    let (pattern, mut stats) = match input {
+        MethylationInput::GzFiles(paths) => {
             extract_methylation_patten_from_gz::<epimetheus_io::io::readers::bgzf_bed::Reader>(
                 contigs,
-                &path,
+                &paths,
                 motifs,
-                threads,
-                min_valid_read_coverage,
-                min_valid_cov_to_diff_fraction,
-                allow_mismatch,
-                output_type,
+                occurrences.as_ref(),
+                options,
+                output_stream.as_ref(),
             )
         }
+        MethylationInput::GzFile(_) => unreachable!("Converted to GzFiles above"),
+        MethylationInput::BedFile(_, _) if options.combine_c_mods => {
+            bail!("Combining 5mC and 5hmC requires a .bed.gz or DataFrame pileup")
+        }
+        MethylationInput::BedFile(_, _) if options.check_mod_type_consistency => {
+            bail!("Checking mod type consistency requires a .bed.gz or DataFrame pileup")
+        }
         MethylationInput::BedFile(path, batch_size) => {
             let file = File::open(&path)?;
             let buf_reader = BufReader::new(file);
@@ -114,48 +457,88 @@ pub fn extract_methylation_pattern(
                 buf_reader,
                 contigs,
                 batch_size,
-                min_valid_read_coverage,
-                min_valid_cov_to_diff_fraction,
-                allow_mismatch,
-            );
-            extract_methylation_pattern_bed(&mut loader, motifs, threads, output_type)
+                options.min_valid_read_coverage,
+                options.min_valid_cov_to_diff_fraction,
+                options.allow_mismatch,
+            )
+            .with_parse_error_mode(options.on_parse_error)
+            .with_min_score(options.min_score)
+            .with_coverage_field(options.coverage_field.clone())
+            .with_max_memory(options.max_memory);
+            extract_methylation_pattern_bed(
+                &mut loader,
+                motifs,
+                occurrences.as_ref(),
+                options,
+                output_stream.as_ref(),
+            )
         }
         MethylationInput::DataFrame(df) => extract_methylation_pattern_polars(
             contigs,
             df,
             motifs,
-            threads,
-            min_valid_read_coverage,
-            min_valid_cov_to_diff_fraction,
-            output_type,
+            occurrences.as_ref(),
+            options,
         ),
+    }?;
+    stats.elapsed = start.elapsed();
+    if options.min_motif_observations > 1 {
+        info!(
+            "Dropped {} contig-motif results with fewer than {} motif observations",
+            stats.n_contig_motifs_below_min_observations, options.min_motif_observations
+        );
     }
+
+    let pattern = match stream_or_keep(pattern, output_stream.as_ref())? {
+        Some(pattern) => pattern,
+        None => merge_methylation_results(Vec::new(), &options.output_type),
+    };
+
+    Ok(MethylationResult { pattern, stats })
 }
 
 fn extract_methylation_patten_from_gz<R: PileupReader + Clone>(
     contigs: AHashMap<String, Contig>,
-    pileup_path: &Path,
+    pileup_paths: &[PathBuf],
     motifs: Vec<Motif>,
-    threads: usize,
-    min_valid_read_coverage: u32,
-    min_valid_cov_to_diff_fraction: f32,
-    allow_mismatch: bool,
-    output_type: &MethylationOutput,
-) -> Result<MethylationPatternVariant> {
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(threads)
+    occurrences: Option<&MotifOccurrenceTable>,
+    options: &MethylationPatternOptions,
+    output_stream: Option<&mpsc::Sender<MethylationPatternVariant>>,
+) -> Result<(MethylationPatternVariant, MethylationRunStats)> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads)
         .build()
         .expect("Could not initialize threadpool");
 
-    let contigs_in_index: HashSet<String> = R::from_path(pileup_path)?
-        .available_contigs()
-        .into_iter()
-        .collect();
+    // A contig is looked up in every pileup whose index lists it, so it only has to be
+    // in one of them. Querying a pileup for a contig missing from its index fails.
+    let contigs_per_pileup = pileup_paths
+        .iter()
+        .map(|pileup_path| {
+            Ok(R::from_path_with_truncation(pileup_path, options.allow_truncated)?
+                .available_contigs()
+                .into_iter()
+                .collect::<HashSet<String>>())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let contigs_in_index: HashSet<&String> = contigs_per_pileup.iter().flatten().collect();
 
-    let filtered_contigs: Vec<(&String, &Contig)> = if allow_mismatch {
+    let mut filtered_contigs: Vec<(&String, &Contig)> = if options.allow_mismatch {
         contigs
             .iter()
-            .filter(|(contig_id, _)| contigs_in_index.contains(*contig_id))
+            .filter(|(contig_id, _)| {
+                let in_index = contigs_in_index.contains(*contig_id);
+                if !in_index {
+                    record_warning(
+                        WarningEvent::new(
+                            WarningType::ContigMismatch,
+                            "Contig in assembly not found in pileup. Skipping contig.",
+                        )
+                        .with_contig(contig_id.as_str()),
+                    );
+                }
+                in_index
+            })
             .collect()
     } else {
         let contig_vec = contigs.iter().collect();
@@ -172,104 +555,289 @@ fn extract_methylation_patten_from_gz<R: PileupReader + Clone>(
         }
         contig_vec
     };
+    // Process contigs in a stable order. The assembly map iterates differently
+    // every run.
+    filtered_contigs.sort_by(|(a, _), (b, _)| a.cmp(b));
 
     let progress_bar = ProgressBar::new(filtered_contigs.len() as u64);
+    let consistency_motifs = options.check_mod_type_consistency.then_some(motifs.as_slice());
+    let n_below_min_observations = AtomicUsize::new(0);
 
-    let per_contig_results = filtered_contigs
-        .par_iter()
-        .map(|(contig_id, contig)| -> Result<MethylationPatternVariant> {
-            let pileup_records = load_pileup_records_for_contig::<R>(pileup_path, contig_id)?;
-            debug!(
-                "{}\nPileup records before filtering: {}",
+    // The pattern, filtered and malformed records, whether the contig had no records and
+    // the coverage histogram of its records.
+    type ContigResult = (
+        Option<MethylationPatternVariant>,
+        usize,
+        usize,
+        bool,
+        CoverageHistogram,
+    );
+    let process_contig = |(contig_id, contig): &(&String, &Contig)| -> Result<ContigResult> {
+        let mut pileup_records = Vec::new();
+        let mut n_malformed = 0;
+        for (pileup_path, pileup_contigs) in pileup_paths.iter().zip(&contigs_per_pileup) {
+            if !pileup_contigs.contains(*contig_id) {
+                continue;
+            }
+            let (records, n) = load_pileup_records_for_contig::<R>(
+                pileup_path,
                 contig_id,
-                pileup_records.len()
-            );
+                options.on_parse_error,
+                options.allow_truncated,
+            )?;
+            pileup_records.extend(records);
+            n_malformed += n;
+        }
+        if pileup_paths.len() > 1 {
+            pileup_records = merge_pileup_records(pileup_records);
+        }
+        if pileup_records.is_empty() {
+            debug!("{}: in the pileup index but has no records", contig_id);
+            let pattern = if options.report_missing {
+                Some(empty_contig_methylation_pattern(contig, &motifs, &options.output_type))
+            } else {
+                None
+            };
+            progress_bar.inc(1);
+            return Ok((pattern, 0, n_malformed, true, CoverageHistogram::new()));
+        }
+        if options.combine_c_mods {
+            pileup_records = combine_cytosine_modifications(pileup_records);
+        }
+        debug!(
+            "{}\nPileup records before filtering: {}",
+            contig_id,
+            pileup_records.len()
+        );
 
-            let mut meth_records = Vec::new();
-            for rec in pileup_records {
-                let meth = MethylationRecord::try_from_with_filters(
-                    rec,
-                    min_valid_read_coverage,
-                    min_valid_cov_to_diff_fraction,
-                )?;
+        let n_pileup_records = pileup_records.len();
+        let mut meth_records = Vec::new();
+        let mut filtered_positions = FilteredPositions::new();
+        let mut coverage_histogram = CoverageHistogram::new();
+        for rec in pileup_records {
+            coverage_histogram.add(rec.mod_type, options.coverage_field.value(&rec));
+            let meth = MethylationRecord::try_from_with_filters(
+                rec,
+                options.min_valid_read_coverage,
+                options.min_valid_cov_to_diff_fraction,
+                options.min_score,
+                &options.coverage_field,
+                Some(&mut filtered_positions),
+            )?;
 
-                match meth {
-                    Some(m) => meth_records.push(m),
-                    None => continue,
-                }
+            match meth {
+                Some(m) => meth_records.push(m),
+                None => continue,
             }
+        }
 
-            debug!(
-                "{}\nMethylation records after filtering: {}",
-                contig_id,
-                meth_records.len()
+        debug!(
+            "{}\nMethylation records after filtering: {}",
+            contig_id,
+            meth_records.len()
+        );
+
+        if meth_records.is_empty() && n_pileup_records > 0 {
+            record_warning(
+                WarningEvent::new(
+                    WarningType::FilteredRecords,
+                    format!(
+                        "All {} pileup records were removed by the coverage filters",
+                        n_pileup_records
+                    ),
+                )
+                .with_contig(contig_id.as_str()),
             );
+        }
 
-            let contig_w_meth = populate_contig_with_methylation(contig, meth_records)?;
+        let n_filtered = n_pileup_records - meth_records.len();
+        let mut contig_w_meth = populate_contig_with_methylation(
+            contig,
+            meth_records,
+            consistency_motifs,
+            options.allow_mismatch,
+        )?;
+        contig_w_meth.filtered_positions = filtered_positions;
 
-            let positions =
-                calculate_contig_read_methylation_single(&contig_w_meth, motifs.clone())?;
+        let positions = calculate_contig_read_methylation_with_occurrences(
+            &contig_w_meth,
+            motifs.clone(),
+            occurrences,
+        )?;
+        let mut positions = if options.collapse_strands {
+            positions.collapse_strands()
+        } else {
+            positions
+        };
+        n_below_min_observations.fetch_add(
+            positions.retain_min_motif_observations(options.min_motif_observations),
+            Ordering::Relaxed,
+        );
 
-            progress_bar.inc(1);
-            match output_type {
-                MethylationOutput::Raw => Ok(MethylationPatternVariant::Raw(positions)),
-                MethylationOutput::Median => Ok(MethylationPatternVariant::Median(
-                    positions.to_median_degrees(),
-                )),
-                MethylationOutput::WeightedMean => Ok(MethylationPatternVariant::WeightedMean(
-                    positions.to_weighted_mean_degress(),
-                )),
+        let pattern = Some(positions.into_variant(&options.output_type, options.call_threshold));
+
+        progress_bar.inc(1);
+        Ok((pattern, n_filtered, n_malformed, false, coverage_histogram))
+    };
+
+    let batches = match options.max_memory {
+        Some(max_memory) => partition_contigs_by_memory(&filtered_contigs, max_memory),
+        None => vec![filtered_contigs.clone()],
+    };
+    // Streamed results are sent in contig order once their chunk is finished, so only
+    // the results of a chunk are held at once.
+    let chunk_size = match output_stream {
+        Some(_) => options.threads.max(1) * STREAM_CHUNK_CONTIGS_PER_THREAD,
+        None => usize::MAX,
+    };
+    let mut per_contig_results = Vec::with_capacity(filtered_contigs.len());
+    for (i, batch) in batches.iter().enumerate() {
+        if batches.len() > 1 {
+            let batch_memory: u64 = batch.iter().map(|(_, contig)| contig.estimated_memory()).sum();
+            info!(
+                "Processing batch {}/{} of {} contigs (~{:.2} GB estimated) to stay within the memory limit",
+                i + 1,
+                batches.len(),
+                batch.len(),
+                batch_memory as f64 / 1e9
+            );
+        }
+        for chunk in batch.chunks(chunk_size) {
+            let chunk_results = pool.install(|| {
+                chunk
+                    .par_iter()
+                    .map(&process_contig)
+                    .collect::<Result<Vec<ContigResult>>>()
+            })?;
+            for (pattern, n_filtered, n_malformed, empty, histogram) in chunk_results {
+                let pattern = match pattern {
+                    Some(pattern) => stream_or_keep(pattern, output_stream)?,
+                    None => None,
+                };
+                per_contig_results.push((pattern, n_filtered, n_malformed, empty, histogram));
             }
-        })
-        .collect::<Result<Vec<MethylationPatternVariant>>>()?;
+        }
+    }
 
-    let merged_results = merge_methylation_results(per_contig_results, output_type);
+    let mut coverage_histogram = CoverageHistogram::new();
+    for (_, _, _, _, histogram) in per_contig_results.iter_mut() {
+        coverage_histogram.merge(std::mem::take(histogram));
+    }
+    let stats = MethylationRunStats {
+        n_contigs_processed: filtered_contigs.len(),
+        n_contigs_skipped: contigs.len() - filtered_contigs.len(),
+        n_records_filtered: per_contig_results.iter().map(|(_, n, _, _, _)| n).sum(),
+        n_records_malformed: per_contig_results.iter().map(|(_, _, n, _, _)| n).sum(),
+        n_contigs_empty: per_contig_results
+            .iter()
+            .filter(|(_, _, _, empty, _)| *empty)
+            .count(),
+        n_contig_motifs_below_min_observations: n_below_min_observations.into_inner(),
+        coverage_histogram,
+        ..Default::default()
+    };
+    let per_contig_results = per_contig_results
+        .into_iter()
+        .filter_map(|(pattern, _, _, _, _)| pattern)
+        .collect();
+    let merged_results = merge_methylation_results(per_contig_results, &options.output_type);
+
+    Ok((merged_results, stats))
+}
+
+/// Spinner for the plain BED path, which has no index and so no total to show a bar
+/// against. It is hidden when stderr is not a terminal, so log files stay clean.
+fn bed_progress_spinner(stderr_is_terminal: bool) -> ProgressBar {
+    if !stderr_is_terminal {
+        return ProgressBar::hidden();
+    }
 
-    Ok(merged_results)
+    let spinner = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.green} [{elapsed_precise}] {msg}")
+            .expect("Valid progress template"),
+    );
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner.set_message("Processing contigs...");
+    spinner
+}
+
+/// The first contig ids joined by ", ", with the number of ids left out.
+fn preview_contig_ids(contig_ids: &[String]) -> String {
+    const MAX_SHOWN: usize = 10;
+
+    let shown = contig_ids[..contig_ids.len().min(MAX_SHOWN)].join(", ");
+    if contig_ids.len() > MAX_SHOWN {
+        format!("{} and {} more", shown, contig_ids.len() - MAX_SHOWN)
+    } else {
+        shown
+    }
 }
 
 fn extract_methylation_pattern_bed<L: BatchLoader<GenomeWorkspace>>(
     loader: &mut L,
     motifs: Vec<Motif>,
-    threads: usize,
-    output_type: &MethylationOutput,
-) -> Result<MethylationPatternVariant> {
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(threads)
+    occurrences: Option<&MotifOccurrenceTable>,
+    options: &MethylationPatternOptions,
+    output_stream: Option<&mpsc::Sender<MethylationPatternVariant>>,
+) -> Result<(MethylationPatternVariant, MethylationRunStats)> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads)
         .build()
         .expect("Could not initialize threadpool");
 
     let mut all_batch_results = Vec::new();
     let mut contigs_processed = 0;
+    let mut records_processed = 0u64;
     let mut batch_processing_time = Instant::now();
+    let spinner = bed_progress_spinner(std::io::stderr().is_terminal());
+    let n_below_min_observations = AtomicUsize::new(0);
 
     for batch_result in
         epimetheus_io::services::data_loading_service::process_batches_from_loader(loader)
     {
-        let populated_contigs = batch_result?;
+        let mut populated_contigs: Vec<(String, Contig)> = batch_result?.into_iter().collect();
+        populated_contigs.sort_by(|(a, _), (b, _)| a.cmp(b));
         debug!("Workspace initialized");
+        records_processed += populated_contigs
+            .iter()
+            .map(|(_, contig)| contig.methylated_positions.len() as u64)
+            .sum::<u64>();
 
-        let batch_methylation_patterns: Result<Vec<MethylationPatternVariant>> = populated_contigs
-            .par_iter()
-            .map(|(_, contig)| {
-                let positions = calculate_contig_read_methylation_single(contig, motifs.clone())?;
-
-                match output_type {
-                    MethylationOutput::Raw => Ok(MethylationPatternVariant::Raw(positions)),
-                    MethylationOutput::Median => Ok(MethylationPatternVariant::Median(
-                        positions.to_median_degrees(),
-                    )),
-                    MethylationOutput::WeightedMean => Ok(MethylationPatternVariant::WeightedMean(
-                        positions.to_weighted_mean_degress(),
-                    )),
-                }
-            })
-            .collect();
+        // The contigs of a batch are independent, so they are processed in parallel
+        // on the pool of `options.threads` threads.
+        let process_contig = |(_, contig): &(String, Contig)| -> Result<MethylationPatternVariant> {
+            let positions = calculate_contig_read_methylation_with_occurrences(
+                contig,
+                motifs.clone(),
+                occurrences,
+            )?;
+            let mut positions = if options.collapse_strands {
+                positions.collapse_strands()
+            } else {
+                positions
+            };
+            n_below_min_observations.fetch_add(
+                positions.retain_min_motif_observations(options.min_motif_observations),
+                Ordering::Relaxed,
+            );
 
-        let batch_patterns = batch_methylation_patterns?;
-        all_batch_results.extend(batch_patterns);
+            Ok(positions.into_variant(&options.output_type, options.call_threshold))
+        };
+        let batch_methylation_patterns: Result<Vec<MethylationPatternVariant>> =
+            pool.install(|| populated_contigs.par_iter().map(process_contig).collect());
+
+        for pattern in batch_methylation_patterns? {
+            all_batch_results.extend(stream_or_keep(pattern, output_stream)?);
+        }
 
         contigs_processed += populated_contigs.len();
+        spinner.set_message(format!(
+            "Processed {} contigs ({} records)",
+            HumanCount(contigs_processed as u64),
+            HumanCount(records_processed)
+        ));
         let elapsed = batch_processing_time.elapsed();
         if contigs_processed % 100 == 0 {
             info!(
@@ -280,63 +848,108 @@ fn extract_methylation_pattern_bed<L: BatchLoader<GenomeWorkspace>>(
         }
         batch_processing_time = Instant::now();
     }
+    spinner.finish_and_clear();
 
-    let merged_results = merge_methylation_results(all_batch_results, output_type);
+    let skipped_contigs = loader.skipped_contigs();
+    if !skipped_contigs.is_empty() {
+        warn!(
+            "Skipped {} records of {} pileup contigs not found in the assembly: {}",
+            loader.n_skipped_records(),
+            skipped_contigs.len(),
+            preview_contig_ids(&skipped_contigs)
+        );
+    }
+
+    let stats = MethylationRunStats {
+        n_contigs_processed: contigs_processed,
+        n_contigs_skipped: loader.n_skipped_contigs(),
+        n_records_skipped: loader.n_skipped_records(),
+        n_records_filtered: loader.n_filtered_records(),
+        n_records_malformed: loader.n_malformed_records(),
+        n_contig_motifs_below_min_observations: n_below_min_observations.into_inner(),
+        coverage_histogram: loader.coverage_histogram(),
+        ..Default::default()
+    };
+    let merged_results = merge_methylation_results(all_batch_results, &options.output_type);
 
-    Ok(merged_results)
+    Ok((merged_results, stats))
 }
 
 fn extract_methylation_pattern_polars(
     contigs: AHashMap<String, Contig>,
     pileup_df: DataFrame,
     motifs: Vec<Motif>,
-    threads: usize,
-    min_valid_read_coverage: u32,
-    min_valid_cov_to_diff_fraction: f32,
-    output_type: &MethylationOutput,
-) -> Result<MethylationPatternVariant> {
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(threads)
+    occurrences: Option<&MotifOccurrenceTable>,
+    options: &MethylationPatternOptions,
+) -> Result<(MethylationPatternVariant, MethylationRunStats)> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads)
         .build()
         .expect("Could not initialize threadpool");
 
-    let pileup_records: Result<Vec<PileupRecord>, _> = (0..pileup_df.height())
-        .map(|i| -> Result<PileupRecord, anyhow::Error> {
-            let row = pileup_df.get_row(i)?;
-
-            Ok(PileupRecord::new(
-                row.0[0].get_str().unwrap().to_string(),
-                row.0[1].try_extract::<u32>()?,
-                row.0[2].try_extract::<u32>()?,
-                row.0[3].get_str().unwrap().parse()?,
-                row.0[4].try_extract::<u32>()?,
-                row.0[5].get_str().unwrap().parse()?,
-                row.0[6].try_extract::<u32>()?,
-                row.0[7].try_extract::<u32>()?,
-                row.0[8].get_str().unwrap().to_string(),
-                row.0[9].try_extract::<u32>()?,
-                row.0[10].try_extract::<f64>()?,
-                row.0[11].try_extract::<u32>()?,
-                row.0[12].try_extract::<u32>()?,
-                row.0[13].try_extract::<u32>()?,
-                row.0[14].try_extract::<u32>()?,
-                row.0[15].try_extract::<u32>()?,
-                row.0[16].try_extract::<u32>()?,
-                row.0[17].try_extract::<u32>()?,
-            ))
-        })
-        .collect();
-    let pileup_records = pileup_records?;
+    let parse_row = |i: usize| -> Result<PileupRecord, anyhow::Error> {
+        let row = pileup_df.get_row(i)?;
+
+        Ok(PileupRecord::new(
+            row.0[0].get_str().unwrap().to_string(),
+            row.0[1].try_extract::<u32>()?,
+            row.0[2].try_extract::<u32>()?,
+            row.0[3].get_str().unwrap().parse()?,
+            row.0[4].try_extract::<u32>()?,
+            row.0[5].get_str().unwrap().parse()?,
+            row.0[6].try_extract::<u32>()?,
+            row.0[7].try_extract::<u32>()?,
+            row.0[8].get_str().unwrap().to_string(),
+            row.0[9].try_extract::<u32>()?,
+            row.0[10].try_extract::<f64>()?,
+            row.0[11].try_extract::<u32>()?,
+            row.0[12].try_extract::<u32>()?,
+            row.0[13].try_extract::<u32>()?,
+            row.0[14].try_extract::<u32>()?,
+            row.0[15].try_extract::<u32>()?,
+            row.0[16].try_extract::<u32>()?,
+            row.0[17].try_extract::<u32>()?,
+        ))
+    };
+
+    let mut pileup_records = Vec::with_capacity(pileup_df.height());
+    let mut n_records_malformed = 0;
+    for i in 0..pileup_df.height() {
+        match (parse_row(i), options.on_parse_error) {
+            (Ok(record), _) => pileup_records.push(record),
+            (Err(e), ParseErrorMode::Fail) => {
+                return Err(e.context(format!("Malformed pileup record in row {}", i)));
+            }
+            (Err(e), ParseErrorMode::Skip) => {
+                let message = format!("Skipping malformed pileup record in row {}: {}", i, e);
+                warn!("{}", message);
+                record_warning(WarningEvent::new(WarningType::MalformedRecord, message));
+                n_records_malformed += 1;
+            }
+        }
+    }
+    if options.combine_c_mods {
+        pileup_records = combine_cytosine_modifications(pileup_records);
+    }
 
     let mut meth_records = Vec::new();
+    let mut n_records_filtered = 0;
+    let mut filtered_by_contig: AHashMap<String, FilteredPositions> = AHashMap::new();
+    let mut coverage_histogram = CoverageHistogram::new();
     for rec in &pileup_records {
+        if contigs.contains_key(&rec.contig) {
+            coverage_histogram.add(rec.mod_type, options.coverage_field.value(rec));
+        }
         match MethylationRecord::try_from_with_filters(
             rec.clone(),
-            min_valid_read_coverage,
-            min_valid_cov_to_diff_fraction,
+            options.min_valid_read_coverage,
+            options.min_valid_cov_to_diff_fraction,
+            options.min_score,
+            &options.coverage_field,
+            Some(filtered_by_contig.entry(rec.contig.clone()).or_default()),
         )? {
             Some(m) => meth_records.push(m),
-            None => continue,
+            None => n_records_filtered += 1,
         }
     }
 
@@ -347,40 +960,86 @@ fn extract_methylation_pattern_polars(
             acc
         });
 
-    let per_contig_results = records_by_contig
-        .par_iter()
-        .filter_map(|(contig_id, meth_records)| {
-            contigs
-                .get(contig_id)
-                .map(|contig| -> Result<MethylationPatternVariant> {
-                    let contig_w_meth =
-                        populate_contig_with_methylation(contig, meth_records.clone())?;
-                    let positions =
-                        calculate_contig_read_methylation_single(&contig_w_meth, motifs.clone())?;
-
-                    match output_type {
-                        MethylationOutput::Raw => Ok(MethylationPatternVariant::Raw(positions)),
-                        MethylationOutput::Median => Ok(MethylationPatternVariant::Median(
-                            positions.to_median_degrees(),
-                        )),
-                        MethylationOutput::WeightedMean => {
-                            Ok(MethylationPatternVariant::WeightedMean(
-                                positions.to_weighted_mean_degress(),
-                            ))
+    for contig_id in records_by_contig.keys() {
+        if !contigs.contains_key(contig_id) {
+            record_warning(
+                WarningEvent::new(
+                    WarningType::ContigMismatch,
+                    "Contig in pileup not found in assembly. Skipping records.",
+                )
+                .with_contig(contig_id.as_str()),
+            );
+        }
+    }
+
+    let consistency_motifs = options.check_mod_type_consistency.then_some(motifs.as_slice());
+    let n_below_min_observations = AtomicUsize::new(0);
+    let per_contig_results = pool.install(|| {
+        records_by_contig
+            .par_iter()
+            .filter_map(|(contig_id, meth_records)| {
+                contigs
+                    .get(contig_id)
+                    .map(|contig| -> Result<MethylationPatternVariant> {
+                        let mut contig_w_meth = populate_contig_with_methylation(
+                            contig,
+                            meth_records.clone(),
+                            consistency_motifs,
+                            options.allow_mismatch,
+                        )?;
+                        if let Some(filtered) = filtered_by_contig.get(contig_id) {
+                            contig_w_meth.filtered_positions = filtered.clone();
                         }
-                    }
-                })
-        })
-        .collect::<Result<Vec<MethylationPatternVariant>>>()?;
+                        let positions = calculate_contig_read_methylation_with_occurrences(
+                            &contig_w_meth,
+                            motifs.clone(),
+                            occurrences,
+                        )?;
+                        let mut positions = if options.collapse_strands {
+                            positions.collapse_strands()
+                        } else {
+                            positions
+                        };
+                        n_below_min_observations.fetch_add(
+                            positions.retain_min_motif_observations(options.min_motif_observations),
+                            Ordering::Relaxed,
+                        );
 
-    let merged_results = merge_methylation_results(per_contig_results, output_type);
+                        Ok(positions.into_variant(&options.output_type, options.call_threshold))
+                    })
+            })
+            .collect::<Result<Vec<MethylationPatternVariant>>>()
+    })?;
 
-    Ok(merged_results)
+    let stats = MethylationRunStats {
+        n_contigs_processed: per_contig_results.len(),
+        n_contigs_skipped: records_by_contig
+            .keys()
+            .filter(|contig_id| !contigs.contains_key(*contig_id))
+            .count(),
+        n_records_filtered,
+        n_records_malformed,
+        n_contig_motifs_below_min_observations: n_below_min_observations.into_inner(),
+        coverage_histogram,
+        ..Default::default()
+    };
+    let merged_results = merge_methylation_results(per_contig_results, &options.output_type);
+
+    Ok((merged_results, stats))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use epimetheus_core::models::pileup::PileupRecordString;
+    use epimetheus_methylome::sequence::Sequence;
+
+    #[test]
+    fn test_bed_progress_spinner_hidden_without_terminal() {
+        let spinner = bed_progress_spinner(false);
+        assert!(spinner.is_hidden());
+        assert_eq!(spinner.length(), None);
+    }
 
     #[test]
     fn test_from_pileup() {
@@ -456,4 +1115,496 @@ mod tests {
 
         assert_eq!(pileup_records[0].contig, "contig_2");
     }
+
+    #[test]
+    fn test_extract_methylation_pattern_stats() -> Result<()> {
+        let pileup_df = df!(
+            "contig" => ["contig_1", "contig_1", "contig_2"],
+            "start" => [2u32, 7, 2],
+            "end" => [3u32, 8, 3],
+            "mod_type" => ["a", "a", "a"],
+            "score" => [20u32, 1, 20],
+            "strand" => ["+", "+", "+"],
+            "start_pos" => [2u32, 7, 2],
+            "end_pos" => [3u32, 8, 3],
+            "color" => ["255,0,0", "255,0,0", "255,0,0"],
+            "n_valid_cov" => [20u32, 1, 20],
+            "fraction_modified" => [100.0, 100.0, 100.0],
+            "n_modified" => [20u32, 1, 20],
+            "n_canonical" => [0u32, 0, 0],
+            "n_other_mod" => [0u32, 0, 0],
+            "n_delete" => [0u32, 0, 0],
+            "n_fail" => [0u32, 0, 0],
+            "n_diff" => [0u32, 0, 0],
+            "n_no_call" => [0u32, 0, 0],
+        )?;
+
+        let mut contigs = AHashMap::new();
+        contigs.insert(
+            "contig_1".to_string(),
+            Contig::new("contig_1".to_string(), Sequence::from_str("TGATCTGATC")?),
+        );
+
+        let result = extract_methylation_pattern(
+            MethylationInput::DataFrame(pileup_df),
+            contigs,
+            vec![Motif::new("GATC", "a", 1)?],
+            &MethylationPatternOptions::default(),
+            None,
+        )?;
+
+        assert_eq!(result.stats.n_contigs_processed, 1);
+        assert_eq!(result.stats.n_contigs_skipped, 1);
+        assert_eq!(result.stats.n_records_filtered, 1);
+        // Records of contig_2 are not in the assembly and are not counted.
+        assert_eq!(
+            result.stats.coverage_histogram.binned(5)?,
+            vec![
+                (epimetheus_methylome::ModType::SixMA, 0, 1),
+                (epimetheus_methylome::ModType::SixMA, 20, 1)
+            ]
+        );
+        assert!(matches!(result.pattern, MethylationPatternVariant::Median(ref d) if d.len() == 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_methylation_pattern_bed_is_independent_of_threads() -> Result<()> {
+        use std::io::Write;
+
+        let contig_ids = ["contig_1", "contig_2", "contig_3"];
+        let mut pileup = tempfile::Builder::new().suffix(".bed").tempfile()?;
+        for (i, contig_id) in contig_ids.iter().enumerate() {
+            for start in [2, 7] {
+                let n_modified = 5 * (i + 1);
+                writeln!(
+                    pileup,
+                    "{contig_id}\t{start}\t{}\ta\t20\t+\t{start}\t{}\t255,0,0\t20\t0\t{n_modified}\t{}\t0\t0\t0\t0\t0",
+                    start + 1,
+                    start + 1,
+                    20 - n_modified
+                )?;
+            }
+        }
+        pileup.flush()?;
+
+        let run = |threads: usize| -> Result<String> {
+            let contigs: AHashMap<String, Contig> = contig_ids
+                .iter()
+                .map(|contig_id| {
+                    let sequence = Sequence::from_str("TGATCTGATC").unwrap();
+                    (
+                        contig_id.to_string(),
+                        Contig::new(contig_id.to_string(), sequence),
+                    )
+                })
+                .collect();
+            let result = extract_methylation_pattern(
+                MethylationInput::BedFile(pileup.path().to_path_buf(), 1),
+                contigs,
+                vec![Motif::new("GATC", "a", 1)?],
+                &MethylationPatternOptions {
+                    threads,
+                    output_type: MethylationOutput::Raw,
+                    ..Default::default()
+                },
+                None,
+            )?;
+            assert_eq!(result.stats.n_contigs_processed, 3);
+            assert_eq!(result.stats.coverage_histogram.n_records(), 6);
+
+            let out = tempfile::NamedTempFile::new()?;
+            result.write_output(
+                out.path(),
+                ValueScale::Fraction,
+                true,
+                false,
+                StrandFormat::Symbol,
+                &[],
+            )?;
+            Ok(std::fs::read_to_string(out.path())?)
+        };
+
+        let single_threaded = run(1)?;
+        assert_eq!(single_threaded.lines().count(), 7);
+        assert_eq!(run(3)?, single_threaded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bed_called_frequency_uses_call_threshold() -> Result<()> {
+        use std::io::Write;
+
+        let mut pileup = tempfile::Builder::new().suffix(".bed").tempfile()?;
+        for (start, n_modified) in [(2, 5), (7, 15)] {
+            writeln!(
+                pileup,
+                "contig_1\t{start}\t{}\ta\t20\t+\t{start}\t{}\t255,0,0\t20\t0\t{n_modified}\t{}\t0\t0\t0\t0\t0",
+                start + 1,
+                start + 1,
+                20 - n_modified
+            )?;
+        }
+        pileup.flush()?;
+
+        let run = |call_threshold: f64| -> Result<Vec<f64>> {
+            let mut contigs = AHashMap::new();
+            contigs.insert(
+                "contig_1".to_string(),
+                Contig::new("contig_1".to_string(), Sequence::from_str("TGATCTGATC")?),
+            );
+            let result = extract_methylation_pattern(
+                MethylationInput::BedFile(pileup.path().to_path_buf(), 1),
+                contigs,
+                vec![Motif::new("GATC", "a", 1)?],
+                &MethylationPatternOptions {
+                    output_type: MethylationOutput::CalledFrequency,
+                    call_threshold,
+                    ..Default::default()
+                },
+                None,
+            )?;
+            match result.pattern {
+                MethylationPatternVariant::CalledFrequency(degrees) => Ok(degrees
+                    .iter()
+                    .map(|degree| degree.called_frequency)
+                    .collect()),
+                _ => bail!("Expected a called frequency pattern"),
+            }
+        };
+
+        // The positions have 25% and 75% modified reads.
+        assert_eq!(run(DEFAULT_CALL_THRESHOLD)?, vec![0.5]);
+        assert_eq!(run(0.2)?, vec![1.0]);
+        assert_eq!(run(0.9)?, vec![0.0]);
+        assert!(run(1.5).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_motif_occurrence_cache() -> Result<()> {
+        use std::io::Write;
+
+        let mut pileup = tempfile::Builder::new().suffix(".bed").tempfile()?;
+        for start in [2, 7] {
+            writeln!(
+                pileup,
+                "contig_1\t{start}\t{}\ta\t20\t+\t{start}\t{}\t255,0,0\t20\t0\t15\t5\t0\t0\t0\t0\t0",
+                start + 1,
+                start + 1,
+            )?;
+        }
+        pileup.flush()?;
+        let cache_dir = tempfile::TempDir::new()?;
+        let cache = cache_dir.path().join("occurrences.tsv");
+
+        let run = |motif: Motif, cache: Option<&Path>| -> Result<String> {
+            let mut contigs = AHashMap::new();
+            contigs.insert(
+                "contig_1".to_string(),
+                Contig::new("contig_1".to_string(), Sequence::from_str("TGATCTGATC")?),
+            );
+            let result = extract_methylation_pattern(
+                MethylationInput::BedFile(pileup.path().to_path_buf(), 1),
+                contigs,
+                vec![motif],
+                &MethylationPatternOptions {
+                    motif_occurrence_cache: cache.map(Path::to_path_buf),
+                    output_type: MethylationOutput::Raw,
+                    ..Default::default()
+                },
+                None,
+            )?;
+            let out = tempfile::NamedTempFile::new()?;
+            result.write_output(
+                out.path(),
+                ValueScale::Fraction,
+                true,
+                false,
+                StrandFormat::Symbol,
+                &[],
+            )?;
+            Ok(std::fs::read_to_string(out.path())?)
+        };
+
+        let gatc = Motif::new("GATC", "a", 1)?;
+        let scanned = run(gatc.clone(), None)?;
+        assert_eq!(scanned.lines().count(), 3);
+
+        // The first run writes the cache and the second loads it.
+        assert_eq!(run(gatc.clone(), Some(&cache))?, scanned);
+        assert!(cache.exists());
+        assert_eq!(run(gatc.clone(), Some(&cache))?, scanned);
+
+        // A cache of other motifs is not used. The assembly is scanned instead.
+        let tgatc = Motif::new("TGATC", "a", 2)?;
+        assert_eq!(run(tgatc, Some(&cache))?.lines().count(), 3);
+        assert_eq!(run(gatc, Some(&cache))?, scanned);
+
+        Ok(())
+    }
+
+    /// Reader with an index of two contigs, where only contig_1 has records.
+    #[derive(Clone)]
+    struct EmptyContigReader;
+
+    impl PileupReader for EmptyContigReader {
+        fn from_path(_path: &Path) -> Result<Self> {
+            Ok(Self)
+        }
+
+        fn query_contig(&mut self, contig: &str) -> Result<Vec<PileupRecordString>> {
+            let records = match contig {
+                "contig_1" => vec![PileupRecordString::new(
+                    "contig_1\t2\t3\ta\t20\t+\t2\t3\t255,0,0\t20\t100.00\t20\t0\t0\t0\t0\t0\t0"
+                        .to_string(),
+                )],
+                _ => Vec::new(),
+            };
+            Ok(records)
+        }
+
+        fn available_contigs(&self) -> Vec<String> {
+            vec!["contig_1".to_string(), "contig_2".to_string()]
+        }
+    }
+
+    #[test]
+    fn test_indexed_contig_without_records() -> Result<()> {
+        fn run(report_missing: bool) -> Result<(MethylationPatternVariant, MethylationRunStats)> {
+            let mut contigs = AHashMap::new();
+            for contig_id in ["contig_1", "contig_2"] {
+                contigs.insert(
+                    contig_id.to_string(),
+                    Contig::new(contig_id.to_string(), Sequence::from_str("TGATCTGATC")?),
+                );
+            }
+            extract_methylation_patten_from_gz::<EmptyContigReader>(
+                contigs,
+                &[PathBuf::from("indexed.bed.gz")],
+                vec![Motif::new("GATC", "a", 1)?],
+                None,
+                &MethylationPatternOptions {
+                    report_missing,
+                    ..Default::default()
+                },
+                None,
+            )
+        }
+
+        let (pattern, stats) = run(false)?;
+        assert_eq!(stats.n_contigs_empty, 1);
+        let MethylationPatternVariant::Median(degrees) = pattern else {
+            panic!("Expected a median pattern");
+        };
+        assert_eq!(degrees.len(), 1);
+        assert_eq!(degrees[0].contig, "contig_1");
+
+        let (pattern, stats) = run(true)?;
+        assert_eq!(stats.n_contigs_empty, 1);
+        let MethylationPatternVariant::Median(mut degrees) = pattern else {
+            panic!("Expected a median pattern");
+        };
+        degrees.sort_by(|a, b| a.contig.cmp(&b.contig));
+        assert_eq!(degrees.len(), 2);
+        assert_eq!(degrees[1].contig, "contig_2");
+        assert_eq!(degrees[1].n_motif_obs, 0);
+        assert_eq!(degrees[1].motif_occurences_total, 2);
+        assert!(degrees[1].median.is_nan());
+
+        Ok(())
+    }
+
+    /// Reader of a pileup whose index only lists the contig named by the file stem.
+    #[derive(Clone)]
+    struct SingleContigReader {
+        contig: String,
+    }
+
+    impl PileupReader for SingleContigReader {
+        fn from_path(path: &Path) -> Result<Self> {
+            let contig = path.file_stem().unwrap().to_string_lossy().to_string();
+            Ok(Self { contig })
+        }
+
+        fn query_contig(&mut self, contig: &str) -> Result<Vec<PileupRecordString>> {
+            if contig != self.contig {
+                bail!("missing reference sequence name: {}", contig);
+            }
+            Ok(vec![PileupRecordString::new(format!(
+                "{contig}\t2\t3\ta\t20\t+\t2\t3\t255,0,0\t20\t100.00\t20\t0\t0\t0\t0\t0\t0"
+            ))])
+        }
+
+        fn available_contigs(&self) -> Vec<String> {
+            vec![self.contig.clone()]
+        }
+    }
+
+    #[test]
+    fn test_gz_contig_is_only_queried_in_pileups_listing_it() -> Result<()> {
+        let mut contigs = AHashMap::new();
+        for contig_id in ["contig_1", "contig_2"] {
+            contigs.insert(
+                contig_id.to_string(),
+                Contig::new(contig_id.to_string(), Sequence::from_str("TGATCTGATC")?),
+            );
+        }
+        let (pattern, stats) = extract_methylation_patten_from_gz::<SingleContigReader>(
+            contigs,
+            &[PathBuf::from("contig_1.bed.gz"), PathBuf::from("contig_2.bed.gz")],
+            vec![Motif::new("GATC", "a", 1)?],
+            None,
+            &MethylationPatternOptions::default(),
+            None,
+        )?;
+
+        assert_eq!(stats.n_contigs_processed, 2);
+        assert_eq!(stats.n_contigs_empty, 0);
+        let MethylationPatternVariant::Median(degrees) = pattern else {
+            panic!("Expected a median pattern");
+        };
+        assert_eq!(degrees.len(), 2);
+        assert!(degrees.iter().all(|d| d.n_motif_obs == 1));
+
+        Ok(())
+    }
+
+    /// Reader of a pileup with a record on each of 100 contigs.
+    #[derive(Clone)]
+    struct ManyContigReader;
+
+    impl PileupReader for ManyContigReader {
+        fn from_path(_path: &Path) -> Result<Self> {
+            Ok(Self)
+        }
+
+        fn query_contig(&mut self, contig: &str) -> Result<Vec<PileupRecordString>> {
+            Ok(vec![PileupRecordString::new(format!(
+                "{contig}\t2\t3\ta\t20\t+\t2\t3\t255,0,0\t20\t100.00\t20\t0\t0\t0\t0\t0\t0"
+            ))])
+        }
+
+        fn available_contigs(&self) -> Vec<String> {
+            (0..100).map(|i| format!("contig_{:03}", i)).collect()
+        }
+    }
+
+    #[test]
+    fn test_gz_results_are_streamed_in_contig_order() -> Result<()> {
+        let contigs: AHashMap<String, Contig> = ManyContigReader
+            .available_contigs()
+            .into_iter()
+            .map(|contig_id| -> Result<(String, Contig)> {
+                let contig = Contig::new(contig_id.clone(), Sequence::from_str("TGATCTGATC")?);
+                Ok((contig_id, contig))
+            })
+            .collect::<Result<_>>()?;
+
+        let (sender, receiver) = mpsc::channel();
+        let (pattern, _) = extract_methylation_patten_from_gz::<ManyContigReader>(
+            contigs,
+            &[PathBuf::from("indexed.bed.gz")],
+            vec![Motif::new("GATC", "a", 1)?],
+            None,
+            &MethylationPatternOptions {
+                threads: 2,
+                ..Default::default()
+            },
+            Some(&sender),
+        )?;
+        drop(sender);
+        assert_eq!(pattern.n_rows(), 0);
+
+        // 100 contigs are more than one chunk on 2 threads.
+        let streamed: Vec<String> = receiver
+            .into_iter()
+            .flat_map(|pattern| match pattern {
+                MethylationPatternVariant::Median(degrees) => {
+                    degrees.into_iter().map(|degree| degree.contig).collect::<Vec<_>>()
+                }
+                _ => panic!("Expected a median pattern"),
+            })
+            .collect();
+        assert_eq!(streamed, ManyContigReader.available_contigs());
+
+        Ok(())
+    }
+
+    /// Reader that fails unless it is queried on a rayon pool of `N` threads.
+    #[derive(Clone)]
+    struct PoolSizeReader<const N: usize>;
+
+    impl<const N: usize> PileupReader for PoolSizeReader<N> {
+        fn from_path(_path: &Path) -> Result<Self> {
+            Ok(Self)
+        }
+
+        fn query_contig(&mut self, _contig: &str) -> Result<Vec<PileupRecordString>> {
+            let n_threads = rayon::current_num_threads();
+            if n_threads != N {
+                bail!("Queried on {} threads instead of {}", n_threads, N);
+            }
+            Ok(Vec::new())
+        }
+
+        fn available_contigs(&self) -> Vec<String> {
+            vec!["contig_1".to_string(), "contig_2".to_string()]
+        }
+    }
+
+    #[test]
+    fn test_gz_contigs_are_processed_on_pool_of_threads() -> Result<()> {
+        fn run<const N: usize>(threads: usize) -> Result<MethylationRunStats> {
+            let mut contigs = AHashMap::new();
+            for contig_id in ["contig_1", "contig_2"] {
+                contigs.insert(
+                    contig_id.to_string(),
+                    Contig::new(contig_id.to_string(), Sequence::from_str("TGATCTGATC")?),
+                );
+            }
+            let (_, stats) = extract_methylation_patten_from_gz::<PoolSizeReader<N>>(
+                contigs,
+                &[PathBuf::from("indexed.bed.gz")],
+                vec![Motif::new("GATC", "a", 1)?],
+                None,
+                &MethylationPatternOptions {
+                    threads,
+                    ..Default::default()
+                },
+                None,
+            )?;
+            Ok(stats)
+        }
+
+        // At most one of the two sizes can match the global pool, so a run on the
+        // global pool fails one of them.
+        assert_eq!(run::<2>(2)?.n_contigs_empty, 2);
+        assert_eq!(run::<5>(5)?.n_contigs_empty, 2);
+        assert!(run::<5>(2).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_pileup_schema() {
+        let valid = DataFrame::new(
+            PILEUP_COLUMNS
+                .iter()
+                .map(|name| Column::new((*name).into(), [0u32]))
+                .collect(),
+        )
+        .unwrap();
+        assert!(validate_pileup_schema(&valid).is_ok());
+
+        let missing = valid.drop("n_no_call").unwrap();
+        assert!(validate_pileup_schema(&missing).is_err());
+
+        let mut renamed = valid.clone();
+        renamed.rename("strand", "orientation".into()).unwrap();
+        assert!(validate_pileup_schema(&renamed).is_err());
+    }
 }
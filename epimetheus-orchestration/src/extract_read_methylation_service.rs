@@ -2,18 +2,27 @@ use ahash::{AHashMap, HashSet};
 use anyhow::{Context, Result};
 use epimetheus_core::models::contig::Contig;
 use epimetheus_io::io::{
-    readers::{bam::BamReaderIndexed, fastq},
+    readers::{alignment::AlignmentReader, fastq},
     traits::FastqReader,
 };
 use epimetheus_methylome::{
-    Motif, Strand, find_motif_indices_in_sequence,
-    read::{Alignment, MethBase},
+    Motif, Strand, StrandFormat, find_motif_indices_in_sequence,
+    read::Alignment,
+    sequence::Sequence,
 };
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::info;
 use polars::{df, frame::DataFrame};
 use rayon::prelude::*;
 use serde::Serialize;
-use std::{path::Path, sync::mpsc, thread};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
 
 #[derive(Serialize)]
 struct MappingRecord {
@@ -32,19 +41,37 @@ struct MappingRecord {
     pub mapping_status: String,
 }
 
+/// Builds the rayon pool processing the contigs of a BAM. One of `threads` is reserved
+/// for the writer thread, so the pool has `threads - 1` threads, but at least one.
+fn build_contig_pool(threads: usize) -> Result<rayon::ThreadPool> {
+    Ok(rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.saturating_sub(1).max(1))
+        .build()?)
+}
+
+/// Reads of each contig are processed on a pool of `threads - 1` threads (at least
+/// one), while the remaining thread writes the output.
+///
+/// `input_file` is read as CRAM when it ends in `.cram`, which requires `reference`,
+/// and as BAM otherwise.
+///
+/// Reads with a mapping quality below `min_mapping_quality` are skipped while the reads
+/// of a contig are collected and their number is logged at the end. They do not count
+/// towards `max_reads_per_contig`, so a capped contig is filled with retained reads.
 pub fn extract_read_methylation_pattern(
     input_file: &Path,
+    reference: Option<&Path>,
     assembly: AHashMap<String, Contig>,
     motifs: Vec<Motif>,
     output: &Path,
     threads: usize,
+    max_reads_per_contig: Option<usize>,
+    min_mapping_quality: u8,
+    strand_format: StrandFormat,
 ) -> Result<()> {
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(threads)
-        .build()
-        .expect("Could not initialize threadpool");
+    let pool = build_contig_pool(threads)?;
 
-    let mut reader = BamReaderIndexed::new(input_file)?;
+    let mut reader = AlignmentReader::from_path(input_file, reference)?;
 
     let contigs_in_bam: Vec<String> = reader
         .query_contigs()?
@@ -95,154 +122,214 @@ pub fn extract_read_methylation_pattern(
         Ok(())
     });
 
-    contigs_in_bam
-        .par_iter()
-        .try_for_each(|contig_id| -> Result<()> {
-            main_pb.inc(1);
-            let mut local_reader = BamReaderIndexed::new(input_file)?;
-            let reads = local_reader
-                .query_contig_reads(contig_id)
-                .with_context(|| format!("Reading contig: {}", contig_id))?;
-
-            if reads.is_empty() {
-                return Ok(());
-            }
+    let n_low_mapping_quality = AtomicUsize::new(0);
+
+    pool.install(|| {
+        contigs_in_bam
+            .par_iter()
+            .try_for_each(|contig_id| -> Result<()> {
+                main_pb.inc(1);
+                let mut local_reader = AlignmentReader::from_path(input_file, reference)?;
+                let contig_reads = local_reader
+                    .query_contig_reads_capped(
+                        contig_id,
+                        max_reads_per_contig,
+                        min_mapping_quality,
+                    )
+                    .with_context(|| format!("Reading contig: {}", contig_id))?;
+                n_low_mapping_quality
+                    .fetch_add(contig_reads.n_low_mapping_quality, Ordering::Relaxed);
+                let reads = contig_reads.reads;
+                if contig_reads.capped {
+                    info!(
+                        "Contig {} has more reads than the cap. Processing the first {}.",
+                        contig_id,
+                        reads.len()
+                    );
+                }
 
-            // Unwrap because we already filtered for contigs.
-            let contig = assembly.get(contig_id).unwrap();
-            let mut motif_indices_in_contig = AHashMap::new();
-            for motif in &motifs {
-                let fwd = find_motif_indices_in_sequence(&contig.sequence, &motif)
-                    .into_iter()
-                    .collect::<HashSet<usize>>();
-                let rev =
-                    find_motif_indices_in_sequence(&contig.sequence, &motif.reverse_complement())
+                if reads.is_empty() {
+                    return Ok(());
+                }
+
+                // Unwrap because we already filtered for contigs.
+                let contig = assembly.get(contig_id).unwrap();
+                let mut motif_indices_in_contig = AHashMap::new();
+                for motif in &motifs {
+                    let fwd = find_motif_indices_in_sequence(&contig.sequence, &motif)
                         .into_iter()
                         .collect::<HashSet<usize>>();
+                    let rev = find_motif_indices_in_sequence(
+                        &contig.sequence,
+                        &motif.reverse_complement(),
+                    )
+                    .into_iter()
+                    .collect::<HashSet<usize>>();
 
-                motif_indices_in_contig.insert((motif, Strand::Positive), fwd);
-                motif_indices_in_contig.insert((motif, Strand::Negative), rev);
-            }
-
-            for read in reads {
-                let read_sequence = read.get_sequence();
-                let read_length = read_sequence.len();
-                let read_modifications = read.get_modifications();
-                let read_mapping = read.get_mapping().unwrap();
-
-                let map_qual = read_mapping.get_mapping_quality();
-                let strand = read_mapping.get_strand();
+                    motif_indices_in_contig.insert((motif, Strand::Positive), fwd);
+                    motif_indices_in_contig.insert((motif, Strand::Negative), rev);
+                }
 
-                // compute the read mapping from cigar string once.
-                let read_mapping: Vec<Option<Alignment>> =
-                    read_mapping.build_full_position_map(read_length);
-                for motif in &motifs {
-                    let motif_length = motif.sequence.len();
-                    let indices = find_motif_indices_in_sequence(read_sequence, &motif);
-                    for &read_motif_pos in &indices {
-                        let quality =
-                            if let Some(meth_base) = read_modifications.0.get(&read_motif_pos) {
+                for read in reads {
+                    let read_sequence = read.get_sequence();
+                    let read_length = read_sequence.len();
+                    let read_modifications = read.get_modifications();
+                    let read_mapping = read.get_mapping().unwrap();
+
+                    let map_qual = read_mapping.get_mapping_quality();
+                    let strand = read_mapping.get_strand();
+
+                    // compute the read mapping from cigar string once.
+                    let read_mapping: Vec<Option<Alignment>> =
+                        read_mapping.build_full_position_map(read_length);
+                    for motif in &motifs {
+                        let motif_length = motif.sequence.len();
+                        let indices = find_motif_indices_in_sequence(read_sequence, &motif);
+                        for &read_motif_pos in &indices {
+                            let quality = if let Some(meth_base) =
+                                read_modifications.0.get(&read_motif_pos)
+                            {
                                 meth_base.quality.0
                             } else {
                                 0
                             };
 
-                        let original_pos = match strand {
-                            epimetheus_methylome::Strand::Positive => read_motif_pos,
-                            epimetheus_methylome::Strand::Negative => {
-                                read_length - read_motif_pos - 1
-                            }
-                        };
-
-                        let genome_pos = match read_mapping.get(original_pos) {
-                            Some(Some(Alignment::SequenceMatch(pos))) => *pos as i32,
-                            Some(Some(Alignment::SequenceMismatch(pos))) => *pos as i32,
-                            Some(Some(Alignment::AmbiguousMatch(pos))) => *pos as i32,
-                            _ => -1,
-                        };
-
-                        let reference_has_motif = motif_indices_in_contig
-                            .get(&(motif, strand))
-                            .is_some_and(|set| set.contains(&(genome_pos as usize)));
-
-                        let motif_start_in_bam_coords = match strand {
-                            epimetheus_methylome::Strand::Positive => {
-                                read_motif_pos - motif.mod_position as usize
-                            }
-                            epimetheus_methylome::Strand::Negative => {
-                                original_pos - motif.mod_position as usize
-                            }
-                        };
-
-                        let alignments: Vec<Option<&Alignment>> = (0..motif_length)
-                            .map(|offset| {
-                                read_mapping
-                                    .get(motif_start_in_bam_coords + offset)
-                                    .and_then(|opt| opt.as_ref())
-                            })
-                            .collect();
-
-                        let mapping_status = if genome_pos == -1 {
-                            "unmapped"
-                        } else if alignments
-                            .iter()
-                            .any(|a| a.is_none() || matches!(a, Some(Alignment::SoftClipped)))
-                        {
-                            "partial"
-                        } else {
-                            let positions: Vec<usize> = alignments
-                                .iter()
-                                .filter_map(|a| match a {
-                                    Some(Alignment::SequenceMatch(pos))
-                                    | Some(Alignment::SequenceMismatch(pos))
-                                    | Some(Alignment::AmbiguousMatch(pos)) => Some(*pos),
-                                    _ => None,
+                            let original_pos = match strand {
+                                epimetheus_methylome::Strand::Positive => read_motif_pos,
+                                epimetheus_methylome::Strand::Negative => {
+                                    read_length - read_motif_pos - 1
+                                }
+                            };
+
+                            let genome_pos = match read_mapping.get(original_pos) {
+                                Some(Some(Alignment::SequenceMatch(pos))) => *pos as i32,
+                                Some(Some(Alignment::SequenceMismatch(pos))) => *pos as i32,
+                                Some(Some(Alignment::AmbiguousMatch(pos))) => *pos as i32,
+                                _ => -1,
+                            };
+
+                            let reference_has_motif = motif_indices_in_contig
+                                .get(&(motif, strand))
+                                .is_some_and(|set| set.contains(&(genome_pos as usize)));
+
+                            let motif_start_in_bam_coords = match strand {
+                                epimetheus_methylome::Strand::Positive => {
+                                    read_motif_pos - motif.mod_position as usize
+                                }
+                                epimetheus_methylome::Strand::Negative => {
+                                    original_pos - motif.mod_position as usize
+                                }
+                            };
+
+                            let alignments: Vec<Option<&Alignment>> = (0..motif_length)
+                                .map(|offset| {
+                                    read_mapping
+                                        .get(motif_start_in_bam_coords + offset)
+                                        .and_then(|opt| opt.as_ref())
                                 })
                                 .collect();
 
-                            if positions.len() != motif_length {
+                            let mapping_status = if genome_pos == -1 {
+                                "unmapped"
+                            } else if alignments
+                                .iter()
+                                .any(|a| a.is_none() || matches!(a, Some(Alignment::SoftClipped)))
+                            {
                                 "partial"
-                            } else if positions.windows(2).all(|w| w[1] == w[0] + 1) {
-                                "complete"
                             } else {
-                                "gapped"
-                            }
-                        };
-
-                        let rec = MappingRecord {
-                            contig_id: contig_id.clone(),
-                            start_contig: genome_pos,
-                            reference_has_motif,
-                            strand: strand.to_string(),
-                            read_id: read.get_name().to_string(),
-                            read_length,
-                            mapping_quality: map_qual,
-                            start_read: read_motif_pos,
-                            motif: motif.sequence.to_string(),
-                            mod_type: motif.mod_type.to_pileup_code().to_string(),
-                            mod_position: motif.mod_position.to_string(),
-                            basecall_quality: quality,
-                            mapping_status: mapping_status.to_string(),
-                        };
-
-                        sender
-                            .send(rec)
-                            .expect("Unable to send mapping record to writer thread");
+                                let positions: Vec<usize> = alignments
+                                    .iter()
+                                    .filter_map(|a| match a {
+                                        Some(Alignment::SequenceMatch(pos))
+                                        | Some(Alignment::SequenceMismatch(pos))
+                                        | Some(Alignment::AmbiguousMatch(pos)) => Some(*pos),
+                                        _ => None,
+                                    })
+                                    .collect();
+
+                                if positions.len() != motif_length {
+                                    "partial"
+                                } else if positions.windows(2).all(|w| w[1] == w[0] + 1) {
+                                    "complete"
+                                } else {
+                                    "gapped"
+                                }
+                            };
+
+                            let rec = MappingRecord {
+                                contig_id: contig_id.clone(),
+                                start_contig: genome_pos,
+                                reference_has_motif,
+                                strand: strand.format(strand_format).to_string(),
+                                read_id: read.get_name().to_string(),
+                                read_length,
+                                mapping_quality: map_qual,
+                                start_read: read_motif_pos,
+                                motif: motif.sequence.to_string(),
+                                mod_type: motif.mod_type.to_pileup_code().to_string(),
+                                mod_position: motif.mod_position.to_string(),
+                                basecall_quality: quality,
+                                mapping_status: mapping_status.to_string(),
+                            };
+
+                            sender
+                                .send(rec)
+                                .expect("Unable to send mapping record to writer thread");
+                        }
                     }
                 }
-            }
-            Ok(())
-        })?;
+                Ok(())
+            })
+    })?;
     drop(sender);
     let _ = writer_handle.join().unwrap();
+
+    if min_mapping_quality > 0 {
+        info!(
+            "Skipped {} reads with a mapping quality below {}.",
+            n_low_mapping_quality.into_inner(),
+            min_mapping_quality
+        );
+    }
     Ok(())
 }
 
+/// Motif hits in a read as (position, strand, motif), sorted by position, strand and
+/// motif.
+///
+/// Both the motif and its reverse complement are searched, so a motif on the opposite
+/// strand of a single-stranded read is found too. Hits of the reverse complement are on
+/// the minus strand and their position is that of the modified base of the reverse
+/// complement, i.e. the complement of the modified base.
+fn find_read_motif_hits<'a>(
+    sequence: &Sequence,
+    motifs: &'a [Motif],
+) -> Vec<(usize, Strand, &'a Motif)> {
+    let mut hits = Vec::new();
+    for motif in motifs {
+        for pos in find_motif_indices_in_sequence(sequence, motif) {
+            hits.push((pos, Strand::Positive, motif));
+        }
+        for pos in find_motif_indices_in_sequence(sequence, &motif.reverse_complement()) {
+            hits.push((pos, Strand::Negative, motif));
+        }
+    }
+
+    hits.sort_unstable();
+    hits
+}
+
+/// Motif hits of the reads with the basecall quality of the motif's mod type at the hit.
+///
+/// The quality is null when the read has no call of the motif's mod type at the
+/// position. This is always the case for minus strand hits of single-stranded reads,
+/// since the modified base of those is on the strand that was not sequenced.
 pub fn extract_read_methylation_pattern_fastq(
     input_file: &Path,
     read_ids_filter: Option<Vec<String>>,
     motifs: Vec<Motif>,
     threads: usize,
+    strand_format: StrandFormat,
 ) -> Result<DataFrame> {
     rayon::ThreadPoolBuilder::new()
         .num_threads(threads)
@@ -254,7 +341,7 @@ pub fn extract_read_methylation_pattern_fastq(
     let batches: Vec<_> = reads.chunks(BATCH_SIZE).collect();
 
     // Process batches in parallel
-    let results: Vec<(String, u32, u32, String, String, u32, u32)> = batches
+    let results: Vec<(String, u32, &'static str, u32, String, String, u32, Option<u32>)> = batches
         .into_par_iter()
         .map(|batch| {
             let mut batch_data = Vec::new();
@@ -264,39 +351,30 @@ pub fn extract_read_methylation_pattern_fastq(
                 let modifications = read.get_modifications();
                 let read_length = read.get_sequence().len();
 
-                for motif in &motifs {
-                    // Find all motif positions in this read
-                    let indices = find_motif_indices_in_sequence(sequence, motif);
-
-                    if !indices.is_empty() {
-                        let motif_sequence = motif
-                            .sequence
-                            .iter()
-                            .map(|b| b.to_string())
-                            .collect::<String>();
-
-                        for pos in indices {
-                            let quality = modifications
-                                .0
-                                .get(&pos)
-                                .unwrap_or(&MethBase::new(
-                                    motif.mod_type.clone(),
-                                    epimetheus_methylome::read::MethQual(0),
-                                ))
-                                .clone();
-                            let d = (
-                                read.get_name().clone(),
-                                pos as u32,
-                                read_length as u32,
-                                motif_sequence.clone(),
-                                motif.mod_type.to_pileup_code().to_string(),
-                                motif.mod_position as u32,
-                                quality.quality.0 as u32,
-                            );
-
-                            batch_data.push(d);
-                        }
-                    }
+                for (pos, strand, motif) in find_read_motif_hits(sequence, &motifs) {
+                    let motif_sequence = motif
+                        .sequence
+                        .iter()
+                        .map(|b| b.to_string())
+                        .collect::<String>();
+
+                    let quality = modifications
+                        .0
+                        .get(&pos)
+                        .filter(|meth_base| meth_base.base == motif.mod_type)
+                        .map(|meth_base| meth_base.quality.0 as u32);
+                    let d = (
+                        read.get_name().clone(),
+                        pos as u32,
+                        strand.format(strand_format),
+                        read_length as u32,
+                        motif_sequence,
+                        motif.mod_type.to_pileup_code().to_string(),
+                        motif.mod_position as u32,
+                        quality,
+                    );
+
+                    batch_data.push(d);
                 }
             }
             batch_data
@@ -308,15 +386,17 @@ pub fn extract_read_methylation_pattern_fastq(
     // Convert results data to vectors for DataFrame
     let mut read_ids = Vec::with_capacity(results.len());
     let mut starts = Vec::with_capacity(results.len());
+    let mut strands = Vec::with_capacity(results.len());
     let mut read_lengths = Vec::with_capacity(results.len());
     let mut motif_sequences = Vec::with_capacity(results.len());
     let mut mod_types = Vec::with_capacity(results.len());
     let mut mod_positions = Vec::with_capacity(results.len());
     let mut qualities = Vec::with_capacity(results.len());
 
-    for (read_id, start, read_length, motif_seq, mod_type, mod_pos, quality) in results {
+    for (read_id, start, strand, read_length, motif_seq, mod_type, mod_pos, quality) in results {
         read_ids.push(read_id);
         starts.push(start);
+        strands.push(strand);
         read_lengths.push(read_length);
         motif_sequences.push(motif_seq);
         mod_types.push(mod_type);
@@ -328,6 +408,7 @@ pub fn extract_read_methylation_pattern_fastq(
     let df = df! [
         "read_id" => read_ids,
         "start" => starts,
+        "strand" => strands,
         "read_length" => read_lengths,
         "motif_seq" => motif_sequences,
         "mod_type" => mod_types,
@@ -337,3 +418,68 @@ pub fn extract_read_methylation_pattern_fastq(
 
     Ok(df)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contig_pool_reserves_writer_thread() -> Result<()> {
+        assert_eq!(build_contig_pool(4)?.current_num_threads(), 3);
+        assert_eq!(build_contig_pool(2)?.current_num_threads(), 1);
+        assert_eq!(build_contig_pool(1)?.current_num_threads(), 1);
+        assert_eq!(build_contig_pool(0)?.current_num_threads(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_read_motif_hits_reverse_complement_only() -> Result<()> {
+        // The read only has CGGGA, the reverse complement of TCCCG.
+        let read = Sequence::from_str("AACGGGAAA")?;
+        let motifs = vec![Motif::new("TCCCG", "m", 1)?];
+
+        let hits: Vec<(usize, Strand)> = find_read_motif_hits(&read, &motifs)
+            .into_iter()
+            .map(|(pos, strand, _)| (pos, strand))
+            .collect();
+        assert_eq!(hits, vec![(5, Strand::Negative)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fastq_quality_is_only_read_from_calls_of_the_motif_mod_type() -> Result<()> {
+        use std::io::Write;
+
+        // TCCCG on the plus strand with its modified C at 1, and CGGGA (the reverse
+        // complement) on the minus strand with the complement of its modified C at 10.
+        // read_1 calls 5mC at the first C and read_2 calls 5hmC there.
+        let mut fastq = tempfile::Builder::new().suffix(".fastq").tempfile()?;
+        for (read_id, mod_code) in [("read_1", "m"), ("read_2", "h")] {
+            writeln!(
+                fastq,
+                "@{read_id} MM:Z:C+{mod_code}.,0; ML:B:C,200\nTCCCGAACGGGA\n+\nIIIIIIIIIIII"
+            )?;
+        }
+        fastq.flush()?;
+
+        let df = extract_read_methylation_pattern_fastq(
+            fastq.path(),
+            None,
+            vec![Motif::new("TCCCG", "m", 1)?],
+            1,
+            StrandFormat::Symbol,
+        )?;
+
+        let starts: Vec<Option<u32>> = df.column("start")?.u32()?.into_iter().collect();
+        let strands: Vec<Option<&str>> = df.column("strand")?.str()?.into_iter().collect();
+        let qualities: Vec<Option<u32>> = df.column("quality")?.u32()?.into_iter().collect();
+        assert_eq!(starts, vec![Some(1), Some(10), Some(1), Some(10)]);
+        assert_eq!(strands, vec![Some("+"), Some("-"), Some("+"), Some("-")]);
+        // The minus strand hit of a single-stranded read has no call, and the 5hmC call
+        // of read_2 is not a call of the 5mC motif.
+        assert_eq!(qualities, vec![Some(200), None, None, None]);
+
+        Ok(())
+    }
+}
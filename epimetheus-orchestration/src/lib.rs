@@ -1,3 +1,4 @@
 pub mod bam_tag_merge_service;
+pub mod contig_summary_service;
 pub mod extract_methylation_pattern_service;
 pub mod extract_read_methylation_service;
@@ -5,7 +5,10 @@
 //!
 //! The main functions include:
 //! - `methylation_pattern`: Extract methylation patterns for DNA motifs
+//! - `methylation_pattern_from_dataframes`: Extract methylation patterns from in-memory
+//!   pileup and contig DataFrames
 //! - `remove_child_motifs`: Remove redundant child motifs through clustering
+//! - `find_motif_positions`: Find motif positions on both strands of a sequence
 //! - `query_pileup_records`: Query specific contigs from pileup files
 //! - `bgzf_pileup`: Compress pileup files using BGZF format
 
@@ -13,19 +16,27 @@ use ahash::AHashMap;
 use epimetheus_core::models::contig::Contig;
 use epimetheus_core::models::methylation::MethylationOutput;
 use epimetheus_core::models::methylation::MethylationPatternVariant;
+use epimetheus_core::models::methylation::StrandFormat;
+use epimetheus_core::models::methylation::ValueScale;
 use epimetheus_core::models::pileup::PileupColumn;
+use epimetheus_core::models::pileup::PileupRecord;
 use epimetheus_core::services::domain::motif_processor::create_motifs;
 use epimetheus_io::io::traits::FastaReader;
+use epimetheus_methylome::find_motif_indices_in_sequence;
+use epimetheus_methylome::sequence::Sequence;
 use epimetheus_io::io::traits::PileupReader;
+use epimetheus_io::io::writers::bgzip::DEFAULT_COMPRESSION_LEVEL;
 use epimetheus_io::io::writers::bgzip::Writer;
 use epimetheus_io::io::writers::bgzip::WriterType;
 use epimetheus_io::services::compression_service::CompressorService;
 use epimetheus_io::services::file_processing_service::query_pileup;
 use epimetheus_orchestration::extract_methylation_pattern_service::MethylationInput;
+use epimetheus_orchestration::extract_methylation_pattern_service::MethylationPatternOptions;
 use epimetheus_orchestration::extract_methylation_pattern_service::extract_methylation_pattern;
 use polars::prelude::*;
 use pyo3::prelude::*;
 use pyo3_polars::PyDataFrame;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
@@ -38,7 +49,11 @@ use epimetheus_io::io::readers::bed;
 
 fn create_methylation_pattern_df(
     meth_pattern: MethylationPatternVariant,
+    as_percentage: bool,
 ) -> anyhow::Result<DataFrame> {
+    let value_scale = if as_percentage { ValueScale::Percent } else { ValueScale::Fraction };
+    let value_column = value_scale.value_column();
+    let factor = if as_percentage { 100.0 } else { 1.0 };
     let df = match meth_pattern {
         epimetheus_core::models::methylation::MethylationPatternVariant::Median(degrees) => {
             let contig_vec: Vec<String> = degrees.iter().map(|d| d.contig.clone()).collect();
@@ -54,20 +69,24 @@ fn create_methylation_pattern_df(
                 .iter()
                 .map(|d| d.motif.mod_position as u64)
                 .collect();
-            let methylation_value_vec: Vec<f64> = degrees.iter().map(|d| d.median).collect();
+            let methylation_value_vec: Vec<f64> = degrees.iter().map(|d| d.median * factor).collect();
             let mean_read_cov_vec: Vec<f64> = degrees.iter().map(|d| d.mean_read_cov).collect();
             let n_motif_obs_vec: Vec<u32> = degrees.iter().map(|d| d.n_motif_obs).collect();
             let n_motif_occurences_total: Vec<u32> = degrees.iter().map(|d| d.motif_occurences_total).collect();
+            let n_filtered_low_cov_vec: Vec<u32> = degrees.iter().map(|d| d.n_filtered_low_cov).collect();
+            let n_filtered_diff_vec: Vec<u32> = degrees.iter().map(|d| d.n_filtered_diff).collect();
 
             df![
                 "contig" => contig_vec,
                 "motif" => motif_vec,
                 "mod_type" => mod_type_vec,
                 "mod_position" => mod_position_vec,
-                "methylation_value" => methylation_value_vec,
+                value_column => methylation_value_vec,
                 "mean_read_cov" => mean_read_cov_vec,
                 "n_motif_obs" => n_motif_obs_vec,
                 "motif_occurences_total" => n_motif_occurences_total,
+                "n_filtered_low_cov" => n_filtered_low_cov_vec,
+                "n_filtered_diff" => n_filtered_diff_vec,
             ]?
         }
         epimetheus_core::models::methylation::MethylationPatternVariant::WeightedMean(degrees) => {
@@ -84,20 +103,130 @@ fn create_methylation_pattern_df(
                 .iter()
                 .map(|d| d.motif.mod_position as u64)
                 .collect();
-            let methylation_value_vec: Vec<f64> = degrees.iter().map(|d| d.w_mean).collect();
+            let methylation_value_vec: Vec<f64> = degrees.iter().map(|d| d.w_mean * factor).collect();
             let mean_read_cov_vec: Vec<f64> = degrees.iter().map(|d| d.mean_read_cov).collect();
             let n_motif_obs_vec: Vec<u32> = degrees.iter().map(|d| d.n_motif_obs).collect();
             let n_motif_occurences_total: Vec<u32> = degrees.iter().map(|d| d.motif_occurences_total).collect();
+            let n_filtered_low_cov_vec: Vec<u32> = degrees.iter().map(|d| d.n_filtered_low_cov).collect();
+            let n_filtered_diff_vec: Vec<u32> = degrees.iter().map(|d| d.n_filtered_diff).collect();
 
             df![
                 "contig" => contig_vec,
                 "motif" => motif_vec,
                 "mod_type" => mod_type_vec,
                 "mod_position" => mod_position_vec,
-                "methylation_value" => methylation_value_vec,
+                value_column => methylation_value_vec,
                 "mean_read_cov" => mean_read_cov_vec,
                 "n_motif_obs" => n_motif_obs_vec,
                 "motif_occurences_total" => n_motif_occurences_total,
+                "n_filtered_low_cov" => n_filtered_low_cov_vec,
+                "n_filtered_diff" => n_filtered_diff_vec,
+            ]?
+        }
+        epimetheus_core::models::methylation::MethylationPatternVariant::WeightedMedian(degrees) => {
+            let contig_vec: Vec<String> = degrees.iter().map(|d| d.contig.clone()).collect();
+            let motif_vec: Vec<String> = degrees
+                .iter()
+                .map(|d| d.motif.sequence_to_string())
+                .collect();
+            let mod_type_vec: Vec<String> = degrees
+                .iter()
+                .map(|d| d.motif.mod_type.to_pileup_code().to_string())
+                .collect();
+            let mod_position_vec: Vec<u64> = degrees
+                .iter()
+                .map(|d| d.motif.mod_position as u64)
+                .collect();
+            let methylation_value_vec: Vec<f64> = degrees.iter().map(|d| d.w_median * factor).collect();
+            let mean_read_cov_vec: Vec<f64> = degrees.iter().map(|d| d.mean_read_cov).collect();
+            let n_motif_obs_vec: Vec<u32> = degrees.iter().map(|d| d.n_motif_obs).collect();
+            let n_motif_occurences_total: Vec<u32> = degrees.iter().map(|d| d.motif_occurences_total).collect();
+            let n_filtered_low_cov_vec: Vec<u32> = degrees.iter().map(|d| d.n_filtered_low_cov).collect();
+            let n_filtered_diff_vec: Vec<u32> = degrees.iter().map(|d| d.n_filtered_diff).collect();
+
+            df![
+                "contig" => contig_vec,
+                "motif" => motif_vec,
+                "mod_type" => mod_type_vec,
+                "mod_position" => mod_position_vec,
+                value_column => methylation_value_vec,
+                "mean_read_cov" => mean_read_cov_vec,
+                "n_motif_obs" => n_motif_obs_vec,
+                "motif_occurences_total" => n_motif_occurences_total,
+                "n_filtered_low_cov" => n_filtered_low_cov_vec,
+                "n_filtered_diff" => n_filtered_diff_vec,
+            ]?
+        }
+        epimetheus_core::models::methylation::MethylationPatternVariant::TrimmedMean(degrees) => {
+            let contig_vec: Vec<String> = degrees.iter().map(|d| d.contig.clone()).collect();
+            let motif_vec: Vec<String> = degrees
+                .iter()
+                .map(|d| d.motif.sequence_to_string())
+                .collect();
+            let mod_type_vec: Vec<String> = degrees
+                .iter()
+                .map(|d| d.motif.mod_type.to_pileup_code().to_string())
+                .collect();
+            let mod_position_vec: Vec<u64> = degrees
+                .iter()
+                .map(|d| d.motif.mod_position as u64)
+                .collect();
+            let methylation_value_vec: Vec<f64> = degrees.iter().map(|d| d.trimmed_mean * factor).collect();
+            let mean_read_cov_vec: Vec<f64> = degrees.iter().map(|d| d.mean_read_cov).collect();
+            let n_motif_obs_vec: Vec<u32> = degrees.iter().map(|d| d.n_motif_obs).collect();
+            let n_motif_occurences_total: Vec<u32> = degrees.iter().map(|d| d.motif_occurences_total).collect();
+            let n_filtered_low_cov_vec: Vec<u32> = degrees.iter().map(|d| d.n_filtered_low_cov).collect();
+            let n_filtered_diff_vec: Vec<u32> = degrees.iter().map(|d| d.n_filtered_diff).collect();
+            let trim_applied_vec: Vec<bool> = degrees.iter().map(|d| d.trim_applied).collect();
+
+            df![
+                "contig" => contig_vec,
+                "motif" => motif_vec,
+                "mod_type" => mod_type_vec,
+                "mod_position" => mod_position_vec,
+                value_column => methylation_value_vec,
+                "mean_read_cov" => mean_read_cov_vec,
+                "n_motif_obs" => n_motif_obs_vec,
+                "motif_occurences_total" => n_motif_occurences_total,
+                "n_filtered_low_cov" => n_filtered_low_cov_vec,
+                "n_filtered_diff" => n_filtered_diff_vec,
+                "trim_applied" => trim_applied_vec,
+            ]?
+        }
+        epimetheus_core::models::methylation::MethylationPatternVariant::CalledFrequency(degrees) => {
+            let contig_vec: Vec<String> = degrees.iter().map(|d| d.contig.clone()).collect();
+            let motif_vec: Vec<String> = degrees
+                .iter()
+                .map(|d| d.motif.sequence_to_string())
+                .collect();
+            let mod_type_vec: Vec<String> = degrees
+                .iter()
+                .map(|d| d.motif.mod_type.to_pileup_code().to_string())
+                .collect();
+            let mod_position_vec: Vec<u64> = degrees
+                .iter()
+                .map(|d| d.motif.mod_position as u64)
+                .collect();
+            let methylation_value_vec: Vec<f64> = degrees.iter().map(|d| d.called_frequency * factor).collect();
+            let mean_read_cov_vec: Vec<f64> = degrees.iter().map(|d| d.mean_read_cov).collect();
+            let n_motif_obs_vec: Vec<u32> = degrees.iter().map(|d| d.n_motif_obs).collect();
+            let n_motif_occurences_total: Vec<u32> = degrees.iter().map(|d| d.motif_occurences_total).collect();
+            let n_filtered_low_cov_vec: Vec<u32> = degrees.iter().map(|d| d.n_filtered_low_cov).collect();
+            let n_filtered_diff_vec: Vec<u32> = degrees.iter().map(|d| d.n_filtered_diff).collect();
+            let n_called_methylated_vec: Vec<u32> = degrees.iter().map(|d| d.n_called_methylated).collect();
+
+            df![
+                "contig" => contig_vec,
+                "motif" => motif_vec,
+                "mod_type" => mod_type_vec,
+                "mod_position" => mod_position_vec,
+                value_column => methylation_value_vec,
+                "mean_read_cov" => mean_read_cov_vec,
+                "n_motif_obs" => n_motif_obs_vec,
+                "motif_occurences_total" => n_motif_occurences_total,
+                "n_filtered_low_cov" => n_filtered_low_cov_vec,
+                "n_filtered_diff" => n_filtered_diff_vec,
+                "n_called_methylated" => n_called_methylated_vec,
             ]?
         }
         epimetheus_core::models::methylation::MethylationPatternVariant::Raw(positions) => {
@@ -132,6 +261,36 @@ fn create_methylation_pattern_df(
                 "n_valid_cov" => n_valid_cov_vec,
             ]?
         }
+        epimetheus_core::models::methylation::MethylationPatternVariant::StrandAsymmetry(degrees) => {
+            let contig_vec: Vec<String> = degrees.iter().map(|d| d.contig.clone()).collect();
+            let motif_vec: Vec<String> = degrees
+                .iter()
+                .map(|d| d.motif.sequence_to_string())
+                .collect();
+            let mod_type_vec: Vec<String> = degrees
+                .iter()
+                .map(|d| d.motif.mod_type.to_pileup_code().to_string())
+                .collect();
+            let mod_position_vec: Vec<u64> = degrees
+                .iter()
+                .map(|d| d.motif.mod_position as u64)
+                .collect();
+            let methylation_plus_vec: Vec<f64> = degrees.iter().map(|d| d.methylation_plus * factor).collect();
+            let methylation_minus_vec: Vec<f64> = degrees.iter().map(|d| d.methylation_minus * factor).collect();
+            let mean_asymmetry_vec: Vec<f64> = degrees.iter().map(|d| d.mean_asymmetry * factor).collect();
+            let n_sites_vec: Vec<u32> = degrees.iter().map(|d| d.n_sites).collect();
+
+            df![
+                "contig" => contig_vec,
+                "motif" => motif_vec,
+                "mod_type" => mod_type_vec,
+                "mod_position" => mod_position_vec,
+                "methylation_plus" => methylation_plus_vec,
+                "methylation_minus" => methylation_minus_vec,
+                "mean_asymmetry" => mean_asymmetry_vec,
+                "n_sites" => n_sites_vec,
+            ]?
+        }
     };
     Ok(df)
 }
@@ -153,6 +312,13 @@ fn create_methylation_pattern_df(
 ///     min_valid_cov_to_diff_fraction (float): Minimum fraction of valid coverage to difference coverage
 ///     allow_assembly_pileup_mismatch (bool): Whether to allow mismatches between assembly and pileup
 ///     output_type (MethylationOutput): Output format type
+///     min_motif_observations (int): Leave out contig-motifs observed at fewer motif positions
+///     call_threshold (float): Fraction of modified reads at or above which a position is
+///         called methylated with `MethylationOutput.CalledFrequency`
+///     as_percentage (bool): Emit the methylation values as percentages (0-100) in a
+///         `methylation_percent` column instead of fractions in `methylation_value`
+///     output_bgzf (bool): Compress the output TSV with BGZF, so `output` is a valid
+///         gzip file. No tabix index is written, as the TSV is not sorted by position
 ///
 /// Returns:
 ///     polars.DataFrame: DataFrame containing methylation pattern results
@@ -172,7 +338,10 @@ fn create_methylation_pattern_df(
     batch_size=100,
     min_valid_cov_to_diff_fraction = 0.8,
     allow_assembly_pileup_mismatch = false,
-    
+    min_motif_observations = 1,
+    call_threshold = 0.5,
+    as_percentage = false,
+    output_bgzf = false,
 ))]
 fn methylation_pattern(
     pileup: &str,
@@ -186,6 +355,10 @@ fn methylation_pattern(
     batch_size: usize,
     min_valid_cov_to_diff_fraction: f32,
     allow_assembly_pileup_mismatch: bool,
+    min_motif_observations: u32,
+    call_threshold: f64,
+    as_percentage: bool,
+    output_bgzf: bool,
 ) -> PyResult<PyDataFrame> {
     let parsed_contigs = if let Ok(path) = assembly.extract::<String>() {
         if let Some(contigs_filter) = contigs {
@@ -239,6 +412,10 @@ fn methylation_pattern(
         batch_size,
         min_valid_cov_to_diff_fraction,
         allow_assembly_pileup_mismatch,
+        min_motif_observations,
+        call_threshold,
+        as_percentage,
+        output_bgzf,
     )
 }
 
@@ -258,6 +435,10 @@ fn methylation_pattern_internal(
     batch_size: usize,
     min_valid_cov_to_diff_fraction: f32,
     allow_assembly_pileup_mismatch: bool,
+    min_motif_observations: u32,
+    call_threshold: f64,
+    as_percentage: bool,
+    output_bgzf: bool,
 ) -> PyResult<PyDataFrame> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).try_init().ok();
 
@@ -279,20 +460,55 @@ fn methylation_pattern_internal(
         input,
         contigs,
         motifs,
-        threads,
-        min_valid_read_coverage,
-        min_valid_cov_to_diff_fraction,
-        allow_assembly_pileup_mismatch,
-        &output_type,
+        &MethylationPatternOptions {
+            threads,
+            min_valid_read_coverage,
+            min_valid_cov_to_diff_fraction,
+            allow_mismatch: allow_assembly_pileup_mismatch,
+            min_motif_observations,
+            output_type,
+            call_threshold,
+            ..Default::default()
+        },
+        None,
     )
-    .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+    .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?
+    .pattern;
 
+    let value_scale = if as_percentage { ValueScale::Percent } else { ValueScale::Fraction };
     if let Some(output_path) = output {
-        meth_pattern.write_output(Path::new(output_path))
-            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        if output_bgzf {
+            let mut writer =
+                Writer::from_path(Path::new(output_path), DEFAULT_COMPRESSION_LEVEL)
+                    .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+            meth_pattern
+                .write_output_to(
+                    &mut writer,
+                    value_scale,
+                    true,
+                    false,
+                    StrandFormat::Symbol,
+                    &[],
+                )
+                .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+            writer
+                .finish()
+                .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        } else {
+            meth_pattern
+                .write_output(
+                    Path::new(output_path),
+                    value_scale,
+                    true,
+                    false,
+                    StrandFormat::Symbol,
+                    &[],
+                )
+                .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        }
     }
 
-    let res_df = create_methylation_pattern_df(meth_pattern)
+    let res_df = create_methylation_pattern_df(meth_pattern, as_percentage)
         .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
     Ok(PyDataFrame(res_df))
 }
@@ -317,6 +533,53 @@ fn remove_child_motifs(output: &str, motifs: Vec<String>) -> PyResult<()> {
         .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
 }
 
+/// Find the positions of a motif in a DNA sequence on both strands.
+///
+/// Useful for prototyping motif scanning. Positions are 0-based indices of the
+/// modified base, as used for the methylation pattern. Minus strand positions are
+/// matches of the motif's reverse complement on the given sequence.
+///
+/// Args:
+///     sequence (str): DNA sequence containing only A, C, G and T (case-insensitive)
+///     motif (str): Motif in the format `<sequence>_<mod_type>_<mod_position>`,
+///                  e.g. "GATC_a_1"
+///
+/// Returns:
+///     Tuple[List[int], List[int]]: Positions on the plus strand and on the minus strand
+///
+/// Raises:
+///     PyValueError: If the sequence contains other bases than A, C, G and T, or the
+///                   motif cannot be parsed
+#[pyfunction]
+fn find_motif_positions(sequence: &str, motif: &str) -> PyResult<(Vec<usize>, Vec<usize>)> {
+    let sequence = sequence.to_ascii_uppercase();
+    if let Some((i, base)) = sequence
+        .char_indices()
+        .find(|(_, base)| !matches!(base, 'A' | 'C' | 'G' | 'T'))
+    {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Invalid base '{}' at index {}. The sequence may only contain A, C, G and T",
+            base, i
+        )));
+    }
+    let sequence = Sequence::from_str(&sequence)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+
+    let motifs = create_motifs(&vec![motif.to_string()])
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    let [motif] = motifs.as_slice() else {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Expected a single motif but found {}",
+            motifs.len()
+        )));
+    };
+
+    let plus = find_motif_indices_in_sequence(&sequence, motif);
+    let minus = find_motif_indices_in_sequence(&sequence, &motif.reverse_complement());
+
+    Ok((plus, minus))
+}
+
 /// Query pileup records for specific contigs and return as Polars DataFrame.
 ///
 /// This function reads a pileup file and extracts all methylation records
@@ -326,6 +589,8 @@ fn remove_child_motifs(output: &str, motifs: Vec<String>) -> PyResult<()> {
 ///     pileup_path (str): Path to the pileup file (BED format, can be gzipped)
 ///     contigs (List[str]): List of contig names to query
 ///     columns (List[PileupColumn] | None): List of column names to filter by to reduce memory
+///     summarize (bool): Return one row per contig, mod type and strand instead of one row
+///                       per record. Cannot be combined with `columns`.
 ///
 ///
 /// Returns:
@@ -349,16 +614,33 @@ fn remove_child_motifs(output: &str, motifs: Vec<String>) -> PyResult<()> {
 ///         - n_diff: Number of different reads
 ///         - n_no_call: Number of no-call reads
 ///
+///     With `summarize` the DataFrame has the columns:
+///         - contig: Contig/chromosome name
+///         - mod_type: Modification type code
+///         - strand: DNA strand (+ or -)
+///         - n_positions: Number of pileup records
+///         - total_valid_cov: Sum of n_valid_cov
+///         - mean_fraction_modified: Mean of fraction_modified
+///         - median_fraction_modified: Median of fraction_modified
+///
 /// Raises:
 ///     PyIOError: If the pileup file cannot be read
+///     PyValueError: If both `columns` and `summarize` are given
 ///     PyRuntimeError: If querying fails due to data processing issues
 #[pyfunction]
-#[pyo3(signature = (pileup_path, contigs, columns=None))]
+#[pyo3(signature = (pileup_path, contigs, columns=None, summarize=false))]
 fn query_pileup_records(
     pileup_path: &str,
     contigs: Vec<String>,
     columns: Option<Vec<PileupColumn>>,
+    summarize: bool,
 ) -> PyResult<PyDataFrame> {
+    if summarize && columns.is_some() {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "'columns' cannot be combined with 'summarize'",
+        ));
+    }
+
     let cols = if let Some(cols) = columns {
         cols.iter().map(PileupColumn::to_string).collect::<Vec<_>>()
     } else {
@@ -374,72 +656,13 @@ fn query_pileup_records(
         let records = query_pileup(&mut reader, &[contig])
             .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
 
-        // Pre-allocate vectors for columns
-        let mut contig_vec = Vec::new();
-        let mut start_vec = Vec::new();
-        let mut end_vec = Vec::new();
-        let mut mod_type_vec = Vec::new();
-        let mut score_vec = Vec::new();
-        let mut strand_vec = Vec::new();
-        let mut start_pos_vec = Vec::new();
-        let mut end_pos_vec = Vec::new();
-        let mut color_vec = Vec::new();
-        let mut n_valid_cov_vec = Vec::new();
-        let mut fraction_modified_vec = Vec::new();
-        let mut n_modified_vec = Vec::new();
-        let mut n_canonical_vec = Vec::new();
-        let mut n_other_mod_vec = Vec::new();
-        let mut n_delete_vec = Vec::new();
-        let mut n_fail_vec = Vec::new();
-        let mut n_diff_vec = Vec::new();
-        let mut n_no_call_vec = Vec::new();
-
-        for record in records {
-            contig_vec.push(record.contig);
-            start_vec.push(record.start);
-            end_vec.push(record.end);
-            mod_type_vec.push(record.mod_type.to_pileup_code().to_string());
-            score_vec.push(record.score);
-            strand_vec.push(record.strand.to_string());
-            start_pos_vec.push(record.start_pos);
-            end_pos_vec.push(record.end_pos);
-            color_vec.push(record.color);
-            n_valid_cov_vec.push(record.n_valid_cov);
-            fraction_modified_vec.push(record.fraction_modified);
-            n_modified_vec.push(record.n_modified);
-            n_canonical_vec.push(record.n_canonical);
-            n_other_mod_vec.push(record.n_other_mod);
-            n_delete_vec.push(record.n_delete);
-            n_fail_vec.push(record.n_fail);
-            n_diff_vec.push(record.n_diff);
-            n_no_call_vec.push(record.n_no_call);
+        let df_tmp = if summarize {
+            summarize_pileup_records(&records)
+        } else {
+            pileup_records_to_df(records, &cols)
         }
-
-        let mut df_tmp = df! [
-            "contig" => contig_vec,
-            "start" => start_vec,
-            "end" => end_vec,
-            "mod_type" => mod_type_vec,
-            "score" => score_vec,
-            "strand" => strand_vec,
-            "start_pos" => start_pos_vec,
-            "end_pos" => end_pos_vec,
-            "color" => color_vec,
-            "n_valid_cov" => n_valid_cov_vec,
-            "fraction_modified" => fraction_modified_vec,
-            "n_modified" => n_modified_vec,
-            "n_canonical" => n_canonical_vec,
-            "n_other_mod" => n_other_mod_vec,
-            "n_delete" => n_delete_vec,
-            "n_fail" => n_fail_vec,
-            "n_diff" => n_diff_vec,
-            "n_no_call" => n_no_call_vec,
-        ]
         .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
 
-        if cols.len() > 0 {
-            df_tmp = df_tmp.select(cols.clone()).unwrap();
-        }
         match &mut df {
             None => df = Some(df_tmp),
             Some(df_ref) => {
@@ -455,6 +678,256 @@ fn query_pileup_records(
     Ok(PyDataFrame(df))
 }
 
+/// Builds a DataFrame with the pileup columns from the records. All columns are
+/// present, also without records, unless `cols` selects a subset.
+fn pileup_records_to_df(records: Vec<PileupRecord>, cols: &[String]) -> PolarsResult<DataFrame> {
+    // Pre-allocate vectors for columns
+    let mut contig_vec = Vec::with_capacity(records.len());
+    let mut start_vec = Vec::with_capacity(records.len());
+    let mut end_vec = Vec::with_capacity(records.len());
+    let mut mod_type_vec = Vec::with_capacity(records.len());
+    let mut score_vec = Vec::with_capacity(records.len());
+    let mut strand_vec = Vec::with_capacity(records.len());
+    let mut start_pos_vec = Vec::with_capacity(records.len());
+    let mut end_pos_vec = Vec::with_capacity(records.len());
+    let mut color_vec = Vec::with_capacity(records.len());
+    let mut n_valid_cov_vec = Vec::with_capacity(records.len());
+    let mut fraction_modified_vec = Vec::with_capacity(records.len());
+    let mut n_modified_vec = Vec::with_capacity(records.len());
+    let mut n_canonical_vec = Vec::with_capacity(records.len());
+    let mut n_other_mod_vec = Vec::with_capacity(records.len());
+    let mut n_delete_vec = Vec::with_capacity(records.len());
+    let mut n_fail_vec = Vec::with_capacity(records.len());
+    let mut n_diff_vec = Vec::with_capacity(records.len());
+    let mut n_no_call_vec = Vec::with_capacity(records.len());
+
+    for record in records {
+        contig_vec.push(record.contig);
+        start_vec.push(record.start);
+        end_vec.push(record.end);
+        mod_type_vec.push(record.mod_type.to_pileup_code().to_string());
+        score_vec.push(record.score);
+        strand_vec.push(record.strand.to_string());
+        start_pos_vec.push(record.start_pos);
+        end_pos_vec.push(record.end_pos);
+        color_vec.push(record.color);
+        n_valid_cov_vec.push(record.n_valid_cov);
+        fraction_modified_vec.push(record.fraction_modified);
+        n_modified_vec.push(record.n_modified);
+        n_canonical_vec.push(record.n_canonical);
+        n_other_mod_vec.push(record.n_other_mod);
+        n_delete_vec.push(record.n_delete);
+        n_fail_vec.push(record.n_fail);
+        n_diff_vec.push(record.n_diff);
+        n_no_call_vec.push(record.n_no_call);
+    }
+
+    let df = df! [
+        "contig" => contig_vec,
+        "start" => start_vec,
+        "end" => end_vec,
+        "mod_type" => mod_type_vec,
+        "score" => score_vec,
+        "strand" => strand_vec,
+        "start_pos" => start_pos_vec,
+        "end_pos" => end_pos_vec,
+        "color" => color_vec,
+        "n_valid_cov" => n_valid_cov_vec,
+        "fraction_modified" => fraction_modified_vec,
+        "n_modified" => n_modified_vec,
+        "n_canonical" => n_canonical_vec,
+        "n_other_mod" => n_other_mod_vec,
+        "n_delete" => n_delete_vec,
+        "n_fail" => n_fail_vec,
+        "n_diff" => n_diff_vec,
+        "n_no_call" => n_no_call_vec,
+    ]?;
+
+    if cols.is_empty() {
+        Ok(df)
+    } else {
+        df.select(cols.iter().cloned())
+    }
+}
+
+/// Summarizes the records per contig, mod type and strand, sorted by these keys.
+fn summarize_pileup_records(records: &[PileupRecord]) -> PolarsResult<DataFrame> {
+    let mut groups: BTreeMap<(&str, &str, String), Vec<&PileupRecord>> = BTreeMap::new();
+    for record in records {
+        groups
+            .entry((
+                record.contig.as_str(),
+                record.mod_type.to_pileup_code(),
+                record.strand.to_string(),
+            ))
+            .or_default()
+            .push(record);
+    }
+
+    let mut contig_vec = Vec::with_capacity(groups.len());
+    let mut mod_type_vec = Vec::with_capacity(groups.len());
+    let mut strand_vec = Vec::with_capacity(groups.len());
+    let mut n_positions_vec = Vec::with_capacity(groups.len());
+    let mut total_valid_cov_vec = Vec::with_capacity(groups.len());
+    let mut mean_fraction_modified_vec = Vec::with_capacity(groups.len());
+    let mut median_fraction_modified_vec = Vec::with_capacity(groups.len());
+
+    for ((contig, mod_type, strand), group) in groups {
+        let mut fractions: Vec<f64> = group.iter().map(|r| r.fraction_modified).collect();
+        fractions.sort_by(|a, b| a.total_cmp(b));
+        let mid = fractions.len() / 2;
+        let median = if fractions.len() % 2 == 0 {
+            (fractions[mid - 1] + fractions[mid]) / 2.0
+        } else {
+            fractions[mid]
+        };
+
+        contig_vec.push(contig.to_string());
+        mod_type_vec.push(mod_type.to_string());
+        strand_vec.push(strand);
+        n_positions_vec.push(group.len() as u64);
+        total_valid_cov_vec.push(group.iter().map(|r| r.n_valid_cov as u64).sum::<u64>());
+        mean_fraction_modified_vec.push(fractions.iter().sum::<f64>() / fractions.len() as f64);
+        median_fraction_modified_vec.push(median);
+    }
+
+    df! [
+        "contig" => contig_vec,
+        "mod_type" => mod_type_vec,
+        "strand" => strand_vec,
+        "n_positions" => n_positions_vec,
+        "total_valid_cov" => total_valid_cov_vec,
+        "mean_fraction_modified" => mean_fraction_modified_vec,
+        "median_fraction_modified" => median_fraction_modified_vec,
+    ]
+}
+
+/// Query a compressed pileup one contig at a time.
+///
+/// Unlike `query_pileup_records`, which returns a single DataFrame for all contigs,
+/// `PileupQuery` yields one DataFrame per contig, in the order the contigs were
+/// given, so large pileups can be processed incrementally. Each DataFrame has the
+/// full pileup schema (or the selected `columns`), also when the contig has no
+/// records. The reader is closed once all contigs have been yielded, or by `close()`.
+///
+/// Args:
+///     pileup_path (str): Path to the compressed pileup file (.bed.gz) with a .tbi index
+///     contigs (List[str]): List of contig names to query
+///     columns (List[PileupColumn] | None): List of column names to filter by to reduce memory
+///
+/// Example:
+///     query = PileupQuery("pileup.bed.gz", ["contig_1", "contig_2"])
+///     while (df := query.next_contig_df()) is not None:
+///         ...
+///
+///     # or
+///     for df in PileupQuery("pileup.bed.gz", ["contig_1", "contig_2"]):
+///         ...
+///
+/// Raises:
+///     PyIOError: If the pileup file cannot be read
+///     PyRuntimeError: If querying a contig fails
+#[pyclass(unsendable)]
+pub struct PileupQuery {
+    reader: Option<epimetheus_io::io::readers::bgzf_bed::Reader>,
+    contigs: std::collections::VecDeque<String>,
+    columns: Vec<String>,
+}
+
+#[pymethods]
+impl PileupQuery {
+    #[new]
+    #[pyo3(signature = (pileup_path, contigs, columns=None))]
+    fn new(
+        pileup_path: &str,
+        contigs: Vec<String>,
+        columns: Option<Vec<PileupColumn>>,
+    ) -> PyResult<Self> {
+        let reader =
+            epimetheus_io::io::readers::bgzf_bed::Reader::from_path(Path::new(pileup_path))
+                .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
+
+        Ok(Self {
+            reader: Some(reader),
+            contigs: contigs.into(),
+            columns: columns
+                .unwrap_or_default()
+                .iter()
+                .map(PileupColumn::to_string)
+                .collect(),
+        })
+    }
+
+    /// Returns the DataFrame of the next contig, or None when all contigs have been
+    /// yielded. The reader is closed when None is returned.
+    fn next_contig_df(&mut self) -> PyResult<Option<PyDataFrame>> {
+        let (Some(reader), Some(contig)) = (self.reader.as_mut(), self.contigs.pop_front()) else {
+            self.close();
+            return Ok(None);
+        };
+
+        let records = query_pileup(reader, &[contig])
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+        let df = pileup_records_to_df(records, &self.columns)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+
+        Ok(Some(PyDataFrame(df)))
+    }
+
+    /// Number of contigs not yet yielded.
+    fn remaining(&self) -> usize {
+        if self.reader.is_some() {
+            self.contigs.len()
+        } else {
+            0
+        }
+    }
+
+    /// Whether the underlying reader has been closed.
+    fn is_closed(&self) -> bool {
+        self.reader.is_none()
+    }
+
+    /// Closes the reader. Subsequent calls to `next_contig_df` return None.
+    fn close(&mut self) {
+        self.reader = None;
+        self.contigs.clear();
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> PyResult<Option<PyDataFrame>> {
+        self.next_contig_df()
+    }
+}
+
+/// List the contigs in a compressed pileup with their max indexed end coordinate.
+///
+/// The tabix index does not store contig lengths, so the length is the largest
+/// record end found in the last indexed window of each contig.
+///
+/// Args:
+///     pileup_path (str): Path to the compressed pileup file (.bed.gz) with a .tbi index
+///
+/// Returns:
+///     List[Tuple[str, Optional[int]]]: Contig names and their max end coordinate
+///
+/// Raises:
+///     PyIOError: If the pileup file cannot be read
+///     PyRuntimeError: If querying the index fails
+#[pyfunction]
+fn pileup_contig_lengths(pileup_path: &str) -> PyResult<Vec<(String, Option<u64>)>> {
+    let mut reader =
+        epimetheus_io::io::readers::bgzf_bed::Reader::from_path(Path::new(pileup_path))
+            .map_err(|e| pyo3::exceptions::PyIOError::new_err(e.to_string()))?;
+
+    reader
+        .available_contigs_with_lengths()
+        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+}
+
 /// Compress a pileup file using BGZF compression.
 ///
 /// This function compresses a pileup file using the BGZF (Blocked GZip Format)
@@ -539,8 +1012,11 @@ pub struct BgzfWriter {
 
 #[pymethods]
 impl BgzfWriter {
+    /// Creates a BGZF writer with a compression level from 0 (no compression) to 9 (best
+    /// compression).
     #[new]
-    fn new(output_path: &str, force: bool) -> PyResult<Self> {
+    #[pyo3(signature = (output_path, force, compression_level=DEFAULT_COMPRESSION_LEVEL))]
+    fn new(output_path: &str, force: bool, compression_level: u32) -> PyResult<Self> {
         let path = PathBuf::from(output_path);
 
         if !force && path.exists() {
@@ -551,7 +1027,7 @@ impl BgzfWriter {
         }
 
         let writer = WriterType::File(
-            Writer::from_path(&path)
+            Writer::from_path(&path, compression_level)
                 .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?,
         );
 
@@ -561,6 +1037,24 @@ impl BgzfWriter {
         })
     }
 
+    /// Opens a finished BGZF pileup to append lines. The index of the existing lines is
+    /// rebuilt without recompressing them and written with the appended lines by `finish`.
+    #[staticmethod]
+    #[pyo3(signature = (output_path, compression_level=DEFAULT_COMPRESSION_LEVEL))]
+    fn reopen(output_path: &str, compression_level: u32) -> PyResult<Self> {
+        let path = PathBuf::from(output_path);
+
+        let writer = WriterType::File(
+            Writer::reopen(&path, compression_level)
+                .map_err(|e| pyo3::exceptions::PyIOError::new_err(format!("{:#}", e)))?,
+        );
+
+        Ok(Self {
+            writer: Some(writer),
+            output_path: path,
+        })
+    }
+
     fn write_lines(&mut self, lines: Vec<String>) -> PyResult<()> {
         if let Some(ref mut writer) = self.writer {
             writer
@@ -584,6 +1078,23 @@ impl BgzfWriter {
         }
         Ok(())
     }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Calls `finish` when leaving a `with` block. Returns False, so an exception raised
+    /// inside the block propagates after the file and its index are written.
+    #[pyo3(signature = (_exc_type=None, _exc_value=None, _traceback=None))]
+    fn __exit__(
+        &mut self,
+        _exc_type: Option<Bound<'_, PyAny>>,
+        _exc_value: Option<Bound<'_, PyAny>>,
+        _traceback: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        self.finish()?;
+        Ok(false)
+    }
 }
 
 /// Extract methylation patterns directly from a Polars DataFrame.
@@ -601,7 +1112,11 @@ impl BgzfWriter {
 ///     motifs (List[str]): List of DNA motifs to search for (e.g., ['GATC', 'CCWGG'])
 ///     min_valid_read_coverage (int): Minimum number of valid reads required for a position
 ///     min_valid_cov_to_diff_fraction (float): Minimum fraction of valid coverage to difference coverage
-///     output_type (MethylationOutput): Output format type (Raw, Median, or WeightedMean)
+///     output_type (MethylationOutput): Output format type (Raw, Median, WeightedMean, WeightedMedian, TrimmedMean, CalledFrequency or StrandAsymmetry)
+///     call_threshold (float): Fraction of modified reads at or above which a position is
+///         called methylated with `MethylationOutput.CalledFrequency`
+///     as_percentage (bool): Emit the methylation values as percentages (0-100) in a
+///         `methylation_percent` column instead of fractions in `methylation_value`
 ///
 /// Returns:
 ///     polars.DataFrame: DataFrame containing methylation pattern results
@@ -617,6 +1132,8 @@ impl BgzfWriter {
     threads,
     min_valid_read_coverage = 5,
     min_valid_cov_to_diff_fraction = 0.8,
+    call_threshold = 0.5,
+    as_percentage = false,
 ))]
 fn methylation_pattern_from_dataframe(
     pileup_df: PyDataFrame,
@@ -626,6 +1143,229 @@ fn methylation_pattern_from_dataframe(
     threads: usize,
     min_valid_read_coverage: u32,
     min_valid_cov_to_diff_fraction: f32,
+    call_threshold: f64,
+    as_percentage: bool,
+) -> PyResult<PyDataFrame> {
+    Python::with_gil(|py| {
+        py.allow_threads(|| -> anyhow::Result<DataFrame> {
+            let contigs = epimetheus_io::io::readers::fasta::Reader::read_fasta(
+                Path::new(assembly),
+                Some(pileup_df_contig_ids(&pileup_df.0)?),
+            )?;
+
+            methylation_pattern_df(
+                pileup_df.0,
+                contigs,
+                motifs,
+                output_type,
+                threads,
+                min_valid_read_coverage,
+                min_valid_cov_to_diff_fraction,
+                call_threshold,
+                as_percentage,
+            )
+        })
+    })
+    .map(PyDataFrame)
+    .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+}
+
+/// Extract methylation patterns from a Polars DataFrame with the contig sequences in a
+/// second DataFrame.
+///
+/// Like `methylation_pattern_from_dataframe`, but the contigs are taken from `contigs_df`
+/// instead of a FASTA file, so sequences already in memory (e.g. from Bio::SeqIO) need not
+/// be written to disk first.
+///
+/// Args:
+///     pileup_df (polars.DataFrame): DataFrame containing pileup record data, as for
+///         `methylation_pattern_from_dataframe`
+///     contigs_df (polars.DataFrame): DataFrame with the string columns `contig` and
+///         `sequence`. Contigs not in the pileup are ignored.
+///     threads (int): Number of threads to use for parallel processing
+///     motifs (List[str]): List of DNA motifs to search for (e.g., ['GATC', 'CCWGG'])
+///     min_valid_read_coverage (int): Minimum number of valid reads required for a position
+///     min_valid_cov_to_diff_fraction (float): Minimum fraction of valid coverage to difference coverage
+///     output_type (MethylationOutput): Output format type (Raw, Median, WeightedMean, WeightedMedian, TrimmedMean, CalledFrequency or StrandAsymmetry)
+///     call_threshold (float): Fraction of modified reads at or above which a position is
+///         called methylated with `MethylationOutput.CalledFrequency`
+///     as_percentage (bool): Emit the methylation values as percentages (0-100) in a
+///         `methylation_percent` column instead of fractions in `methylation_value`
+///
+/// Returns:
+///     polars.DataFrame: DataFrame containing methylation pattern results
+///
+/// Raises:
+///     PyRuntimeError: If `contigs_df` lacks a column, has a missing value or a contig
+///         twice, or if processing fails
+#[pyfunction]
+#[pyo3(signature = (
+    pileup_df,
+    contigs_df,
+    motifs,
+    output_type,
+    threads,
+    min_valid_read_coverage = 5,
+    min_valid_cov_to_diff_fraction = 0.8,
+    call_threshold = 0.5,
+    as_percentage = false,
+))]
+fn methylation_pattern_from_dataframes(
+    pileup_df: PyDataFrame,
+    contigs_df: PyDataFrame,
+    motifs: Vec<String>,
+    output_type: MethylationOutput,
+    threads: usize,
+    min_valid_read_coverage: u32,
+    min_valid_cov_to_diff_fraction: f32,
+    call_threshold: f64,
+    as_percentage: bool,
+) -> PyResult<PyDataFrame> {
+    Python::with_gil(|py| {
+        py.allow_threads(|| -> anyhow::Result<DataFrame> {
+            let contigs =
+                contigs_from_dataframe(&contigs_df.0, &pileup_df_contig_ids(&pileup_df.0)?)?;
+
+            methylation_pattern_df(
+                pileup_df.0,
+                contigs,
+                motifs,
+                output_type,
+                threads,
+                min_valid_read_coverage,
+                min_valid_cov_to_diff_fraction,
+                call_threshold,
+                as_percentage,
+            )
+        })
+    })
+    .map(PyDataFrame)
+    .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+}
+
+/// The contig ids of a pileup DataFrame in order of appearance.
+fn pileup_df_contig_ids(pileup_df: &DataFrame) -> anyhow::Result<Vec<String>> {
+    Ok(pileup_df
+        .column("contig")?
+        .unique_stable()?
+        .into_materialized_series()
+        .iter()
+        .map(|v| v.get_str().unwrap_or("").to_string())
+        .collect())
+}
+
+/// Builds the contigs of `contig_ids` from a DataFrame with the columns `contig` and
+/// `sequence`.
+fn contigs_from_dataframe(
+    contigs_df: &DataFrame,
+    contig_ids: &[String],
+) -> anyhow::Result<AHashMap<String, Contig>> {
+    let ids = contigs_df.column("contig")?.str()?;
+    let sequences = contigs_df.column("sequence")?.str()?;
+    let wanted: ahash::AHashSet<&str> = contig_ids.iter().map(String::as_str).collect();
+
+    let mut seen = ahash::AHashSet::new();
+    let mut contigs = AHashMap::new();
+    for (i, (id, sequence)) in ids.into_iter().zip(sequences.into_iter()).enumerate() {
+        let (Some(id), Some(sequence)) = (id, sequence) else {
+            anyhow::bail!(
+                "Missing contig or sequence in row {} of the contigs DataFrame",
+                i
+            );
+        };
+        if !seen.insert(id) {
+            anyhow::bail!(
+                "Contig '{}' appears more than once in the contigs DataFrame",
+                id
+            );
+        }
+        if !wanted.contains(id) {
+            continue;
+        }
+        contigs.insert(
+            id.to_string(),
+            Contig::from_string(id.to_string(), sequence.to_string())?,
+        );
+    }
+
+    Ok(contigs)
+}
+
+/// Methylation pattern of a pileup DataFrame as a DataFrame.
+fn methylation_pattern_df(
+    pileup_df: DataFrame,
+    contigs: AHashMap<String, Contig>,
+    motifs: Vec<String>,
+    output_type: MethylationOutput,
+    threads: usize,
+    min_valid_read_coverage: u32,
+    min_valid_cov_to_diff_fraction: f32,
+    call_threshold: f64,
+    as_percentage: bool,
+) -> anyhow::Result<DataFrame> {
+    let motifs = create_motifs(&motifs)?;
+
+    let input = MethylationInput::DataFrame(pileup_df);
+
+    let meth_pattern = extract_methylation_pattern(
+        input,
+        contigs,
+        motifs,
+        &MethylationPatternOptions {
+            threads,
+            min_valid_read_coverage,
+            min_valid_cov_to_diff_fraction,
+            output_type,
+            call_threshold,
+            ..Default::default()
+        },
+        None,
+    )?
+    .pattern;
+
+    // Convert MethylationPatternVariant to DataFrame
+    create_methylation_pattern_df(meth_pattern, as_percentage)
+}
+
+/// Extract the median and weighted mean methylation together from a Polars DataFrame.
+///
+/// Both statistics are computed from the same motif positions, so the assembly is
+/// read and the motifs are searched once. The values equal those of
+/// `methylation_pattern_from_dataframe` with `MethylationOutput.Median` and
+/// `MethylationOutput.WeightedMean`.
+///
+/// Args:
+///     pileup_df (polars.DataFrame): DataFrame containing pileup record data (see
+///         `methylation_pattern_from_dataframe`)
+///     assembly (str): Path to the assembly FASTA file
+///     motifs (List[str]): List of DNA motifs to search for (e.g., ['GATC_a_1', 'CCWGG_m_1'])
+///     threads (int): Number of threads to use for parallel processing
+///     min_valid_read_coverage (int): Minimum number of valid reads required for a position
+///     min_valid_cov_to_diff_fraction (float): Minimum fraction of valid coverage to difference coverage
+///
+/// Returns:
+///     polars.DataFrame: One row per contig-motif, sorted by contig and motif, with the
+///         columns contig, motif, mod_type, mod_position, median, weighted_mean,
+///         mean_read_cov, n_motif_obs and motif_occurences_total
+///
+/// Raises:
+///     PyRuntimeError: If processing fails due to data format or processing issues
+#[pyfunction]
+#[pyo3(signature = (
+    pileup_df,
+    assembly,
+    motifs,
+    threads,
+    min_valid_read_coverage = 5,
+    min_valid_cov_to_diff_fraction = 0.8,
+))]
+fn methylation_summary_from_dataframe(
+    pileup_df: PyDataFrame,
+    assembly: &str,
+    motifs: Vec<String>,
+    threads: usize,
+    min_valid_read_coverage: u32,
+    min_valid_cov_to_diff_fraction: f32,
 ) -> PyResult<PyDataFrame> {
     Python::with_gil(|py| {
         py.allow_threads(|| -> anyhow::Result<DataFrame> {
@@ -644,21 +1384,60 @@ fn methylation_pattern_from_dataframe(
             )?;
             let motifs = create_motifs(&motifs)?;
 
-            let input = MethylationInput::DataFrame(pileup_df.0);
-
             let meth_pattern = extract_methylation_pattern(
-                input,
+                MethylationInput::DataFrame(pileup_df.0),
                 contigs,
                 motifs,
-                threads,
-                min_valid_read_coverage,
-                min_valid_cov_to_diff_fraction,
-                false, // allow_mismatch not relevant for DataFrame input
-                &output_type,
-            )?;
+                &MethylationPatternOptions {
+                    threads,
+                    min_valid_read_coverage,
+                    min_valid_cov_to_diff_fraction,
+                    output_type: MethylationOutput::Raw,
+                    ..Default::default()
+                },
+                None,
+            )?
+            .pattern;
+
+            let MethylationPatternVariant::Raw(positions) = meth_pattern else {
+                anyhow::bail!("Expected raw methylation positions");
+            };
+            let mut summary = positions.to_summary();
+            summary.sort_by(|a, b| (&a.contig, &a.motif).cmp(&(&b.contig, &b.motif)));
+
+            let contig_vec: Vec<String> = summary.iter().map(|s| s.contig.clone()).collect();
+            let motif_vec: Vec<String> = summary
+                .iter()
+                .map(|s| s.motif.sequence_to_string())
+                .collect();
+            let mod_type_vec: Vec<String> = summary
+                .iter()
+                .map(|s| s.motif.mod_type.to_pileup_code().to_string())
+                .collect();
+            let mod_position_vec: Vec<u64> = summary
+                .iter()
+                .map(|s| s.motif.mod_position as u64)
+                .collect();
+            let median_vec: Vec<f64> = summary.iter().map(|s| s.median).collect();
+            let weighted_mean_vec: Vec<f64> = summary.iter().map(|s| s.weighted_mean).collect();
+            let mean_read_cov_vec: Vec<f64> = summary.iter().map(|s| s.mean_read_cov).collect();
+            let n_motif_obs_vec: Vec<u32> = summary.iter().map(|s| s.n_motif_obs).collect();
+            let n_motif_occurences_total: Vec<u32> = summary
+                .iter()
+                .map(|s| s.motif_occurences_total)
+                .collect();
 
-            // Convert MethylationPatternVariant to DataFrame
-            let df = create_methylation_pattern_df(meth_pattern)?;
+            let df = df![
+                "contig" => contig_vec,
+                "motif" => motif_vec,
+                "mod_type" => mod_type_vec,
+                "mod_position" => mod_position_vec,
+                "median" => median_vec,
+                "weighted_mean" => weighted_mean_vec,
+                "mean_read_cov" => mean_read_cov_vec,
+                "n_motif_obs" => n_motif_obs_vec,
+                "motif_occurences_total" => n_motif_occurences_total,
+            ]?;
             Ok(df)
         })
     })
@@ -670,12 +1449,17 @@ fn methylation_pattern_from_dataframe(
 fn epymetheus(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(methylation_pattern, m)?)?;
     m.add_function(wrap_pyfunction!(methylation_pattern_from_dataframe, m)?)?;
+    m.add_function(wrap_pyfunction!(methylation_pattern_from_dataframes, m)?)?;
+    m.add_function(wrap_pyfunction!(methylation_summary_from_dataframe, m)?)?;
     m.add_function(wrap_pyfunction!(remove_child_motifs, m)?)?;
+    m.add_function(wrap_pyfunction!(find_motif_positions, m)?)?;
     m.add_function(wrap_pyfunction!(query_pileup_records, m)?)?;
+    m.add_function(wrap_pyfunction!(pileup_contig_lengths, m)?)?;
     m.add_function(wrap_pyfunction!(bgzf_pileup, m)?)?;
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
     m.add_class::<MethylationOutput>()?;
     m.add_class::<PileupColumn>()?;
     m.add_class::<BgzfWriter>()?;
+    m.add_class::<PileupQuery>()?;
     Ok(())
 }
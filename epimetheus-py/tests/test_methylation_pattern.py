@@ -1,3 +1,4 @@
+import gzip
 import os
 import tempfile
 import pytest
@@ -88,13 +89,102 @@ def test_methylation_pattern_weighted_mean_from_df(data_dir, tmp_path):
     assert _normalize(actual) == _normalize(expected_text)
 
 
+def test_methylation_pattern_from_dataframes_matches_fasta(data_dir):
+    pileup = os.path.join(data_dir, "geobacillus.bed.gz")
+    assembly = os.path.join(data_dir, "geobacillus-plasmids.assembly.fasta")
+
+    df = epymetheus.query_pileup_records(pileup, ["contig_2", "contig_3"])
+    motifs = ["GATC_a_1", "GATC_m_3", "RGATCY_a_2"]
+    contigs_df = pl.DataFrame(
+        [(record.id, str(record.seq)) for record in SeqIO.parse(assembly, "fasta")],
+        schema=["contig", "sequence"],
+        orient="row",
+    )
+
+    def run(contigs):
+        kwargs = dict(
+            motifs=motifs,
+            threads=1,
+            min_valid_read_coverage=3,
+            min_valid_cov_to_diff_fraction=0.8,
+            output_type=MethylationOutput.WeightedMean,
+        )
+        if isinstance(contigs, str):
+            result = epymetheus.methylation_pattern_from_dataframe(df, contigs, **kwargs)
+        else:
+            result = epymetheus.methylation_pattern_from_dataframes(df, contigs, **kwargs)
+        return result.sort(["contig", "motif", "mod_type"])
+
+    from_fasta = run(assembly)
+    from_dataframe = run(contigs_df)
+    assert len(from_fasta) > 0
+    assert from_dataframe.equals(from_fasta)
+
+    with pytest.raises(RuntimeError):
+        run(pl.concat([contigs_df, contigs_df]))
+    with pytest.raises(Exception):
+        run(contigs_df.rename({"sequence": "seq"}))
+
+
+def test_methylation_summary_from_df_matches_single_outputs(data_dir):
+    pileup = os.path.join(data_dir, "geobacillus.bed.gz")
+    assembly = os.path.join(data_dir, "geobacillus-plasmids.assembly.fasta")
+
+    df = epymetheus.query_pileup_records(pileup, ["contig_2", "contig_3"])
+    motifs = ["GATC_a_1", "GATC_m_3", "RGATCY_a_2"]
+    keys = ["contig", "motif", "mod_type", "mod_position"]
+
+    def single(output_type, name):
+        return epymetheus.methylation_pattern_from_dataframe(
+            df,
+            assembly,
+            motifs=motifs,
+            threads=1,
+            min_valid_read_coverage=3,
+            min_valid_cov_to_diff_fraction=0.8,
+            output_type=output_type,
+        ).rename({"methylation_value": name})
+
+    median = single(MethylationOutput.Median, "median")
+    weighted_mean = single(MethylationOutput.WeightedMean, "weighted_mean")
+
+    summary = epymetheus.methylation_summary_from_dataframe(
+        df,
+        assembly,
+        motifs=motifs,
+        threads=1,
+        min_valid_read_coverage=3,
+        min_valid_cov_to_diff_fraction=0.8,
+    )
+
+    assert summary.columns == [
+        "contig",
+        "motif",
+        "mod_type",
+        "mod_position",
+        "median",
+        "weighted_mean",
+        "mean_read_cov",
+        "n_motif_obs",
+        "motif_occurences_total",
+    ]
+    assert len(summary) == len(median) == len(weighted_mean)
+
+    expected = median.join(weighted_mean.select(keys + ["weighted_mean"]), on=keys)
+    joined = summary.join(expected, on=keys, suffix="_single")
+    assert len(joined) == len(summary)
+    for column in ["median", "weighted_mean", "mean_read_cov", "n_motif_obs"]:
+        assert (joined[column] - joined[f"{column}_single"]).abs().max() < 1e-12
+
+
 def test_methylation_output_pickle():
     """Test that MethylationOutput enum variants can be pickled and unpickled correctly."""
     # Test all enum variants
     variants = [
         MethylationOutput.Raw,
         MethylationOutput.Median,
-        MethylationOutput.WeightedMean
+        MethylationOutput.WeightedMean,
+        MethylationOutput.WeightedMedian
     ]
 
     # Check module information
@@ -214,3 +304,154 @@ def test_methylation_pattern_from_contigs_with_filter(data_dir):
 
     # Should have some results (not empty)
     assert len(result_df) > 0
+
+
+def test_methylation_pattern_min_motif_observations(data_dir):
+    pileup = os.path.join(data_dir, "geobacillus-plasmids.pileup.bed")
+    assembly = os.path.join(data_dir, "geobacillus-plasmids.assembly.fasta")
+    motifs = ["GATC_a_1", "GATC_m_3", "RGATCY_a_2"]
+
+    unfiltered = epymetheus.methylation_pattern(
+        pileup,
+        assembly,
+        motifs=motifs,
+        output_type=MethylationOutput.Median,
+    )
+    threshold = int(unfiltered.get_column("n_motif_obs").max())
+    filtered = epymetheus.methylation_pattern(
+        pileup,
+        assembly,
+        motifs=motifs,
+        output_type=MethylationOutput.Median,
+        min_motif_observations=threshold,
+    )
+
+    assert filtered.height == unfiltered.filter(pl.col("n_motif_obs") >= threshold).height
+    assert filtered.height < unfiltered.height
+    assert filtered.get_column("n_motif_obs").min() >= threshold
+
+
+def test_methylation_pattern_filtered_counts(data_dir):
+    pileup = os.path.join(data_dir, "geobacillus-plasmids.pileup.bed")
+    assembly = os.path.join(data_dir, "geobacillus-plasmids.assembly.fasta")
+    motifs = ["GATC_a_1", "GATC_m_3", "RGATCY_a_2"]
+    keys = ["contig", "motif", "mod_type", "mod_position"]
+
+    def run(min_valid_read_coverage):
+        return epymetheus.methylation_pattern(
+            pileup,
+            assembly,
+            motifs=motifs,
+            output_type=MethylationOutput.WeightedMean,
+            min_valid_read_coverage=min_valid_read_coverage,
+        )
+
+    loose = run(1)
+    strict = run(10)
+    assert {"n_filtered_low_cov", "n_filtered_diff"} <= set(strict.columns)
+    assert strict.get_column("n_filtered_low_cov").sum() > loose.get_column("n_filtered_low_cov").sum()
+
+    # Positions no longer observed with the stricter coverage are counted as filtered.
+    joined = loose.join(strict, on=keys, suffix="_strict")
+    n_lost = joined.get_column("n_motif_obs") - joined.get_column("n_motif_obs_strict")
+    n_gained = joined.get_column("n_filtered_low_cov_strict") - joined.get_column("n_filtered_low_cov")
+    assert (n_gained >= n_lost).all()
+
+
+def test_methylation_pattern_weighted_median(data_dir):
+    pileup = os.path.join(data_dir, "geobacillus-plasmids.pileup.bed")
+    assembly = os.path.join(data_dir, "geobacillus-plasmids.assembly.fasta")
+    motifs = ["GATC_a_1", "GATC_m_3", "RGATCY_a_2"]
+    keys = ["contig", "motif", "mod_type", "mod_position"]
+
+    median = epymetheus.methylation_pattern(
+        pileup,
+        assembly,
+        motifs=motifs,
+        output_type=MethylationOutput.Median,
+    )
+    weighted_median = epymetheus.methylation_pattern(
+        pileup,
+        assembly,
+        motifs=motifs,
+        output_type=MethylationOutput.WeightedMedian,
+    )
+
+    assert weighted_median.columns == median.columns
+    joined = median.join(weighted_median, on=keys, suffix="_weighted")
+    assert joined.height == median.height
+    assert (joined.get_column("n_motif_obs") == joined.get_column("n_motif_obs_weighted")).all()
+    assert joined.get_column("methylation_value_weighted").is_between(0.0, 1.0).all()
+
+
+def test_methylation_pattern_call_threshold(data_dir):
+    pileup = os.path.join(data_dir, "geobacillus-plasmids.pileup.bed")
+    assembly = os.path.join(data_dir, "geobacillus-plasmids.assembly.fasta")
+    motifs = ["GATC_a_1", "GATC_m_3", "RGATCY_a_2"]
+
+    def run(call_threshold):
+        return epymetheus.methylation_pattern(
+            pileup,
+            assembly,
+            motifs=motifs,
+            output_type=MethylationOutput.CalledFrequency,
+            call_threshold=call_threshold,
+        )
+
+    # Every observed position is called methylated at a threshold of 0.
+    all_called = run(0.0)
+    assert (all_called.get_column("n_called_methylated") == all_called.get_column("n_motif_obs")).all()
+    assert run(0.5).get_column("n_called_methylated").sum() < all_called.get_column("n_called_methylated").sum()
+
+
+def test_methylation_pattern_as_percentage(data_dir, tmp_path):
+    pileup = os.path.join(data_dir, "geobacillus-plasmids.pileup.bed")
+    assembly = os.path.join(data_dir, "geobacillus-plasmids.assembly.fasta")
+    motifs = ["GATC_a_1", "GATC_m_3", "RGATCY_a_2"]
+    keys = ["contig", "motif", "mod_type", "mod_position"]
+    outfile = tmp_path / "out.tsv"
+
+    fraction = epymetheus.methylation_pattern(
+        pileup,
+        assembly,
+        motifs=motifs,
+        output_type=MethylationOutput.Median,
+    )
+    percent = epymetheus.methylation_pattern(
+        pileup,
+        assembly,
+        motifs=motifs,
+        output=str(outfile),
+        output_type=MethylationOutput.Median,
+        as_percentage=True,
+    )
+
+    assert "methylation_percent" in percent.columns
+    assert "methylation_value" not in percent.columns
+    assert outfile.read_text().splitlines()[0].split("\t")[4] == "methylation_percent"
+    joined = fraction.join(percent, on=keys)
+    assert joined.height == fraction.height
+    diff = joined.get_column("methylation_percent") - joined.get_column("methylation_value") * 100
+    assert (diff.abs() < 1e-9).all()
+
+
+def test_methylation_pattern_output_bgzf(data_dir, tmp_path):
+    pileup = os.path.join(data_dir, "geobacillus-plasmids.pileup.bed")
+    assembly = os.path.join(data_dir, "geobacillus-plasmids.assembly.fasta")
+    expected = os.path.join(data_dir, "expected_out_median.tsv")
+    outfile = tmp_path / "out.tsv.gz"
+
+    epymetheus.methylation_pattern(
+        pileup,
+        assembly,
+        motifs=["GATC_a_1", "GATC_m_3", "RGATCY_a_2"],
+        output=str(outfile),
+        output_type=MethylationOutput.Median,
+        output_bgzf=True,
+    )
+
+    with gzip.open(outfile, "rt") as f:
+        actual = f.read()
+    expected_text = open(expected).read()
+    assert _normalize(actual) == _normalize(expected_text)
+    assert not (tmp_path / "out.tsv.gz.tbi").exists()
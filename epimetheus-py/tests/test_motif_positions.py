@@ -0,0 +1,19 @@
+import pytest
+from epymetheus import epymetheus
+
+
+def test_find_motif_positions_both_strands():
+    plus, minus = epymetheus.find_motif_positions("AACGGGAAATCCCG", "TCCCG_m_1")
+    assert plus == [10]
+    assert minus == [5]
+
+
+def test_find_motif_positions_palindrome():
+    plus, minus = epymetheus.find_motif_positions("TGATCTGATC", "GATC_a_1")
+    assert plus == [2, 7]
+    assert minus == [3, 8]
+
+
+def test_find_motif_positions_invalid_base():
+    with pytest.raises(ValueError):
+        epymetheus.find_motif_positions("GATNC", "GATC_a_1")
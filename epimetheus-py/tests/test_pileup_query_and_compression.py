@@ -72,6 +72,17 @@ def test_query_data(data_dir, tmp_path):
     assert len(df.filter(pl.col("contig") == "contig_2")) > 0, "No records matched contig_2"
     assert len(df.filter(pl.col("contig") == "contig_10")) == 0, "Records matched contig_10 contig 10 should not be present"
 
+def test_pileup_contig_lengths(data_dir):
+    """Test listing contigs with their max indexed end coordinate"""
+    pileup_input = os.path.join(data_dir, "geobacillus.bed.gz")
+
+    lengths = dict(pileup_contig_lengths(pileup_input))
+    assert set(lengths.keys()) == {"contig_2", "contig_3"}
+
+    df = query_pileup_records(pileup_input, contigs=["contig_2", "contig_3"])
+    for contig, length in lengths.items():
+        assert length == df.filter(pl.col("contig") == contig)["end"].max()
+
 def test_query_with_col_filter(data_dir, tmp_path):
     """Test querying multiple contigs and the data"""
     pileup_input = os.path.join(data_dir, "geobacillus.bed.gz")
@@ -91,6 +102,71 @@ def test_query_with_col_filter(data_dir, tmp_path):
 
 
 
+def test_query_summarize(data_dir):
+    """Test summarizing records per contig, mod type and strand"""
+    pileup_input = os.path.join(data_dir, "geobacillus.bed.gz")
+    records = query_pileup_records(pileup_input, contigs=["contig_2", "contig_3"])
+    expected = (
+        records.group_by(["contig", "mod_type", "strand"])
+        .agg(
+            pl.len().cast(pl.UInt64).alias("n_positions"),
+            pl.col("n_valid_cov").cast(pl.UInt64).sum().alias("total_valid_cov"),
+            pl.col("fraction_modified").mean().alias("mean_fraction_modified"),
+            pl.col("fraction_modified").median().alias("median_fraction_modified"),
+        )
+        .sort(["contig", "mod_type", "strand"])
+    )
+
+    summary = query_pileup_records(pileup_input, contigs=["contig_2", "contig_3"], summarize=True)
+
+    assert summary.columns == expected.columns
+    assert summary["n_positions"].sum() == len(records)
+    keys = ["contig", "mod_type", "strand", "n_positions", "total_valid_cov"]
+    assert summary.select(keys).equals(expected.select(keys))
+    for column in ["mean_fraction_modified", "median_fraction_modified"]:
+        assert (summary[column] - expected[column]).abs().max() < 1e-9
+
+
+def test_query_summarize_with_columns_fails(data_dir):
+    pileup_input = os.path.join(data_dir, "geobacillus.bed.gz")
+    with pytest.raises(ValueError):
+        query_pileup_records(pileup_input, contigs=["contig_2"], columns=[PileupColumn.Contig], summarize=True)
+
+
+def test_pileup_query_streams_contigs(data_dir):
+    """Test streaming one DataFrame per contig"""
+    pileup_input = os.path.join(data_dir, "geobacillus.bed.gz")
+    expected = query_pileup_records(pileup_input, contigs=["contig_2", "contig_3"])
+
+    query = PileupQuery(pileup_input, ["contig_2", "contig_10", "contig_3"])
+    assert query.remaining() == 3
+
+    dfs = list(query)
+    assert len(dfs) == 3
+    for df in dfs:
+        assert df.columns == expected.columns, "Each chunk should have the full schema"
+
+    assert dfs[0]["contig"].unique().to_list() == ["contig_2"]
+    assert len(dfs[1]) == 0, "contig_10 should yield an empty DataFrame"
+    assert dfs[2]["contig"].unique().to_list() == ["contig_3"]
+    assert sum(len(df) for df in dfs) == len(expected)
+
+    assert query.is_closed(), "Reader should be closed when exhausted"
+    assert query.next_contig_df() is None
+
+def test_pileup_query_close(data_dir):
+    """Test closing a PileupQuery before it is exhausted"""
+    pileup_input = os.path.join(data_dir, "geobacillus.bed.gz")
+
+    query = PileupQuery(pileup_input, ["contig_2", "contig_3"], columns=[PileupColumn.Contig, PileupColumn.Start])
+    df = query.next_contig_df()
+    assert df.columns == ["contig", "start"]
+
+    query.close()
+    assert query.is_closed()
+    assert query.remaining() == 0
+    assert query.next_contig_df() is None
+
 def test_bgzf_compression_with_auto_output(data_dir, tmp_path):
     """Test bgzf compression with automatic output naming"""
     # Copy input file to temp directory so we can test auto-naming
@@ -199,3 +275,77 @@ def test_bgzf_compression_from_lines(data_dir, tmp_path):
         if tbi_file.exists():
             tbi_file.unlink()
         raise e
+
+
+def test_bgzf_writer_context_manager(data_dir, tmp_path):
+    """BgzfWriter finishes the file and index when used in a with block"""
+    pileup_input = os.path.join(data_dir, "geobacillus.bed.gz")
+    records = query_pileup_records(pileup_input, ["contig_2"], None)
+    lines = records.write_csv(separator="\t", include_header=False).strip().split("\n")
+
+    output = tmp_path / "context.bed.gz"
+    with BgzfWriter(str(output), force=True) as writer:
+        writer.write_lines(lines)
+
+    assert Path(f"{output}.tbi").exists()
+    assert len(query_pileup_records(str(output), ["contig_2"], None)) == len(lines)
+
+    # The file is finished before an exception raised in the block propagates.
+    failed_output = tmp_path / "failed.bed.gz"
+    with pytest.raises(ValueError, match="boom"):
+        with BgzfWriter(str(failed_output), force=True) as writer:
+            writer.write_lines(lines)
+            raise ValueError("boom")
+
+    assert Path(f"{failed_output}.tbi").exists()
+    assert len(query_pileup_records(str(failed_output), ["contig_2"], None)) == len(lines)
+
+
+def test_bgzf_writer_reopen_appends_lines(data_dir, tmp_path):
+    """BgzfWriter.reopen appends to a finished file and indexes all lines"""
+    pileup_input = os.path.join(data_dir, "geobacillus.bed.gz")
+    contigs = ["contig_2", "contig_3"]
+    lines = {
+        c: query_pileup_records(pileup_input, [c], None)
+        .write_csv(separator="\t", include_header=False)
+        .strip()
+        .split("\n")
+        for c in contigs
+    }
+
+    output = tmp_path / "appended.bed.gz"
+    with BgzfWriter(str(output), force=True) as writer:
+        writer.write_lines(lines["contig_2"])
+
+    with BgzfWriter.reopen(str(output)) as writer:
+        writer.write_lines(lines["contig_3"])
+
+    for c in contigs:
+        assert len(query_pileup_records(str(output), [c], None)) == len(lines[c])
+
+    with pytest.raises(IOError):
+        BgzfWriter.reopen(str(tmp_path / "missing.bed.gz"))
+
+
+def test_bgzf_writer_compression_level(data_dir, tmp_path):
+    """BgzfWriter compresses with the given compression level"""
+    pileup_input = os.path.join(data_dir, "geobacillus.bed.gz")
+    lines = (
+        query_pileup_records(pileup_input, ["contig_2"], None)
+        .write_csv(separator="\t", include_header=False)
+        .strip()
+        .split("\n")
+    )
+
+    sizes = {}
+    for level in [0, 9]:
+        output = tmp_path / f"level_{level}.bed.gz"
+        with BgzfWriter(str(output), force=True, compression_level=level) as writer:
+            writer.write_lines(lines)
+        assert len(query_pileup_records(str(output), ["contig_2"], None)) == len(lines)
+        sizes[level] = output.stat().st_size
+
+    assert sizes[9] < sizes[0]
+
+    with pytest.raises(RuntimeError):
+        BgzfWriter(str(tmp_path / "invalid.bed.gz"), force=True, compression_level=10)
@@ -153,34 +153,27 @@ impl IupacBase {
         }
     }
 
-    /// Returns the complement of the given IUPAC base.
+    /// Returns the complement of the base.
     ///
     /// Complements are defined as follows:
     /// - A ↔ T
     /// - G ↔ C
     /// - R ↔ Y
-    /// - S ↔ S
-    /// - W ↔ W
     /// - K ↔ M
     /// - B ↔ V
     /// - D ↔ H
-    /// - N ↔ N
-    ///
-    /// # Arguments
-    /// - `base`: A reference to an `IupacBase`.
-    ///
-    /// # Returns
-    /// The complement of the input base as an `IupacBase`.
+    /// - S, W and N are their own complement
     ///
     /// # Examples
     /// ```
     /// use epimetheus_methylome::IupacBase;
     ///
-    /// let complement = IupacBase::to_complement_base(&IupacBase::A);
-    /// assert_eq!(complement, IupacBase::T);
+    /// assert_eq!(IupacBase::A.complement(), IupacBase::T);
+    /// assert_eq!(IupacBase::R.complement(), IupacBase::Y);
+    /// assert_eq!(IupacBase::S.complement(), IupacBase::S);
     /// ```
-    pub fn to_complement_base(base: &IupacBase) -> Self {
-        match base {
+    pub fn complement(&self) -> IupacBase {
+        match self {
             IupacBase::A => IupacBase::T,
             IupacBase::T => IupacBase::A,
             IupacBase::G => IupacBase::C,
@@ -192,13 +185,26 @@ impl IupacBase {
             IupacBase::K => IupacBase::M,
             IupacBase::M => IupacBase::K,
             IupacBase::B => IupacBase::V,
+            IupacBase::V => IupacBase::B,
             IupacBase::D => IupacBase::H,
             IupacBase::H => IupacBase::D,
-            IupacBase::V => IupacBase::B,
             IupacBase::N => IupacBase::N,
         }
     }
 
+    /// Returns the complement of the given IUPAC base. See [`IupacBase::complement`].
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::IupacBase;
+    ///
+    /// let complement = IupacBase::to_complement_base(&IupacBase::A);
+    /// assert_eq!(complement, IupacBase::T);
+    /// ```
+    pub fn to_complement_base(base: &IupacBase) -> Self {
+        base.complement()
+    }
+
     /// Converts the `IupacBase` into its corresponding regular expression representation.
     ///
     /// This is useful for pattern matching ambiguous nucleotide sequences.
@@ -355,6 +361,71 @@ impl IupacBase {
             &ModType::SixMA => IupacBase::A,
             &ModType::FiveMC => IupacBase::C,
             &ModType::FourMC => IupacBase::C,
+            &ModType::FiveHMC => IupacBase::C,
+            &ModType::CombinedC => IupacBase::C,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_BASES: [IupacBase; 15] = [
+        IupacBase::A,
+        IupacBase::C,
+        IupacBase::G,
+        IupacBase::T,
+        IupacBase::R,
+        IupacBase::Y,
+        IupacBase::S,
+        IupacBase::W,
+        IupacBase::K,
+        IupacBase::M,
+        IupacBase::B,
+        IupacBase::D,
+        IupacBase::H,
+        IupacBase::V,
+        IupacBase::N,
+    ];
+
+    #[test]
+    fn test_complement_all_codes() {
+        let complements: Vec<IupacBase> = ALL_BASES.iter().map(IupacBase::complement).collect();
+        assert_eq!(
+            complements,
+            vec![
+                IupacBase::T,
+                IupacBase::G,
+                IupacBase::C,
+                IupacBase::A,
+                IupacBase::Y,
+                IupacBase::R,
+                IupacBase::S,
+                IupacBase::W,
+                IupacBase::M,
+                IupacBase::K,
+                IupacBase::V,
+                IupacBase::H,
+                IupacBase::D,
+                IupacBase::B,
+                IupacBase::N,
+            ]
+        );
+
+        for base in ALL_BASES {
+            assert_eq!(base.complement().complement(), base);
+
+            // The complement of a degenerate base covers the complements of its bases.
+            let complemented_nucleotides = base
+                .to_possible_nucleotides()
+                .iter()
+                .map(IupacBase::complement)
+                .collect();
+            assert_eq!(
+                IupacBase::from_nucleotides(&complemented_nucleotides).unwrap(),
+                base.complement()
+            );
         }
     }
 }
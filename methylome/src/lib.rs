@@ -8,12 +8,22 @@ pub mod sequence;
 pub mod strand;
 
 pub use iupac::IupacBase;
-pub use modtype::ModType;
-pub use motif::Motif;
-pub use strand::Strand;
+pub use modtype::{ModBaseMap, ModType};
+pub use motif::{Motif, MotifType, VariableGap};
+pub use strand::{Strand, StrandFormat};
 
 use crate::sequence::Sequence;
 
+/// Returns the position of the modified base of every occurrence of `motif` on the plus
+/// strand of `sequence`, in increasing order.
+///
+/// A motif with a variable gap (see [`VariableGap`]) matches with any gap length in its
+/// range, and each matching length is an occurrence of its own. Its `mod_position`
+/// counts the gap at its minimum length, so a modified base after the gap is reported at
+/// `start + mod_position + extension`, where `extension` is the number of gap bases
+/// beyond the minimum in that occurrence. Occurrences sharing the modified base, as
+/// those with a modified base before the gap and different gap lengths do, report it
+/// once.
 pub fn find_motif_indices_in_sequence(sequence: &Sequence, motif: &Motif) -> Vec<usize> {
     // let regex_str = motif.to_regex();
     // let re = Regex::new(&regex_str).expect("Expected regex pattern");
@@ -23,31 +33,79 @@ pub fn find_motif_indices_in_sequence(sequence: &Sequence, motif: &Motif) -> Vec
     //     .map(|m| m.start() as usize + motif.mod_position as usize)
     //     .collect();
 
-    let motif_bases = motif.sequence.clone();
-    let motif_len = motif_bases.len();
-    let mut indices = Vec::new();
+    let mod_position = motif.mod_position as usize;
+    let Some(gap) = motif.variable_gap() else {
+        return find_motif_anchorings(sequence, motif)
+            .into_iter()
+            .map(|(start, _)| start + mod_position)
+            .collect();
+    };
+
+    let after_gap = mod_position >= gap.end();
+    let mut indices: Vec<usize> = find_motif_anchorings(sequence, motif)
+        .into_iter()
+        .map(|(start, extension)| start + mod_position + if after_gap { extension } else { 0 })
+        .collect();
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+}
+
+/// Returns the start and gap extension of every window of `sequence` matching `motif`,
+/// sorted by start and extension. The extension is the number of bases a variable gap
+/// is longer than its minimum, and always 0 for motifs without one.
+fn find_motif_anchorings(sequence: &Sequence, motif: &Motif) -> Vec<(usize, usize)> {
+    let motif_len = motif.sequence.len();
+    let mut anchorings = Vec::new();
 
     if sequence.len() < motif_len {
-        return indices;
+        return anchorings;
     }
 
+    // The N gap of a bipartite motif matches any base, so only the two anchored halves
+    // are compared.
+    let anchored_bases: Vec<(usize, IupacBase)> = motif
+        .sequence
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, base)| motif.motif_type() != MotifType::Bipartite || *base != IupacBase::N)
+        .collect();
+    // Bases after a variable gap move with its length.
+    let (gap_end, max_extension) = motif
+        .variable_gap()
+        .map_or((motif_len, 0), |gap| (gap.end(), gap.extension()));
+
     for i in 0..=(sequence.len() - motif_len) {
-        let mut matches = true;
+        for extension in 0..=max_extension.min(sequence.len() - motif_len - i) {
+            let matches = anchored_bases.iter().all(|&(j, motif_base)| {
+                let offset = if j >= gap_end { extension } else { 0 };
+                (sequence[i + j + offset].mask() & motif_base.mask()) != 0
+            });
 
-        for (j, &motif_base) in motif_bases.iter().enumerate() {
-            let seq_base = sequence[i + j];
-            if (seq_base.mask() & motif_base.mask()) == 0 {
-                matches = false;
-                break;
+            if matches {
+                anchorings.push((i, extension));
             }
         }
-
-        if matches {
-            indices.push(i + motif.mod_position as usize);
-        }
     }
 
-    indices
+    anchorings
+}
+
+/// Counts the occurrences of `motif` on both strands of `sequence`.
+///
+/// An occurrence is a window matching the motif on the plus strand or its reverse
+/// complement. A window matching on both strands, as every occurrence of a palindromic
+/// motif like GATC does, is counted once. With a variable gap, each matching gap length
+/// is a window of its own.
+pub fn count_motif_occurrences(sequence: &Sequence, motif: &Motif) -> usize {
+    let mut windows: Vec<(usize, usize)> = find_motif_anchorings(sequence, motif)
+        .into_iter()
+        .chain(find_motif_anchorings(sequence, &motif.reverse_complement()))
+        .collect();
+    windows.sort_unstable();
+    windows.dedup();
+    windows.len()
 }
 
 #[cfg(test)]
@@ -135,4 +193,67 @@ mod tests {
         let indices = find_motif_indices_in_sequence(read.get_sequence(), &motif);
         assert_eq!(indices, vec![6, 11]);
     }
+
+    #[test]
+    fn test_count_motif_occurrences() {
+        let sequence = Sequence::from_str("GATCAAGATCTTGATCCCGGAAA").unwrap();
+
+        // Palindromic: each GATC matches both strands but is counted once.
+        let gatc = Motif::new("GATC", "a", 1).unwrap();
+        assert_eq!(count_motif_occurrences(&sequence, &gatc), 3);
+
+        // Not palindromic: TCC at 14 on the plus strand and GGA (TCC on the minus) at 18.
+        let tcc = Motif::new("TCC", "m", 1).unwrap();
+        assert_eq!(count_motif_occurrences(&sequence, &tcc), 2);
+    }
+
+    #[test]
+    fn test_find_bipartite_motif_indices() {
+        let motif = Motif::new("CAAYNNNNCCT", "a", 2).unwrap();
+        // Any bases fill the gap; the second window has a G in the anchored half.
+        let sequence = Sequence::from_str("CAACGTACCCTCAATAAAAGCT").unwrap();
+        assert_eq!(find_motif_indices_in_sequence(&sequence, &motif), vec![2]);
+
+        // CAACGTACCCT on the minus strand.
+        let revcomp = Sequence::from_str("AGGGTACGTTG").unwrap();
+        assert_eq!(
+            find_motif_indices_in_sequence(&revcomp, &motif.reverse_complement()),
+            vec![8]
+        );
+    }
+
+    #[test]
+    fn test_find_variable_gap_motif_indices() {
+        // A one base gap at 3 and a three base gap at 12.
+        let sequence = Sequence::from_str("GACAGTCTTGACAAAGTCTT").unwrap();
+
+        // The C after the gap moves with the gap length.
+        let after_gap = Motif::new("GACN(1-3)GTC", "m", 6).unwrap();
+        assert_eq!(
+            find_motif_indices_in_sequence(&sequence, &after_gap),
+            vec![6, 17]
+        );
+        let before_gap = Motif::new("GACN(1-3)GTC", "a", 1).unwrap();
+        assert_eq!(
+            find_motif_indices_in_sequence(&sequence, &before_gap),
+            vec![1, 10]
+        );
+        // A fixed gap keeps matching its length only.
+        let fixed = Motif::new("GACN(3)GTC", "a", 1).unwrap();
+        assert_eq!(find_motif_indices_in_sequence(&sequence, &fixed), vec![10]);
+
+        // Two gap lengths match from the same start.
+        let sequence = Sequence::from_str("GACAGTCGTC").unwrap();
+        let after_gap = Motif::new("GACN(1-4)GTC", "m", 6).unwrap();
+        assert_eq!(
+            find_motif_indices_in_sequence(&sequence, &after_gap),
+            vec![6, 9]
+        );
+        let before_gap = Motif::new("GACN(1-4)GTC", "a", 1).unwrap();
+        assert_eq!(
+            find_motif_indices_in_sequence(&sequence, &before_gap),
+            vec![1]
+        );
+        assert_eq!(count_motif_occurrences(&sequence, &before_gap), 2);
+    }
 }
@@ -1,5 +1,7 @@
-use anyhow::{Result, bail};
-use std::{fmt, str::FromStr};
+use anyhow::{Context, Result, bail};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::IupacBase;
 
 /// Represents a DNA base modification type.
 ///
@@ -10,6 +12,8 @@ use std::{fmt, str::FromStr};
 /// - `SixMA`: N6-methyladenine (6mA), represented by the pileup code `a`.
 /// - `FiveMC`: 5-methylcytosine (5mC), represented by the pileup code `m`.
 /// - `FourMC`: 4-methylcytosine (4mC), represented by the pileup code `21839`.
+/// - `FiveHMC`: 5-hydroxymethylcytosine (5hmC), represented by the pileup code `h`.
+/// - `CombinedC`: 5mC and 5hmC pooled into one signal, represented by the pileup code `C`.
 ///
 /// # Examples
 /// ```
@@ -23,9 +27,36 @@ pub enum ModType {
     SixMA,
     FiveMC,
     FourMC,
+    FiveHMC,
+    CombinedC,
 }
 
 impl ModType {
+    /// All supported modification types.
+    pub const ALL: [ModType; 5] = [
+        ModType::SixMA,
+        ModType::FiveMC,
+        ModType::FourMC,
+        ModType::FiveHMC,
+        ModType::CombinedC,
+    ];
+
+    /// Returns the pileup codes of all supported modification types, comma separated.
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::ModType;
+    ///
+    /// assert_eq!(ModType::supported_pileup_codes(), "a, m, 21839, h, C");
+    /// ```
+    pub fn supported_pileup_codes() -> String {
+        Self::ALL
+            .iter()
+            .map(ModType::to_pileup_code)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     /// Returns the pileup code corresponding to the modification type.
     ///
     /// Pileup codes are compact representations of modification types used
@@ -33,6 +64,8 @@ impl ModType {
     /// - `SixMA` (6mA): `"a"`
     /// - `FiveMC` (5mC): `"m"`
     /// - `FourMC` (4mC): `"21839"`
+    /// - `FiveHMC` (5hmC): `"h"`
+    /// - `CombinedC` (5mC + 5hmC): `"C"`
     ///
     /// # Examples
     /// ```
@@ -46,6 +79,8 @@ impl ModType {
             ModType::SixMA => "a",
             ModType::FiveMC => "m",
             ModType::FourMC => "21839",
+            ModType::FiveHMC => "h",
+            ModType::CombinedC => "C",
         }
     }
 
@@ -56,6 +91,8 @@ impl ModType {
     /// - `A+a` (6mA): `"a"`
     /// - `C+m` (5mC): `"m"`
     /// - `C+21839` (4mC): `"21839"`
+    /// - `C+h` (5hmC): `"h"`
+    /// - `C+C` (any cytosine modification): `"C"`
     ///
     /// # Examples
     /// ```
@@ -69,6 +106,8 @@ impl ModType {
             ('A', "a") => Some(ModType::SixMA),
             ('C', "m") => Some(ModType::FiveMC),
             ('C', "21839") => Some(ModType::FourMC),
+            ('C', "h") => Some(ModType::FiveHMC),
+            ('C', "C") => Some(ModType::CombinedC),
             _ => None,
         }
     }
@@ -97,6 +136,8 @@ impl fmt::Display for ModType {
             ModType::SixMA => write!(f, "6mA (a)"),
             ModType::FiveMC => write!(f, "5mC (m)"),
             ModType::FourMC => write!(f, "4mC (21839)"),
+            ModType::FiveHMC => write!(f, "5hmC (h)"),
+            ModType::CombinedC => write!(f, "5mC+5hmC (C)"),
         }
     }
 }
@@ -107,13 +148,15 @@ impl fmt::Display for ModType {
 /// - `"a"` for `SixMA` (6mA)
 /// - `"m"` for `FiveMC` (5mC)
 /// - `"21839"` for `FourMC` (4mC)
+/// - `"h"` for `FiveHMC` (5hmC)
+/// - `"C"` for `CombinedC` (5mC + 5hmC)
 ///
 /// # Arguments
 /// - `mod_type`: A string slice representing the modification type.
 ///
 /// # Returns
 /// - `Ok(ModType)` if the string matches a supported modification type.
-/// - `Err` if the string does not match any supported modification type.
+/// - `Err` listing the supported pileup codes if the string does not match any of them.
 ///
 /// # Examples
 /// ```
@@ -133,7 +176,147 @@ impl FromStr for ModType {
             "a" => Ok(ModType::SixMA),
             "m" => Ok(ModType::FiveMC),
             "21839" => Ok(ModType::FourMC),
-            _ => bail!("Unsupported mod type: {}", s),
+            "h" => Ok(ModType::FiveHMC),
+            "C" => Ok(ModType::CombinedC),
+            _ => bail!(
+                "Unsupported mod type: {}. Supported mod types: {}",
+                s,
+                Self::supported_pileup_codes()
+            ),
         }
     }
 }
+
+/// Maps pileup mod codes to the base they modify.
+///
+/// Codes without an entry use the default of [`IupacBase::from_mod_type`]: A for
+/// `a` and C for `m`, `h`, `21839` and `C`. The mapping is parsed from comma
+/// separated `<code>:<base>` pairs, where the base must be one of A, C, G or T.
+///
+/// # Examples
+/// ```
+/// use epimetheus_methylome::{IupacBase, ModBaseMap, ModType};
+///
+/// let map = "a:A,m:C,g:G".parse::<ModBaseMap>().unwrap();
+/// assert_eq!(map.base_for(&ModType::SixMA), IupacBase::A);
+/// assert_eq!(map.base_for(&ModType::FiveHMC), IupacBase::C);
+/// assert_eq!(map.unsupported_codes(), vec!["g"]);
+///
+/// assert!("a:N".parse::<ModBaseMap>().is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModBaseMap {
+    bases: BTreeMap<String, IupacBase>,
+}
+
+impl ModBaseMap {
+    /// Returns the base the mod type is expected on.
+    pub fn base_for(&self, mod_type: &ModType) -> IupacBase {
+        self.bases
+            .get(mod_type.to_pileup_code())
+            .copied()
+            .unwrap_or_else(|| IupacBase::from_mod_type(mod_type))
+    }
+
+    /// Codes in the mapping that are not a supported [`ModType`]. No motif can use
+    /// them, so their entries have no effect.
+    pub fn unsupported_codes(&self) -> Vec<&str> {
+        self.bases
+            .keys()
+            .filter(|code| ModType::from_str(code).is_err())
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+impl FromStr for ModBaseMap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bases = BTreeMap::new();
+
+        for entry in s.split(',').map(str::trim) {
+            let Some((code, base)) = entry.split_once(':') else {
+                bail!("Invalid mod base mapping '{}'. Expected '<code>:<base>'", entry);
+            };
+            let (code, base) = (code.trim(), base.trim());
+            if code.is_empty() {
+                bail!("Missing mod code in mod base mapping '{}'", entry);
+            }
+
+            let mut chars = base.chars();
+            let base = match (chars.next(), chars.next()) {
+                (Some(c), None) => IupacBase::parse_char(c)
+                    .with_context(|| format!("Invalid base in mod base mapping '{}'", entry))?,
+                _ => bail!("Expected a single base in mod base mapping '{}'", entry),
+            };
+            if !matches!(base, IupacBase::A | IupacBase::C | IupacBase::G | IupacBase::T) {
+                bail!(
+                    "Base '{}' in mod base mapping '{}' is ambiguous. Use A, C, G or T.",
+                    base,
+                    entry
+                );
+            }
+
+            if let Some(existing) = bases.insert(code.to_string(), base) {
+                if existing != base {
+                    bail!(
+                        "Mod code '{}' is mapped to both '{}' and '{}'",
+                        code,
+                        existing,
+                        base
+                    );
+                }
+            }
+        }
+
+        Ok(Self { bases })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pileup_code_round_trip() {
+        for mod_type in ModType::ALL {
+            assert_eq!(ModType::from_str(mod_type.to_pileup_code()).unwrap(), mod_type);
+        }
+        assert_eq!(ModType::from_str("h").unwrap(), ModType::FiveHMC);
+    }
+
+    #[test]
+    fn test_unsupported_mod_type_lists_supported_codes() {
+        let err = ModType::from_str("x").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported mod type: x. Supported mod types: a, m, 21839, h, C"
+        );
+    }
+
+    #[test]
+    fn test_mod_base_map_defaults() {
+        let map = ModBaseMap::default();
+        assert_eq!(map.base_for(&ModType::SixMA), IupacBase::A);
+        assert_eq!(map.base_for(&ModType::FiveMC), IupacBase::C);
+        assert_eq!(map.base_for(&ModType::FourMC), IupacBase::C);
+        assert_eq!(map.base_for(&ModType::FiveHMC), IupacBase::C);
+        assert_eq!(map.base_for(&ModType::CombinedC), IupacBase::C);
+    }
+
+    #[test]
+    fn test_mod_base_map_from_str() {
+        let map = ModBaseMap::from_str("a:A, m:T,a:A").unwrap();
+        assert_eq!(map.base_for(&ModType::FiveMC), IupacBase::T);
+        assert_eq!(map.base_for(&ModType::FiveHMC), IupacBase::C);
+        assert!(map.unsupported_codes().is_empty());
+
+        assert!(ModBaseMap::from_str("a:A,a:C").is_err());
+        assert!(ModBaseMap::from_str("a").is_err());
+        assert!(ModBaseMap::from_str(":A").is_err());
+        assert!(ModBaseMap::from_str("a:AT").is_err());
+        assert!(ModBaseMap::from_str("a:R").is_err());
+        assert!(ModBaseMap::from_str("a:Z").is_err());
+    }
+}
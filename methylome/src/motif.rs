@@ -1,9 +1,137 @@
-use crate::{IupacBase, ModType, sequence::Sequence};
-use anyhow::{Result, bail};
-use std::str::FromStr;
+use crate::{IupacBase, ModBaseMap, ModType, sequence::Sequence};
+use anyhow::{Result, anyhow, bail};
+use std::{ops::Range, str::FromStr};
 
 pub type Position = u8;
 
+/// Shortest run of `N` that separates the two halves of a bipartite motif.
+pub const BIPARTITE_MIN_GAP: usize = 3;
+
+/// The structure of a motif sequence. See [`Motif::motif_type`].
+///
+/// - `ShortPalindrome`: Equals its reverse complement, e.g. `GATC` or `CCNGG`.
+/// - `ShortNonPalindrome`: Differs from its reverse complement, e.g. `TCCCG`.
+/// - `Bipartite`: Two anchored halves separated by a run of at least
+///   [`BIPARTITE_MIN_GAP`] `N`, e.g. `CAAYNNNNCCT`, or by a [`VariableGap`]. Runs of
+///   `N` at the ends do not make a motif bipartite.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MotifType {
+    ShortPalindrome,
+    ShortNonPalindrome,
+    Bipartite,
+}
+
+impl MotifType {
+    fn classify(sequence: &Sequence, variable_gap: Option<&VariableGap>) -> Self {
+        if variable_gap.is_some() {
+            return Self::Bipartite;
+        }
+
+        let first = sequence.iter().position(|base| *base != IupacBase::N);
+        let last = sequence.iter().rposition(|base| *base != IupacBase::N);
+        if let (Some(first), Some(last)) = (first, last) {
+            let has_gap = sequence[first..=last]
+                .split(|base| *base != IupacBase::N)
+                .any(|run| run.len() >= BIPARTITE_MIN_GAP);
+            if has_gap {
+                return Self::Bipartite;
+            }
+        }
+
+        if *sequence == sequence.reverse_complement() {
+            Self::ShortPalindrome
+        } else {
+            Self::ShortNonPalindrome
+        }
+    }
+}
+
+/// A spacer of `N` whose length varies between occurrences, written `N(min-max)` in a
+/// motif string, e.g. `GACN(5-7)GTC`.
+///
+/// The motif sequence holds the gap at its `min` length, starting at `start`. Methods
+/// working on the sequence alone, like [`Motif::degeneracy`], see the gap at that length.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VariableGap {
+    pub start: usize,
+    pub min: usize,
+    pub max: usize,
+}
+
+impl VariableGap {
+    /// Position in the motif sequence right after the gap at its minimum length.
+    pub fn end(&self) -> usize {
+        self.start + self.min
+    }
+
+    /// Number of bases the gap can grow beyond its minimum length.
+    pub fn extension(&self) -> usize {
+        self.max - self.min
+    }
+}
+
+/// Expands the gap lengths of a motif string. `N(k)` becomes `k` `N` and `N(min-max)`
+/// becomes `min` `N`, with the gap returned separately.
+fn expand_gaps(sequence_str: &str) -> Result<(String, Option<VariableGap>)> {
+    let mut expanded = String::with_capacity(sequence_str.len());
+    let mut variable_gap = None;
+    let mut rest = sequence_str;
+
+    while let Some(open) = rest.find('(') {
+        expanded.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find(')').map(|close| open + close) else {
+            bail!("Unclosed '(' in motif sequence: {}", sequence_str);
+        };
+        if expanded.pop() != Some('N') {
+            bail!(
+                "A gap length must follow an N, as in GACN(6)GTC: {}",
+                sequence_str
+            );
+        }
+
+        let spec = &rest[open + 1..close];
+        let parse = |length: &str| {
+            length.trim().parse::<usize>().map_err(|_| {
+                anyhow!(
+                    "Invalid gap length '{}' in motif sequence: {}",
+                    spec,
+                    sequence_str
+                )
+            })
+        };
+        let (min, max) = match spec.split_once('-') {
+            Some((min, max)) => (parse(min)?, parse(max)?),
+            None => (parse(spec)?, parse(spec)?),
+        };
+        if min == 0 || min > max {
+            bail!(
+                "Invalid gap length '{}' in motif sequence {}: gaps are at least 1 long and the minimum can not exceed the maximum",
+                spec,
+                sequence_str
+            );
+        }
+
+        if min < max {
+            if variable_gap.is_some() {
+                bail!(
+                    "Only one variable gap is supported per motif: {}",
+                    sequence_str
+                );
+            }
+            variable_gap = Some(VariableGap {
+                start: expanded.len(),
+                min,
+                max,
+            });
+        }
+        expanded.extend(std::iter::repeat('N').take(min));
+        rest = &rest[close + 1..];
+    }
+    expanded.push_str(rest);
+
+    Ok((expanded, variable_gap))
+}
+
 /// Represents a biological motif, which includes a nucleotide sequence,
 /// its modification type, and the position of the modification.
 ///
@@ -11,11 +139,15 @@ pub type Position = u8;
 /// - `sequence`: A vector of IUPAC bases representing the motif sequence.
 /// - `mod_type`: The type of modification (e.g., 6mA, 5mC).
 /// - `mod_position`: The position of the modification within the sequence (0-indexed).
+///
+/// The [`MotifType`] is derived from the sequence when the motif is constructed.
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Motif {
     pub sequence: Sequence,
     pub mod_type: ModType,
     pub mod_position: Position,
+    motif_type: MotifType,
+    variable_gap: Option<VariableGap>,
 }
 
 impl Motif {
@@ -23,12 +155,15 @@ impl Motif {
     ///
     /// # Arguments
     /// - `sequence`: A string representing the nucleotide sequence (using IUPAC codes).
+    ///   `N(k)` is a run of `k` `N`, and one `N(min-max)` per motif is a [`VariableGap`]
+    ///   matching any length from `min` to `max`.
     /// - `mod_type`: A string representing the modification type (e.g., "a" (6mA), "m" (5mC), "21839" (4mC)0).
-    /// - `mod_position`: The 0-indexed position of the modification in the sequence.
+    /// - `mod_position`: The 0-indexed position of the modification in the sequence. A
+    ///   variable gap counts with its minimum length.
     ///
     /// # Errors
     /// Returns an error if:
-    /// - The `sequence` contains invalid IUPAC codes.
+    /// - The `sequence` contains invalid IUPAC codes or an invalid gap length.
     /// - The `mod_position` is out of bounds for the sequence.
     /// - The `mod_type` does not match the base at `mod_position` (e.g., 6mA must modify an 'A').
     ///
@@ -38,13 +173,37 @@ impl Motif {
     ///
     /// let motif = Motif::new("GATC", "a", 1).unwrap();
     /// assert_eq!(motif.mod_type, ModType::SixMA);
+    ///
+    /// let bipartite = Motif::new("GACN(5-7)GTC", "a", 1).unwrap();
+    /// assert_eq!(bipartite.sequence_to_string(), "GACN(5-7)GTC");
     /// ```
     pub fn new(sequence_str: &str, mod_type: &str, mod_position: u8) -> Result<Self> {
+        Self::new_with_mod_base(sequence_str, mod_type, mod_position, &ModBaseMap::default())
+    }
+
+    /// Constructs a new `Motif` like [`Motif::new`], but checks the base at
+    /// `mod_position` against `mod_base_map` instead of the default bases.
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::{ModBaseMap, Motif};
+    ///
+    /// let map = "m:G".parse::<ModBaseMap>().unwrap();
+    /// assert!(Motif::new_with_mod_base("GATC", "m", 0, &map).is_ok());
+    /// assert!(Motif::new("GATC", "m", 0).is_err());
+    /// ```
+    pub fn new_with_mod_base(
+        sequence_str: &str,
+        mod_type: &str,
+        mod_position: u8,
+        mod_base_map: &ModBaseMap,
+    ) -> Result<Self> {
         let mod_type = ModType::from_str(mod_type)?;
 
-        let parsed_sequence = Sequence::from_str(sequence_str)?;
+        let (expanded, variable_gap) = expand_gaps(sequence_str)?;
+        let parsed_sequence = Sequence::from_str(&expanded)?;
 
-        if mod_position as usize > parsed_sequence.len() - 1 {
+        if mod_position as usize >= parsed_sequence.len() {
             bail!(
                 "mod_position {} is out of bounds for sequence of length {}. Note mod_position is 0-indexed.",
                 mod_position,
@@ -53,26 +212,13 @@ impl Motif {
         }
 
         let base_at_position = &parsed_sequence[mod_position as usize];
-        match mod_type {
-            ModType::SixMA => {
-                if *base_at_position != IupacBase::A {
-                    bail!(
-                        "mod_position {} points to base '{}' which is invalid for 6mA.",
-                        mod_position,
-                        base_at_position
-                    );
-                }
-            }
-            ModType::FiveMC | ModType::FourMC => {
-                if *base_at_position != IupacBase::C {
-                    bail!(
-                        "mod_position {} points to base '{}' which is invalid for {} modification type.",
-                        mod_position,
-                        base_at_position,
-                        mod_type
-                    );
-                }
-            }
+        if *base_at_position != mod_base_map.base_for(&mod_type) {
+            bail!(
+                "mod_position {} points to base '{}' which is invalid for {} modification type.",
+                mod_position,
+                base_at_position,
+                mod_type
+            );
         }
 
         if parsed_sequence.first() == Some(&IupacBase::N)
@@ -85,16 +231,133 @@ impl Motif {
         }
 
         Ok(Self {
+            motif_type: MotifType::classify(&parsed_sequence, variable_gap.as_ref()),
             sequence: parsed_sequence,
             mod_type,
             mod_position,
+            variable_gap,
         })
     }
 
+    /// Returns whether the motif is a short palindrome, a short non-palindrome or
+    /// bipartite. See [`MotifType`].
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::{Motif, MotifType};
+    ///
+    /// assert_eq!(Motif::new("GATC", "a", 1).unwrap().motif_type(), MotifType::ShortPalindrome);
+    /// assert_eq!(
+    ///     Motif::new("CAAYNNNNCCT", "a", 2).unwrap().motif_type(),
+    ///     MotifType::Bipartite
+    /// );
+    /// ```
+    pub fn motif_type(&self) -> MotifType {
+        self.motif_type
+    }
+
+    /// Returns the `N(min-max)` gap of the motif, or `None` when all its gaps have a
+    /// fixed length.
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::{Motif, VariableGap};
+    ///
+    /// let motif = Motif::new("GACN(5-7)GTC", "a", 1).unwrap();
+    /// assert_eq!(motif.variable_gap(), Some(VariableGap { start: 3, min: 5, max: 7 }));
+    /// assert_eq!(Motif::new("GACN(6)GTC", "a", 1).unwrap().variable_gap(), None);
+    /// ```
+    pub fn variable_gap(&self) -> Option<VariableGap> {
+        self.variable_gap
+    }
+
+    /// Returns the positions of the `N` run separating the two halves of a bipartite
+    /// motif, or `None` for other motifs. A variable gap is returned at its minimum
+    /// length. Otherwise, with several runs of at least [`BIPARTITE_MIN_GAP`] `N`, the
+    /// first is returned.
+    ///
+    /// Spacer positions only set the distance between the halves. Unlike a degenerate
+    /// `N` inside a half, as in `CCNGG`, they are not compared by
+    /// [`Motif::hamming_distance`].
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::Motif;
+    ///
+    /// assert_eq!(Motif::new("CAAYNNNNCCT", "a", 2).unwrap().spacer_range(), Some(4..8));
+    /// assert_eq!(Motif::new("CCNGG", "m", 1).unwrap().spacer_range(), None);
+    /// ```
+    pub fn spacer_range(&self) -> Option<Range<usize>> {
+        if self.motif_type != MotifType::Bipartite {
+            return None;
+        }
+        if let Some(gap) = self.variable_gap {
+            return Some(gap.start..gap.end());
+        }
+
+        // Runs of N at the ends are not spacers.
+        let first = self
+            .sequence
+            .iter()
+            .position(|base| *base != IupacBase::N)?;
+        let mut run_start = None;
+        for (i, base) in self.sequence.iter().enumerate().skip(first) {
+            if *base == IupacBase::N {
+                run_start.get_or_insert(i);
+            } else if let Some(start) = run_start.take() {
+                if i - start >= BIPARTITE_MIN_GAP {
+                    return Some(start..i);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the number of positions where the sequences of two motifs of the same
+    /// length differ, or `None` when the lengths or variable gaps differ. The mod type
+    /// and position are not compared.
+    ///
+    /// Positions in the spacer of either motif (see [`Motif::spacer_range`]) are
+    /// skipped, so bipartite motifs with the same halves have distance 0 whatever the
+    /// bases facing their spacers. A degenerate `N` outside a spacer is compared like
+    /// any other base.
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::Motif;
+    ///
+    /// let bipartite = Motif::new("CAAYNNNNCCT", "a", 2).unwrap();
+    /// let other = Motif::new("CAACNNNNCCA", "a", 2).unwrap();
+    /// assert_eq!(bipartite.hamming_distance(&other), Some(2));
+    ///
+    /// let degenerate = Motif::new("CCNGG", "m", 1).unwrap();
+    /// let specific = Motif::new("CCWGG", "m", 1).unwrap();
+    /// assert_eq!(degenerate.hamming_distance(&specific), Some(1));
+    /// ```
+    pub fn hamming_distance(&self, other: &Motif) -> Option<usize> {
+        if self.sequence.len() != other.sequence.len() || self.variable_gap != other.variable_gap {
+            return None;
+        }
+
+        let in_spacer = |motif: &Motif, i: usize| {
+            motif
+                .spacer_range()
+                .is_some_and(|spacer| spacer.contains(&i))
+        };
+        let distance = self
+            .sequence
+            .iter()
+            .zip(other.sequence.iter())
+            .enumerate()
+            .filter(|(i, (a, b))| a != b && !in_spacer(self, *i) && !in_spacer(other, *i))
+            .count();
+        Some(distance)
+    }
+
     /// Returns the reverse complement of the motif.
     ///
     /// The reverse complement reverses the sequence and replaces each base
-    /// with its complement (e.g., A ↔ T, C ↔ G). The modification position
+    /// with its complement (see [`IupacBase::complement`]). The modification position
     /// is adjusted to reflect its position in the reverse-complemented sequence.
     ///
     /// # Examples
@@ -112,13 +375,56 @@ impl Motif {
             sequence: self.sequence.reverse_complement(),
             mod_type: self.mod_type.clone(),
             mod_position: self.sequence.len() as u8 - self.mod_position - 1,
+            motif_type: self.motif_type,
+            variable_gap: self.variable_gap.map(|gap| VariableGap {
+                start: self.sequence.len() - gap.end(),
+                ..gap
+            }),
         }
     }
 
+    /// Returns true when the motif sequence equals its reverse complement, so an
+    /// occurrence on the plus strand is also an occurrence on the minus strand.
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::Motif;
+    ///
+    /// assert!(Motif::new("RGATCY", "a", 2).unwrap().is_palindromic());
+    /// assert!(!Motif::new("TCCCG", "m", 1).unwrap().is_palindromic());
+    /// ```
+    pub fn is_palindromic(&self) -> bool {
+        let revcomp = self.reverse_complement();
+        self.sequence == revcomp.sequence && self.variable_gap == revcomp.variable_gap
+    }
+
+    /// Returns true when `other` is the reverse complement of this bipartite motif with
+    /// the modification on the other half of the site, e.g. `CAAYNNNNCCT_a_2` and
+    /// `AGGNNNNRTTG_a_0`. Together they describe both modified bases of a site.
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::Motif;
+    ///
+    /// let motif = Motif::new("CAAYNNNNCCT", "a", 2).unwrap();
+    /// assert!(motif.is_bipartite_partner(&Motif::new("AGGNNNNRTTG", "a", 0).unwrap()));
+    /// assert!(!motif.is_bipartite_partner(&motif.reverse_complement()));
+    /// ```
+    pub fn is_bipartite_partner(&self, other: &Motif) -> bool {
+        let revcomp = self.reverse_complement();
+        self.motif_type == MotifType::Bipartite
+            && !self.is_palindromic()
+            && other.mod_type == self.mod_type
+            && other.sequence == revcomp.sequence
+            && other.variable_gap == revcomp.variable_gap
+            && other.mod_position != revcomp.mod_position
+    }
+
     /// Converts the motif sequence into a regular expression string.
     ///
     /// Each base in the sequence is mapped to its corresponding regex
     /// pattern based on IUPAC codes. For example, `R` (purine) becomes `[AG]`.
+    /// A variable gap becomes a counted repetition.
     ///
     /// # Examples
     /// ```
@@ -127,14 +433,22 @@ impl Motif {
     /// let motif = Motif::new("RGATCY", "a", 2).unwrap();
     /// let regex = motif.to_regex();
     /// assert_eq!(regex, "[AG]GATC[CT]");
+    ///
+    /// let motif = Motif::new("GACN(5-7)GTC", "a", 1).unwrap();
+    /// assert_eq!(motif.to_regex(), "GAC.{5,7}GTC");
     /// ```
     pub fn to_regex(&self) -> String {
-        self.sequence.iter().map(IupacBase::to_regex).collect()
+        self.render(
+            |base| base.to_regex().to_string(),
+            |gap| format!("{}{{{},{}}}", IupacBase::N.to_regex(), gap.min, gap.max),
+        )
     }
 
     /// Converts the motif sequence into a plain string representation.
     ///
     /// This method maps each IUPAC base in the sequence to its corresponding character.
+    /// A variable gap is written back as `N(min-max)`, so the string parses to the same
+    /// motif.
     ///
     /// # Examples
     /// ```
@@ -145,7 +459,28 @@ impl Motif {
     /// assert_eq!(sequence, "GATC");
     /// ```
     pub fn sequence_to_string(&self) -> String {
-        self.sequence.iter().map(IupacBase::to_string).collect()
+        self.render(IupacBase::to_string, |gap| {
+            format!("N({}-{})", gap.min, gap.max)
+        })
+    }
+
+    /// Writes the sequence base by base, with the variable gap written by `gap`.
+    fn render(
+        &self,
+        base: impl Fn(&IupacBase) -> String,
+        gap: impl Fn(&VariableGap) -> String,
+    ) -> String {
+        let Some(variable_gap) = self.variable_gap else {
+            return self.sequence.iter().map(base).collect();
+        };
+
+        let mut rendered: String = self.sequence[..variable_gap.start]
+            .iter()
+            .map(&base)
+            .collect();
+        rendered.push_str(&gap(&variable_gap));
+        rendered.extend(self.sequence[variable_gap.end()..].iter().map(&base));
+        rendered
     }
 
     /// Converts a motif sequence into its possible raw DNA sequences.
@@ -183,6 +518,52 @@ impl Motif {
         sequences
     }
 
+    /// Number of DNA sequences the motif matches, i.e. the product of the number of
+    /// possible nucleotides of each base.
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::Motif;
+    ///
+    /// assert_eq!(Motif::new("GATC", "a", 1).unwrap().degeneracy(), 1);
+    /// assert_eq!(Motif::new("RGATCY", "a", 2).unwrap().degeneracy(), 4);
+    /// ```
+    pub fn degeneracy(&self) -> u64 {
+        self.sequence
+            .iter()
+            .map(|base| base.to_possible_nucleotides().len() as u64)
+            .product()
+    }
+
+    /// Expected GC fraction of the motif. Each base counts the fraction of its possible
+    /// nucleotides that are G or C, so `S` counts 1 and `N` counts 0.5.
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::Motif;
+    ///
+    /// assert_eq!(Motif::new("GATC", "a", 1).unwrap().gc_content(), 0.5);
+    /// assert_eq!(Motif::new("CCWGG", "m", 1).unwrap().gc_content(), 0.8);
+    /// ```
+    pub fn gc_content(&self) -> f64 {
+        if self.sequence.is_empty() {
+            return 0.0;
+        }
+        let gc: f64 = self
+            .sequence
+            .iter()
+            .map(|base| {
+                let nucleotides = base.to_possible_nucleotides();
+                let n_gc = nucleotides
+                    .iter()
+                    .filter(|nuc| matches!(nuc, IupacBase::G | IupacBase::C))
+                    .count();
+                n_gc as f64 / nucleotides.len() as f64
+            })
+            .sum();
+        gc / self.sequence.len() as f64
+    }
+
     /// Checks if current motif is the parent motif of another motif
     ///
     /// # Examples
@@ -216,6 +597,19 @@ impl Motif {
             return false;
         }
 
+        // The length of a variable gap differs between occurrences, so the parent may only
+        // span the gap of the child with the same gap.
+        let parent_span = mod_offset as usize..mod_offset as usize + self.sequence.len();
+        match (self.variable_gap, child.variable_gap) {
+            (None, None) => {}
+            (None, Some(gap)) if parent_span.end <= gap.start || parent_span.start >= gap.end() => {
+            }
+            (Some(parent_gap), Some(gap))
+                if parent_span.start + parent_gap.start == gap.start
+                    && (parent_gap.min, parent_gap.max) == (gap.min, gap.max) => {}
+            _ => return false,
+        }
+
         self.sequence
             .iter()
             .zip(child.sequence[(mod_offset as usize)..].iter())
@@ -237,6 +631,7 @@ impl Motif {
     pub fn extend_motif_with_n(&mut self, n: usize) -> &mut Self {
         self.sequence
             .extend(std::iter::repeat(IupacBase::N).take(n));
+        self.motif_type = MotifType::classify(&self.sequence, self.variable_gap.as_ref());
         self
     }
     /// Extend motif with N's
@@ -256,6 +651,10 @@ impl Motif {
 
         self.sequence.splice(0..0, ns.iter().cloned());
         self.mod_position = self.mod_position + n as u8;
+        if let Some(gap) = self.variable_gap.as_mut() {
+            gap.start += n;
+        }
+        self.motif_type = MotifType::classify(&self.sequence, self.variable_gap.as_ref());
         self
     }
 }
@@ -272,6 +671,26 @@ mod tests {
         assert_eq!(motif.mod_position, 1);
     }
 
+    #[test]
+    fn test_is_bipartite_partner() {
+        let motif = Motif::new("CAAYNNNNCCT", "a", 2).unwrap();
+        let partner = Motif::new("AGGNNNNRTTG", "a", 0).unwrap();
+        assert!(motif.is_bipartite_partner(&partner));
+        assert!(partner.is_bipartite_partner(&motif));
+        // The same modified base seen from the other strand.
+        assert!(!motif.is_bipartite_partner(&motif.reverse_complement()));
+
+        let gapped = Motif::new("GACN(5-7)TTC", "a", 1).unwrap();
+        assert!(gapped.is_bipartite_partner(&Motif::new("GAAN(5-7)GTC", "a", 1).unwrap()));
+        assert!(!gapped.is_bipartite_partner(&Motif::new("GAAN(5-6)GTC", "a", 1).unwrap()));
+
+        // Short motifs and palindromes have no partner.
+        let short = Motif::new("GAAG", "a", 1).unwrap();
+        assert!(!short.is_bipartite_partner(&Motif::new("CTTC", "a", 2).unwrap()));
+        let palindrome = Motif::new("GAANNNNNNTTC", "a", 1).unwrap();
+        assert!(!palindrome.is_bipartite_partner(&Motif::new("GAANNNNNNTTC", "a", 2).unwrap()));
+    }
+
     #[test]
     fn test_out_of_bounds() {
         let result = Motif::new("GATC", "m", 4);
@@ -282,11 +701,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_mod_position_boundary() {
+        assert_eq!(Motif::new("GATC", "m", 3).unwrap().mod_position, 3);
+        assert!(Motif::new("GATC", "m", 4).is_err());
+        assert!(Motif::new("GATC", "m", 9).is_err());
+        // An empty sequence has no valid mod position.
+        assert!(Motif::new("", "a", 0).is_err());
+    }
+
     #[test]
     fn test_unidentified_motif_type() {
         let result = Motif::new("GATC", "d", 1);
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err().to_string(), "Unsupported mod type: d");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Unsupported mod type: d. Supported mod types: a, m, 21839, h, C"
+        );
     }
 
     #[test]
@@ -379,4 +810,168 @@ mod tests {
         assert!(parent.is_child_motif(&child));
         assert!(!child.is_child_motif(&parent));
     }
+
+    #[test]
+    fn test_spacer_range() {
+        let spacer = |sequence: &str, mod_position: u8| {
+            Motif::new(sequence, "a", mod_position).unwrap().spacer_range()
+        };
+        assert_eq!(spacer("CAAYNNNNCCT", 2), Some(4..8));
+        assert_eq!(spacer("AGGNNNNRTTG", 0), Some(3..7));
+        assert_eq!(spacer("GANTC", 1), None);
+        assert_eq!(spacer("GATC", 1), None);
+
+        // Runs of N at the ends are not spacers.
+        let mut motif = Motif::new("GATC", "a", 1).unwrap();
+        motif.prepend_n(4).extend_motif_with_n(4);
+        assert_eq!(motif.spacer_range(), None);
+    }
+
+    #[test]
+    fn test_hamming_distance_ignores_spacers() {
+        let motif = |sequence: &str| Motif::new(sequence, "a", 2).unwrap();
+
+        // Only the halves count: the last base differs.
+        assert_eq!(
+            motif("CAAYNNNNCCT").hamming_distance(&motif("CAAYNNNNCCA")),
+            Some(1)
+        );
+        // A spacer facing specified bases of a motif of the same length is skipped.
+        assert_eq!(
+            motif("CAAYNNNNCCT").hamming_distance(&motif("CAAYGATCCCT")),
+            Some(0)
+        );
+        // A genuine N is compared.
+        assert_eq!(motif("GNATC").hamming_distance(&motif("GGATC")), Some(1));
+        assert_eq!(
+            motif("CAAYNNNNCCT").hamming_distance(&motif("CAAYNNNCCT")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_motif_type() {
+        let motif_type = |sequence: &str, mod_position: u8| {
+            Motif::new(sequence, "a", mod_position).unwrap().motif_type()
+        };
+
+        assert_eq!(motif_type("GATC", 1), MotifType::ShortPalindrome);
+        assert_eq!(motif_type("RGATCY", 2), MotifType::ShortPalindrome);
+        assert_eq!(motif_type("GANTC", 1), MotifType::ShortPalindrome);
+        assert_eq!(motif_type("GAAGA", 1), MotifType::ShortNonPalindrome);
+        assert_eq!(motif_type("CAAYNNNNCCT", 2), MotifType::Bipartite);
+        assert_eq!(motif_type("AGGNNNNRTTG", 0), MotifType::Bipartite);
+        // A run of two N does not separate two halves.
+        assert_eq!(motif_type("GANNTC", 1), MotifType::ShortPalindrome);
+        assert_eq!(motif_type("GANNAC", 1), MotifType::ShortNonPalindrome);
+
+        let bipartite = Motif::new("CAAYNNNNCCT", "a", 2).unwrap();
+        assert_eq!(
+            bipartite.reverse_complement().motif_type(),
+            MotifType::Bipartite
+        );
+    }
+
+    #[test]
+    fn test_motif_type_ignores_flanking_n() {
+        let mut motif = Motif::new("GATC", "a", 1).unwrap();
+        motif.extend_motif_with_n(4);
+        assert_eq!(motif.sequence_to_string(), "GATCNNNN");
+        assert_eq!(motif.motif_type(), MotifType::ShortNonPalindrome);
+
+        motif.prepend_n(4);
+        assert_eq!(motif.sequence_to_string(), "NNNNGATCNNNN");
+        assert_eq!(motif.motif_type(), MotifType::ShortPalindrome);
+    }
+
+    #[test]
+    fn test_degeneracy_and_gc_content() {
+        let motif = Motif::new("RGATCY", "a", 2).unwrap();
+        assert_eq!(motif.degeneracy(), 4);
+        assert_eq!(motif.gc_content(), 0.5);
+
+        let motif = Motif::new("CCNGG", "m", 0).unwrap();
+        assert_eq!(motif.degeneracy(), 4);
+        assert!((motif.gc_content() - 0.9).abs() < 1e-12);
+
+        let motif = Motif::new("GCGC", "m", 1).unwrap();
+        assert_eq!(motif.degeneracy(), 1);
+        assert_eq!(motif.gc_content(), 1.0);
+    }
+
+    #[test]
+    fn test_gap_lengths() {
+        let fixed = Motif::new("GACN(6)GTC", "a", 1).unwrap();
+        assert_eq!(fixed, Motif::new("GACNNNNNNGTC", "a", 1).unwrap());
+        assert_eq!(fixed.sequence_to_string(), "GACNNNNNNGTC");
+
+        let variable = Motif::new("GACN(4-6)GTC", "m", 9).unwrap();
+        assert_eq!(
+            variable.variable_gap(),
+            Some(VariableGap {
+                start: 3,
+                min: 4,
+                max: 6
+            })
+        );
+        assert_eq!(variable.sequence_to_string(), "GACN(4-6)GTC");
+        assert_eq!(variable.motif_type(), MotifType::Bipartite);
+        assert_eq!(variable.spacer_range(), Some(3..7));
+        assert!(variable.is_palindromic());
+        assert_eq!(variable.hamming_distance(&fixed), None);
+
+        let revcomp = Motif::new("GAAN(2-5)TTCC", "a", 1)
+            .unwrap()
+            .reverse_complement();
+        assert_eq!(revcomp.sequence_to_string(), "GGAAN(2-5)TTC");
+        assert_eq!(revcomp.mod_position, 7);
+
+        // A short variable gap still separates two halves.
+        assert_eq!(
+            Motif::new("GATN(1-2)GATC", "a", 1).unwrap().motif_type(),
+            MotifType::Bipartite
+        );
+    }
+
+    #[test]
+    fn test_invalid_gap_lengths() {
+        let error = |sequence: &str| Motif::new(sequence, "a", 1).unwrap_err().to_string();
+
+        assert_eq!(
+            error("GACN(6-4)GTC"),
+            "Invalid gap length '6-4' in motif sequence GACN(6-4)GTC: gaps are at least 1 long and the minimum can not exceed the maximum"
+        );
+        assert_eq!(
+            error("GACN(0)GTC"),
+            "Invalid gap length '0' in motif sequence GACN(0)GTC: gaps are at least 1 long and the minimum can not exceed the maximum"
+        );
+        assert_eq!(
+            error("GACN(x)GTC"),
+            "Invalid gap length 'x' in motif sequence: GACN(x)GTC"
+        );
+        assert_eq!(
+            error("GACN(4-6GTC"),
+            "Unclosed '(' in motif sequence: GACN(4-6GTC"
+        );
+        assert_eq!(
+            error("GAC(4)GTC"),
+            "A gap length must follow an N, as in GACN(6)GTC: GAC(4)GTC"
+        );
+        assert_eq!(
+            error("GAN(1-2)TCN(3-4)GA"),
+            "Only one variable gap is supported per motif: GAN(1-2)TCN(3-4)GA"
+        );
+    }
+
+    #[test]
+    fn test_is_child_motif_variable_gap() {
+        let motif = |sequence: &str| Motif::new(sequence, "a", 1).unwrap();
+        let child = motif("GACN(4-6)GTC");
+
+        assert!(motif("GAC").is_child_motif(&child));
+        assert!(child.is_child_motif(&child.clone()));
+        // The parent would span the gap at one length only.
+        assert!(!motif("GACNNNNGTC").is_child_motif(&child));
+        assert!(!motif("GACN(4-5)GTC").is_child_motif(&child));
+    }
 }
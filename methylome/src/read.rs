@@ -252,7 +252,11 @@ pub fn convert_skip_distances_to_positions(
 ) -> Result<BaseModifications> {
     let mut base_mods = BaseModifications::new();
 
-    for (mod_type, skips_with_qual) in skip_distances.distances {
+    let segments = skip_distances
+        .distances
+        .into_iter()
+        .chain(skip_distances.repeated_segments);
+    for (mod_type, skips_with_qual) in segments {
         let target_base = IupacBase::from_mod_type(&mod_type);
 
         let target_positions: Vec<usize> = seq
@@ -279,6 +283,13 @@ pub fn convert_skip_distances_to_positions(
                 };
 
                 match base_mods.0.get(&seq_pos) {
+                    // Duplicate call of the same modification. Keep the most confident.
+                    Some(existing) if existing.base == new_meth_base.base => {
+                        if new_meth_base.quality.0 > existing.quality.0 {
+                            base_mods.0.insert(seq_pos, new_meth_base);
+                        }
+                    }
+                    // Conflicting modifications. Keep the most confident and drop ties.
                     Some(existing) => {
                         if new_meth_base.quality.0 > existing.quality.0 {
                             base_mods.0.insert(seq_pos, new_meth_base);
@@ -315,9 +326,17 @@ fn parse_header_tags(header: &str) -> HashMap<String, String> {
 
 #[derive(Debug)]
 pub struct SkipDistance(pub usize);
+
+/// Skip distances per modification type, parsed from the MM tag segments.
+///
+/// A modification type can occur in several segments (e.g. `C+m` and `C-m`). Skip
+/// distances are relative to their own segment, so segments are not merged:
+/// `distances` holds the first segment of each modification type and
+/// `repeated_segments` the later ones in tag order.
 #[derive(Debug)]
 pub struct MethSkipDistances {
     pub distances: HashMap<ModType, Vec<(SkipDistance, MethQual)>>,
+    pub repeated_segments: Vec<(ModType, Vec<(SkipDistance, MethQual)>)>,
 }
 
 impl MethSkipDistances {
@@ -344,9 +363,13 @@ impl MethSkipDistances {
     /// ```
     pub fn from_meth_tags(mm_str: &str, quality_scores: Vec<MethQual>) -> Result<Self> {
         let mut distances = HashMap::new();
+        let mut repeated_segments = Vec::new();
 
         if mm_str.chars().collect::<Vec<char>>().len() == 0 {
-            return Ok(Self { distances });
+            return Ok(Self {
+                distances,
+                repeated_segments,
+            });
         }
 
         let total_modifications: usize = mm_str
@@ -376,12 +399,24 @@ impl MethSkipDistances {
                         .into_iter()
                         .filter_map(|skip| quality_iter.next().map(|qual| (skip, qual)))
                         .collect();
-                    distances.insert(mod_type, distances_with_qual);
+                    if distances.contains_key(&mod_type) {
+                        repeated_segments.push((mod_type, distances_with_qual));
+                    } else {
+                        distances.insert(mod_type, distances_with_qual);
+                    }
                 }
             }
         }
 
-        Ok(Self { distances })
+        Ok(Self {
+            distances,
+            repeated_segments,
+        })
+    }
+
+    /// Returns the skip distances of the first segment with the modification type.
+    pub fn get(&self, mod_type: &ModType) -> Option<&Vec<(SkipDistance, MethQual)>> {
+        self.distances.get(mod_type)
     }
 }
 
@@ -465,6 +500,41 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_duplicate_modification_is_deduplicated() {
+        // C+m and C-m both call position 1 (the first C) as 5mC.
+        let sequence = Sequence::from_str("GCGC").unwrap();
+        let quality_scores = vec![MethQual::new(100), MethQual::new(200)];
+        let skip_distances =
+            MethSkipDistances::from_meth_tags("C+m.,0;C-m.,0;", quality_scores).unwrap();
+        assert_eq!(skip_distances.distances.len(), 1);
+        assert_eq!(skip_distances.repeated_segments.len(), 1);
+
+        let mods = convert_skip_distances_to_positions(&sequence, skip_distances).unwrap();
+
+        assert_eq!(mods.0.len(), 1);
+        assert_eq!(
+            *mods.0.get(&1).unwrap(),
+            MethBase {
+                base: ModType::FiveMC,
+                quality: MethQual(200)
+            }
+        );
+    }
+
+    #[test]
+    fn test_duplicate_modification_with_equal_quality_is_kept() {
+        let sequence = Sequence::from_str("GCGC").unwrap();
+        let quality_scores = vec![MethQual::new(150), MethQual::new(150)];
+        let skip_distances =
+            MethSkipDistances::from_meth_tags("C+m.,0;C-m.,0;", quality_scores).unwrap();
+
+        let mods = convert_skip_distances_to_positions(&sequence, skip_distances).unwrap();
+
+        assert_eq!(mods.0.len(), 1);
+        assert_eq!(mods.0.get(&1).unwrap().quality, MethQual(150));
+    }
+
     #[test]
     fn test_read_construction() {
         let description = "MM:Z:A+a.,0,0,0,0,0,2,0,9,0,0,0,0,1,0,0,0,0,2,0,0,0,0,16,0,0,0,4,0,0,0,1,11,1,0,1,0,0,0,0,0,4,0,0,0,2,0,10,6,5,11,0,11,1,6,0,0,0,0,0,2,3,12,0,4,16,0,0,1,0,1,4,0,0,0,0,0;C+21839.,6,0,1,1,0,1,0,0,0,0,0,0,0,0,0,0,0,9,0,0,0,2,11,0,0,0,0,6,0,5,4,2,9,0,1,3,0,0,0,5,2,1,11,1,0,3,0,0;C+m.,6,0,1,1,0,1,0,0,0,0,0,0,0,0,0,0,0,9,0,0,0,2,11,0,0,0,0,6,0,5,4,2,9,0,1,3,0,0,0,5,2,1,11,1,0,3,0,0; ML:B:C,204,119,22,36,26,40,16,20,15,25,97,104,150,20,112,20,16,34,81,66,52,12,30,67,20,155,15,21,28,20,85,22,13,14,13,19,13,17,24,12,12,14,30,13,20,20,147,16,17,22,36,41,37,163,29,14,71,28,58,12,12,14,14,12,12,15,64,25,137,42,19,34,29,23,231,46,6,16,17,30,9,41,40,25,27,26,14,179,86,24,8,23,42,15,48,12,16,13,15,14,10,16,162,21,9,3,16,14,8,31,3,2,7,4,6,21,3,15,12,19,20,12,83,45,12,18,10,26,17,33,68,70,49,53,23,13,23,21,48,40,83,5,5,5,5,2,11,13,29,60,7,24,12,16,2,3,14,14,44,12,20,13,13,9,14,6,10,6,7,4,2,34";
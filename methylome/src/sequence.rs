@@ -44,7 +44,7 @@ impl Sequence {
     pub fn reverse_complement(&self) -> Sequence {
         self.iter()
             .rev()
-            .map(IupacBase::to_complement_base)
+            .map(IupacBase::complement)
             .collect()
     }
 }
@@ -17,9 +17,54 @@ impl Display for Strand {
 
 impl Strand {
     pub fn to_string(&self) -> String {
-        match self {
-            Strand::Positive => "+".to_string(),
-            Strand::Negative => "-".to_string(),
+        self.format(StrandFormat::Symbol).to_string()
+    }
+
+    /// Renders the strand in the given output format.
+    ///
+    /// # Examples
+    /// ```
+    /// use epimetheus_methylome::{Strand, StrandFormat};
+    ///
+    /// assert_eq!(Strand::Negative.format(StrandFormat::Word), "rev");
+    /// assert_eq!(Strand::Negative.format(StrandFormat::Numeric), "-1");
+    /// ```
+    pub fn format(&self, format: StrandFormat) -> &'static str {
+        match (format, self) {
+            (StrandFormat::Symbol, Strand::Positive) => "+",
+            (StrandFormat::Symbol, Strand::Negative) => "-",
+            (StrandFormat::Word, Strand::Positive) => "fwd",
+            (StrandFormat::Word, Strand::Negative) => "rev",
+            (StrandFormat::Numeric, Strand::Positive) => "1",
+            (StrandFormat::Numeric, Strand::Negative) => "-1",
+        }
+    }
+}
+
+/// How a [`Strand`] is written in output files.
+/// - `Symbol`: `+`/`-` (default)
+/// - `Word`: `fwd`/`rev`
+/// - `Numeric`: `1`/`-1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StrandFormat {
+    #[default]
+    Symbol,
+    Word,
+    Numeric,
+}
+
+impl FromStr for StrandFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "symbol" => Ok(StrandFormat::Symbol),
+            "word" => Ok(StrandFormat::Word),
+            "numeric" => Ok(StrandFormat::Numeric),
+            _ => bail!(
+                "Could not parse '{}' to StrandFormat. Expected 'symbol', 'word' or 'numeric'",
+                s
+            ),
         }
     }
 }
@@ -62,3 +107,40 @@ impl TryFrom<char> for Strand {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_symbol() {
+        assert_eq!(Strand::Positive.format(StrandFormat::Symbol), "+");
+        assert_eq!(Strand::Negative.format(StrandFormat::Symbol), "-");
+        assert_eq!(
+            Strand::Negative.format(StrandFormat::default()),
+            Strand::Negative.to_string()
+        );
+    }
+
+    #[test]
+    fn test_format_word() {
+        assert_eq!(Strand::Positive.format(StrandFormat::Word), "fwd");
+        assert_eq!(Strand::Negative.format(StrandFormat::Word), "rev");
+    }
+
+    #[test]
+    fn test_format_numeric() {
+        assert_eq!(Strand::Positive.format(StrandFormat::Numeric), "1");
+        assert_eq!(Strand::Negative.format(StrandFormat::Numeric), "-1");
+    }
+
+    #[test]
+    fn test_parse_strand_format() {
+        assert_eq!("word".parse::<StrandFormat>().unwrap(), StrandFormat::Word);
+        assert_eq!(
+            "numeric".parse::<StrandFormat>().unwrap(),
+            StrandFormat::Numeric
+        );
+        assert!("fwd".parse::<StrandFormat>().is_err());
+    }
+}